@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE "cluster_cargo_networks" (
+  "key" VARCHAR NOT NULL UNIQUE PRIMARY KEY,
+  "cluster_cargo_key" VARCHAR NOT NULL references cluster_cargoes("key"),
+  "network_key" VARCHAR NOT NULL references cluster_networks("key"),
+  "is_primary" BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+INSERT INTO "cluster_cargo_networks"
+  ("key", "cluster_cargo_key", "network_key", "is_primary")
+SELECT "key" || '-' || "network_key", "key", "network_key", TRUE
+FROM "cluster_cargoes";
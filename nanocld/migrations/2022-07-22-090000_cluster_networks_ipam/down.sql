@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "cluster_networks" DROP COLUMN "ipv6_subnet";
+ALTER TABLE "cluster_networks" DROP COLUMN "internal";
+ALTER TABLE "cluster_networks" DROP COLUMN "ip_range";
+ALTER TABLE "cluster_networks" DROP COLUMN "subnet";
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE "cluster_networks" ADD COLUMN "subnet" VARCHAR;
+ALTER TABLE "cluster_networks" ADD COLUMN "ip_range" VARCHAR;
+ALTER TABLE "cluster_networks" ADD COLUMN "internal" BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE "cluster_networks" ADD COLUMN "ipv6_subnet" VARCHAR;
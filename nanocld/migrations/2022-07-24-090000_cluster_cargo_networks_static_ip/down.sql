@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX "cluster_cargo_networks_ipv6_address_idx";
+DROP INDEX "cluster_cargo_networks_ipv4_address_idx";
+ALTER TABLE "cluster_cargo_networks" DROP COLUMN "ipv6_address";
+ALTER TABLE "cluster_cargo_networks" DROP COLUMN "ipv4_address";
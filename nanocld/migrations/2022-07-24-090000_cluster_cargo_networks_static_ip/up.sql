@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE "cluster_cargo_networks" ADD COLUMN "ipv4_address" VARCHAR;
+ALTER TABLE "cluster_cargo_networks" ADD COLUMN "ipv6_address" VARCHAR;
+CREATE UNIQUE INDEX "cluster_cargo_networks_ipv4_address_idx" ON "cluster_cargo_networks" ("network_key", "ipv4_address");
+CREATE UNIQUE INDEX "cluster_cargo_networks_ipv6_address_idx" ON "cluster_cargo_networks" ("network_key", "ipv6_address");
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE "cluster_network_policies" (
+  "key" VARCHAR NOT NULL UNIQUE PRIMARY KEY,
+  "cluster_key" VARCHAR NOT NULL references clusters("key"),
+  "network_key" VARCHAR NOT NULL UNIQUE references cluster_networks("key"),
+  "allowed_networks" VARCHAR[] NOT NULL,
+  "allow_egress" BOOLEAN NOT NULL DEFAULT TRUE
+);
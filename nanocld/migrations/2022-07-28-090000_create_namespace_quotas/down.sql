@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "cargoes" DROP COLUMN "cpu_limit";
+ALTER TABLE "cargoes" DROP COLUMN "memory_limit";
+DROP TABLE "namespace_quotas";
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE "namespace_quotas" (
+  "namespace_name" VARCHAR NOT NULL UNIQUE PRIMARY KEY references namespaces("name"),
+  "max_cargoes" BIGINT,
+  "max_clusters" BIGINT,
+  "max_networks" BIGINT,
+  "max_replicas" BIGINT,
+  "max_memory" BIGINT,
+  "max_cpu" BIGINT
+);
+
+ALTER TABLE "cargoes" ADD COLUMN "memory_limit" BIGINT;
+ALTER TABLE "cargoes" ADD COLUMN "cpu_limit" BIGINT;
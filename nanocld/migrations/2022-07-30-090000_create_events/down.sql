@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE "events";
+DROP TYPE "event_outcome";
+DROP TYPE "event_kind";
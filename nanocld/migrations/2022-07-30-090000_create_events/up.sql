@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TYPE "event_kind" AS ENUM (
+  'create', 'start', 'join', 'variable', 'template', 'deploy', 'crash'
+);
+
+CREATE TYPE "event_outcome" AS ENUM ('success', 'failure');
+
+CREATE TABLE "events" (
+  "key" UUID NOT NULL UNIQUE PRIMARY KEY,
+  "cluster_key" VARCHAR NOT NULL,
+  "kind" event_kind NOT NULL,
+  "actor" VARCHAR NOT NULL,
+  "target" VARCHAR,
+  "outcome" event_outcome NOT NULL,
+  "error" TEXT,
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX "events_cluster_key_created_at" ON "events" ("cluster_key", "created_at");
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE "clusters" ADD COLUMN "domains" TEXT[] NOT NULL DEFAULT '{}';
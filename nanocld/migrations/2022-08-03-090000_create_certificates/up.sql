@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE "certificates" (
+  "name" VARCHAR NOT NULL UNIQUE PRIMARY KEY,
+  "domains" TEXT[] NOT NULL,
+  "issuer" VARCHAR NOT NULL,
+  "not_before" TIMESTAMPTZ NOT NULL,
+  "not_after" TIMESTAMPTZ NOT NULL,
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE "cluster_routes" (
+  "key" VARCHAR NOT NULL UNIQUE PRIMARY KEY,
+  "cluster_key" VARCHAR NOT NULL references clusters("key"),
+  "name" VARCHAR NOT NULL,
+  "domain" VARCHAR NOT NULL,
+  "path" VARCHAR NOT NULL DEFAULT '/',
+  "cargo_name" VARCHAR,
+  "port" INTEGER,
+  "tls" BOOLEAN NOT NULL DEFAULT FALSE,
+  "redirect_to" VARCHAR,
+  "headers" VARCHAR[] NOT NULL DEFAULT '{}',
+  "websocket" BOOLEAN NOT NULL DEFAULT FALSE,
+  "connect_timeout" INTEGER,
+  "read_timeout" INTEGER
+);
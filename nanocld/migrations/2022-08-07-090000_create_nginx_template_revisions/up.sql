@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE "nginx_template_revisions" (
+  "template_name" VARCHAR NOT NULL references nginx_templates("name"),
+  "revision" INTEGER NOT NULL,
+  "mode" nginx_template_modes NOT NULL,
+  "content" TEXT NOT NULL,
+  "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY ("template_name", "revision")
+);
+
+INSERT INTO "nginx_template_revisions" ("template_name", "revision", "mode", "content")
+SELECT "name", 1, "mode", "content" FROM "nginx_templates";
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX "nginx_logs_host_date_gmt_idx";
+DROP INDEX "nginx_logs_date_gmt_idx";
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+CREATE INDEX "nginx_logs_date_gmt_idx" ON "nginx_logs" ("date_gmt", "key");
+CREATE INDEX "nginx_logs_host_date_gmt_idx" ON "nginx_logs" ("host", "date_gmt");
@@ -0,0 +1,32 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "nginx_logs" RENAME TO "nginx_logs_partitioned";
+ALTER TABLE "nginx_logs_partitioned"
+  RENAME CONSTRAINT "nginx_logs_pkey" TO "nginx_logs_partitioned_pkey";
+DROP INDEX "nginx_logs_host_date_gmt_idx";
+DROP INDEX "nginx_logs_date_gmt_idx";
+CREATE TABLE "nginx_logs" (
+  "key" UUID NOT NULL PRIMARY KEY,
+  "date_gmt" timestamptz NOT NULL,
+  "uri" VARCHAR NOT NULL,
+  "host" VARCHAR NOT NULL,
+  "remote_addr" VARCHAR NOT NULL,
+  "realip_remote_addr" VARCHAR NOT NULL,
+  "server_protocol" VARCHAR NOT NULL,
+  "request_method" VARCHAR NOT NULL,
+  "content_length" bigint NOT NULL,
+  "status" int NOT NULL,
+  "request_time" FLOAT8 NOT NULL,
+  "body_bytes_sent" bigint NOT NULL,
+  "proxy_host" VARCHAR,
+  "upstream_addr" VARCHAR,
+  "query_string" VARCHAR,
+  "request_body" VARCHAR,
+  "content_type" VARCHAR,
+  "http_user_agent" VARCHAR,
+  "http_referrer" VARCHAR,
+  "http_accept_language" VARCHAR
+);
+CREATE INDEX "nginx_logs_date_gmt_idx" ON "nginx_logs" ("date_gmt", "key");
+CREATE INDEX "nginx_logs_host_date_gmt_idx" ON "nginx_logs" ("host", "date_gmt");
+INSERT INTO "nginx_logs" SELECT * FROM "nginx_logs_partitioned";
+DROP TABLE "nginx_logs_partitioned";
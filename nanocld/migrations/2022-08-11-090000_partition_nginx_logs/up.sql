@@ -0,0 +1,57 @@
+-- Your SQL goes here
+ALTER TABLE "nginx_logs" RENAME TO "nginx_logs_unpartitioned";
+ALTER TABLE "nginx_logs_unpartitioned"
+  RENAME CONSTRAINT "nginx_logs_pkey" TO "nginx_logs_unpartitioned_pkey";
+DROP INDEX "nginx_logs_host_date_gmt_idx";
+DROP INDEX "nginx_logs_date_gmt_idx";
+CREATE TABLE "nginx_logs" (
+  "key" UUID NOT NULL,
+  "date_gmt" timestamptz NOT NULL,
+  "uri" VARCHAR NOT NULL,
+  "host" VARCHAR NOT NULL,
+  "remote_addr" VARCHAR NOT NULL,
+  "realip_remote_addr" VARCHAR NOT NULL,
+  "server_protocol" VARCHAR NOT NULL,
+  "request_method" VARCHAR NOT NULL,
+  "content_length" bigint NOT NULL,
+  "status" int NOT NULL,
+  "request_time" FLOAT8 NOT NULL,
+  "body_bytes_sent" bigint NOT NULL,
+  "proxy_host" VARCHAR,
+  "upstream_addr" VARCHAR,
+  "query_string" VARCHAR,
+  "request_body" VARCHAR,
+  "content_type" VARCHAR,
+  "http_user_agent" VARCHAR,
+  "http_referrer" VARCHAR,
+  "http_accept_language" VARCHAR,
+  PRIMARY KEY ("key", "date_gmt")
+) PARTITION BY RANGE ("date_gmt");
+-- Daily partitions are created by the daemon
+-- logs outside of them are stored in the default one
+CREATE TABLE "nginx_logs_default" PARTITION OF "nginx_logs" DEFAULT;
+CREATE INDEX "nginx_logs_date_gmt_idx" ON "nginx_logs" ("date_gmt", "key");
+CREATE INDEX "nginx_logs_host_date_gmt_idx" ON "nginx_logs" ("host", "date_gmt");
+-- Partitions of the existing logs and the next days are created first
+-- a partition can't be created once the default one has logs in his range
+DO $$
+DECLARE
+  day date;
+BEGIN
+  FOR day IN
+    SELECT DISTINCT ("date_gmt" AT TIME ZONE 'UTC')::date
+      FROM "nginx_logs_unpartitioned"
+    UNION
+    SELECT (now() AT TIME ZONE 'UTC')::date + offset_days
+      FROM generate_series(0, 2) AS offset_days
+  LOOP
+    EXECUTE format(
+      'CREATE TABLE %I PARTITION OF "nginx_logs" FOR VALUES FROM (%L) TO (%L)',
+      'nginx_logs_' || to_char(day, 'YYYYMMDD'),
+      to_char(day, 'YYYY-MM-DD') || 'T00:00:00Z',
+      to_char(day + 1, 'YYYY-MM-DD') || 'T00:00:00Z'
+    );
+  END LOOP;
+END $$;
+INSERT INTO "nginx_logs" SELECT * FROM "nginx_logs_unpartitioned";
+DROP TABLE "nginx_logs_unpartitioned";
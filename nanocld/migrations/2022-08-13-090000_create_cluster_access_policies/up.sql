@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE "cluster_access_policies" (
+  "key" VARCHAR NOT NULL UNIQUE PRIMARY KEY,
+  "cluster_key" VARCHAR NOT NULL references clusters("key"),
+  "name" VARCHAR NOT NULL,
+  "domain" VARCHAR,
+  "allow" VARCHAR[] NOT NULL DEFAULT '{}',
+  "deny" VARCHAR[] NOT NULL DEFAULT '{}',
+  "rate" INTEGER,
+  "burst" INTEGER,
+  "connections" INTEGER
+);
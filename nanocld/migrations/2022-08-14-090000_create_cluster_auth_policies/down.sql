@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE "cluster_auth_policies";
+DROP TYPE "cluster_auth_kind";
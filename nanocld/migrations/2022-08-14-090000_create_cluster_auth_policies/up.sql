@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TYPE "cluster_auth_kind" AS ENUM ('basic', 'forward');
+
+CREATE TABLE "cluster_auth_policies" (
+  "key" VARCHAR NOT NULL UNIQUE PRIMARY KEY,
+  "cluster_key" VARCHAR NOT NULL references clusters("key"),
+  "name" VARCHAR NOT NULL,
+  "kind" cluster_auth_kind NOT NULL,
+  "domain" VARCHAR,
+  "route_name" VARCHAR,
+  "realm" VARCHAR,
+  "users" VARCHAR[] NOT NULL DEFAULT '{}',
+  "cargo_name" VARCHAR,
+  "port" INTEGER,
+  "path" VARCHAR
+);
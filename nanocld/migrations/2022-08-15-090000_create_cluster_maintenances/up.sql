@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE "cluster_maintenances" (
+  "cluster_key" VARCHAR NOT NULL UNIQUE PRIMARY KEY references clusters("key"),
+  "enabled" BOOLEAN NOT NULL DEFAULT FALSE,
+  "body" TEXT,
+  "allow" VARCHAR[] NOT NULL DEFAULT '{}',
+  "bad_gateway_body" TEXT,
+  "gateway_timeout_body" TEXT
+);
@@ -7,7 +7,7 @@ use crate::config::DaemonConfig;
 use crate::{services, repositories};
 use crate::models::{Pool, NamespacePartial};
 
-use crate::errors::DaemonError;
+use crate::errors::{DaemonError, IntoHttpResponseError};
 
 embed_migrations!("./migrations");
 
@@ -68,6 +68,8 @@ async fn boot_docker_services(
   services::dnsmasq::boot(config, docker_api).await?;
   // Boot nginx service to manage proxy
   services::nginx::boot(config, docker_api).await?;
+  // Prepare the directory of acme challenges served by nginx
+  services::acme::boot(&config.state_dir).map_err(|err| err.to_http_error())?;
   services::ipsec::boot(config, docker_api).await?;
   Ok(())
 }
@@ -91,6 +93,13 @@ pub async fn boot(
   embedded_migrations::run(&conn)?;
   // Create default namesapce
   create_default_nsp(&pool).await?;
+  // Reconcile host firewall with network policies
+  let docker_state = web::types::State::new(docker_api.to_owned());
+  if let Err(err) =
+    services::network_policy::reconcile(&docker_state, &pool).await
+  {
+    log::warn!("unable to reconcile network policies {}", err.msg);
+  }
 
   log::info!("booted");
   // Return state
@@ -30,4 +30,37 @@ pub(crate) struct Cli {
   /// Nanocld config dir
   #[clap(long, default_value = "/etc/nanocl")]
   pub(crate) config_dir: String,
+  /// Ranges where cluster network subnets are allocated when not specified
+  #[clap(long = "network-pool", default_value = "172.28.0.0/14")]
+  pub(crate) network_pools: Vec<String>,
+  /// Seconds between reconciliations of clusters with docker 0 to disable
+  #[clap(long, default_value = "60")]
+  pub(crate) reconcile_interval: u32,
+  /// ACME directory used to issue certificates of cluster domains
+  /// like https://acme-v02.api.letsencrypt.org/directory
+  #[clap(long)]
+  pub(crate) acme_directory: Option<String>,
+  /// Contact email of the ACME account
+  #[clap(long)]
+  pub(crate) acme_email: Option<String>,
+  /// Don't verify the certificate of the ACME directory, only for testing
+  #[clap(long)]
+  pub(crate) acme_insecure: bool,
+  /// Days nginx access logs are kept 0 to keep them forever
+  #[clap(long, default_value = "0")]
+  pub(crate) nginx_log_retention: u32,
+  /// Maximum number of nginx access logs kept 0 for no limit
+  #[clap(long, default_value = "0")]
+  pub(crate) nginx_log_max_rows: i64,
+  /// Export pruned nginx access logs as gzipped ndjson files
+  /// in `state_dir/nginx/archives` before deleting them
+  #[clap(long)]
+  pub(crate) nginx_log_archive: bool,
+  /// Number of nginx access logs written in database at once
+  #[clap(long, default_value = "500")]
+  pub(crate) nginx_log_batch_size: usize,
+  /// Milliseconds nginx access logs wait before being written in database
+  /// when the batch isn't full
+  #[clap(long, default_value = "1000")]
+  pub(crate) nginx_log_flush_interval: u64,
 }
@@ -7,6 +7,16 @@ pub struct DaemonConfig {
   // Todo use a config to setup deamon config
   #[allow(dead_code)]
   pub(crate) config_dir: String,
+  pub(crate) network_pools: Vec<String>,
+  pub(crate) reconcile_interval: u32,
+  pub(crate) acme_directory: Option<String>,
+  pub(crate) acme_email: Option<String>,
+  pub(crate) acme_insecure: bool,
+  pub(crate) nginx_log_retention: u32,
+  pub(crate) nginx_log_max_rows: i64,
+  pub(crate) nginx_log_archive: bool,
+  pub(crate) nginx_log_batch_size: usize,
+  pub(crate) nginx_log_flush_interval: u64,
 }
 
 impl From<Cli> for DaemonConfig {
@@ -15,6 +25,16 @@ impl From<Cli> for DaemonConfig {
       hosts: args.hosts,
       state_dir: args.state_dir,
       config_dir: args.config_dir,
+      network_pools: args.network_pools,
+      reconcile_interval: args.reconcile_interval,
+      acme_directory: args.acme_directory,
+      acme_email: args.acme_email,
+      acme_insecure: args.acme_insecure,
+      nginx_log_retention: args.nginx_log_retention,
+      nginx_log_max_rows: args.nginx_log_max_rows,
+      nginx_log_archive: args.nginx_log_archive,
+      nginx_log_batch_size: args.nginx_log_batch_size,
+      nginx_log_flush_interval: args.nginx_log_flush_interval,
     }
   }
 }
@@ -74,6 +74,13 @@ async fn create_cargo(
     &nsp,
     payload,
   );
+  services::quota::ensure_cargo_available(
+    &nsp,
+    payload.memory_limit,
+    payload.cpu_limit,
+    &pool,
+  )
+  .await?;
   let environnements = payload.environnements.to_owned();
   let item = repositories::cargo::create(nsp, payload, &pool).await?;
   if let Some(environnements) = environnements {
@@ -132,6 +139,11 @@ async fn delete_cargo_by_name(
   let gen_key = nsp + "-" + &name.into_inner();
 
   repositories::cargo::find_by_key(gen_key.clone(), &pool).await?;
+  repositories::cluster_cargo_network::delete_by_cargo_key(
+    gen_key.to_owned(),
+    &pool,
+  )
+  .await?;
   repositories::cluster_cargo::delete_by_cargo_key(gen_key.to_owned(), &pool)
     .await?;
   let res =
@@ -0,0 +1,171 @@
+/// Manage certificates installed on the proxy
+use ntex::web;
+use ntex::util::Bytes;
+use ntex::http::StatusCode;
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::models::{Pool, CertificatePartial};
+
+use crate::errors::HttpResponseError;
+
+/// List all certificates
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/certificates",
+  responses(
+      (status = 200, description = "Array of certificates", body = [CertificateItem]),
+  ),
+))]
+#[web::get("/certificates")]
+async fn list_certificate(
+  pool: web::types::State<Pool>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let items = repositories::certificate::list(&pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&items))
+}
+
+/// Upload a certificate with his private key
+/// The body is read as bytes because a certificate chain
+/// is often bigger than the json limit
+#[cfg_attr(feature = "openapi", utoipa::path(
+  post,
+  path = "/certificates",
+  request_body = CertificatePartial,
+  responses(
+    (status = 201, description = "Certificate installed", body = CertificateItem),
+    (status = 400, description = "Invalid certificate or key", body = ApiError),
+    (status = 409, description = "Certificate name already used", body = ApiError),
+  ),
+))]
+#[web::post("/certificates")]
+async fn create_certificate(
+  pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
+  body: Bytes,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let payload =
+    serde_json::from_slice::<CertificatePartial>(&body).map_err(|err| {
+      HttpResponseError {
+        msg: format!("invalid payload {}", err),
+        status: StatusCode::UNPROCESSABLE_ENTITY,
+      }
+    })?;
+  let item = services::certificate::create(payload, &config, &pool).await?;
+
+  Ok(web::HttpResponse::Created().json(&item))
+}
+
+/// Inspect a certificate by it's name
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/certificates/{name}/inspect",
+  params(
+    ("name" = String, path, description = "Name of the certificate"),
+  ),
+  responses(
+    (status = 200, description = "Certificate found", body = CertificateItem),
+    (status = 404, description = "Certificate not found", body = ApiError),
+  ),
+))]
+#[web::get("/certificates/{name}/inspect")]
+async fn inspect_certificate_by_name(
+  pool: web::types::State<Pool>,
+  name: web::types::Path<String>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let item =
+    repositories::certificate::find_by_name(name.into_inner(), &pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&item))
+}
+
+/// Delete a certificate by it's name
+#[cfg_attr(feature = "openapi", utoipa::path(
+  delete,
+  path = "/certificates/{name}",
+  params(
+    ("name" = String, path, description = "Name of the certificate"),
+  ),
+  responses(
+    (status = 200, description = "Certificate deleted", body = PgDeleteGeneric),
+    (status = 404, description = "Certificate not found", body = ApiError),
+    (status = 409, description = "Certificate used by a proxy template", body = ApiError),
+  ),
+))]
+#[web::delete("/certificates/{name}")]
+async fn delete_certificate_by_name(
+  pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
+  name: web::types::Path<String>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let res =
+    services::certificate::delete(name.into_inner(), &config, &pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+pub fn ntex_config(config: &mut web::ServiceConfig) {
+  config.service(list_certificate);
+  config.service(create_certificate);
+  config.service(inspect_certificate_by_name);
+  config.service(delete_certificate_by_name);
+}
+
+#[cfg(test)]
+mod test_certificate {
+  use serde_json::json;
+  use ntex::http::StatusCode;
+
+  use crate::utils::test::*;
+
+  use super::ntex_config;
+
+  async fn test_list(srv: &TestServer) -> TestReturn {
+    let resp = srv.get("/certificates").send().await?;
+
+    assert!(resp.status().is_success());
+    Ok(())
+  }
+
+  async fn test_fail_create(srv: &TestServer) -> TestReturn {
+    let resp = srv
+      .post("/certificates")
+      .send_json(&json!({
+        "name": "controller-invalid",
+        "certificate": "not a certificate",
+        "certificate_key": "not a key",
+      }))
+      .await?;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = srv
+      .post("/certificates")
+      .send_json(&json!({ "name": 1 }))
+      .await?;
+
+    assert!(resp.status().is_client_error());
+    Ok(())
+  }
+
+  async fn test_inspect_not_found(srv: &TestServer) -> TestReturn {
+    let resp = srv
+      .get("/certificates/controller-not-found/inspect")
+      .send()
+      .await?;
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    Ok(())
+  }
+
+  #[ntex::test]
+  async fn main() -> TestReturn {
+    let srv = generate_server(ntex_config).await;
+
+    test_list(&srv).await?;
+    test_fail_create(&srv).await?;
+    test_inspect_not_found(&srv).await?;
+    Ok(())
+  }
+}
@@ -10,15 +10,24 @@ use crate::repositories;
 use crate::services::cluster::JoinCargoOptions;
 use crate::models::{
   Pool, ClusterJoinBody, ClusterPartial, ClusterItemWithRelation,
+  ClusterNetworkItem, NamespaceUsage, EventKind,
 };
 
-use crate::errors::HttpResponseError;
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+
+use super::utils::get_actor;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct ClusterQuery {
   pub(crate) namespace: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct ClusterHistoryQuery {
+  pub(crate) namespace: Option<String>,
+  pub(crate) limit: Option<i64>,
+}
+
 /// List all cluster
 #[cfg_attr(feature = "openapi", utoipa::path(
   get,
@@ -62,6 +71,7 @@ async fn list_cluster(
 ))]
 #[web::post("/clusters")]
 async fn create_cluster(
+  req: web::HttpRequest,
   pool: web::types::State<Pool>,
   web::types::Query(qs): web::types::Query<ClusterQuery>,
   web::types::Json(json): web::types::Json<ClusterPartial>,
@@ -70,8 +80,28 @@ async fn create_cluster(
     None => String::from("global"),
     Some(namespace) => namespace,
   };
+  let cluster_key = nsp.to_owned() + "-" + &json.name;
+  let requested = NamespaceUsage {
+    clusters: 1,
+    ..Default::default()
+  };
   let res =
-    repositories::cluster::create_for_namespace(nsp, json, &pool).await?;
+    match services::quota::ensure_available(&nsp, requested, &pool).await {
+      Err(err) => Err(err),
+      Ok(_) => {
+        repositories::cluster::create_for_namespace(nsp, json, &pool).await
+      }
+    };
+  services::history::record(
+    &cluster_key,
+    EventKind::Create,
+    &get_actor(&req),
+    None,
+    &res,
+    &pool,
+  )
+  .await;
+  let res = res?;
   Ok(web::HttpResponse::Created().json(&res))
 }
 
@@ -92,6 +122,7 @@ async fn create_cluster(
 #[web::delete("clusters/{name}")]
 async fn delete_cluster_by_name(
   pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
   docker_api: web::types::State<bollard::Docker>,
   name: web::types::Path<String>,
   web::types::Query(qs): web::types::Query<ClusterQuery>,
@@ -114,7 +145,84 @@ async fn delete_cluster_by_name(
     &pool,
   )
   .await?;
+  repositories::cluster_network_policy::delete_by_cluster_key(
+    gen_key.to_owned(),
+    &pool,
+  )
+  .await?;
+  let routes = repositories::cluster_route::delete_by_cluster_key(
+    gen_key.to_owned(),
+    &pool,
+  )
+  .await?;
+  let access_policies =
+    repositories::cluster_access_policy::delete_by_cluster_key(
+      gen_key.to_owned(),
+      &pool,
+    )
+    .await?;
+  let auth_policies = repositories::cluster_auth_policy::list_by_cluster(
+    gen_key.to_owned(),
+    &pool,
+  )
+  .await?;
+  repositories::cluster_auth_policy::delete_by_cluster_key(
+    gen_key.to_owned(),
+    &pool,
+  )
+  .await?;
+  let maintenance = repositories::cluster_maintenance::find_by_cluster_key(
+    gen_key.to_owned(),
+    &pool,
+  )
+  .await?;
+  repositories::cluster_maintenance::delete_by_cluster_key(
+    gen_key.to_owned(),
+    &pool,
+  )
+  .await?;
+  // Routes and the zones and upstreams of the policies they use
+  // are removed together
+  let mut nginx_files = Vec::new();
+  if routes.count > 0 {
+    nginx_files.push(services::route::config_file_path(
+      &gen_key,
+      &config.state_dir,
+    ));
+  }
+  if access_policies.count > 0 {
+    nginx_files.push(services::access_policy::config_file_path(
+      &gen_key,
+      &config.state_dir,
+    ));
+  }
+  if !auth_policies.is_empty() {
+    nginx_files.push(services::auth_policy::config_file_path(
+      &gen_key,
+      &config.state_dir,
+    ));
+  }
+  if maintenance.as_ref().map(|item| item.enabled) == Some(true) {
+    nginx_files.push(services::maintenance::config_file_path(
+      &gen_key,
+      &config.state_dir,
+    ));
+  }
+  services::nginx::remove_config_files(
+    &nginx_files,
+    &config.state_dir,
+    &docker_api,
+  )
+  .await
+  .map_err(|err| err.to_http_error())?;
+  for policy in &auth_policies {
+    services::auth_policy::remove_htpasswd_file(&policy.key, &config.state_dir)
+      .map_err(|err| err.to_http_error())?;
+  }
+  services::maintenance::remove_pages(&gen_key, &config.state_dir)
+    .map_err(|err| err.to_http_error())?;
   services::cluster::delete_networks(item, &docker_api, &pool).await?;
+  services::network_policy::reconcile(&docker_api, &pool).await?;
   let res = repositories::cluster::delete_by_key(gen_key, &pool).await?;
   Ok(web::HttpResponse::Ok().json(&res))
 }
@@ -148,6 +256,13 @@ async fn inspect_cluster_by_name(
   let item =
     repositories::cluster::find_by_key(gen_key.to_owned(), &pool).await?;
   let proxy_templates = item.proxy_templates.to_owned();
+  let domains = item.domains.to_owned();
+  let network_policies =
+    repositories::cluster_network_policy::list_by_cluster_key(
+      item.key.to_owned(),
+      &pool,
+    )
+    .await?;
   let networks =
     repositories::cluster_network::list_for_cluster(item, &pool).await?;
 
@@ -156,7 +271,9 @@ async fn inspect_cluster_by_name(
     key: gen_key,
     namespace: nsp,
     proxy_templates,
+    domains,
     networks: Some(networks),
+    network_policies: Some(network_policies),
   };
 
   Ok(web::HttpResponse::Ok().json(&res))
@@ -178,6 +295,7 @@ async fn inspect_cluster_by_name(
 ))]
 #[web::post("/clusters/{name}/start")]
 async fn start_cluster_by_name(
+  req: web::HttpRequest,
   name: web::types::Path<String>,
   web::types::Query(qs): web::types::Query<ClusterQuery>,
   pool: web::types::State<Pool>,
@@ -191,7 +309,20 @@ async fn start_cluster_by_name(
   };
   let gen_key = nsp.to_owned() + "-" + &name;
   let cluster = repositories::cluster::find_by_key(gen_key, &pool).await?;
-  services::cluster::start(&cluster, &config, &pool, &docker_api).await?;
+  let actor = get_actor(&req);
+  let res =
+    services::cluster::start(&cluster, &config, &pool, &docker_api, &actor)
+      .await;
+  services::history::record(
+    &cluster.key,
+    EventKind::Start,
+    &actor,
+    None,
+    &res,
+    &pool,
+  )
+  .await;
+  res?;
   Ok(web::HttpResponse::Ok().into())
 }
 
@@ -212,6 +343,7 @@ async fn start_cluster_by_name(
 ))]
 #[web::post("/clusters/{name}/join")]
 async fn join_cargo_to_cluster(
+  req: web::HttpRequest,
   pool: web::types::State<Pool>,
   docker_api: web::types::State<bollard::Docker>,
   name: web::types::Path<String>,
@@ -244,26 +376,94 @@ async fn join_cargo_to_cluster(
 
   let cluster = repositories::cluster::find_by_key(cluster_key, &pool).await?;
   let cargo = repositories::cargo::find_by_key(cargo_key, &pool).await?;
+  services::quota::ensure_replica_available(&cargo, &pool).await?;
   let network_key = cluster.key.to_owned() + "-" + &payload.network;
   let network =
     repositories::cluster_network::find_by_key(network_key, &pool).await?;
+  let mut networks = Vec::new();
+  for network_name in payload.networks.unwrap_or_default() {
+    let network_key = cluster.key.to_owned() + "-" + &network_name;
+    if network_key == network.key
+      || networks
+        .iter()
+        .any(|n: &ClusterNetworkItem| n.key == network_key)
+    {
+      continue;
+    }
+    let network =
+      repositories::cluster_network::find_by_key(network_key, &pool).await?;
+    networks.push(network);
+  }
 
   log::debug!(
     "joining cargo {:?} into cluster {:?}",
     cargo.key,
     cluster.key
   );
+  let cluster_key = cluster.key.to_owned();
+  let target = cargo.key.to_owned();
   let join_cargo_opts = JoinCargoOptions {
     cluster,
     cargo,
     network,
+    networks,
+    ipv4_address: payload.ipv4_address,
+    ipv6_address: payload.ipv6_address,
     is_creating_relation: true,
   };
-  services::cluster::join_cargo(&join_cargo_opts, &docker_api, &pool).await?;
+  let lock = services::cluster::lock_cluster_cargoes().await;
+  let res =
+    services::cluster::join_cargo(&join_cargo_opts, &docker_api, &pool).await;
+  drop(lock);
+  services::history::record(
+    &cluster_key,
+    EventKind::Join,
+    &get_actor(&req),
+    Some(target),
+    &res,
+    &pool,
+  )
+  .await;
+  res?;
   log::debug!("join success.");
   Ok(web::HttpResponse::Ok().into())
 }
 
+/// List latest actions made on a cluster
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/clusters/{name}/history",
+  params(
+    ("name" = String, path, description = "Name of the cluster"),
+    ("namespace" = Option<String>, query, description = "Namespace where the cluster is if empty we use 'global' as value"),
+    ("limit" = Option<i64>, query, description = "Maximum number of events to return default to 100"),
+  ),
+  responses(
+    (status = 200, description = "Events of the cluster newest first", body = [EventItem]),
+    (status = 400, description = "Generic database error", body = ApiError),
+  ),
+))]
+#[web::get("/clusters/{name}/history")]
+async fn list_cluster_history(
+  name: web::types::Path<String>,
+  pool: web::types::State<Pool>,
+  web::types::Query(qs): web::types::Query<ClusterHistoryQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let nsp = match qs.namespace {
+    None => String::from("global"),
+    Some(namespace) => namespace,
+  };
+  let cluster_key = nsp + "-" + &name.into_inner();
+  let items = repositories::event::list_by_cluster_key(
+    cluster_key,
+    qs.limit.unwrap_or(100),
+    &pool,
+  )
+  .await?;
+
+  Ok(web::HttpResponse::Ok().json(&items))
+}
+
 /// Count cluster
 #[cfg_attr(feature = "openapi", utoipa::path(
   get,
@@ -311,6 +511,7 @@ pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(delete_cluster_by_name);
   config.service(start_cluster_by_name);
   config.service(join_cargo_to_cluster);
+  config.service(list_cluster_history);
   config.service(count_cluster);
 }
 
@@ -344,6 +545,7 @@ mod test_namespace_cluster {
     let item = ClusterPartial {
       name: String::from("test_cluster"),
       proxy_templates: None,
+      domains: None,
     };
     let resp = srv.post("/clusters").send_json(&item).await?;
 
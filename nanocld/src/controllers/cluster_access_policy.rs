@@ -0,0 +1,162 @@
+use ntex::web;
+use serde::{Serialize, Deserialize};
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::models::{Pool, ClusterAccessPolicyPartial};
+
+use super::utils::{gen_nsp_key_by_name, get_actor};
+
+use crate::errors::HttpResponseError;
+
+#[derive(Serialize, Deserialize)]
+pub struct ClusterAccessPolicyQuery {
+  namespace: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ClusterAccessPolicyPath {
+  c_name: String,
+  p_name: String,
+}
+
+/// Create an access policy and render it in the proxy
+#[cfg_attr(feature = "openapi", utoipa::path(
+  post,
+  path = "/clusters/{c_name}/access_policies",
+  request_body = ClusterAccessPolicyPartial,
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is stored is empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 201, description = "Fresh cluster access policy", body = ClusterAccessPolicyItem),
+    (status = 400, description = "Invalid access policy or nginx configuration", body = ApiError),
+    (status = 404, description = "Cluster name or Namespace not valid", body = ApiError),
+    (status = 409, description = "An access policy with this name already exists", body = ApiError),
+  ),
+))]
+#[web::post("/clusters/{c_name}/access_policies")]
+async fn create_cluster_access_policy(
+  req: web::HttpRequest,
+  pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
+  docker_api: web::types::State<bollard::Docker>,
+  c_name: web::types::Path<String>,
+  web::types::Query(qs): web::types::Query<ClusterAccessPolicyQuery>,
+  web::types::Json(payload): web::types::Json<ClusterAccessPolicyPartial>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &c_name.into_inner());
+  let cluster = repositories::cluster::find_by_key(cluster_key, &pool).await?;
+  let policy = services::access_policy::create(
+    &cluster,
+    payload,
+    &config,
+    &docker_api,
+    &pool,
+    &get_actor(&req),
+  )
+  .await?;
+
+  Ok(web::HttpResponse::Created().json(&policy))
+}
+
+/// List access policies of a cluster
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/clusters/{c_name}/access_policies",
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is stored is empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "List of access policies for given cluster", body = [ClusterAccessPolicyItem]),
+    (status = 400, description = "Generic database error", body = ApiError),
+  ),
+))]
+#[web::get("/clusters/{c_name}/access_policies")]
+async fn list_cluster_access_policy(
+  pool: web::types::State<Pool>,
+  c_name: web::types::Path<String>,
+  web::types::Query(qs): web::types::Query<ClusterAccessPolicyQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &c_name.into_inner());
+  let policies =
+    repositories::cluster_access_policy::list_by_cluster(cluster_key, &pool)
+      .await?;
+
+  Ok(web::HttpResponse::Ok().json(&policies))
+}
+
+/// Get cluster access policy by it's name
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/clusters/{c_name}/access_policies/{p_name}",
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("p_name" = String, path, description = "name of the access policy"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is stored is empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "Cluster access policy", body = ClusterAccessPolicyItem),
+    (status = 404, description = "Access policy not found", body = ApiError),
+  ),
+))]
+#[web::get("/clusters/{c_name}/access_policies/{p_name}")]
+async fn get_cluster_access_policy_by_name(
+  pool: web::types::State<Pool>,
+  url_path: web::types::Path<ClusterAccessPolicyPath>,
+  web::types::Query(qs): web::types::Query<ClusterAccessPolicyQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let policy_name = format!("{}-{}", &url_path.c_name, &url_path.p_name);
+  let policy_key = gen_nsp_key_by_name(&qs.namespace, &policy_name);
+
+  let res =
+    repositories::cluster_access_policy::find_by_key(policy_key, &pool).await?;
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+/// Delete an access policy and remove it from the proxy
+#[cfg_attr(feature = "openapi", utoipa::path(
+  delete,
+  path = "/clusters/{c_name}/access_policies/{p_name}",
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("p_name" = String, path, description = "name of the access policy"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is stored is empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "Generic delete response", body = PgDeleteGeneric),
+    (status = 404, description = "Cluster or access policy not found", body = ApiError),
+  ),
+))]
+#[web::delete("/clusters/{c_name}/access_policies/{p_name}")]
+async fn delete_cluster_access_policy(
+  req: web::HttpRequest,
+  pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
+  docker_api: web::types::State<bollard::Docker>,
+  url_path: web::types::Path<ClusterAccessPolicyPath>,
+  web::types::Query(qs): web::types::Query<ClusterAccessPolicyQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &url_path.c_name);
+  let cluster = repositories::cluster::find_by_key(cluster_key, &pool).await?;
+  let res = services::access_policy::delete(
+    &cluster,
+    &url_path.p_name,
+    &config,
+    &docker_api,
+    &pool,
+    &get_actor(&req),
+  )
+  .await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+pub fn ntex_config(config: &mut web::ServiceConfig) {
+  config.service(create_cluster_access_policy);
+  config.service(list_cluster_access_policy);
+  config.service(get_cluster_access_policy_by_name);
+  config.service(delete_cluster_access_policy);
+}
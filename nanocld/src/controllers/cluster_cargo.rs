@@ -1,14 +1,12 @@
-use futures::{stream, StreamExt};
 use ntex::web;
 use serde::{Serialize, Deserialize};
 
 use crate::config::DaemonConfig;
 use crate::{services, repositories};
-use crate::models::Pool;
+use crate::models::{Pool, EventKind};
 use crate::errors::HttpResponseError;
-use crate::services::cluster::JoinCargoOptions;
 
-use super::utils::gen_nsp_key_by_name;
+use super::utils::{gen_nsp_key_by_name, get_actor};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClusterCargoQuery {
@@ -16,14 +14,15 @@ pub struct ClusterCargoQuery {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ClusterCargoPatchPath {
+pub struct ClusterCargoPath {
   cluster_name: String,
   cargo_name: String,
 }
 
 #[web::patch("/clusters/{cluster_name}/cargoes/{cargo_name}")]
 async fn update_cluster_cargo_by_name(
-  req_path: web::types::Path<ClusterCargoPatchPath>,
+  req: web::HttpRequest,
+  req_path: web::types::Path<ClusterCargoPath>,
   daemon_config: web::types::State<DaemonConfig>,
   pool: web::types::State<Pool>,
   docker_api: web::types::State<bollard::Docker>,
@@ -38,52 +37,85 @@ async fn update_cluster_cargo_by_name(
   )
   .await?;
 
-  println!("cluster_cargo : {:#?}", &cluster_cargo);
-
-  let network = repositories::cluster_network::find_by_key(
-    cluster_cargo.network_key,
+  let lock = services::cluster::lock_cluster_cargoes().await;
+  let res = services::cluster::redeploy_cargo(
+    cluster_cargo,
+    &daemon_config,
+    &docker_api,
     &pool,
+    &get_actor(&req),
   )
-  .await?;
-
-  let cluster =
-    repositories::cluster::find_by_key(cluster_key.to_owned(), &pool).await?;
-  let cargo =
-    repositories::cargo::find_by_key(cargo_key.to_owned(), &pool).await?;
-  let cnt_to_remove =
-    services::cluster::list_containers(&cluster_key, &cargo_key, &docker_api)
-      .await?;
-
-  println!("container to remove {:#?}", &cnt_to_remove);
-
-  let opts = JoinCargoOptions {
-    cluster: cluster.to_owned(),
-    cargo,
-    network,
-    is_creating_relation: false,
-  };
+  .await;
+  drop(lock);
+  services::history::record(
+    &cluster_key,
+    EventKind::Deploy,
+    &get_actor(&req),
+    Some(cargo_key),
+    &res,
+    &pool,
+  )
+  .await;
+  res?;
 
-  services::cluster::join_cargo(&opts, &docker_api, &pool).await?;
+  Ok(web::HttpResponse::Ok().into())
+}
 
-  services::cluster::start(&cluster, &daemon_config, &pool, &docker_api)
-    .await?;
+/// Remove a cargo from a cluster
+#[cfg_attr(feature = "openapi", utoipa::path(
+  delete,
+  path = "/clusters/{cluster_name}/cargoes/{cargo_name}",
+  params(
+    ("cluster_name" = String, path, description = "Name of the cluster"),
+    ("cargo_name" = String, path, description = "Name of the cargo"),
+    ("namespace" = Option<String>, query, description = "Namespace where the cluster and the cargo are stored if empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "Cargo removed from the cluster", body = PgDeleteGeneric),
+    (status = 400, description = "Generic database error", body = ApiError),
+    (status = 404, description = "Cargo is not joined to the cluster", body = ApiError),
+  ),
+))]
+#[web::delete("/clusters/{cluster_name}/cargoes/{cargo_name}")]
+async fn delete_cluster_cargo_by_name(
+  req: web::HttpRequest,
+  req_path: web::types::Path<ClusterCargoPath>,
+  daemon_config: web::types::State<DaemonConfig>,
+  pool: web::types::State<Pool>,
+  docker_api: web::types::State<bollard::Docker>,
+  web::types::Query(qs): web::types::Query<ClusterCargoQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &req_path.cluster_name);
+  let cargo_key = gen_nsp_key_by_name(&qs.namespace, &req_path.cargo_name);
 
-  let mut stream = stream::iter(cnt_to_remove);
+  repositories::cluster_cargo::get_by_key(
+    format!("{}-{}", &cluster_key, &cargo_key),
+    &pool,
+  )
+  .await?;
 
-  while let Some(container) = stream.next().await {
-    let options = Some(bollard::container::RemoveContainerOptions {
-      force: true,
-      ..Default::default()
-    });
-    println!("removing container {:#?}", &container);
-    docker_api
-      .remove_container(&container.id.clone().unwrap_or_default(), options)
-      .await?;
-  }
+  let cluster = repositories::cluster::find_by_key(cluster_key, &pool).await?;
+  let cargo = repositories::cargo::find_by_key(cargo_key, &pool).await?;
+
+  log::debug!(
+    "removing cargo {:?} from cluster {:?}",
+    cargo.key,
+    cluster.key
+  );
+  let res = services::cluster::unjoin_cargo(
+    &cluster,
+    &cargo,
+    &daemon_config,
+    &docker_api,
+    &pool,
+    &get_actor(&req),
+  )
+  .await?;
 
-  Ok(web::HttpResponse::Ok().into())
+  Ok(web::HttpResponse::Ok().json(&res))
 }
 
 pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(update_cluster_cargo_by_name);
+  config.service(delete_cluster_cargo_by_name);
 }
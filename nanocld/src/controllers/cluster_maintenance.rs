@@ -0,0 +1,89 @@
+use ntex::web;
+use serde::{Serialize, Deserialize};
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::models::{Pool, ClusterMaintenancePartial};
+
+use super::utils::{gen_nsp_key_by_name, get_actor};
+
+use crate::errors::HttpResponseError;
+
+#[derive(Serialize, Deserialize)]
+pub struct ClusterMaintenanceQuery {
+  namespace: Option<String>,
+}
+
+/// Turn the maintenance of a cluster on or off and update his error pages
+/// Pages are refused when no route nor http template of the cluster
+/// includes them with `{{{error_pages}}}`
+#[cfg_attr(feature = "openapi", utoipa::path(
+  post,
+  path = "/clusters/{c_name}/maintenance",
+  request_body = ClusterMaintenancePartial,
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is stored is empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "Updated cluster maintenance", body = ClusterMaintenanceItem),
+    (status = 400, description = "Invalid maintenance or nginx configuration", body = ApiError),
+    (status = 404, description = "Cluster name or Namespace not valid", body = ApiError),
+  ),
+))]
+#[web::post("/clusters/{c_name}/maintenance")]
+async fn update_cluster_maintenance(
+  req: web::HttpRequest,
+  pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
+  docker_api: web::types::State<bollard::Docker>,
+  c_name: web::types::Path<String>,
+  web::types::Query(qs): web::types::Query<ClusterMaintenanceQuery>,
+  web::types::Json(payload): web::types::Json<ClusterMaintenancePartial>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &c_name.into_inner());
+  let cluster = repositories::cluster::find_by_key(cluster_key, &pool).await?;
+  let maintenance = services::maintenance::update(
+    &cluster,
+    payload,
+    &config,
+    &docker_api,
+    &pool,
+    &get_actor(&req),
+  )
+  .await?;
+
+  Ok(web::HttpResponse::Ok().json(&maintenance))
+}
+
+/// Inspect the maintenance of a cluster
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/clusters/{c_name}/maintenance",
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is stored is empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "Cluster maintenance, disabled when never set", body = ClusterMaintenanceItem),
+    (status = 400, description = "Generic database error", body = ApiError),
+    (status = 404, description = "Cluster name or Namespace not valid", body = ApiError),
+  ),
+))]
+#[web::get("/clusters/{c_name}/maintenance")]
+async fn inspect_cluster_maintenance(
+  pool: web::types::State<Pool>,
+  c_name: web::types::Path<String>,
+  web::types::Query(qs): web::types::Query<ClusterMaintenanceQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &c_name.into_inner());
+  let cluster = repositories::cluster::find_by_key(cluster_key, &pool).await?;
+  let maintenance = services::maintenance::inspect(&cluster, &pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&maintenance))
+}
+
+pub fn ntex_config(config: &mut web::ServiceConfig) {
+  config.service(update_cluster_maintenance);
+  config.service(inspect_cluster_maintenance);
+}
@@ -4,9 +4,11 @@ use ntex::web;
 use ntex::http::StatusCode;
 use serde::{Serialize, Deserialize};
 
-use crate::errors::HttpResponseError;
+use crate::services;
+use crate::config::DaemonConfig;
 use crate::repositories::{cluster, cluster_network, self};
-use crate::models::{ClusterNetworkPartial, Pool};
+use crate::models::{ClusterNetworkPartial, NamespaceUsage, Pool};
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClusterNetworkQuery {
@@ -63,10 +65,11 @@ async fn list_cluster_network(
 #[web::post("/clusters/{c_name}/networks")]
 async fn create_cluster_network(
   pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
   docker_api: web::types::State<bollard::Docker>,
   c_name: web::types::Path<String>,
   web::types::Query(qs): web::types::Query<ClusterNetworkQuery>,
-  web::types::Json(payload): web::types::Json<ClusterNetworkPartial>,
+  web::types::Json(mut payload): web::types::Json<ClusterNetworkPartial>,
 ) -> Result<web::HttpResponse, HttpResponseError> {
   let name = c_name.into_inner();
   let nsp = match qs.namespace {
@@ -89,13 +92,71 @@ async fn create_cluster_network(
       msg: format!("Unable to create network with name {} a similar network have same name", name),
     });
   }
-  let config = bollard::network::CreateNetworkOptions {
+  let requested = NamespaceUsage {
+    networks: 1,
+    ..Default::default()
+  };
+  services::quota::ensure_available(&nsp, requested, &pool).await?;
+  let subnet = match &payload.subnet {
+    None => {
+      let subnet =
+        services::ipam::allocate_subnet(&config.network_pools, &docker_api)
+          .await
+          .map_err(|err| err.to_http_error())?;
+      services::ipam::validate_range(
+        &subnet,
+        payload.ip_range.as_deref(),
+        payload.gateway.as_deref(),
+      )
+      .map_err(|err| err.to_http_error())?;
+      subnet
+    }
+    Some(subnet) => services::ipam::validate_subnet(
+      subnet,
+      payload.ip_range.as_deref(),
+      payload.gateway.as_deref(),
+      &docker_api,
+    )
+    .await
+    .map_err(|err| err.to_http_error())?,
+  };
+  services::ipam::ensure_family(&subnet, false)
+    .map_err(|err| err.to_http_error())?;
+  payload.subnet = Some(subnet.to_string());
+  let mut ipam_configs = vec![bollard::models::IpamConfig {
+    subnet: payload.subnet.to_owned(),
+    ip_range: payload.ip_range.to_owned(),
+    gateway: payload.gateway.to_owned(),
+    ..Default::default()
+  }];
+  if let Some(ipv6_subnet) = &payload.ipv6_subnet {
+    let ipv6_subnet =
+      services::ipam::validate_subnet(ipv6_subnet, None, None, &docker_api)
+        .await
+        .map_err(|err| err.to_http_error())?;
+    services::ipam::ensure_family(&ipv6_subnet, true)
+      .map_err(|err| err.to_http_error())?;
+    payload.ipv6_subnet = Some(ipv6_subnet.to_string());
+    ipam_configs.push(bollard::models::IpamConfig {
+      subnet: payload.ipv6_subnet.to_owned(),
+      ..Default::default()
+    });
+  }
+  let network_options = bollard::network::CreateNetworkOptions {
     name: gen_name,
     driver: String::from("bridge"),
+    internal: payload.internal.unwrap_or(false),
+    enable_ipv6: payload.ipv6_subnet.is_some(),
+    options: payload.driver_options.to_owned().unwrap_or_default(),
+    ipam: bollard::models::Ipam {
+      driver: Some(String::from("default")),
+      config: Some(ipam_configs),
+      ..Default::default()
+    },
     labels,
     ..Default::default()
   };
-  let id = match docker_api.create_network(config).await {
+  let id = match docker_api.create_network(network_options).await {
     Err(err) => {
       return Err(HttpResponseError {
         status: StatusCode::BAD_REQUEST,
@@ -132,8 +193,17 @@ async fn create_cluster_network(
       msg: String::from("Unable to get ipam config"),
     })?;
 
+  // The default gateway is the one of the ipv4 subnet
   let default_gateway = ipam_config
-    .get(0)
+    .iter()
+    .find(|config| {
+      config
+        .subnet
+        .as_ref()
+        .and_then(|subnet| subnet.parse::<services::ipam::Cidr>().ok())
+        .map(|subnet| subnet.addr.is_ipv4())
+        .unwrap_or(false)
+    })
     .ok_or(HttpResponseError {
       status: StatusCode::INTERNAL_SERVER_ERROR,
       msg: String::from("Unable to get ipam config"),
@@ -173,7 +243,7 @@ struct InspectClusterNetworkPath {
     ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is if empty we use 'default' as value"),
   ),
   responses(
-    (status = 200, description = "Network item", body = ClusterNetworkItem),
+    (status = 200, description = "Network item with his docker state", body = ClusterNetworkInspect),
     (status = 400, description = "Generic database error", body = ApiError),
     (status = 404, description = "Namespace name not valid", body = ApiError),
   ),
@@ -181,6 +251,7 @@ struct InspectClusterNetworkPath {
 #[web::get("/clusters/{c_name}/networks/{n_name}/inspect")]
 async fn inspect_cluster_network_by_name(
   pool: web::types::State<Pool>,
+  docker_api: web::types::State<bollard::Docker>,
   url_path: web::types::Path<InspectClusterNetworkPath>,
   web::types::Query(qs): web::types::Query<ClusterNetworkQuery>,
 ) -> Result<web::HttpResponse, HttpResponseError> {
@@ -192,6 +263,8 @@ async fn inspect_cluster_network_by_name(
   };
   let gen_key = nsp + "-" + &c_name + "-" + &n_name;
   let network = cluster_network::find_by_key(gen_key, &pool).await?;
+  let network =
+    services::cluster_network::inspect(network, &docker_api, &pool).await?;
   Ok(web::HttpResponse::Ok().json(&network))
 }
 
@@ -225,6 +298,11 @@ async fn delete_cluster_network_by_name(
   };
   let gen_key = nsp + "-" + &c_name + "-" + &n_name;
   let network = cluster_network::find_by_key(gen_key, &pool).await?;
+  repositories::cluster_network_policy::delete_by_key(
+    network.key.to_owned(),
+    &pool,
+  )
+  .await?;
 
   if let Err(err) = docker_api.remove_network(&network.docker_network_id).await
   {
@@ -235,6 +313,7 @@ async fn delete_cluster_network_by_name(
   }
 
   let res = cluster_network::delete_by_key(network.key, &pool).await?;
+  services::network_policy::reconcile(&docker_api, &pool).await?;
   Ok(web::HttpResponse::Ok().json(&res))
 }
 
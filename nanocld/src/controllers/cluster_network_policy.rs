@@ -0,0 +1,152 @@
+use ntex::web;
+use ntex::http::StatusCode;
+use serde::{Serialize, Deserialize};
+
+use crate::{services, repositories};
+use crate::errors::HttpResponseError;
+use crate::models::{Pool, ClusterNetworkPolicyItem, ClusterNetworkPolicyPartial};
+
+use super::utils::gen_nsp_key_by_name;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClusterNetworkPolicyQuery {
+  pub(crate) namespace: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClusterNetworkPolicyPath {
+  c_name: String,
+  n_name: String,
+}
+
+/// List network policies of given cluster
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/clusters/{c_name}/network_policies",
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is if empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "List of network policies", body = [ClusterNetworkPolicyItem]),
+    (status = 400, description = "Generic database error", body = ApiError),
+    (status = 404, description = "Cluster not found", body = ApiError),
+  ),
+))]
+#[web::get("/clusters/{c_name}/network_policies")]
+async fn list_cluster_network_policy(
+  pool: web::types::State<Pool>,
+  c_name: web::types::Path<String>,
+  web::types::Query(qs): web::types::Query<ClusterNetworkPolicyQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &c_name.into_inner());
+  let cluster = repositories::cluster::find_by_key(cluster_key, &pool).await?;
+  let items = repositories::cluster_network_policy::list_by_cluster_key(
+    cluster.key,
+    &pool,
+  )
+  .await?;
+
+  Ok(web::HttpResponse::Ok().json(&items))
+}
+
+/// Create or replace the policy of a cluster network
+#[cfg_attr(feature = "openapi", utoipa::path(
+  put,
+  request_body = ClusterNetworkPolicyPartial,
+  path = "/clusters/{c_name}/networks/{n_name}/policy",
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("n_name" = String, path, description = "name of the network"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is if empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "Network policy", body = ClusterNetworkPolicyItem),
+    (status = 400, description = "Allowed network not valid", body = ApiError),
+    (status = 404, description = "Cluster network not found", body = ApiError),
+  ),
+))]
+#[web::put("/clusters/{c_name}/networks/{n_name}/policy")]
+async fn put_cluster_network_policy(
+  pool: web::types::State<Pool>,
+  docker_api: web::types::State<bollard::Docker>,
+  url_path: web::types::Path<ClusterNetworkPolicyPath>,
+  web::types::Query(qs): web::types::Query<ClusterNetworkPolicyQuery>,
+  web::types::Json(payload): web::types::Json<ClusterNetworkPolicyPartial>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &url_path.c_name);
+  let network_key = cluster_key.to_owned() + "-" + &url_path.n_name;
+  let network =
+    repositories::cluster_network::find_by_key(network_key, &pool).await?;
+  let mut allowed_networks = Vec::new();
+  for allowed in payload.allowed_networks {
+    let allowed_key = match allowed.split_once('/') {
+      None => cluster_key.to_owned() + "-" + &allowed,
+      Some((c_name, n_name)) => {
+        gen_nsp_key_by_name(&qs.namespace, &c_name.to_owned()) + "-" + n_name
+      }
+    };
+    if let Err(err) =
+      repositories::cluster_network::find_by_key(allowed_key.to_owned(), &pool)
+        .await
+    {
+      if err.status != StatusCode::NOT_FOUND {
+        return Err(err);
+      }
+      return Err(HttpResponseError {
+        msg: format!("Unable to allow network {} it doesn't exist", allowed),
+        status: StatusCode::BAD_REQUEST,
+      });
+    }
+    if !allowed_networks.contains(&allowed_key) {
+      allowed_networks.push(allowed_key);
+    }
+  }
+  let item = ClusterNetworkPolicyItem {
+    key: network.key.to_owned(),
+    cluster_key,
+    network_key: network.key,
+    allowed_networks,
+    allow_egress: payload.allow_egress,
+  };
+  let item = repositories::cluster_network_policy::upsert(item, &pool).await?;
+  services::network_policy::reconcile(&docker_api, &pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&item))
+}
+
+/// Delete the policy of a cluster network
+#[cfg_attr(feature = "openapi", utoipa::path(
+  delete,
+  path = "/clusters/{c_name}/networks/{n_name}/policy",
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("n_name" = String, path, description = "name of the network"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is if empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "Pg delete response", body = PgDeleteGeneric),
+    (status = 400, description = "Generic database error", body = ApiError),
+  ),
+))]
+#[web::delete("/clusters/{c_name}/networks/{n_name}/policy")]
+async fn delete_cluster_network_policy(
+  pool: web::types::State<Pool>,
+  docker_api: web::types::State<bollard::Docker>,
+  url_path: web::types::Path<ClusterNetworkPolicyPath>,
+  web::types::Query(qs): web::types::Query<ClusterNetworkPolicyQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &url_path.c_name);
+  let key = cluster_key + "-" + &url_path.n_name;
+  let res =
+    repositories::cluster_network_policy::delete_by_key(key, &pool).await?;
+  services::network_policy::reconcile(&docker_api, &pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+pub fn ntex_config(config: &mut web::ServiceConfig) {
+  config.service(list_cluster_network_policy);
+  config.service(put_cluster_network_policy);
+  config.service(delete_cluster_network_policy);
+}
@@ -0,0 +1,160 @@
+use ntex::web;
+use serde::{Serialize, Deserialize};
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::models::{Pool, ClusterRoutePartial};
+
+use super::utils::{gen_nsp_key_by_name, get_actor};
+
+use crate::errors::HttpResponseError;
+
+#[derive(Serialize, Deserialize)]
+pub struct ClusterRouteQuery {
+  namespace: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ClusterRoutePath {
+  c_name: String,
+  r_name: String,
+}
+
+/// Create a route and render it in the proxy
+#[cfg_attr(feature = "openapi", utoipa::path(
+  post,
+  path = "/clusters/{c_name}/routes",
+  request_body = ClusterRoutePartial,
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is stored is empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 201, description = "Fresh cluster route", body = ClusterRouteItem),
+    (status = 400, description = "Invalid route or nginx configuration", body = ApiError),
+    (status = 404, description = "Cluster name or Namespace not valid", body = ApiError),
+    (status = 409, description = "A route already match the domain and path", body = ApiError),
+  ),
+))]
+#[web::post("/clusters/{c_name}/routes")]
+async fn create_cluster_route(
+  req: web::HttpRequest,
+  pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
+  docker_api: web::types::State<bollard::Docker>,
+  c_name: web::types::Path<String>,
+  web::types::Query(qs): web::types::Query<ClusterRouteQuery>,
+  web::types::Json(payload): web::types::Json<ClusterRoutePartial>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &c_name.into_inner());
+  let cluster = repositories::cluster::find_by_key(cluster_key, &pool).await?;
+  let route = services::route::create(
+    &cluster,
+    payload,
+    &config,
+    &docker_api,
+    &pool,
+    &get_actor(&req),
+  )
+  .await?;
+
+  Ok(web::HttpResponse::Created().json(&route))
+}
+
+/// List routes of a cluster
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/clusters/{c_name}/routes",
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is stored is empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "List of routes for given cluster", body = [ClusterRouteItem]),
+    (status = 400, description = "Generic database error", body = ApiError),
+  ),
+))]
+#[web::get("/clusters/{c_name}/routes")]
+async fn list_cluster_route(
+  pool: web::types::State<Pool>,
+  c_name: web::types::Path<String>,
+  web::types::Query(qs): web::types::Query<ClusterRouteQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &c_name.into_inner());
+  let routes =
+    repositories::cluster_route::list_by_cluster(cluster_key, &pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&routes))
+}
+
+/// Get cluster route by it's name
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/clusters/{c_name}/routes/{r_name}",
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("r_name" = String, path, description = "name of the route"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is stored is empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "Cluster route", body = ClusterRouteItem),
+    (status = 404, description = "Route not found", body = ApiError),
+  ),
+))]
+#[web::get("/clusters/{c_name}/routes/{r_name}")]
+async fn get_cluster_route_by_name(
+  pool: web::types::State<Pool>,
+  url_path: web::types::Path<ClusterRoutePath>,
+  web::types::Query(qs): web::types::Query<ClusterRouteQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let route_name = format!("{}-{}", &url_path.c_name, &url_path.r_name);
+  let route_key = gen_nsp_key_by_name(&qs.namespace, &route_name);
+
+  let res = repositories::cluster_route::find_by_key(route_key, &pool).await?;
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+/// Delete a route and remove it from the proxy
+#[cfg_attr(feature = "openapi", utoipa::path(
+  delete,
+  path = "/clusters/{c_name}/routes/{r_name}",
+  params(
+    ("c_name" = String, path, description = "name of the cluster"),
+    ("r_name" = String, path, description = "name of the route"),
+    ("namespace" = Option<String>, query, description = "Name of the namespace where the cluster is stored is empty we use 'global' as value"),
+  ),
+  responses(
+    (status = 200, description = "Generic delete response", body = PgDeleteGeneric),
+    (status = 404, description = "Cluster or route not found", body = ApiError),
+  ),
+))]
+#[web::delete("/clusters/{c_name}/routes/{r_name}")]
+async fn delete_cluster_route(
+  req: web::HttpRequest,
+  pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
+  docker_api: web::types::State<bollard::Docker>,
+  url_path: web::types::Path<ClusterRoutePath>,
+  web::types::Query(qs): web::types::Query<ClusterRouteQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &url_path.c_name);
+  let cluster = repositories::cluster::find_by_key(cluster_key, &pool).await?;
+  let res = services::route::delete(
+    &cluster,
+    &url_path.r_name,
+    &config,
+    &docker_api,
+    &pool,
+    &get_actor(&req),
+  )
+  .await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+pub fn ntex_config(config: &mut web::ServiceConfig) {
+  config.service(create_cluster_route);
+  config.service(list_cluster_route);
+  config.service(get_cluster_route_by_name);
+  config.service(delete_cluster_route);
+}
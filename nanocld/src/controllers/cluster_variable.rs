@@ -1,10 +1,10 @@
 use ntex::web;
 use serde::{Serialize, Deserialize};
 
-use crate::repositories;
-use crate::models::{Pool, ClusterVariablePartial};
+use crate::{services, repositories};
+use crate::models::{Pool, ClusterVariablePartial, EventKind};
 
-use super::utils::gen_nsp_key_by_name;
+use super::utils::{gen_nsp_key_by_name, get_actor};
 
 use crate::errors::HttpResponseError;
 
@@ -30,6 +30,7 @@ pub struct ClusterVaribleQuery {
 ))]
 #[web::post("/clusters/{c_name}/variables")]
 async fn create_cluster_variable(
+  req: web::HttpRequest,
   pool: web::types::State<Pool>,
   c_name: web::types::Path<String>,
   web::types::Query(qs): web::types::Query<ClusterVaribleQuery>,
@@ -39,12 +40,23 @@ async fn create_cluster_variable(
   let cluster_key = gen_nsp_key_by_name(&qs.namespace, &name);
 
   repositories::cluster::find_by_key(cluster_key.to_owned(), &pool).await?;
-  let cluster_var = repositories::cluster_variable::create(
+  let target = payload.name.to_owned();
+  let res = repositories::cluster_variable::create(
     cluster_key.to_owned(),
     payload,
     &pool,
   )
-  .await?;
+  .await;
+  services::history::record(
+    &cluster_key,
+    EventKind::Variable,
+    &get_actor(&req),
+    Some(target),
+    &res,
+    &pool,
+  )
+  .await;
+  let cluster_var = res?;
 
   Ok(web::HttpResponse::Created().json(&cluster_var))
 }
@@ -105,15 +117,26 @@ pub struct ClusterVariablePath {
 ))]
 #[web::delete("/clusters/{c_name}/variables/{v_name}")]
 async fn delete_cluster_variable(
+  req: web::HttpRequest,
   pool: web::types::State<Pool>,
   url_path: web::types::Path<ClusterVariablePath>,
   web::types::Query(qs): web::types::Query<ClusterVaribleQuery>,
 ) -> Result<web::HttpResponse, HttpResponseError> {
   let var_name = format!("{}-{}", &url_path.c_name, &url_path.v_name);
   let var_key = gen_nsp_key_by_name(&qs.namespace, &var_name);
-
-  let res =
-    repositories::cluster_variable::delete_by_key(var_key, &pool).await?;
+  let cluster_key = gen_nsp_key_by_name(&qs.namespace, &url_path.c_name);
+
+  let res = repositories::cluster_variable::delete_by_key(var_key, &pool).await;
+  services::history::record(
+    &cluster_key,
+    EventKind::Variable,
+    &get_actor(&req),
+    Some(url_path.v_name.to_owned()),
+    &res,
+    &pool,
+  )
+  .await;
+  let res = res?;
   Ok(web::HttpResponse::Ok().json(&res))
 }
 
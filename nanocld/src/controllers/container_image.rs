@@ -3,12 +3,13 @@ use ntex::{web, http::StatusCode, channel::mpsc, rt, util::Bytes};
 
 use crate::{
   errors::HttpResponseError,
-  models::{ContainerImagePartial, Pool},
+  models::{ContainerImagePartial, Pool, EventKind},
   config::DaemonConfig,
-  repositories,
-  services::{self, cluster::JoinCargoOptions},
+  repositories, services,
 };
 
+use super::utils::get_actor;
+
 #[web::get("/containers/images")]
 async fn list_container_image(
   docker_api: web::types::State<bollard::Docker>,
@@ -103,6 +104,7 @@ async fn delete_container_image_by_name(
 
 #[web::post("/containers/images/{id_or_name}/deploy")]
 async fn deploy_container_image(
+  req: web::HttpRequest,
   id_or_name: web::types::Path<String>,
   pool: web::types::State<Pool>,
   daemon_config: web::types::State<DaemonConfig>,
@@ -120,55 +122,28 @@ async fn deploy_container_image(
       repositories::cluster_cargo::find_by_cargo_key(cargo.key, &pool).await?;
     let mut cluster_cargoes_stream = stream::iter(cluster_cargoes);
     while let Some(cluster_cargo) = cluster_cargoes_stream.next().await {
-      let network = repositories::cluster_network::find_by_key(
-        cluster_cargo.network_key,
-        &pool,
-      )
-      .await?;
-
-      let cluster = repositories::cluster::find_by_key(
-        cluster_cargo.cluster_key.to_owned(),
+      let cluster_key = cluster_cargo.cluster_key.to_owned();
+      let target = cluster_cargo.cargo_key.to_owned();
+      let lock = services::cluster::lock_cluster_cargoes().await;
+      let res = services::cluster::redeploy_cargo(
+        cluster_cargo,
+        &daemon_config,
+        &docker_api,
         &pool,
+        &get_actor(&req),
       )
-      .await?;
-      let cargo = repositories::cargo::find_by_key(
-        cluster_cargo.cargo_key.to_owned(),
+      .await;
+      drop(lock);
+      services::history::record(
+        &cluster_key,
+        EventKind::Deploy,
+        &get_actor(&req),
+        Some(target),
+        &res,
         &pool,
       )
-      .await?;
-      let cnt_to_remove = services::cluster::list_containers(
-        &cluster_cargo.cluster_key,
-        &cluster_cargo.cargo_key,
-        &docker_api,
-      )
-      .await?;
-
-      println!("container to remove {:#?}", &cnt_to_remove);
-
-      let opts = JoinCargoOptions {
-        cluster: cluster.to_owned(),
-        cargo,
-        network,
-        is_creating_relation: false,
-      };
-
-      services::cluster::join_cargo(&opts, &docker_api, &pool).await?;
-
-      services::cluster::start(&cluster, &daemon_config, &pool, &docker_api)
-        .await?;
-
-      let mut stream = stream::iter(cnt_to_remove);
-
-      while let Some(container) = stream.next().await {
-        let options = Some(bollard::container::RemoveContainerOptions {
-          force: true,
-          ..Default::default()
-        });
-        println!("removing container {:#?}", &container);
-        docker_api
-          .remove_container(&container.id.clone().unwrap_or_default(), options)
-          .await?;
-      }
+      .await;
+      res?;
     }
   }
 
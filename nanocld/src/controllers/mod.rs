@@ -11,10 +11,19 @@ pub mod cargo;
 pub mod git_repository;
 /// Manage cluster network
 pub mod cluster_network;
+/// Manage cluster network policies
+pub mod cluster_network_policy;
 /// Manage nginx template
 pub mod nginx_template;
+/// Manage proxy certificates
+pub mod certificate;
 /// Manage cluster variable
 pub mod cluster_variable;
+/// Manage cluster routes
+pub mod cluster_route;
+pub mod cluster_access_policy;
+pub mod cluster_auth_policy;
+pub mod cluster_maintenance;
 /// Manage container_image
 pub mod container_image;
 /// Manage nginx logs
@@ -1,11 +1,24 @@
 /// Manage nanocl namespace
 use ntex::web;
+use serde::{Serialize, Deserialize};
 
-use crate::repositories::namespace;
-use crate::models::{NamespacePartial, Pool};
+use crate::services;
+use crate::config::DaemonConfig;
+use crate::repositories::{namespace, namespace_quota};
+use crate::models::{
+  NamespacePartial, NamespaceInspect, NamespaceQuotaItem,
+  NamespaceQuotaPartial, Pool,
+};
 
 use crate::errors::HttpResponseError;
 
+use super::utils::get_actor;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamespaceDeleteQuery {
+  pub(crate) dry_run: Option<bool>,
+}
+
 /// List all namespace
 #[cfg_attr(feature = "openapi", utoipa::path(
   get,
@@ -44,25 +57,38 @@ async fn create_namespace(
   Ok(web::HttpResponse::Created().json(&item))
 }
 
-/// Delete namespace by it's name
+/// Delete namespace by it's name with everything it contains
 #[cfg_attr(feature = "openapi", utoipa::path(
     delete,
     path = "/namespaces/{name}",
     responses(
-        (status = 200, description = "database generic delete response", body = PgDeleteGeneric),
+        (status = 200, description = "Resources removed with the namespace", body = NamespaceDeleteReport),
+        (status = 404, description = "Namespace not found", body = ApiError),
     ),
     params(
         ("name" = String, path, description = "name of the namespace"),
+        ("dry_run" = Option<bool>, query, description = "Only list what would be removed"),
     )
 ))]
 #[web::delete("/namespaces/{name}")]
 async fn delete_namespace_by_name(
+  req: web::HttpRequest,
   pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
+  docker_api: web::types::State<bollard::Docker>,
   id: web::types::Path<String>,
+  web::types::Query(qs): web::types::Query<NamespaceDeleteQuery>,
 ) -> Result<web::HttpResponse, HttpResponseError> {
-  let id_or_name = id.into_inner();
-  let res = namespace::delete_by_name(id_or_name, &pool).await?;
-  Ok(web::HttpResponse::Ok().json(&res))
+  let report = services::namespace::delete_cascade(
+    &id.into_inner(),
+    qs.dry_run.unwrap_or(false),
+    &config,
+    &docker_api,
+    &pool,
+    &get_actor(&req),
+  )
+  .await?;
+  Ok(web::HttpResponse::Ok().json(&report))
 }
 
 /// Inspect namespace by it's name
@@ -70,7 +96,7 @@ async fn delete_namespace_by_name(
   get,
   path = "/namespaces/{name}/inspect",
   responses(
-      (status = 200, description = "Namespace found", body = NamespaceItem),
+      (status = 200, description = "Namespace with his quota and usage", body = NamespaceInspect),
       (status = 404, description = "Namespace not found", body = ApiError),
   ),
   params(
@@ -83,11 +109,75 @@ async fn inspect_namespace_by_name(
   pool: web::types::State<Pool>,
 ) -> Result<web::HttpResponse, HttpResponseError> {
   let name = name.into_inner();
-  let item = namespace::inspect_by_name(name, &pool).await?;
+  let item = namespace::inspect_by_name(name.to_owned(), &pool).await?;
+  let quota =
+    namespace_quota::find_by_namespace(name.to_owned(), &pool).await?;
+  let usage = services::quota::get_usage(&name, &pool).await?;
+  let item = NamespaceInspect {
+    namespace: item,
+    quota,
+    usage,
+  };
 
   Ok(web::HttpResponse::Ok().json(&item))
 }
 
+/// Set resource quota of a namespace
+#[cfg_attr(feature = "openapi", utoipa::path(
+  put,
+  path = "/namespaces/{name}/quota",
+  request_body = NamespaceQuotaPartial,
+  responses(
+      (status = 200, description = "Namespace quota", body = NamespaceQuotaItem),
+      (status = 404, description = "Namespace not found", body = ApiError),
+  ),
+  params(
+    ("name" = String, path, description = "name of the namespace"),
+  )
+))]
+#[web::put("/namespaces/{name}/quota")]
+async fn put_namespace_quota(
+  name: web::types::Path<String>,
+  pool: web::types::State<Pool>,
+  web::types::Json(payload): web::types::Json<NamespaceQuotaPartial>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let namespace = namespace::inspect_by_name(name.into_inner(), &pool).await?;
+  let item = NamespaceQuotaItem {
+    namespace_name: namespace.name,
+    max_cargoes: payload.max_cargoes,
+    max_clusters: payload.max_clusters,
+    max_networks: payload.max_networks,
+    max_replicas: payload.max_replicas,
+    max_memory: payload.max_memory,
+    max_cpu: payload.max_cpu,
+  };
+  let item = namespace_quota::upsert(item, &pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&item))
+}
+
+/// Remove resource quota of a namespace
+#[cfg_attr(feature = "openapi", utoipa::path(
+  delete,
+  path = "/namespaces/{name}/quota",
+  responses(
+      (status = 200, description = "database generic delete response", body = PgDeleteGeneric),
+  ),
+  params(
+    ("name" = String, path, description = "name of the namespace"),
+  )
+))]
+#[web::delete("/namespaces/{name}/quota")]
+async fn delete_namespace_quota(
+  name: web::types::Path<String>,
+  pool: web::types::State<Pool>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let res =
+    namespace_quota::delete_by_namespace(name.into_inner(), &pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
 /// # ntex config
 /// Bind namespace routes to ntex http server
 ///
@@ -106,13 +196,19 @@ pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(create_namespace);
   config.service(inspect_namespace_by_name);
   config.service(delete_namespace_by_name);
+  config.service(put_namespace_quota);
+  config.service(delete_namespace_quota);
 }
 
 #[cfg(test)]
 mod test_namespace {
   use serde_json::json;
+  use ntex::http::StatusCode;
 
-  use crate::models::{NamespacePartial, PgDeleteGeneric};
+  use crate::models::{
+    NamespacePartial, NamespaceInspect, NamespaceQuotaItem,
+    NamespaceDeleteReport, PgDeleteGeneric,
+  };
   use crate::utils::test::*;
 
   use super::ntex_config;
@@ -164,15 +260,80 @@ mod test_namespace {
     Ok(())
   }
 
-  async fn test_delete(srv: &TestServer) -> TestReturn {
+  async fn test_quota(srv: &TestServer) -> TestReturn {
     let mut resp = srv
-      .delete(format!("/namespaces/{name}", name = "controller-default"))
+      .put(format!(
+        "/namespaces/{name}/quota",
+        name = "controller-default"
+      ))
+      .send_json(&json!({
+        "max_cargoes": 2,
+        "max_memory": 1024,
+      }))
+      .await?;
+
+    assert!(resp.status().is_success());
+    let body = resp.json::<NamespaceQuotaItem>().await?;
+    assert_eq!(body.max_cargoes, Some(2));
+    assert_eq!(body.max_clusters, None);
+
+    let mut resp = srv
+      .get(format!(
+        "/namespaces/{name}/inspect",
+        name = "controller-default"
+      ))
+      .send()
+      .await?;
+
+    let body = resp.json::<NamespaceInspect>().await?;
+    assert_eq!(body.quota.and_then(|quota| quota.max_memory), Some(1024));
+    assert_eq!(body.usage.cargoes, 0);
+
+    let mut resp = srv
+      .delete(format!(
+        "/namespaces/{name}/quota",
+        name = "controller-default"
+      ))
       .send()
       .await?;
 
     let body = resp.json::<PgDeleteGeneric>().await?;
     assert_eq!(body.count, 1);
+    Ok(())
+  }
+
+  async fn test_delete(srv: &TestServer) -> TestReturn {
+    let mut resp = srv
+      .delete(format!(
+        "/namespaces/{name}?dry_run=true",
+        name = "controller-default"
+      ))
+      .send()
+      .await?;
+
+    assert!(resp.status().is_success());
+    let body = resp.json::<NamespaceDeleteReport>().await?;
+    assert!(body.dry_run);
+
+    let mut resp = srv
+      .delete(format!("/namespaces/{name}", name = "controller-default"))
+      .send()
+      .await?;
+
     assert!(resp.status().is_success());
+    let body = resp.json::<NamespaceDeleteReport>().await?;
+    assert!(!body.dry_run);
+    assert!(body.clusters.is_empty());
+
+    let resp = srv
+      .get(format!(
+        "/namespaces/{name}/inspect",
+        name = "controller-default"
+      ))
+      .send()
+      .await?;
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
     Ok(())
   }
 
@@ -183,6 +344,7 @@ mod test_namespace {
     test_fail_create(&srv).await?;
     test_create(&srv).await?;
     test_inspect_by_id(&srv).await?;
+    test_quota(&srv).await?;
     test_list(&srv).await?;
     test_delete(&srv).await?;
     Ok(())
@@ -2,21 +2,56 @@ use ntex::{web, rt};
 use ntex::{channel::mpsc::channel, util::Bytes};
 use futures::{
   SinkExt, StreamExt,
+  channel::oneshot,
   channel::mpsc::{unbounded, UnboundedSender},
 };
 
+use crate::services;
+use crate::models::{
+  Pool, NginxLogSearchQuery, NginxLogStreamQuery, NginxStatsQuery,
+};
 use crate::events::system::EventSystemClient;
 use crate::{errors::HttpResponseError, events::system::EventMessage};
 
+use super::utils::gen_nsp_key_by_name;
+use super::system::event_system_error;
+
+/// Stream access logs as they are written, one json log per line
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/nginx/logs",
+  params(
+    ("host" = Option<String>, query, description = "Host of the requests"),
+    ("cluster" = Option<String>, query, description = "Cluster whose domains and route domains are used as hosts"),
+    ("namespace" = Option<String>, query, description = "Namespace of the cluster default to 'global'"),
+    ("status_min" = Option<i32>, query, description = "Minimum status of the responses"),
+    ("status_max" = Option<i32>, query, description = "Maximum status of the responses"),
+    ("path" = Option<String>, query, description = "Regex matched against the uri of the requests"),
+    ("sample" = Option<f64>, query, description = "Part of the matching logs to stream default to 1"),
+  ),
+  responses(
+    (status = 200, description = "Stream of logs", body = NginxLogItem),
+    (status = 400, description = "Invalid filters", body = ApiError),
+    (status = 404, description = "Cluster not found", body = ApiError),
+  ),
+))]
 #[web::get("nginx/logs")]
 async fn stream_nginx_logs(
+  pool: web::types::State<Pool>,
   system_event: web::types::State<UnboundedSender<EventMessage>>,
+  web::types::Query(qs): web::types::Query<NginxLogStreamQuery>,
 ) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = qs
+    .cluster
+    .as_ref()
+    .map(|name| gen_nsp_key_by_name(&qs.namespace, name));
+  let filter =
+    services::nginx_log::stream_filter(qs, cluster_key, &pool).await?;
   let mut system_event = system_event.as_ref();
   let (tx, mut rx) = unbounded::<Bytes>();
   let (tx_body, rx_body) = channel::<Result<Bytes, web::error::Error>>();
 
-  let client = EventSystemClient::new(tx);
+  let client = EventSystemClient::new(tx, filter);
   let _res = system_event.send(EventMessage::NginxLog(client)).await;
   rt::spawn(async move {
     while let Some(msg) = rx.next().await {
@@ -34,6 +69,143 @@ async fn stream_nginx_logs(
   )
 }
 
+/// Search in stored access logs
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/nginx/logs/search",
+  params(
+    ("since" = Option<String>, query, description = "Logs at or after this rfc3339 date"),
+    ("until" = Option<String>, query, description = "Logs before this rfc3339 date"),
+    ("host" = Option<String>, query, description = "Host of the request"),
+    ("uri_prefix" = Option<String>, query, description = "Start of the uri of the request"),
+    ("status_min" = Option<i32>, query, description = "Minimum status of the response"),
+    ("status_max" = Option<i32>, query, description = "Maximum status of the response"),
+    ("method" = Option<String>, query, description = "Method of the request"),
+    ("remote_addr" = Option<String>, query, description = "Address of the client"),
+    ("min_request_time" = Option<f64>, query, description = "Minimum request time in seconds"),
+    ("sort" = Option<NginxLogSort>, query, description = "Order of logs default to date_desc"),
+    ("cursor" = Option<String>, query, description = "Cursor of the next page returned by a previous search"),
+    ("limit" = Option<i64>, query, description = "Maximum number of logs to return default to 100"),
+  ),
+  responses(
+    (status = 200, description = "Page of logs", body = NginxLogSearchResult),
+    (status = 400, description = "Invalid filters or cursor", body = ApiError),
+  ),
+))]
+#[web::get("/nginx/logs/search")]
+async fn search_nginx_logs(
+  pool: web::types::State<Pool>,
+  web::types::Query(qs): web::types::Query<NginxLogSearchQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let res = services::nginx_log::search(qs, &pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+/// Traffic statistics of a host, of a cluster or of all hosts
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/nginx/stats",
+  params(
+    ("since" = Option<String>, query, description = "Start of the window as rfc3339 date default to one hour before until"),
+    ("until" = Option<String>, query, description = "End of the window as rfc3339 date default to now"),
+    ("host" = Option<String>, query, description = "Host of the requests"),
+    ("cluster" = Option<String>, query, description = "Cluster whose domains and route domains are used as hosts"),
+    ("namespace" = Option<String>, query, description = "Namespace of the cluster default to 'global'"),
+    ("top" = Option<i64>, query, description = "Number of uris and user agents in tops default to 10"),
+  ),
+  responses(
+    (status = 200, description = "Traffic statistics", body = NginxStats),
+    (status = 400, description = "Invalid window or scope", body = ApiError),
+    (status = 404, description = "Cluster not found", body = ApiError),
+  ),
+))]
+#[web::get("/nginx/stats")]
+async fn get_nginx_stats(
+  pool: web::types::State<Pool>,
+  web::types::Query(qs): web::types::Query<NginxStatsQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let cluster_key = qs
+    .cluster
+    .as_ref()
+    .map(|name| gen_nsp_key_by_name(&qs.namespace, name));
+  let res = services::nginx_log::stats(qs, cluster_key, &pool).await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+/// State of the ingestion of access logs in database
+/// `throttled` and `lag_bytes` tell when logs are read faster than written
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/nginx/logs/ingestion",
+  responses(
+    (status = 200, description = "Ingestion metrics", body = NginxLogIngestionMetrics),
+  ),
+))]
+#[web::get("/nginx/logs/ingestion")]
+async fn get_nginx_log_ingestion(
+  system_event: web::types::State<UnboundedSender<EventMessage>>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let mut system_event = system_event.as_ref();
+  let (tx, rx) = oneshot::channel();
+  system_event
+    .send(EventMessage::NginxLogIngestion(tx))
+    .await
+    .map_err(event_system_error)?;
+  let metrics = rx.await.map_err(event_system_error)?;
+
+  Ok(web::HttpResponse::Ok().json(&metrics))
+}
+
 pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(stream_nginx_logs);
+  config.service(search_nginx_logs);
+  config.service(get_nginx_stats);
+  config.service(get_nginx_log_ingestion);
+}
+
+#[cfg(test)]
+mod test_nginx_log {
+  use ntex::http::StatusCode;
+
+  use crate::utils::test::*;
+
+  use super::ntex_config;
+
+  async fn test_search(srv: &TestServer) -> TestReturn {
+    let resp = srv
+      .get("/nginx/logs/search?status_min=400&sort=request_time_desc&limit=10")
+      .send()
+      .await?;
+    assert!(resp.status().is_success());
+
+    let resp = srv
+      .get("/nginx/logs/search?cursor=not-a-cursor")
+      .send()
+      .await?;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+  }
+
+  async fn test_stats(srv: &TestServer) -> TestReturn {
+    let resp = srv.get("/nginx/stats?top=5").send().await?;
+    assert!(resp.status().is_success());
+
+    let resp = srv
+      .get("/nginx/stats?host=example.com&cluster=dev")
+      .send()
+      .await?;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+  }
+
+  #[ntex::test]
+  async fn main() -> TestReturn {
+    let srv = generate_server(ntex_config).await;
+
+    test_search(&srv).await?;
+    test_stats(&srv).await?;
+    Ok(())
+  }
 }
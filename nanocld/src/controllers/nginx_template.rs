@@ -1,10 +1,28 @@
 use ntex::web;
+use serde::{Serialize, Deserialize};
 
-use crate::repositories;
-use crate::models::{Pool, NginxTemplateItem};
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::models::{
+  Pool, NginxTemplateItem, NginxTemplateLintBody, NginxTemplateUpdateBody,
+};
+
+use super::utils::get_actor;
 
 use crate::errors::HttpResponseError;
 
+#[derive(Serialize, Deserialize)]
+pub struct NginxTemplateDiffQuery {
+  from: i32,
+  to: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NginxTemplateRevisionPath {
+  name: String,
+  revision: i32,
+}
+
 /// List all nginx template
 #[cfg_attr(feature = "openapi", utoipa::path(
   get,
@@ -26,8 +44,10 @@ async fn list_nginx_template(
 #[cfg_attr(feature = "openapi", utoipa::path(
   post,
   path = "/nginx_templates",
+  request_body = NginxTemplateItem,
   responses(
-    (status = 201, description = "Nginx template created", body = NginxTemplateItem)
+    (status = 201, description = "Nginx template created", body = NginxTemplateItem),
+    (status = 400, description = "Invalid content with line and column of errors", body = ApiError),
   )
 ))]
 #[web::post("/nginx_templates")]
@@ -35,20 +55,176 @@ async fn create_nginx_template(
   pool: web::types::State<Pool>,
   web::types::Json(payload): web::types::Json<NginxTemplateItem>,
 ) -> Result<web::HttpResponse, HttpResponseError> {
-  let res = repositories::nginx_template::create(payload, &pool).await?;
+  let res = services::nginx_template::create(payload, &pool).await?;
 
   Ok(web::HttpResponse::Created().json(&res))
 }
 
+/// Check the content of a nginx template without storing it
+#[cfg_attr(feature = "openapi", utoipa::path(
+  post,
+  path = "/nginx_templates/lint",
+  request_body = NginxTemplateLintBody,
+  responses(
+    (status = 200, description = "Errors and warnings found", body = [NginxTemplateDiagnostic]),
+  )
+))]
+#[web::post("/nginx_templates/lint")]
+async fn lint_nginx_template(
+  pool: web::types::State<Pool>,
+  web::types::Json(payload): web::types::Json<NginxTemplateLintBody>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let res =
+    services::nginx_template::lint(&payload.name, &payload.content, &pool)
+      .await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+/// Update a nginx template with a new revision
+/// and render again the clusters using it
+#[cfg_attr(feature = "openapi", utoipa::path(
+  put,
+  path = "/nginx_templates/{name}",
+  request_body = NginxTemplateUpdateBody,
+  params(
+    ("name" = String, path, description = "Name of the nginx template"),
+  ),
+  responses(
+    (status = 200, description = "New revision with rendered clusters", body = NginxTemplateUpdateReport),
+    (status = 400, description = "Invalid content or clusters failing to render it", body = ApiError),
+    (status = 404, description = "Nginx template not found", body = ApiError),
+  ),
+))]
+#[web::put("/nginx_templates/{name}")]
+async fn update_nginx_template_by_name(
+  req: web::HttpRequest,
+  pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
+  docker_api: web::types::State<bollard::Docker>,
+  name: web::types::Path<String>,
+  web::types::Json(payload): web::types::Json<NginxTemplateUpdateBody>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let res = services::nginx_template::update(
+    name.into_inner(),
+    payload,
+    &config,
+    &docker_api,
+    &pool,
+    &get_actor(&req),
+  )
+  .await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+/// List revisions of a nginx template from the newest
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/nginx_templates/{name}/revisions",
+  params(
+    ("name" = String, path, description = "Name of the nginx template"),
+  ),
+  responses(
+    (status = 200, description = "Array of revisions", body = [NginxTemplateRevisionItem]),
+  ),
+))]
+#[web::get("/nginx_templates/{name}/revisions")]
+async fn list_nginx_template_revision(
+  pool: web::types::State<Pool>,
+  name: web::types::Path<String>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let items = repositories::nginx_template_revision::list_by_template(
+    name.into_inner(),
+    &pool,
+  )
+  .await?;
+
+  Ok(web::HttpResponse::Ok().json(&items))
+}
+
+/// Diff two revisions of a nginx template
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/nginx_templates/{name}/diff",
+  params(
+    ("name" = String, path, description = "Name of the nginx template"),
+    ("from" = i32, query, description = "Revision to compare from"),
+    ("to" = i32, query, description = "Revision to compare to"),
+  ),
+  responses(
+    (status = 200, description = "Line diff", body = NginxTemplateDiff),
+    (status = 404, description = "Revision not found", body = ApiError),
+  ),
+))]
+#[web::get("/nginx_templates/{name}/diff")]
+async fn diff_nginx_template_revision(
+  pool: web::types::State<Pool>,
+  name: web::types::Path<String>,
+  web::types::Query(qs): web::types::Query<NginxTemplateDiffQuery>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let res =
+    services::nginx_template::diff(name.into_inner(), qs.from, qs.to, &pool)
+      .await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
+/// Rollback a nginx template to a previous revision
+/// the content of the revision is stored as a new revision
+#[cfg_attr(feature = "openapi", utoipa::path(
+  post,
+  path = "/nginx_templates/{name}/revisions/{revision}/rollback",
+  params(
+    ("name" = String, path, description = "Name of the nginx template"),
+    ("revision" = i32, path, description = "Revision to restore"),
+  ),
+  responses(
+    (status = 200, description = "New revision with rendered clusters", body = NginxTemplateUpdateReport),
+    (status = 400, description = "Clusters failing to render the revision", body = ApiError),
+    (status = 404, description = "Revision not found", body = ApiError),
+  ),
+))]
+#[web::post("/nginx_templates/{name}/revisions/{revision}/rollback")]
+async fn rollback_nginx_template_revision(
+  req: web::HttpRequest,
+  pool: web::types::State<Pool>,
+  config: web::types::State<DaemonConfig>,
+  docker_api: web::types::State<bollard::Docker>,
+  url_path: web::types::Path<NginxTemplateRevisionPath>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let url_path = url_path.into_inner();
+  let res = services::nginx_template::rollback(
+    url_path.name,
+    url_path.revision,
+    &config,
+    &docker_api,
+    &pool,
+    &get_actor(&req),
+  )
+  .await?;
+
+  Ok(web::HttpResponse::Ok().json(&res))
+}
+
 /// Delete nginx template by name
+#[cfg_attr(feature = "openapi", utoipa::path(
+  delete,
+  path = "/nginx_templates/{name}",
+  params(
+    ("name" = String, path, description = "Name of the nginx template"),
+  ),
+  responses(
+    (status = 200, description = "Generic delete response", body = PgDeleteGeneric),
+    (status = 409, description = "Nginx template used by clusters", body = ApiError),
+  ),
+))]
 #[web::delete("/nginx_templates/{name}")]
 async fn delete_nginx_template_by_name(
   pool: web::types::State<Pool>,
   name: web::types::Path<String>,
 ) -> Result<web::HttpResponse, HttpResponseError> {
-  let res =
-    repositories::nginx_template::delete_by_name(name.into_inner(), &pool)
-      .await?;
+  let res = services::nginx_template::delete(name.into_inner(), &pool).await?;
 
   Ok(web::HttpResponse::Ok().json(&res))
 }
@@ -68,6 +244,104 @@ async fn inspect_nginx_template_by_name(
 pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(list_nginx_template);
   config.service(create_nginx_template);
+  config.service(lint_nginx_template);
+  config.service(update_nginx_template_by_name);
+  config.service(list_nginx_template_revision);
+  config.service(diff_nginx_template_revision);
+  config.service(rollback_nginx_template_revision);
   config.service(delete_nginx_template_by_name);
   config.service(inspect_nginx_template_by_name);
 }
+
+#[cfg(test)]
+mod test_nginx_template {
+  use serde_json::json;
+  use ntex::http::StatusCode;
+
+  use crate::utils::test::*;
+
+  use super::ntex_config;
+
+  async fn test_revisions(srv: &TestServer) -> TestReturn {
+    let resp = srv
+      .post("/nginx_templates")
+      .send_json(&json!({
+        "name": "controller-revisions",
+        "mode": "http",
+        "content": "server {\n  listen 80;\n}",
+      }))
+      .await?;
+    assert!(resp.status().is_success());
+
+    let resp = srv
+      .put("/nginx_templates/controller-revisions")
+      .send_json(&json!({
+        "content": "server {\n  listen 8080;\n}",
+      }))
+      .await?;
+    assert!(resp.status().is_success());
+
+    let resp = srv
+      .get("/nginx_templates/controller-revisions/diff")
+      .query(&json!({ "from": 1, "to": 2 }))?
+      .send()
+      .await?;
+    assert!(resp.status().is_success());
+
+    let resp = srv
+      .post("/nginx_templates/controller-revisions/revisions/1/rollback")
+      .send()
+      .await?;
+    assert!(resp.status().is_success());
+
+    let resp = srv
+      .get("/nginx_templates/controller-revisions/revisions")
+      .send()
+      .await?;
+    assert!(resp.status().is_success());
+
+    let resp = srv
+      .post("/nginx_templates/controller-revisions/revisions/42/rollback")
+      .send()
+      .await?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = srv
+      .delete("/nginx_templates/controller-revisions")
+      .send()
+      .await?;
+    assert!(resp.status().is_success());
+    Ok(())
+  }
+
+  async fn test_lint(srv: &TestServer) -> TestReturn {
+    let resp = srv
+      .post("/nginx_templates/lint")
+      .send_json(&json!({
+        "name": "controller-lint",
+        "content": "server_name {{vars.domain}};\n{{cargoes.api.ip}}",
+      }))
+      .await?;
+    assert!(resp.status().is_success());
+
+    let resp = srv
+      .post("/nginx_templates")
+      .send_json(&json!({
+        "name": "controller-lint",
+        "mode": "http",
+        "content": "{{#cargoes.api}}",
+      }))
+      .await?;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+  }
+
+  #[ntex::test]
+  async fn main() -> TestReturn {
+    let srv = generate_server(ntex_config).await;
+
+    test_revisions(&srv).await?;
+    test_lint(&srv).await?;
+    Ok(())
+  }
+}
@@ -1,7 +1,11 @@
 use ntex::web;
+use ntex::http::StatusCode;
 use serde_json::json;
+use futures::SinkExt;
+use futures::channel::{oneshot, mpsc::UnboundedSender};
 
 use crate::errors::HttpResponseError;
+use crate::events::system::EventMessage;
 
 use crate::version;
 
@@ -14,6 +18,93 @@ async fn get_version() -> Result<web::HttpResponse, HttpResponseError> {
   })))
 }
 
+pub(crate) fn event_system_error<T>(err: T) -> HttpResponseError
+where
+  T: std::fmt::Display,
+{
+  HttpResponseError {
+    msg: format!("unable to reach event system {}", err),
+    status: StatusCode::INTERNAL_SERVER_ERROR,
+  }
+}
+
+/// Get the report of the last reconciliation of clusters with docker
+#[cfg_attr(feature = "openapi", utoipa::path(
+  get,
+  path = "/system/reconcile",
+  responses(
+    (status = 200, description = "Last reconciliation report", body = ReconcileReport),
+    (status = 404, description = "No reconciliation have been made yet", body = ApiError),
+  ),
+))]
+#[web::get("/system/reconcile")]
+async fn get_last_reconcile(
+  system_event: web::types::State<UnboundedSender<EventMessage>>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let mut system_event = system_event.as_ref();
+  let (tx, rx) = oneshot::channel();
+  system_event
+    .send(EventMessage::LastReconcile(tx))
+    .await
+    .map_err(event_system_error)?;
+  let report = rx.await.map_err(event_system_error)?;
+  let report = report.ok_or(HttpResponseError {
+    msg: String::from("no reconciliation have been made yet"),
+    status: StatusCode::NOT_FOUND,
+  })?;
+
+  Ok(web::HttpResponse::Ok().json(&report))
+}
+
+/// Reconcile clusters with docker now
+#[cfg_attr(feature = "openapi", utoipa::path(
+  post,
+  path = "/system/reconcile",
+  responses(
+    (status = 200, description = "Reconciliation report", body = ReconcileReport),
+  ),
+))]
+#[web::post("/system/reconcile")]
+async fn reconcile(
+  system_event: web::types::State<UnboundedSender<EventMessage>>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let mut system_event = system_event.as_ref();
+  let (tx, rx) = oneshot::channel();
+  system_event
+    .send(EventMessage::Reconcile(tx))
+    .await
+    .map_err(event_system_error)?;
+  let report = rx.await.map_err(event_system_error)?;
+
+  Ok(web::HttpResponse::Ok().json(&report))
+}
+
+/// Renew acme certificates of cluster domains now
+#[cfg_attr(feature = "openapi", utoipa::path(
+  post,
+  path = "/system/certificates/renew",
+  responses(
+    (status = 200, description = "Renewal report", body = CertificateRenewReport),
+  ),
+))]
+#[web::post("/system/certificates/renew")]
+async fn renew_certificates(
+  system_event: web::types::State<UnboundedSender<EventMessage>>,
+) -> Result<web::HttpResponse, HttpResponseError> {
+  let mut system_event = system_event.as_ref();
+  let (tx, rx) = oneshot::channel();
+  system_event
+    .send(EventMessage::RenewCertificates(tx))
+    .await
+    .map_err(event_system_error)?;
+  let report = rx.await.map_err(event_system_error)?;
+
+  Ok(web::HttpResponse::Ok().json(&report))
+}
+
 pub fn ntex_config(config: &mut web::ServiceConfig) {
   config.service(get_version);
+  config.service(get_last_reconcile);
+  config.service(reconcile);
+  config.service(renew_certificates);
 }
@@ -1,3 +1,5 @@
+use ntex::web;
+
 pub fn gen_nsp_key_by_name(
   namespace: &Option<String>,
   name: &String,
@@ -7,3 +9,17 @@ pub fn gen_nsp_key_by_name(
     Some(namespace) => format!("{}-{}", namespace, name),
   }
 }
+
+/// Name of the header clients use to tell who make the request
+pub const ACTOR_HEADER: &str = "x-nanocl-actor";
+
+/// Get who make the request defaults to `api`
+pub fn get_actor(req: &web::HttpRequest) -> String {
+  req
+    .headers()
+    .get(ACTOR_HEADER)
+    .and_then(|value| value.to_str().ok())
+    .filter(|value| !value.is_empty())
+    .map(|value| value.to_owned())
+    .unwrap_or_else(|| String::from("api"))
+}
@@ -1,26 +1,50 @@
 use uuid::Uuid;
 use ntex::{web, rt};
 use ntex::util::Bytes;
-use std::collections::HashMap;
+use ntex::time::Millis;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex, MutexGuard};
-use futures::channel::mpsc;
+use futures::lock::Mutex as AsyncMutex;
+use futures::channel::{mpsc, oneshot};
 use futures::{StreamExt, SinkExt, stream};
+use bollard::system::EventsOptions;
 
-use crate::services;
-use crate::models::Pool;
+use crate::{services, repositories};
+use crate::models::{
+  Pool, EventKind, ReconcileReport, CertificateRenewReport,
+  NginxLogIngestionMetrics,
+};
 use crate::config::DaemonConfig;
+use crate::services::history::SYSTEM_ACTOR;
+use crate::services::nginx_log::NginxLogFilter;
+use crate::services::nginx_log_ingestion::SharedNginxLogIngestionMetrics;
+
+/// Seconds between two renewals of acme certificates
+const CERTIFICATE_RENEW_INTERVAL: u32 = 12 * 60 * 60;
+/// Seconds between two prunings of nginx access logs
+const NGINX_LOG_PRUNE_INTERVAL: u32 = 60 * 60;
+/// Seconds before connecting again to the docker events stream
+/// doubled on every failed attempt
+const DOCKER_EVENTS_MIN_BACKOFF: u32 = 1;
+const DOCKER_EVENTS_MAX_BACKOFF: u32 = 60;
 
 #[derive(Debug, Clone)]
 pub struct EventSystemClient {
   pub(crate) id: Uuid,
   pub(crate) sender: mpsc::UnboundedSender<Bytes>,
+  /// Only matching nginx logs are sent to the client
+  pub(crate) filter: NginxLogFilter,
 }
 
 impl EventSystemClient {
-  pub fn new(sender: mpsc::UnboundedSender<Bytes>) -> Self {
+  pub fn new(
+    sender: mpsc::UnboundedSender<Bytes>,
+    filter: NginxLogFilter,
+  ) -> Self {
     EventSystemClient {
       id: Uuid::new_v4(),
       sender,
+      filter,
     }
   }
 }
@@ -30,11 +54,15 @@ type EventSystemClients = HashMap<Uuid, EventSystemClient>;
 #[derive(Clone)]
 pub struct EventSystemConfig {
   config: DaemonConfig,
-  // Todo watch docker_api event
-  #[allow(dead_code)]
   docker_api: web::types::State<bollard::Docker>,
   pool: web::types::State<Pool>,
   clients: Arc<Mutex<EventSystemClients>>,
+  /// Ensure only one reconciliation run at a time
+  /// and keep the report of the last one
+  last_reconcile: Arc<AsyncMutex<Option<ReconcileReport>>>,
+  /// Ensure only one renewal of certificates run at a time
+  renewing: Arc<AsyncMutex<()>>,
+  nginx_log_ingestion: SharedNginxLogIngestionMetrics,
 }
 
 #[derive(Clone)]
@@ -44,6 +72,14 @@ pub struct EventSystem(EventSystemConfig);
 pub enum EventMessage {
   /// Connect to real time nginx logs
   NginxLog(EventSystemClient),
+  /// Reconcile clusters with docker now and send back the report
+  Reconcile(oneshot::Sender<ReconcileReport>),
+  /// Send back the report of the last reconciliation
+  LastReconcile(oneshot::Sender<Option<ReconcileReport>>),
+  /// Renew acme certificates of cluster domains and send back the report
+  RenewCertificates(oneshot::Sender<CertificateRenewReport>),
+  /// Send back the state of the ingestion of nginx access logs
+  NginxLogIngestion(oneshot::Sender<NginxLogIngestionMetrics>),
 }
 
 fn unlock_mutex<T>(mutex: &'_ Arc<Mutex<T>>) -> Option<MutexGuard<'_, T>> {
@@ -62,29 +98,41 @@ impl EventSystem {
   }
 
   pub fn start_tasks(&self) {
-    let state_dir = self.0.config.state_dir.clone();
+    let config = self.0.config.clone();
     let pool = self.0.pool.clone();
+    let metrics = self.0.nginx_log_ingestion.clone();
     let clients = self.0.clients.clone();
-    let mut receiver = services::nginx::watch_nginx_logs(state_dir, pool);
+    let mut receiver = services::nginx::watch_nginx_logs(config, pool, metrics);
     rt::Arbiter::new().exec_fn(move || {
       rt::spawn(async move {
         while let Some(data) = receiver.next().await {
           let iter: EventSystemClients =
             if let Some(clients) = unlock_mutex(&clients) {
-              println!("clients length {}", &clients.len());
               clients.clone()
             } else {
               HashMap::new()
             };
+          // Serialized once for all clients, one log per line
+          let mut payload = None;
           let mut stream = stream::iter(iter);
           while let Some((id, mut client)) = stream.next().await {
-            let data = serde_json::to_string(&data).unwrap();
             if client.sender.is_closed() {
               if let Some(mut clients_guard) = unlock_mutex(&clients) {
                 clients_guard.remove(&id);
               }
-            } else if let Err(err) = client.sender.send(Bytes::from(data)).await
-            {
+              continue;
+            }
+            if !client.filter.matches(&data) {
+              continue;
+            }
+            let payload = payload
+              .get_or_insert_with(|| {
+                let mut payload = serde_json::to_vec(&data).unwrap();
+                payload.push(b'\n');
+                Bytes::from(payload)
+              })
+              .clone();
+            if let Err(err) = client.sender.send(payload).await {
               log::info!("Unable to send nginx log to client {:#?}", err);
             }
           }
@@ -94,6 +142,199 @@ impl EventSystem {
     });
   }
 
+  /// Record crashes of cluster containers in the history of their cluster
+  /// Containers killed by docker or nanocl are not considered as crashed
+  /// the docker events stream is connected again when it ends
+  pub fn watch_container_crashes(&self) {
+    let docker_api = self.0.docker_api.clone();
+    let pool = self.0.pool.clone();
+    rt::Arbiter::new().exec_fn(move || {
+      rt::spawn(async move {
+        let mut killed = HashSet::new();
+        let mut backoff = DOCKER_EVENTS_MIN_BACKOFF;
+        loop {
+          let mut filters = HashMap::new();
+          filters.insert("type", vec!["container"]);
+          filters.insert("event", vec!["kill", "die"]);
+          filters.insert("label", vec!["cluster"]);
+          let options = Some(EventsOptions {
+            filters,
+            ..Default::default()
+          });
+          let mut events = docker_api.events(options);
+          while let Some(event) = events.next().await {
+            let event = match event {
+              Err(err) => {
+                log::warn!("Unable to read docker event {:#?}", err);
+                break;
+              }
+              Ok(event) => event,
+            };
+            backoff = DOCKER_EVENTS_MIN_BACKOFF;
+            let actor = match event.actor {
+              None => continue,
+              Some(actor) => actor,
+            };
+            let id = actor.id.unwrap_or_default();
+            let attributes = actor.attributes.unwrap_or_default();
+            if event.action.as_deref() == Some("kill") {
+              killed.insert(id);
+              continue;
+            }
+            if killed.remove(&id) {
+              continue;
+            }
+            let exit_code = attributes
+              .get("exitCode")
+              .map(|code| code.as_str())
+              .unwrap_or("0");
+            let cluster_key = match attributes.get("cluster") {
+              Some(cluster_key) if exit_code != "0" => cluster_key,
+              _ => continue,
+            };
+            let event = services::history::gen_event(
+              cluster_key,
+              EventKind::Crash,
+              SYSTEM_ACTOR,
+              attributes.get("name").cloned(),
+              Some(format!("container exited with code {}", exit_code)),
+            );
+            if let Err(err) = repositories::event::create(event, &pool).await {
+              log::warn!("Unable to record container crash {}", err);
+            }
+          }
+          log::error!(
+            "Docker events stream ended, connecting again in {}s",
+            backoff
+          );
+          ntex::time::sleep(Millis::from_secs(backoff)).await;
+          backoff = (backoff * 2).min(DOCKER_EVENTS_MAX_BACKOFF);
+        }
+      });
+    });
+  }
+
+  /// Reconcile clusters with docker and keep the report
+  async fn reconcile(config: &EventSystemConfig) -> ReconcileReport {
+    let mut last_reconcile = config.last_reconcile.lock().await;
+    let report = match services::reconcile::reconcile(
+      &config.config,
+      &config.docker_api,
+      &config.pool,
+    )
+    .await
+    {
+      Err(err) => ReconcileReport {
+        errors: vec![err.msg],
+        finished_at: Some(chrono::Utc::now()),
+        ..Default::default()
+      },
+      Ok(report) => report,
+    };
+    if !report.errors.is_empty() {
+      log::warn!("reconciliation errors {:#?}", &report.errors);
+    }
+    *last_reconcile = Some(report.to_owned());
+    report
+  }
+
+  /// Periodically reconcile clusters with docker
+  pub fn watch_reconcile(&self) {
+    let interval = self.0.config.reconcile_interval;
+    if interval == 0 {
+      return;
+    }
+    let config = self.0.clone();
+    rt::Arbiter::new().exec_fn(move || {
+      rt::spawn(async move {
+        loop {
+          ntex::time::sleep(Millis::from_secs(interval)).await;
+          log::debug!("reconciling clusters with docker");
+          EventSystem::reconcile(&config).await;
+        }
+      });
+    });
+  }
+
+  /// Renew acme certificates of cluster domains
+  async fn renew_certificates(
+    config: &EventSystemConfig,
+  ) -> CertificateRenewReport {
+    let _renewing = config.renewing.lock().await;
+    let report = match services::acme::renew(
+      &config.config,
+      &config.docker_api,
+      &config.pool,
+    )
+    .await
+    {
+      Err(err) => CertificateRenewReport {
+        errors: vec![err.msg],
+        finished_at: Some(chrono::Utc::now()),
+        ..Default::default()
+      },
+      Ok(report) => report,
+    };
+    if !report.errors.is_empty() {
+      log::warn!("certificate renewal errors {:#?}", &report.errors);
+    }
+    report
+  }
+
+  /// Periodically renew acme certificates when a directory is configured
+  pub fn watch_certificates(&self) {
+    if self.0.config.acme_directory.is_none() {
+      return;
+    }
+    let config = self.0.clone();
+    rt::Arbiter::new().exec_fn(move || {
+      rt::spawn(async move {
+        loop {
+          log::debug!("renewing acme certificates");
+          EventSystem::renew_certificates(&config).await;
+          ntex::time::sleep(Millis::from_secs(CERTIFICATE_RENEW_INTERVAL))
+            .await;
+        }
+      });
+    });
+  }
+
+  /// Periodically prune nginx access logs
+  /// when a retention or a maximum number of logs is configured
+  pub fn watch_nginx_log_pruning(&self) {
+    let config = self.0.config.clone();
+    if config.nginx_log_retention == 0 && config.nginx_log_max_rows == 0 {
+      return;
+    }
+    let pool = self.0.pool.clone();
+    rt::Arbiter::new().exec_fn(move || {
+      rt::spawn(async move {
+        loop {
+          log::debug!("pruning nginx access logs");
+          match services::nginx_log::prune(&config, &pool).await {
+            Err(err) => log::warn!("unable to prune nginx logs {}", err.msg),
+            Ok(report) => {
+              if report.deleted > 0 {
+                log::info!(
+                  "pruned {} nginx logs archived in {:?}",
+                  report.deleted,
+                  report.archives
+                );
+              }
+              if !report.partitions.is_empty() {
+                log::info!(
+                  "dropped nginx log partitions of {:?}",
+                  report.partitions
+                );
+              }
+            }
+          }
+          ntex::time::sleep(Millis::from_secs(NGINX_LOG_PRUNE_INTERVAL)).await;
+        }
+      });
+    });
+  }
+
   pub async fn handle_events(&mut self, event: EventMessage) {
     match event {
       EventMessage::NginxLog(client) => {
@@ -102,6 +343,33 @@ impl EventSystem {
           clients.insert(client.id, client);
         }
       }
+      EventMessage::Reconcile(sender) => {
+        let config = self.0.clone();
+        rt::spawn(async move {
+          let report = EventSystem::reconcile(&config).await;
+          let _ = sender.send(report);
+        });
+      }
+      EventMessage::LastReconcile(sender) => {
+        let last_reconcile = self.0.last_reconcile.clone();
+        rt::spawn(async move {
+          let report = last_reconcile.lock().await.to_owned();
+          let _ = sender.send(report);
+        });
+      }
+      EventMessage::RenewCertificates(sender) => {
+        let config = self.0.clone();
+        rt::spawn(async move {
+          let report = EventSystem::renew_certificates(&config).await;
+          let _ = sender.send(report);
+        });
+      }
+      EventMessage::NginxLogIngestion(sender) => {
+        let metrics = unlock_mutex(&self.0.nginx_log_ingestion)
+          .map(|metrics| metrics.clone())
+          .unwrap_or_default();
+        let _ = sender.send(metrics);
+      }
     }
   }
 }
@@ -119,8 +387,15 @@ pub async fn start(
     docker_api: web::types::State::new(docker_api),
     pool: web::types::State::new(pool),
     clients: Arc::new(Mutex::new(HashMap::new())),
+    last_reconcile: Arc::new(AsyncMutex::new(None)),
+    renewing: Arc::new(AsyncMutex::new(())),
+    nginx_log_ingestion: SharedNginxLogIngestionMetrics::default(),
   });
   system.start_tasks();
+  system.watch_container_crashes();
+  system.watch_reconcile();
+  system.watch_certificates();
+  system.watch_nginx_log_pruning();
   rt::Arbiter::new().exec_fn(move || {
     rt::spawn(async move {
       while let Some(event) = rx.next().await {
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use chrono::prelude::*;
 use r2d2::PooledConnection;
 use diesel_derive_enum::DbEnum;
@@ -10,7 +12,10 @@ use crate::{
   schema::{
     clusters, namespaces, git_repositories, cluster_networks,
     git_repository_branches, cargoes, nginx_templates, cluster_variables,
-    cluster_cargoes, cargo_environnements, nginx_logs,
+    cluster_cargoes, cargo_environnements, nginx_logs, cluster_cargo_networks,
+    cluster_network_policies, namespace_quotas, events, certificates,
+    cluster_routes, nginx_template_revisions, cluster_access_policies,
+    cluster_auth_policies, cluster_maintenances,
   },
 };
 
@@ -99,6 +104,97 @@ pub struct NamespacePartial {
   pub(crate) name: String,
 }
 
+/// Resource quota of a namespace
+/// a limit set to none is unlimited
+#[derive(
+  Debug,
+  Clone,
+  Serialize,
+  Deserialize,
+  Identifiable,
+  Insertable,
+  Queryable,
+  Associations,
+  AsChangeset,
+)]
+#[primary_key(namespace_name)]
+#[belongs_to(NamespaceItem, foreign_key = "namespace_name")]
+#[table_name = "namespace_quotas"]
+#[changeset_options(treat_none_as_null = "true")]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NamespaceQuotaItem {
+  pub(crate) namespace_name: String,
+  pub(crate) max_cargoes: Option<i64>,
+  pub(crate) max_clusters: Option<i64>,
+  pub(crate) max_networks: Option<i64>,
+  pub(crate) max_replicas: Option<i64>,
+  pub(crate) max_memory: Option<i64>,
+  pub(crate) max_cpu: Option<i64>,
+}
+
+/// Partial namespace quota
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NamespaceQuotaPartial {
+  pub(crate) max_cargoes: Option<i64>,
+  pub(crate) max_clusters: Option<i64>,
+  pub(crate) max_networks: Option<i64>,
+  pub(crate) max_replicas: Option<i64>,
+  /// Summed memory limit of the replicas in bytes
+  pub(crate) max_memory: Option<i64>,
+  /// Summed cpu limit of the replicas in units of 10<sup>-9</sup> cpus
+  pub(crate) max_cpu: Option<i64>,
+}
+
+/// Resources used by a namespace
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NamespaceUsage {
+  pub(crate) cargoes: i64,
+  pub(crate) clusters: i64,
+  pub(crate) networks: i64,
+  pub(crate) replicas: i64,
+  pub(crate) memory: i64,
+  pub(crate) cpu: i64,
+}
+
+/// Namespace with his quota and resource usage
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NamespaceInspect {
+  #[serde(flatten)]
+  pub(crate) namespace: NamespaceItem,
+  pub(crate) quota: Option<NamespaceQuotaItem>,
+  pub(crate) usage: NamespaceUsage,
+}
+
+/// Resources removed with a namespace in the order they are deleted
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NamespaceDeleteReport {
+  /// True when nothing was removed
+  pub(crate) dry_run: bool,
+  /// Names of the containers
+  pub(crate) containers: Vec<String>,
+  /// Keys of the cargoes joined to a cluster
+  pub(crate) joins: Vec<String>,
+  /// Keys of the cargo environment variables
+  pub(crate) cargo_envs: Vec<String>,
+  pub(crate) cargoes: Vec<String>,
+  pub(crate) networks: Vec<String>,
+  pub(crate) cluster_variables: Vec<String>,
+  pub(crate) cluster_routes: Vec<String>,
+  pub(crate) cluster_access_policies: Vec<String>,
+  pub(crate) cluster_auth_policies: Vec<String>,
+  /// Keys of the clusters with a maintenance
+  pub(crate) cluster_maintenances: Vec<String>,
+  pub(crate) clusters: Vec<String>,
+  /// Paths of the nginx config files rendered for the clusters
+  pub(crate) nginx_files: Vec<String>,
+  /// Domain names registered in dnsmasq
+  pub(crate) dns_entries: Vec<String>,
+}
+
 /// Git repository source types
 /// # Examples
 /// ```
@@ -173,6 +269,8 @@ pub struct GitRepositoryBranchPartial {
 pub struct ClusterPartial {
   pub(crate) name: String,
   pub(crate) proxy_templates: Option<Vec<String>>,
+  /// Domains to issue a certificate for with ACME
+  pub(crate) domains: Option<Vec<String>>,
 }
 
 /// Cluster used to encapsulate networks
@@ -196,6 +294,7 @@ pub struct ClusterItem {
   pub(crate) name: String,
   pub(crate) namespace: String,
   pub(crate) proxy_templates: Vec<String>,
+  pub(crate) domains: Vec<String>,
 }
 
 /// Cluster item with his relations
@@ -206,7 +305,9 @@ pub struct ClusterItemWithRelation {
   pub(crate) name: String,
   pub(crate) namespace: String,
   pub(crate) proxy_templates: Vec<String>,
+  pub(crate) domains: Vec<String>,
   pub(crate) networks: Option<Vec<ClusterNetworkItem>>,
+  pub(crate) network_policies: Option<Vec<ClusterNetworkPolicyItem>>,
 }
 
 /// Cluster network partial
@@ -215,6 +316,18 @@ pub struct ClusterItemWithRelation {
 #[cfg_attr(feature = "openapi", derive(Component))]
 pub struct ClusterNetworkPartial {
   pub(crate) name: String,
+  /// Subnet in cidr notation if empty one is allocated from the network pools
+  pub(crate) subnet: Option<String>,
+  /// Range of the subnet where container ips are allocated
+  pub(crate) ip_range: Option<String>,
+  /// Gateway of the subnet
+  pub(crate) gateway: Option<String>,
+  /// Ipv6 subnet in cidr notation to make the network dual stack
+  pub(crate) ipv6_subnet: Option<String>,
+  /// Restrict external access to the network
+  pub(crate) internal: Option<bool>,
+  /// Network driver options
+  pub(crate) driver_options: Option<HashMap<String, String>>,
 }
 
 /// Cluster network item
@@ -240,6 +353,37 @@ pub struct ClusterNetworkItem {
   pub(crate) docker_network_id: String,
   pub(crate) default_gateway: String,
   pub(crate) cluster_key: String,
+  pub(crate) subnet: Option<String>,
+  pub(crate) ip_range: Option<String>,
+  pub(crate) internal: bool,
+  pub(crate) ipv6_subnet: Option<String>,
+}
+
+/// Container connected to a cluster network
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterNetworkContainer {
+  pub(crate) id: String,
+  pub(crate) name: String,
+  pub(crate) cargo_key: Option<String>,
+  pub(crate) ipv4_address: Option<String>,
+  pub(crate) ipv6_address: Option<String>,
+}
+
+/// Cluster network with his live docker state
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterNetworkInspect {
+  #[serde(flatten)]
+  pub(crate) network: ClusterNetworkItem,
+  /// Subnets configured on the docker network
+  pub(crate) subnets: Vec<String>,
+  /// Running containers connected to the network
+  pub(crate) containers: Vec<ClusterNetworkContainer>,
+  /// Number of ipv4 addresses still available
+  pub(crate) free_addresses: u64,
+  /// Differences between the database and the docker network
+  pub(crate) warnings: Vec<String>,
 }
 
 /// Cargo partial
@@ -254,6 +398,10 @@ pub struct CargoPartial {
   pub(crate) dns_entry: Option<String>,
   pub(crate) domainname: Option<String>,
   pub(crate) hostname: Option<String>,
+  /// Memory limit of each container in bytes
+  pub(crate) memory_limit: Option<i64>,
+  /// Cpu limit of each container in units of 10<sup>-9</sup> cpus
+  pub(crate) cpu_limit: Option<i64>,
 }
 
 /// Cargo item is an definition to container create image and start them
@@ -282,6 +430,8 @@ pub struct CargoItem {
   pub(crate) dns_entry: Option<String>,
   pub(crate) domainname: Option<String>,
   pub(crate) hostname: Option<String>,
+  pub(crate) memory_limit: Option<i64>,
+  pub(crate) cpu_limit: Option<i64>,
 }
 
 /// Nginx template mode
@@ -311,11 +461,95 @@ pub struct NginxTemplateItem {
   pub(crate) content: String,
 }
 
+/// New content of a nginx template
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxTemplateUpdateBody {
+  /// Keep the current mode when empty
+  pub(crate) mode: Option<NginxTemplateModes>,
+  pub(crate) content: String,
+}
+
+/// Content of a nginx template at a given revision
+/// this structure ensure read and write in database
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "nginx_template_revisions"]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxTemplateRevisionItem {
+  pub(crate) template_name: String,
+  pub(crate) revision: i32,
+  pub(crate) mode: NginxTemplateModes,
+  pub(crate) content: String,
+  pub(crate) created_at: DateTime<Utc>,
+}
+
+/// Line diff between two revisions of a nginx template
+/// lines are prefixed by `-` when removed, `+` when added
+/// and by a space when unchanged
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxTemplateDiff {
+  pub(crate) from: i32,
+  pub(crate) to: i32,
+  pub(crate) lines: Vec<String>,
+}
+
+/// Severity of a problem found in a nginx template
+/// errors prevent the template to be saved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub enum NginxTemplateDiagnosticLevel {
+  Warning,
+  Error,
+}
+
+/// Problem found in a nginx template
+/// line and column start at 1 and point to the opening of the tag
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxTemplateDiagnostic {
+  pub(crate) level: NginxTemplateDiagnosticLevel,
+  pub(crate) line: usize,
+  pub(crate) column: usize,
+  pub(crate) message: String,
+}
+
+/// Content to check before creating or updating a nginx template
+/// the name is used to find the clusters using the template
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxTemplateLintBody {
+  pub(crate) name: String,
+  pub(crate) content: String,
+}
+
+/// Revision created by an update or a rollback
+/// with the clusters rendered again
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxTemplateUpdateReport {
+  pub(crate) revision: NginxTemplateRevisionItem,
+  /// Keys of the clusters rendered with the new revision
+  pub(crate) rendered: Vec<String>,
+  pub(crate) errors: Vec<String>,
+  /// Warnings found when checking the content of the revision
+  pub(crate) warnings: Vec<NginxTemplateDiagnostic>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(Component))]
 pub struct ClusterJoinBody {
   pub(crate) cargo: String,
+  /// Primary network used for target_ip
   pub(crate) network: String,
+  /// Additional networks to connect the cargo to
+  pub(crate) networks: Option<Vec<String>>,
+  /// Fixed ipv4 address of the cargo in the primary network
+  pub(crate) ipv4_address: Option<String>,
+  /// Fixed ipv6 address of the cargo in the primary network
+  /// only allowed when the network has ipv6 enabled
+  pub(crate) ipv6_address: Option<String>,
 }
 
 #[derive(
@@ -377,6 +611,312 @@ pub struct ClusterCargoPartial {
   pub(crate) network_key: String,
 }
 
+/// Network a cluster cargo is connected to
+/// this structure ensure read and write in database
+#[derive(
+  Debug,
+  Clone,
+  Serialize,
+  Deserialize,
+  Queryable,
+  Insertable,
+  Identifiable,
+  Associations,
+  AsChangeset,
+)]
+#[primary_key(key)]
+#[table_name = "cluster_cargo_networks"]
+#[belongs_to(ClusterCargoItem, foreign_key = "cluster_cargo_key")]
+#[belongs_to(ClusterNetworkItem, foreign_key = "network_key")]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterCargoNetworkItem {
+  pub(crate) key: String,
+  pub(crate) cluster_cargo_key: String,
+  pub(crate) network_key: String,
+  pub(crate) is_primary: bool,
+  pub(crate) ipv4_address: Option<String>,
+  pub(crate) ipv6_address: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterCargoNetworkPartial {
+  pub(crate) cluster_cargo_key: String,
+  pub(crate) network_key: String,
+  pub(crate) is_primary: bool,
+  pub(crate) ipv4_address: Option<String>,
+  pub(crate) ipv6_address: Option<String>,
+}
+
+/// Cluster network policy partial
+/// this structure ensure write in database
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterNetworkPolicyPartial {
+  /// Networks the network can reach as `network` for the same cluster
+  /// or `cluster/network` for another cluster of the namespace
+  pub(crate) allowed_networks: Vec<String>,
+  /// Allow traffic to the internet and to the host networks
+  pub(crate) allow_egress: bool,
+}
+
+/// Cluster network policy item
+/// this structure ensure read and write in database
+#[derive(
+  Debug,
+  Clone,
+  Serialize,
+  Deserialize,
+  Queryable,
+  Insertable,
+  Identifiable,
+  Associations,
+  AsChangeset,
+)]
+#[primary_key(key)]
+#[table_name = "cluster_network_policies"]
+#[belongs_to(ClusterItem, foreign_key = "cluster_key")]
+#[belongs_to(ClusterNetworkItem, foreign_key = "network_key")]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterNetworkPolicyItem {
+  pub(crate) key: String,
+  pub(crate) cluster_key: String,
+  pub(crate) network_key: String,
+  pub(crate) allowed_networks: Vec<String>,
+  pub(crate) allow_egress: bool,
+}
+
+/// Cluster route partial
+/// this structure ensure write in database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterRoutePartial {
+  pub(crate) name: String,
+  pub(crate) domain: String,
+  /// Path prefix matched by the route default to `/`
+  pub(crate) path: Option<String>,
+  /// Name of the cargo receiving the traffic
+  pub(crate) cargo_name: Option<String>,
+  /// Port of the cargo receiving the traffic
+  pub(crate) port: Option<i32>,
+  /// Serve the route in https and redirect http requests to it
+  pub(crate) tls: Option<bool>,
+  /// Answer with a permanent redirect to this url instead of a cargo
+  pub(crate) redirect_to: Option<String>,
+  /// Headers added to the proxied request as `Name: value`
+  pub(crate) headers: Option<Vec<String>>,
+  /// Forward websocket upgrade requests
+  pub(crate) websocket: Option<bool>,
+  /// Timeout in seconds to connect to the cargo
+  pub(crate) connect_timeout: Option<i32>,
+  /// Timeout in seconds between two reads from the cargo
+  pub(crate) read_timeout: Option<i32>,
+}
+
+/// Cluster route item
+/// this structure ensure read and write in database
+#[derive(
+  Debug,
+  Clone,
+  Serialize,
+  Deserialize,
+  Queryable,
+  Insertable,
+  Identifiable,
+  Associations,
+)]
+#[primary_key(key)]
+#[table_name = "cluster_routes"]
+#[belongs_to(ClusterItem, foreign_key = "cluster_key")]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterRouteItem {
+  pub(crate) key: String,
+  pub(crate) cluster_key: String,
+  pub(crate) name: String,
+  pub(crate) domain: String,
+  pub(crate) path: String,
+  pub(crate) cargo_name: Option<String>,
+  pub(crate) port: Option<i32>,
+  pub(crate) tls: bool,
+  pub(crate) redirect_to: Option<String>,
+  pub(crate) headers: Vec<String>,
+  pub(crate) websocket: bool,
+  pub(crate) connect_timeout: Option<i32>,
+  pub(crate) read_timeout: Option<i32>,
+}
+
+/// Cluster access policy partial
+/// this structure ensure write in database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterAccessPolicyPartial {
+  pub(crate) name: String,
+  /// Domain protected by the policy, all domains of the cluster when empty
+  pub(crate) domain: Option<String>,
+  /// Addresses or cidr allowed, the others are denied when not empty
+  pub(crate) allow: Option<Vec<String>>,
+  /// Addresses or cidr denied
+  pub(crate) deny: Option<Vec<String>>,
+  /// Requests per second allowed for each client address
+  pub(crate) rate: Option<i32>,
+  /// Requests above the rate delayed before being rejected
+  pub(crate) burst: Option<i32>,
+  /// Concurrent connections allowed for each client address
+  pub(crate) connections: Option<i32>,
+}
+
+/// Cluster access policy item
+/// this structure ensure read and write in database
+#[derive(
+  Debug,
+  Clone,
+  Serialize,
+  Deserialize,
+  Queryable,
+  Insertable,
+  Identifiable,
+  Associations,
+)]
+#[primary_key(key)]
+#[table_name = "cluster_access_policies"]
+#[belongs_to(ClusterItem, foreign_key = "cluster_key")]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterAccessPolicyItem {
+  pub(crate) key: String,
+  pub(crate) cluster_key: String,
+  pub(crate) name: String,
+  pub(crate) domain: Option<String>,
+  pub(crate) allow: Vec<String>,
+  pub(crate) deny: Vec<String>,
+  pub(crate) rate: Option<i32>,
+  pub(crate) burst: Option<i32>,
+  pub(crate) connections: Option<i32>,
+}
+
+/// Kind of authentication required by a cluster auth policy
+#[derive(Serialize, Deserialize, Debug, PartialEq, DbEnum, Clone)]
+#[serde(rename_all = "snake_case")]
+#[DieselType = "Cluster_auth_kind"]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub enum ClusterAuthKind {
+  /// Users and passwords checked by the proxy
+  Basic,
+  /// Requests checked by a cargo answering 2xx to allow them
+  Forward,
+}
+
+/// Cluster auth policy partial
+/// this structure ensure write in database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterAuthPolicyPartial {
+  pub(crate) name: String,
+  pub(crate) kind: ClusterAuthKind,
+  /// Domain protected by the policy
+  pub(crate) domain: Option<String>,
+  /// Route protected by the policy when no domain is given
+  pub(crate) route_name: Option<String>,
+  /// Realm displayed by browsers for basic auth
+  pub(crate) realm: Option<String>,
+  /// Users of basic auth as `name:password`, passwords are stored hashed
+  pub(crate) users: Option<Vec<String>>,
+  /// Cargo answering the forward auth requests
+  pub(crate) cargo_name: Option<String>,
+  /// Port of the cargo answering the forward auth requests
+  pub(crate) port: Option<i32>,
+  /// Path of the forward auth requests default to /
+  pub(crate) path: Option<String>,
+}
+
+/// Only the names of basic auth users are returned never their hash
+fn serialize_usernames<S>(
+  users: &[String],
+  serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+  S: serde::Serializer,
+{
+  serializer.collect_seq(
+    users
+      .iter()
+      .map(|user| user.split_once(':').map_or(user.as_str(), |(name, _)| name)),
+  )
+}
+
+/// Cluster auth policy item
+/// this structure ensure read and write in database
+#[derive(
+  Debug,
+  Clone,
+  Serialize,
+  Deserialize,
+  Queryable,
+  Insertable,
+  Identifiable,
+  Associations,
+)]
+#[primary_key(key)]
+#[table_name = "cluster_auth_policies"]
+#[belongs_to(ClusterItem, foreign_key = "cluster_key")]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterAuthPolicyItem {
+  pub(crate) key: String,
+  pub(crate) cluster_key: String,
+  pub(crate) name: String,
+  pub(crate) kind: ClusterAuthKind,
+  pub(crate) domain: Option<String>,
+  pub(crate) route_name: Option<String>,
+  pub(crate) realm: Option<String>,
+  /// Basic auth users as htpasswd lines
+  #[serde(serialize_with = "serialize_usernames")]
+  pub(crate) users: Vec<String>,
+  pub(crate) cargo_name: Option<String>,
+  pub(crate) port: Option<i32>,
+  pub(crate) path: Option<String>,
+}
+
+/// Partial cluster maintenance
+/// Unset fields keep their current value, an empty body removes it
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterMaintenancePartial {
+  /// Serve the maintenance page with a 503 instead of the cargoes
+  pub(crate) enabled: bool,
+  /// Html of the maintenance page
+  pub(crate) body: Option<String>,
+  /// Ip addresses or cidr still routed to the cargoes during maintenance
+  pub(crate) allow: Option<Vec<String>>,
+  /// Html served when a cargo can't be reached
+  pub(crate) bad_gateway_body: Option<String>,
+  /// Html served when a cargo takes too long to answer
+  pub(crate) gateway_timeout_body: Option<String>,
+}
+
+/// Maintenance mode and custom error pages of a cluster
+#[derive(
+  Debug,
+  Clone,
+  Serialize,
+  Deserialize,
+  Queryable,
+  Insertable,
+  Identifiable,
+  Associations,
+  AsChangeset,
+)]
+#[primary_key(cluster_key)]
+#[table_name = "cluster_maintenances"]
+#[belongs_to(ClusterItem, foreign_key = "cluster_key")]
+#[changeset_options(treat_none_as_null = "true")]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ClusterMaintenanceItem {
+  pub(crate) cluster_key: String,
+  pub(crate) enabled: bool,
+  pub(crate) body: Option<String>,
+  pub(crate) allow: Vec<String>,
+  pub(crate) bad_gateway_body: Option<String>,
+  pub(crate) gateway_timeout_body: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "openapi", derive(Component))]
 pub struct CargoEnvPartial {
@@ -446,11 +986,14 @@ pub struct NginxLogPartial {
   pub(crate) http_accept_language: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Insertable)]
+/// Access log of a request handled by the proxy
+/// dates are stored in utc to be read back from the database
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
 #[table_name = "nginx_logs"]
+#[cfg_attr(feature = "openapi", derive(Component))]
 pub struct NginxLogItem {
   pub(crate) key: Uuid,
-  pub(crate) date_gmt: DateTime<FixedOffset>,
+  pub(crate) date_gmt: DateTime<Utc>,
   pub(crate) uri: String,
   pub(crate) host: String,
   pub(crate) remote_addr: String,
@@ -475,7 +1018,7 @@ impl From<NginxLogPartial> for NginxLogItem {
   fn from(partial: NginxLogPartial) -> Self {
     NginxLogItem {
       key: Uuid::new_v4(),
-      date_gmt: partial.date_gmt,
+      date_gmt: partial.date_gmt.with_timezone(&Utc),
       uri: partial.uri,
       host: partial.host,
       remote_addr: partial.remote_addr,
@@ -498,9 +1041,263 @@ impl From<NginxLogPartial> for NginxLogItem {
   }
 }
 
+/// Order of stored access logs
+/// the key of the log is used to order logs with the same value
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub enum NginxLogSort {
+  DateDesc,
+  DateAsc,
+  RequestTimeDesc,
+  RequestTimeAsc,
+}
+
+/// Filters to search stored access logs
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct NginxLogSearchQuery {
+  pub(crate) since: Option<DateTime<Utc>>,
+  pub(crate) until: Option<DateTime<Utc>>,
+  pub(crate) host: Option<String>,
+  pub(crate) uri_prefix: Option<String>,
+  pub(crate) status_min: Option<i32>,
+  pub(crate) status_max: Option<i32>,
+  pub(crate) method: Option<String>,
+  pub(crate) remote_addr: Option<String>,
+  pub(crate) min_request_time: Option<f64>,
+  pub(crate) sort: Option<NginxLogSort>,
+  /// Cursor returned by a previous search with the same sort
+  pub(crate) cursor: Option<String>,
+  pub(crate) limit: Option<i64>,
+}
+
+/// Position after the last log of a page
+/// logs are compared on the sorted value then on their key
+#[derive(Debug, Clone, PartialEq)]
+pub enum NginxLogCursor {
+  Date(DateTime<Utc>, Uuid),
+  RequestTime(f64, Uuid),
+}
+
+/// Page of stored access logs
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxLogSearchResult {
+  pub(crate) items: Vec<NginxLogItem>,
+  /// Cursor to get the next page when more logs match
+  pub(crate) next_cursor: Option<String>,
+}
+
+/// Filters of a subscription to the stream of access logs
+/// a host or a cluster can be given, all traffic is streamed otherwise
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NginxLogStreamQuery {
+  pub(crate) host: Option<String>,
+  pub(crate) cluster: Option<String>,
+  pub(crate) namespace: Option<String>,
+  pub(crate) status_min: Option<i32>,
+  pub(crate) status_max: Option<i32>,
+  /// Regex matched against the uri of the request
+  pub(crate) path: Option<String>,
+  /// Part of the matching logs delivered between 0 excluded and 1
+  pub(crate) sample: Option<f64>,
+}
+
+/// State of the ingestion of nginx access logs in database
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxLogIngestionMetrics {
+  /// Logs read waiting to be written
+  pub(crate) buffered: usize,
+  /// Logs written in database at once
+  pub(crate) batch_size: usize,
+  /// Maximum number of logs waiting before reading is paused
+  pub(crate) capacity: usize,
+  /// Reading is paused until buffered logs are written
+  pub(crate) throttled: bool,
+  /// Bytes of the access log not read yet
+  pub(crate) lag_bytes: u64,
+  pub(crate) received: u64,
+  /// Lines which are not valid access logs
+  pub(crate) parse_errors: u64,
+  pub(crate) inserted: u64,
+  pub(crate) batches: u64,
+  pub(crate) failed_batches: u64,
+  pub(crate) last_flush_at: Option<DateTime<Utc>>,
+  /// Duration of the last write in milliseconds
+  pub(crate) last_flush_duration: u64,
+  pub(crate) last_error: Option<String>,
+}
+
+/// Scope and window of nginx traffic statistics
+/// a host or a cluster can be given, all traffic is used otherwise
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NginxStatsQuery {
+  pub(crate) since: Option<DateTime<Utc>>,
+  pub(crate) until: Option<DateTime<Utc>>,
+  pub(crate) host: Option<String>,
+  pub(crate) cluster: Option<String>,
+  pub(crate) namespace: Option<String>,
+  /// Number of uris and user agents in tops
+  pub(crate) top: Option<i64>,
+}
+
+/// Number of responses by status class
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxStatusClasses {
+  pub(crate) status_1xx: i64,
+  pub(crate) status_2xx: i64,
+  pub(crate) status_3xx: i64,
+  pub(crate) status_4xx: i64,
+  pub(crate) status_5xx: i64,
+}
+
+/// Number of requests received during a minute
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxStatsMinute {
+  pub(crate) minute: DateTime<Utc>,
+  pub(crate) requests: i64,
+}
+
+/// Uri or user agent with his number of requests
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxStatsTopItem {
+  pub(crate) value: String,
+  pub(crate) requests: i64,
+}
+
+/// Traffic statistics aggregated from stored access logs
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct NginxStats {
+  pub(crate) since: DateTime<Utc>,
+  pub(crate) until: DateTime<Utc>,
+  /// Hosts of the scope, none when all traffic is used
+  pub(crate) hosts: Option<Vec<String>>,
+  pub(crate) requests: i64,
+  pub(crate) average_requests_per_minute: f64,
+  /// Minutes without requests are omitted
+  pub(crate) requests_per_minute: Vec<NginxStatsMinute>,
+  pub(crate) status: NginxStatusClasses,
+  /// Percentiles of request time in seconds
+  pub(crate) request_time_p50: f64,
+  pub(crate) request_time_p95: f64,
+  pub(crate) request_time_p99: f64,
+  pub(crate) bytes_sent: i64,
+  pub(crate) top_uris: Vec<NginxStatsTopItem>,
+  pub(crate) top_user_agents: Vec<NginxStatsTopItem>,
+}
+
+/// Kind of action recorded in the history of a cluster
+#[derive(Serialize, Deserialize, Debug, PartialEq, DbEnum, Clone)]
+#[serde(rename_all = "snake_case")]
+#[DieselType = "Event_kind"]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub enum EventKind {
+  Create,
+  Start,
+  Join,
+  Variable,
+  Template,
+  Deploy,
+  Crash,
+}
+
+/// Outcome of a recorded action
+#[derive(Serialize, Deserialize, Debug, PartialEq, DbEnum, Clone)]
+#[serde(rename_all = "snake_case")]
+#[DieselType = "Event_outcome"]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub enum EventOutcome {
+  Success,
+  Failure,
+}
+
+/// Action made on a cluster
+/// this structure ensure read and write in database
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "events"]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct EventItem {
+  pub(crate) key: Uuid,
+  pub(crate) cluster_key: String,
+  pub(crate) kind: EventKind,
+  /// Who made the action `system` for actions made by the daemon itself
+  pub(crate) actor: String,
+  /// Cargo, variable or template concerned by the action
+  pub(crate) target: Option<String>,
+  pub(crate) outcome: EventOutcome,
+  pub(crate) error: Option<String>,
+  pub(crate) created_at: DateTime<Utc>,
+}
+
+/// Certificate and private key to install on the proxy in PEM format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct CertificatePartial {
+  pub(crate) name: String,
+  /// Certificate followed by his intermediate certificates
+  pub(crate) certificate: String,
+  pub(crate) certificate_key: String,
+}
+
+/// Certificate installed on the proxy
+/// this structure ensure read and write in database
+#[derive(
+  Debug, Clone, Serialize, Deserialize, Identifiable, Insertable, Queryable,
+)]
+#[primary_key(name)]
+#[table_name = "certificates"]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct CertificateItem {
+  pub(crate) name: String,
+  /// Domains from the subject alternative names
+  pub(crate) domains: Vec<String>,
+  pub(crate) issuer: String,
+  pub(crate) not_before: DateTime<Utc>,
+  pub(crate) not_after: DateTime<Utc>,
+  pub(crate) created_at: DateTime<Utc>,
+}
+
+/// Certificates issued by an ACME renewal of cluster domains
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct CertificateRenewReport {
+  /// Domains with a fresh certificate
+  pub(crate) issued: Vec<String>,
+  /// Clusters whose proxy templates were rendered again
+  pub(crate) rendered: Vec<String>,
+  pub(crate) errors: Vec<String>,
+  pub(crate) finished_at: Option<DateTime<Utc>>,
+}
+
+/// Changes made by a reconciliation between the database and docker
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Component))]
+pub struct ReconcileReport {
+  /// Cluster cargoes whose containers were missing and recreated
+  pub(crate) created: Vec<String>,
+  /// Containers of cluster cargoes that doesn't exist anymore
+  pub(crate) removed: Vec<String>,
+  /// Containers that were not running
+  pub(crate) started: Vec<String>,
+  /// Networks connected back as `container network`
+  pub(crate) reconnected: Vec<String>,
+  /// Clusters whose proxy templates were rendered again
+  pub(crate) rendered: Vec<String>,
+  pub(crate) errors: Vec<String>,
+  pub(crate) finished_at: Option<DateTime<Utc>>,
+}
+
 /// Re exports ours enums and diesel sql_types for schema.rs
 pub mod exports {
   pub use diesel::sql_types::*;
   pub use super::Nginx_template_modes;
   pub use super::Git_repository_source_type;
+  pub use super::Event_kind;
+  pub use super::Event_outcome;
+  pub use super::Cluster_auth_kind;
 }
@@ -15,14 +15,40 @@ use crate::errors::ApiError;
 #[cfg_attr(feature = "openapi", derive(OpenApi))]
 #[cfg_attr(feature = "openapi", openapi(
   handlers(
+    // System
+    system::get_last_reconcile,
+    system::reconcile,
+    system::renew_certificates,
+
     // Namespace
     namespace::list_namespace,
     namespace::create_namespace,
     namespace::delete_namespace_by_name,
     namespace::inspect_namespace_by_name,
+    namespace::put_namespace_quota,
+    namespace::delete_namespace_quota,
 
     // nginx template
     nginx_template::list_nginx_template,
+    nginx_template::create_nginx_template,
+    nginx_template::lint_nginx_template,
+    nginx_template::update_nginx_template_by_name,
+    nginx_template::list_nginx_template_revision,
+    nginx_template::diff_nginx_template_revision,
+    nginx_template::rollback_nginx_template_revision,
+    nginx_template::delete_nginx_template_by_name,
+
+    // Nginx log
+    nginx_log::stream_nginx_logs,
+    nginx_log::search_nginx_logs,
+    nginx_log::get_nginx_stats,
+    nginx_log::get_nginx_log_ingestion,
+
+    // Certificate
+    certificate::list_certificate,
+    certificate::create_certificate,
+    certificate::inspect_certificate_by_name,
+    certificate::delete_certificate_by_name,
 
     // Cargo
     cargo::list_cargo,
@@ -43,19 +69,44 @@ use crate::errors::ApiError;
     cluster::delete_cluster_by_name,
     cluster::inspect_cluster_by_name,
     cluster::start_cluster_by_name,
+    cluster::list_cluster_history,
     cluster::join_cargo_to_cluster,
 
+    // Cluster cargo
+    cluster_cargo::delete_cluster_cargo_by_name,
+
     // Cluster variable
     cluster_variable::list_cluster_variable,
     cluster_variable::create_cluster_variable,
     cluster_variable::delete_cluster_variable,
 
+    // Cluster route
+    cluster_route::create_cluster_route,
+    cluster_route::list_cluster_route,
+    cluster_route::get_cluster_route_by_name,
+    cluster_route::delete_cluster_route,
+    cluster_access_policy::create_cluster_access_policy,
+    cluster_access_policy::list_cluster_access_policy,
+    cluster_access_policy::get_cluster_access_policy_by_name,
+    cluster_access_policy::delete_cluster_access_policy,
+    cluster_auth_policy::create_cluster_auth_policy,
+    cluster_auth_policy::list_cluster_auth_policy,
+    cluster_auth_policy::get_cluster_auth_policy_by_name,
+    cluster_auth_policy::delete_cluster_auth_policy,
+    cluster_maintenance::update_cluster_maintenance,
+    cluster_maintenance::inspect_cluster_maintenance,
+
     // Cluster network
     cluster_network::list_cluster_network,
     cluster_network::create_cluster_network,
     cluster_network::delete_cluster_network_by_name,
     cluster_network::inspect_cluster_network_by_name,
     cluster_network::count_cluster_network_by_namespace,
+
+    // Cluster network policy
+    cluster_network_policy::list_cluster_network_policy,
+    cluster_network_policy::put_cluster_network_policy,
+    cluster_network_policy::delete_cluster_network_policy,
   ),
   components(
     ApiError,
@@ -64,6 +115,27 @@ use crate::errors::ApiError;
 
     // Nginx template
     NginxTemplateItem,
+    NginxTemplateUpdateBody,
+    NginxTemplateRevisionItem,
+    NginxTemplateDiff,
+    NginxTemplateUpdateReport,
+    NginxTemplateLintBody,
+    NginxTemplateDiagnostic,
+    NginxTemplateDiagnosticLevel,
+
+    // Nginx log
+    NginxLogItem,
+    NginxLogSort,
+    NginxLogSearchResult,
+    NginxStats,
+    NginxStatsMinute,
+    NginxStatsTopItem,
+    NginxStatusClasses,
+    NginxLogIngestionMetrics,
+
+    // Certificate
+    CertificateItem,
+    CertificatePartial,
 
     // Git repository
     GitRepositoryItem,
@@ -73,6 +145,11 @@ use crate::errors::ApiError;
     // Namespace
     NamespaceItem,
     NamespacePartial,
+    NamespaceQuotaItem,
+    NamespaceQuotaPartial,
+    NamespaceUsage,
+    NamespaceInspect,
+    NamespaceDeleteReport,
 
     // Cargo
     CargoItem,
@@ -84,14 +161,34 @@ use crate::errors::ApiError;
     ClusterItem,
     ClusterPartial,
     ClusterJoinBody,
+    EventItem,
+    EventKind,
+    EventOutcome,
+    ReconcileReport,
+    CertificateRenewReport,
 
     // Cluster variable
     ClusterVariableItem,
     ClusterVariablePartial,
 
+    // Cluster route
+    ClusterRouteItem,
+    ClusterRoutePartial,
+    ClusterAccessPolicyItem,
+    ClusterAccessPolicyPartial,
+    ClusterAuthKind,
+    ClusterAuthPolicyItem,
+    ClusterAuthPolicyPartial,
+    ClusterMaintenanceItem,
+    ClusterMaintenancePartial,
+
     // Cluster network
     ClusterNetworkItem,
     ClusterNetworkPartial,
+    ClusterNetworkPolicyItem,
+    ClusterNetworkInspect,
+    ClusterNetworkContainer,
+    ClusterNetworkPolicyPartial,
     ClusterItemWithRelation,
 
     // Todo Docker network struct bindings
@@ -40,6 +40,8 @@ pub async fn create(
       dns_entry: item.dns_entry,
       domainname: item.domainname,
       hostname: item.hostname,
+      memory_limit: item.memory_limit,
+      cpu_limit: item.cpu_limit,
     };
     diesel::insert_into(dsl::cargoes)
       .values(&new_item)
@@ -0,0 +1,84 @@
+//! Repository to manage certificates installed on the proxy
+use ntex::web;
+use diesel::prelude::*;
+
+use crate::services;
+use crate::models::{Pool, CertificateItem, PgDeleteGeneric};
+
+use crate::errors::HttpResponseError;
+use super::errors::db_blocking_error;
+
+pub async fn list(
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<CertificateItem>, HttpResponseError> {
+  use crate::schema::certificates::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res =
+    web::block(move || dsl::certificates.order(dsl::name.asc()).load(&conn))
+      .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+pub async fn create(
+  item: CertificateItem,
+  pool: &web::types::State<Pool>,
+) -> Result<CertificateItem, HttpResponseError> {
+  use crate::schema::certificates::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::insert_into(dsl::certificates)
+      .values(&item)
+      .execute(&conn)?;
+    Ok(item)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+pub async fn find_by_name(
+  name: String,
+  pool: &web::types::State<Pool>,
+) -> Result<CertificateItem, HttpResponseError> {
+  use crate::schema::certificates::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::certificates
+      .filter(dsl::name.eq(name))
+      .get_result(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+pub async fn delete_by_name(
+  name: String,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  use crate::schema::certificates::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::delete(dsl::certificates.filter(dsl::name.eq(name))).execute(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(result) => Ok(PgDeleteGeneric { count: result }),
+  }
+}
@@ -45,6 +45,7 @@ pub async fn create_for_namespace(
       namespace: nsp,
       name: item.name,
       proxy_templates: item.proxy_templates.unwrap_or_default(),
+      domains: item.domains.unwrap_or_default(),
     };
 
     diesel::insert_into(dsl::clusters)
@@ -146,6 +147,40 @@ pub async fn find_by_namespace(
   }
 }
 
+/// List clusters of every namespace
+pub async fn list_all(
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<ClusterItem>, HttpResponseError> {
+  use crate::schema::clusters::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || dsl::clusters.load(&conn)).await;
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+/// List clusters rendering the given nginx template
+pub async fn list_by_proxy_template(
+  template_name: String,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<ClusterItem>, HttpResponseError> {
+  use crate::schema::clusters::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::clusters
+      .filter(dsl::proxy_templates.contains(vec![template_name]))
+      .load(&conn)
+  })
+  .await;
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
 /// Return number of deleted entries
 ///
 /// # Arguments
@@ -204,6 +239,7 @@ mod test_cluster {
     let item = ClusterPartial {
       name: String::from(CLUSTER_NAME),
       proxy_templates: None,
+      domains: None,
     };
     // test create cluster
     create_for_namespace(String::from(NSP_NAME), item, &pool_state)
@@ -0,0 +1,106 @@
+use ntex::web;
+use diesel::prelude::*;
+
+use crate::services;
+use crate::models::{Pool, ClusterAccessPolicyItem, PgDeleteGeneric};
+
+use crate::errors::HttpResponseError;
+
+use super::errors::db_blocking_error;
+
+pub async fn create(
+  item: ClusterAccessPolicyItem,
+  pool: &web::types::State<Pool>,
+) -> Result<ClusterAccessPolicyItem, HttpResponseError> {
+  use crate::schema::cluster_access_policies::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::insert_into(dsl::cluster_access_policies)
+      .values(&item)
+      .execute(&conn)?;
+    Ok(item)
+  })
+  .await;
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+pub async fn list_by_cluster(
+  cluster_key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<ClusterAccessPolicyItem>, HttpResponseError> {
+  use crate::schema::cluster_access_policies::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::cluster_access_policies
+      .filter(dsl::cluster_key.eq(cluster_key))
+      .order(dsl::name.asc())
+      .get_results(&conn)
+  })
+  .await;
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+pub async fn find_by_key(
+  key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<ClusterAccessPolicyItem, HttpResponseError> {
+  use crate::schema::cluster_access_policies::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::cluster_access_policies
+      .filter(dsl::key.eq(key))
+      .get_result(&conn)
+  })
+  .await;
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+pub async fn delete_by_cluster_key(
+  cluster_key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  use crate::schema::cluster_access_policies::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::delete(
+      dsl::cluster_access_policies.filter(dsl::cluster_key.eq(cluster_key)),
+    )
+    .execute(&conn)
+  })
+  .await;
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(result) => Ok(PgDeleteGeneric { count: result }),
+  }
+}
+
+pub async fn delete_by_key(
+  key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  use crate::schema::cluster_access_policies::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::delete(dsl::cluster_access_policies.filter(dsl::key.eq(key)))
+      .execute(&conn)
+  })
+  .await;
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(result) => Ok(PgDeleteGeneric { count: result }),
+  }
+}
@@ -52,8 +52,6 @@ pub async fn get_by_cluster_key(
   }
 }
 
-#[allow(dead_code)]
-/// This may be not needed.
 pub async fn delete_by_key(
   key: String,
   pool: &web::types::State<Pool>,
@@ -128,3 +126,17 @@ pub async fn get_by_key(
     Ok(item) => Ok(item),
   }
 }
+
+pub async fn list_all(
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<ClusterCargoItem>, HttpResponseError> {
+  use crate::schema::cluster_cargoes::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || dsl::cluster_cargoes.load(&conn)).await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
@@ -0,0 +1,160 @@
+//! Repository to manage networks of cluster cargoes in database
+use ntex::web;
+use diesel::prelude::*;
+
+use crate::services;
+use crate::models::{
+  Pool, ClusterCargoNetworkItem, ClusterCargoNetworkPartial, PgDeleteGeneric,
+};
+
+use crate::errors::HttpResponseError;
+use super::errors::db_blocking_error;
+
+pub async fn create_many(
+  items: Vec<ClusterCargoNetworkPartial>,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<ClusterCargoNetworkItem>, HttpResponseError> {
+  use crate::schema::cluster_cargo_networks::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    let records = items
+      .into_iter()
+      .map(|item| ClusterCargoNetworkItem {
+        key: format!("{}-{}", item.cluster_cargo_key, item.network_key),
+        cluster_cargo_key: item.cluster_cargo_key,
+        network_key: item.network_key,
+        is_primary: item.is_primary,
+        ipv4_address: item.ipv4_address,
+        ipv6_address: item.ipv6_address,
+      })
+      .collect::<Vec<ClusterCargoNetworkItem>>();
+
+    diesel::insert_into(dsl::cluster_cargo_networks)
+      .values(&records)
+      .execute(&conn)?;
+    Ok(records)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+pub async fn list_by_cluster_cargo_key(
+  cluster_cargo_key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<ClusterCargoNetworkItem>, HttpResponseError> {
+  use crate::schema::cluster_cargo_networks::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::cluster_cargo_networks
+      .filter(dsl::cluster_cargo_key.eq(cluster_cargo_key))
+      .get_results(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+pub async fn list_by_network_key(
+  network_key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<ClusterCargoNetworkItem>, HttpResponseError> {
+  use crate::schema::cluster_cargo_networks::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::cluster_cargo_networks
+      .filter(dsl::network_key.eq(network_key))
+      .get_results(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+/// Find the cargo network using given ipv4 or ipv6 address in a network
+pub async fn find_by_address(
+  network_key: String,
+  address: String,
+  pool: &web::types::State<Pool>,
+) -> Result<Option<ClusterCargoNetworkItem>, HttpResponseError> {
+  use crate::schema::cluster_cargo_networks::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::cluster_cargo_networks
+      .filter(dsl::network_key.eq(network_key))
+      .filter(
+        dsl::ipv4_address
+          .eq(address.to_owned())
+          .or(dsl::ipv6_address.eq(address)),
+      )
+      .first(&conn)
+      .optional()
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+pub async fn delete_by_cluster_cargo_key(
+  cluster_cargo_key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  use crate::schema::cluster_cargo_networks::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::delete(
+      dsl::cluster_cargo_networks
+        .filter(dsl::cluster_cargo_key.eq(cluster_cargo_key)),
+    )
+    .execute(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(result) => Ok(PgDeleteGeneric { count: result }),
+  }
+}
+
+pub async fn delete_by_cargo_key(
+  cargo_key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  use crate::schema::cluster_cargoes;
+  use crate::schema::cluster_cargo_networks::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    let cluster_cargo_keys = cluster_cargoes::table
+      .select(cluster_cargoes::key)
+      .filter(cluster_cargoes::cargo_key.eq(cargo_key));
+    diesel::delete(
+      dsl::cluster_cargo_networks
+        .filter(dsl::cluster_cargo_key.eq_any(cluster_cargo_keys)),
+    )
+    .execute(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(result) => Ok(PgDeleteGeneric { count: result }),
+  }
+}
@@ -0,0 +1,77 @@
+//! Repository to manage the maintenance of clusters in database
+use ntex::web;
+use diesel::prelude::*;
+
+use crate::services;
+use crate::models::{Pool, ClusterMaintenanceItem, PgDeleteGeneric};
+
+use crate::errors::HttpResponseError;
+
+use super::errors::db_blocking_error;
+
+/// Create or replace the maintenance of a cluster
+pub async fn upsert(
+  item: ClusterMaintenanceItem,
+  pool: &web::types::State<Pool>,
+) -> Result<ClusterMaintenanceItem, HttpResponseError> {
+  use crate::schema::cluster_maintenances::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::insert_into(dsl::cluster_maintenances)
+      .values(&item)
+      .on_conflict(dsl::cluster_key)
+      .do_update()
+      .set(&item)
+      .execute(&conn)?;
+    Ok(item)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+/// Find the maintenance of a cluster if there is one
+pub async fn find_by_cluster_key(
+  cluster_key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<Option<ClusterMaintenanceItem>, HttpResponseError> {
+  use crate::schema::cluster_maintenances::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::cluster_maintenances
+      .filter(dsl::cluster_key.eq(cluster_key))
+      .get_result(&conn)
+      .optional()
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+pub async fn delete_by_cluster_key(
+  cluster_key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  use crate::schema::cluster_maintenances::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::delete(
+      dsl::cluster_maintenances.filter(dsl::cluster_key.eq(cluster_key)),
+    )
+    .execute(&conn)
+  })
+  .await;
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(result) => Ok(PgDeleteGeneric { count: result }),
+  }
+}
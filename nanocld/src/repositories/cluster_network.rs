@@ -66,6 +66,10 @@ pub async fn create_for_cluster(
       default_gateway,
       docker_network_id,
       namespace: namespace_name,
+      subnet: item.subnet,
+      ip_range: item.ip_range,
+      internal: item.internal.unwrap_or(false),
+      ipv6_subnet: item.ipv6_subnet,
     };
     diesel::insert_into(dsl::cluster_networks)
       .values(&item)
@@ -143,6 +147,7 @@ mod cluster_networks {
     let new_cluster = ClusterPartial {
       name: String::from("dev"),
       proxy_templates: None,
+      domains: None,
     };
     let cluster = cluster::create_for_namespace(
       String::from("default"),
@@ -173,6 +178,12 @@ mod cluster_networks {
     // create cluster network
     let new_network = ClusterNetworkPartial {
       name: String::from("test-dev"),
+      subnet: None,
+      ip_range: None,
+      gateway: None,
+      ipv6_subnet: None,
+      internal: None,
+      driver_options: None,
     };
     let network = create_for_cluster(
       cluster.namespace,
@@ -0,0 +1,109 @@
+//! Repository to manage network policies of clusters in database
+use ntex::web;
+use diesel::prelude::*;
+
+use crate::services;
+use crate::models::{Pool, ClusterNetworkPolicyItem, PgDeleteGeneric};
+
+use crate::errors::HttpResponseError;
+use super::errors::db_blocking_error;
+
+/// Create or replace the policy of a network
+pub async fn upsert(
+  item: ClusterNetworkPolicyItem,
+  pool: &web::types::State<Pool>,
+) -> Result<ClusterNetworkPolicyItem, HttpResponseError> {
+  use crate::schema::cluster_network_policies::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::insert_into(dsl::cluster_network_policies)
+      .values(&item)
+      .on_conflict(dsl::key)
+      .do_update()
+      .set(&item)
+      .execute(&conn)?;
+    Ok(item)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+pub async fn list_by_cluster_key(
+  cluster_key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<ClusterNetworkPolicyItem>, HttpResponseError> {
+  use crate::schema::cluster_network_policies::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::cluster_network_policies
+      .filter(dsl::cluster_key.eq(cluster_key))
+      .get_results(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+pub async fn list_all(
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<ClusterNetworkPolicyItem>, HttpResponseError> {
+  use crate::schema::cluster_network_policies::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res =
+    web::block(move || dsl::cluster_network_policies.get_results(&conn)).await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+pub async fn delete_by_key(
+  key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  use crate::schema::cluster_network_policies::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::delete(dsl::cluster_network_policies.filter(dsl::key.eq(key)))
+      .execute(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(result) => Ok(PgDeleteGeneric { count: result }),
+  }
+}
+
+pub async fn delete_by_cluster_key(
+  cluster_key: String,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  use crate::schema::cluster_network_policies::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::delete(
+      dsl::cluster_network_policies.filter(dsl::cluster_key.eq(cluster_key)),
+    )
+    .execute(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(result) => Ok(PgDeleteGeneric { count: result }),
+  }
+}
@@ -0,0 +1,54 @@
+//! Repository to manage the history of clusters in database
+use ntex::web;
+use diesel::prelude::*;
+
+use crate::services;
+use crate::models::{Pool, EventItem};
+
+use crate::errors::HttpResponseError;
+use super::errors::db_blocking_error;
+
+pub async fn create(
+  item: EventItem,
+  pool: &web::types::State<Pool>,
+) -> Result<EventItem, HttpResponseError> {
+  use crate::schema::events::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::insert_into(dsl::events)
+      .values(&item)
+      .execute(&conn)?;
+    Ok(item)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+/// List the latest events of a cluster newest first
+pub async fn list_by_cluster_key(
+  cluster_key: String,
+  limit: i64,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<EventItem>, HttpResponseError> {
+  use crate::schema::events::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::events
+      .filter(dsl::cluster_key.eq(cluster_key))
+      .order(dsl::created_at.desc())
+      .limit(limit)
+      .load(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
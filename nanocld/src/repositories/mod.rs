@@ -2,18 +2,30 @@
 pub mod errors;
 
 pub mod namespace;
+pub mod namespace_quota;
 
 pub mod nginx_log;
 pub mod nginx_template;
+pub mod nginx_template_revision;
 
 pub mod cargo;
 pub mod cargo_env;
 
 pub mod cluster;
 pub mod cluster_cargo;
+pub mod cluster_cargo_network;
 pub mod cluster_network;
+pub mod cluster_network_policy;
+pub mod cluster_route;
+pub mod cluster_access_policy;
+pub mod cluster_auth_policy;
+pub mod cluster_maintenance;
 
 pub mod git_repository;
 pub mod git_repository_branch;
 
 pub mod cluster_variable;
+
+pub mod event;
+
+pub mod certificate;
@@ -0,0 +1,101 @@
+//! Repository to manage resource quotas of namespaces in database
+use ntex::web;
+use diesel::prelude::*;
+
+use crate::services;
+use crate::models::{Pool, NamespaceQuotaItem, PgDeleteGeneric};
+
+use crate::errors::HttpResponseError;
+use super::errors::db_blocking_error;
+
+/// Create or replace the quota of a namespace
+pub async fn upsert(
+  item: NamespaceQuotaItem,
+  pool: &web::types::State<Pool>,
+) -> Result<NamespaceQuotaItem, HttpResponseError> {
+  use crate::schema::namespace_quotas::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::insert_into(dsl::namespace_quotas)
+      .values(&item)
+      .on_conflict(dsl::namespace_name)
+      .do_update()
+      .set(&item)
+      .execute(&conn)?;
+    Ok(item)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+/// Find the quota of a namespace if there is one
+pub async fn find_by_namespace(
+  namespace: String,
+  pool: &web::types::State<Pool>,
+) -> Result<Option<NamespaceQuotaItem>, HttpResponseError> {
+  use crate::schema::namespace_quotas::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::namespace_quotas
+      .filter(dsl::namespace_name.eq(namespace))
+      .get_result(&conn)
+      .optional()
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
+
+pub async fn delete_by_namespace(
+  namespace: String,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  use crate::schema::namespace_quotas::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::delete(
+      dsl::namespace_quotas.filter(dsl::namespace_name.eq(namespace)),
+    )
+    .execute(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(result) => Ok(PgDeleteGeneric { count: result }),
+  }
+}
+
+/// List memory and cpu limits of every replica of a namespace
+/// a replica is a cargo joined to a cluster
+pub async fn list_replica_limits(
+  namespace: String,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<(Option<i64>, Option<i64>)>, HttpResponseError> {
+  use crate::schema::{cargoes, cluster_cargoes};
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    cluster_cargoes::table
+      .inner_join(cargoes::table)
+      .filter(cargoes::namespace_name.eq(namespace))
+      .select((cargoes::memory_limit, cargoes::cpu_limit))
+      .load(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
@@ -1,31 +1,452 @@
 //! Repository to manage nginx_logs in database
 use ntex::web;
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, Utc};
 use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Bool, Double, Text, Timestamptz};
 
 use crate::services;
-use crate::models::{Pool, NginxLogItem, NginxLogPartial};
+use crate::models::{
+  Pool, PgDeleteGeneric, NginxLogItem, NginxLogSearchQuery, NginxLogSort,
+  NginxLogCursor, NginxStats, NginxStatsMinute, NginxStatsTopItem,
+  NginxStatusClasses,
+};
 
 use crate::errors::HttpResponseError;
 use super::errors::db_blocking_error;
 
-pub async fn create_log(
-  partial: NginxLogPartial,
+/// Maximum number of logs in one insert
+/// to stay under the limit of parameters of postgresql
+const INSERT_CHUNK: usize = 1000;
+
+/// Insert logs in one transaction with multi rows inserts
+pub async fn create_logs(
+  items: Vec<NginxLogItem>,
   pool: &web::types::State<Pool>,
-) -> Result<NginxLogItem, HttpResponseError> {
+) -> Result<usize, HttpResponseError> {
   use crate::schema::nginx_logs::dsl;
 
   let conn = services::postgresql::get_pool_conn(pool)?;
   let res = web::block(move || {
-    let item = NginxLogItem::from(partial);
-    diesel::insert_into(dsl::nginx_logs)
-      .values(&item)
-      .execute(&conn)?;
-    Ok(item)
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+      let mut count = 0;
+      for chunk in items.chunks(INSERT_CHUNK) {
+        count += diesel::insert_into(dsl::nginx_logs)
+          .values(chunk)
+          .execute(&conn)?;
+      }
+      Ok(count)
+    })
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(count) => Ok(count),
+  }
+}
+
+/// Create the partition of logs of the given day when it doesn't exist
+pub async fn create_partition(
+  day: NaiveDate,
+  pool: &web::types::State<Pool>,
+) -> Result<(), HttpResponseError> {
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    let sql = format!(
+      r#"CREATE TABLE IF NOT EXISTS "nginx_logs_{}" PARTITION OF "nginx_logs"
+  FOR VALUES FROM ('{}T00:00:00Z') TO ('{}T00:00:00Z')"#,
+      day.format("%Y%m%d"),
+      day,
+      day.succ(),
+    );
+    diesel::sql_query(sql).execute(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(_) => Ok(()),
+  }
+}
+
+#[derive(QueryableByName)]
+struct PartitionRow {
+  #[sql_type = "Text"]
+  name: String,
+}
+
+/// Days of the partitions of logs
+pub async fn list_partitions(
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<NaiveDate>, HttpResponseError> {
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::sql_query(
+      r#"SELECT "child"."relname"::text AS "name" FROM "pg_inherits"
+  JOIN "pg_class" AS "child" ON "child"."oid" = "pg_inherits"."inhrelid"
+  JOIN "pg_class" AS "parent" ON "parent"."oid" = "pg_inherits"."inhparent"
+  WHERE "parent"."relname" = 'nginx_logs'"#,
+    )
+    .load::<PartitionRow>(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(rows) => Ok(
+      rows
+        .into_iter()
+        .filter_map(|row| {
+          let day = row.name.strip_prefix("nginx_logs_")?;
+          NaiveDate::parse_from_str(day, "%Y%m%d").ok()
+        })
+        .collect(),
+    ),
+  }
+}
+
+/// Drop the partition of logs of the given day with his logs
+pub async fn drop_partition(
+  day: NaiveDate,
+  pool: &web::types::State<Pool>,
+) -> Result<(), HttpResponseError> {
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    let sql = format!(
+      r#"DROP TABLE IF EXISTS "nginx_logs_{}""#,
+      day.format("%Y%m%d")
+    );
+    diesel::sql_query(sql).execute(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(_) => Ok(()),
+  }
+}
+
+/// Oldest logs, only the ones before the given date when there is one
+pub async fn list_oldest(
+  before: Option<DateTime<Utc>>,
+  limit: i64,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<NginxLogItem>, HttpResponseError> {
+  use crate::schema::nginx_logs::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    let mut sql = dsl::nginx_logs.into_boxed();
+    if let Some(before) = before {
+      sql = sql.filter(dsl::date_gmt.lt(before));
+    }
+    sql
+      .order((dsl::date_gmt.asc(), dsl::key.asc()))
+      .limit(limit)
+      .load::<NginxLogItem>(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+pub async fn count(
+  pool: &web::types::State<Pool>,
+) -> Result<i64, HttpResponseError> {
+  use crate::schema::nginx_logs::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res =
+    web::block(move || dsl::nginx_logs.count().get_result::<i64>(&conn)).await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(count) => Ok(count),
+  }
+}
+
+pub async fn delete_by_keys(
+  keys: Vec<Uuid>,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  use crate::schema::nginx_logs::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    diesel::delete(dsl::nginx_logs.filter(dsl::key.eq_any(keys))).execute(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(count) => Ok(PgDeleteGeneric { count }),
+  }
+}
+
+/// Escape wildcards of a like pattern
+fn escape_like(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('%', "\\%")
+    .replace('_', "\\_")
+}
+
+/// Search logs matching the filters of the query
+/// logs after the cursor are returned in the given sort
+pub async fn search(
+  query: NginxLogSearchQuery,
+  sort: NginxLogSort,
+  cursor: Option<NginxLogCursor>,
+  limit: i64,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<NginxLogItem>, HttpResponseError> {
+  use crate::schema::nginx_logs::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    let mut sql = dsl::nginx_logs.into_boxed();
+    if let Some(since) = query.since {
+      sql = sql.filter(dsl::date_gmt.ge(since));
+    }
+    if let Some(until) = query.until {
+      sql = sql.filter(dsl::date_gmt.lt(until));
+    }
+    if let Some(host) = query.host {
+      sql = sql.filter(dsl::host.eq(host));
+    }
+    if let Some(uri_prefix) = query.uri_prefix {
+      sql = sql.filter(dsl::uri.like(format!("{}%", escape_like(&uri_prefix))));
+    }
+    if let Some(status_min) = query.status_min {
+      sql = sql.filter(dsl::status.ge(status_min));
+    }
+    if let Some(status_max) = query.status_max {
+      sql = sql.filter(dsl::status.le(status_max));
+    }
+    if let Some(method) = query.method {
+      sql = sql.filter(dsl::request_method.eq(method.to_uppercase()));
+    }
+    if let Some(remote_addr) = query.remote_addr {
+      sql = sql.filter(dsl::remote_addr.eq(remote_addr));
+    }
+    if let Some(min_request_time) = query.min_request_time {
+      sql = sql.filter(dsl::request_time.ge(min_request_time));
+    }
+    sql = match (sort, cursor) {
+      (NginxLogSort::DateDesc, Some(NginxLogCursor::Date(date, key))) => sql
+        .filter(
+          dsl::date_gmt
+            .lt(date)
+            .or(dsl::date_gmt.eq(date).and(dsl::key.lt(key))),
+        ),
+      (NginxLogSort::DateAsc, Some(NginxLogCursor::Date(date, key))) => sql
+        .filter(
+          dsl::date_gmt
+            .gt(date)
+            .or(dsl::date_gmt.eq(date).and(dsl::key.gt(key))),
+        ),
+      (
+        NginxLogSort::RequestTimeDesc,
+        Some(NginxLogCursor::RequestTime(time, key)),
+      ) => sql.filter(
+        dsl::request_time
+          .lt(time)
+          .or(dsl::request_time.eq(time).and(dsl::key.lt(key))),
+      ),
+      (
+        NginxLogSort::RequestTimeAsc,
+        Some(NginxLogCursor::RequestTime(time, key)),
+      ) => sql.filter(
+        dsl::request_time
+          .gt(time)
+          .or(dsl::request_time.eq(time).and(dsl::key.gt(key))),
+      ),
+      _ => sql,
+    };
+    sql = match sort {
+      NginxLogSort::DateDesc => {
+        sql.order((dsl::date_gmt.desc(), dsl::key.desc()))
+      }
+      NginxLogSort::DateAsc => sql.order((dsl::date_gmt.asc(), dsl::key.asc())),
+      NginxLogSort::RequestTimeDesc => {
+        sql.order((dsl::request_time.desc(), dsl::key.desc()))
+      }
+      NginxLogSort::RequestTimeAsc => {
+        sql.order((dsl::request_time.asc(), dsl::key.asc()))
+      }
+    };
+    sql.limit(limit).load::<NginxLogItem>(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+/// Logs of the window for the given hosts or for all hosts when `$3` is true
+const STATS_FILTER: &str = r#""date_gmt" >= $1 AND "date_gmt" < $2
+  AND ($3 OR "host" = ANY($4))"#;
+
+/// Bind the parameters of `STATS_FILTER` to a query
+macro_rules! stats_query {
+  ($sql:expr, $since:expr, $until:expr, $hosts:expr) => {
+    diesel::sql_query($sql)
+      .bind::<Timestamptz, _>($since)
+      .bind::<Timestamptz, _>($until)
+      .bind::<Bool, _>($hosts.is_none())
+      .bind::<Array<Text>, _>($hosts.to_owned().unwrap_or_default())
+  };
+}
+
+#[derive(QueryableByName)]
+struct StatsSummaryRow {
+  #[sql_type = "BigInt"]
+  requests: i64,
+  #[sql_type = "BigInt"]
+  status_1xx: i64,
+  #[sql_type = "BigInt"]
+  status_2xx: i64,
+  #[sql_type = "BigInt"]
+  status_3xx: i64,
+  #[sql_type = "BigInt"]
+  status_4xx: i64,
+  #[sql_type = "BigInt"]
+  status_5xx: i64,
+  #[sql_type = "BigInt"]
+  bytes_sent: i64,
+  #[sql_type = "Double"]
+  p50: f64,
+  #[sql_type = "Double"]
+  p95: f64,
+  #[sql_type = "Double"]
+  p99: f64,
+}
+
+#[derive(QueryableByName)]
+struct StatsMinuteRow {
+  #[sql_type = "Timestamptz"]
+  minute: DateTime<Utc>,
+  #[sql_type = "BigInt"]
+  requests: i64,
+}
+
+#[derive(QueryableByName)]
+struct StatsTopRow {
+  #[sql_type = "Text"]
+  value: String,
+  #[sql_type = "BigInt"]
+  requests: i64,
+}
+
+fn stats_top_sql(column: &str) -> String {
+  format!(
+    r#"SELECT "{column}" AS "value", COUNT(*) AS "requests"
+    FROM "nginx_logs" WHERE {filter} AND "{column}" IS NOT NULL
+    GROUP BY "{column}" ORDER BY "requests" DESC, "{column}" LIMIT $5"#,
+    column = column,
+    filter = STATS_FILTER,
+  )
+}
+
+/// Aggregate the logs of a window
+/// only logs of the given hosts are used when some are given
+pub async fn stats(
+  since: DateTime<Utc>,
+  until: DateTime<Utc>,
+  hosts: Option<Vec<String>>,
+  top: i64,
+  pool: &web::types::State<Pool>,
+) -> Result<NginxStats, HttpResponseError> {
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    let summary = stats_query!(
+      format!(
+        r#"SELECT COUNT(*) AS "requests",
+        COUNT(*) FILTER (WHERE "status" < 200) AS "status_1xx",
+        COUNT(*) FILTER (WHERE "status" >= 200 AND "status" < 300) AS "status_2xx",
+        COUNT(*) FILTER (WHERE "status" >= 300 AND "status" < 400) AS "status_3xx",
+        COUNT(*) FILTER (WHERE "status" >= 400 AND "status" < 500) AS "status_4xx",
+        COUNT(*) FILTER (WHERE "status" >= 500) AS "status_5xx",
+        COALESCE(SUM("body_bytes_sent"), 0)::BIGINT AS "bytes_sent",
+        COALESCE(percentile_cont(0.5) WITHIN GROUP (ORDER BY "request_time"), 0) AS "p50",
+        COALESCE(percentile_cont(0.95) WITHIN GROUP (ORDER BY "request_time"), 0) AS "p95",
+        COALESCE(percentile_cont(0.99) WITHIN GROUP (ORDER BY "request_time"), 0) AS "p99"
+        FROM "nginx_logs" WHERE {}"#,
+        STATS_FILTER
+      ),
+      since,
+      until,
+      hosts
+    )
+    .get_result::<StatsSummaryRow>(&conn)?;
+    let minutes = stats_query!(
+      format!(
+        r#"SELECT date_trunc('minute', "date_gmt") AS "minute",
+        COUNT(*) AS "requests"
+        FROM "nginx_logs" WHERE {}
+        GROUP BY 1 ORDER BY 1"#,
+        STATS_FILTER
+      ),
+      since,
+      until,
+      hosts
+    )
+    .load::<StatsMinuteRow>(&conn)?;
+    let top_uris = stats_query!(stats_top_sql("uri"), since, until, hosts)
+      .bind::<BigInt, _>(top)
+      .load::<StatsTopRow>(&conn)?;
+    let top_user_agents =
+      stats_query!(stats_top_sql("http_user_agent"), since, until, hosts)
+        .bind::<BigInt, _>(top)
+        .load::<StatsTopRow>(&conn)?;
+    let window_minutes = (until - since).num_seconds() as f64 / 60.0;
+    let to_top_items = |rows: Vec<StatsTopRow>| {
+      rows
+        .into_iter()
+        .map(|row| NginxStatsTopItem {
+          value: row.value,
+          requests: row.requests,
+        })
+        .collect::<Vec<_>>()
+    };
+    Ok::<_, diesel::result::Error>(NginxStats {
+      since,
+      until,
+      requests: summary.requests,
+      average_requests_per_minute: summary.requests as f64 / window_minutes,
+      requests_per_minute: minutes
+        .into_iter()
+        .map(|row| NginxStatsMinute {
+          minute: row.minute,
+          requests: row.requests,
+        })
+        .collect(),
+      status: NginxStatusClasses {
+        status_1xx: summary.status_1xx,
+        status_2xx: summary.status_2xx,
+        status_3xx: summary.status_3xx,
+        status_4xx: summary.status_4xx,
+        status_5xx: summary.status_5xx,
+      },
+      request_time_p50: summary.p50,
+      request_time_p95: summary.p95,
+      request_time_p99: summary.p99,
+      bytes_sent: summary.bytes_sent,
+      top_uris: to_top_items(top_uris),
+      top_user_agents: to_top_items(top_user_agents),
+      hosts,
+    })
   })
   .await;
 
   match res {
     Err(err) => Err(db_blocking_error(err)),
-    Ok(item) => Ok(item),
+    Ok(stats) => Ok(stats),
   }
 }
@@ -1,8 +1,12 @@
 use ntex::web;
+use chrono::Utc;
 use diesel::prelude::*;
 
 use crate::services;
-use crate::models::{Pool, NginxTemplateItem, PgDeleteGeneric};
+use crate::models::{
+  Pool, NginxTemplateItem, NginxTemplateRevisionItem, NginxTemplateUpdateBody,
+  PgDeleteGeneric,
+};
 
 use crate::errors::HttpResponseError;
 use crate::repositories::errors::db_blocking_error;
@@ -27,12 +31,26 @@ pub async fn create(
 ) -> Result<NginxTemplateItem, HttpResponseError> {
   use crate::schema::nginx_templates::dsl;
 
+  use crate::schema::nginx_template_revisions::dsl as revision_dsl;
+
   let conn = services::postgresql::get_pool_conn(pool)?;
   let res = web::block(move || {
-    diesel::insert_into(dsl::nginx_templates)
-      .values(&item)
-      .execute(&conn)?;
-    Ok(item)
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+      diesel::insert_into(dsl::nginx_templates)
+        .values(&item)
+        .execute(&conn)?;
+      let revision = NginxTemplateRevisionItem {
+        template_name: item.name.to_owned(),
+        revision: 1,
+        mode: item.mode.to_owned(),
+        content: item.content.to_owned(),
+        created_at: Utc::now(),
+      };
+      diesel::insert_into(revision_dsl::nginx_template_revisions)
+        .values(&revision)
+        .execute(&conn)?;
+      Ok(item)
+    })
   })
   .await;
   match res {
@@ -67,10 +85,19 @@ pub async fn delete_by_name(
 ) -> Result<PgDeleteGeneric, HttpResponseError> {
   use crate::schema::nginx_templates::dsl;
 
+  use crate::schema::nginx_template_revisions::dsl as revision_dsl;
+
   let conn = services::postgresql::get_pool_conn(pool)?;
   let res = web::block(move || {
-    diesel::delete(dsl::nginx_templates.filter(dsl::name.eq(name)))
-      .execute(&conn)
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+      diesel::delete(
+        revision_dsl::nginx_template_revisions
+          .filter(revision_dsl::template_name.eq(&name)),
+      )
+      .execute(&conn)?;
+      diesel::delete(dsl::nginx_templates.filter(dsl::name.eq(name)))
+        .execute(&conn)
+    })
   })
   .await;
 
@@ -79,3 +106,50 @@ pub async fn delete_by_name(
     Ok(result) => Ok(PgDeleteGeneric { count: result }),
   }
 }
+
+/// Replace the content of a template and store it as a new revision
+pub async fn update(
+  name: String,
+  item: NginxTemplateUpdateBody,
+  pool: &web::types::State<Pool>,
+) -> Result<NginxTemplateRevisionItem, HttpResponseError> {
+  use crate::schema::nginx_templates::dsl;
+  use crate::schema::nginx_template_revisions::dsl as revision_dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+      let template: NginxTemplateItem = dsl::nginx_templates
+        .filter(dsl::name.eq(&name))
+        .for_update()
+        .get_result(&conn)?;
+      let last_revision: Option<i32> = revision_dsl::nginx_template_revisions
+        .filter(revision_dsl::template_name.eq(&name))
+        .select(diesel::dsl::max(revision_dsl::revision))
+        .get_result(&conn)?;
+      let revision = NginxTemplateRevisionItem {
+        template_name: name.to_owned(),
+        revision: last_revision.unwrap_or_default() + 1,
+        mode: item.mode.unwrap_or(template.mode),
+        content: item.content,
+        created_at: Utc::now(),
+      };
+      diesel::update(dsl::nginx_templates.filter(dsl::name.eq(&name)))
+        .set((
+          dsl::mode.eq(revision.mode.to_owned()),
+          dsl::content.eq(revision.content.to_owned()),
+        ))
+        .execute(&conn)?;
+      diesel::insert_into(revision_dsl::nginx_template_revisions)
+        .values(&revision)
+        .execute(&conn)?;
+      Ok(revision)
+    })
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
@@ -0,0 +1,52 @@
+use ntex::web;
+use diesel::prelude::*;
+
+use crate::services;
+use crate::models::{Pool, NginxTemplateRevisionItem};
+
+use crate::errors::HttpResponseError;
+use crate::repositories::errors::db_blocking_error;
+
+/// List revisions of a template from the latest to the oldest
+pub async fn list_by_template(
+  template_name: String,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<NginxTemplateRevisionItem>, HttpResponseError> {
+  use crate::schema::nginx_template_revisions::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::nginx_template_revisions
+      .filter(dsl::template_name.eq(template_name))
+      .order(dsl::revision.desc())
+      .load(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(items) => Ok(items),
+  }
+}
+
+pub async fn find(
+  template_name: String,
+  revision: i32,
+  pool: &web::types::State<Pool>,
+) -> Result<NginxTemplateRevisionItem, HttpResponseError> {
+  use crate::schema::nginx_template_revisions::dsl;
+
+  let conn = services::postgresql::get_pool_conn(pool)?;
+  let res = web::block(move || {
+    dsl::nginx_template_revisions
+      .filter(dsl::template_name.eq(template_name))
+      .filter(dsl::revision.eq(revision))
+      .get_result(&conn)
+  })
+  .await;
+
+  match res {
+    Err(err) => Err(db_blocking_error(err)),
+    Ok(item) => Ok(item),
+  }
+}
@@ -21,6 +21,68 @@ table! {
         dns_entry -> Nullable<Varchar>,
         domainname -> Nullable<Varchar>,
         hostname -> Nullable<Varchar>,
+        memory_limit -> Nullable<Int8>,
+        cpu_limit -> Nullable<Int8>,
+    }
+}
+
+table! {
+    use crate::models::exports::*;
+
+    certificates (name) {
+        name -> Varchar,
+        domains -> Array<Text>,
+        issuer -> Varchar,
+        not_before -> Timestamptz,
+        not_after -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    use crate::models::exports::*;
+
+    cluster_access_policies (key) {
+        key -> Varchar,
+        cluster_key -> Varchar,
+        name -> Varchar,
+        domain -> Nullable<Varchar>,
+        allow -> Array<Text>,
+        deny -> Array<Text>,
+        rate -> Nullable<Int4>,
+        burst -> Nullable<Int4>,
+        connections -> Nullable<Int4>,
+    }
+}
+
+table! {
+    use crate::models::exports::*;
+
+    cluster_auth_policies (key) {
+        key -> Varchar,
+        cluster_key -> Varchar,
+        name -> Varchar,
+        kind -> Cluster_auth_kind,
+        domain -> Nullable<Varchar>,
+        route_name -> Nullable<Varchar>,
+        realm -> Nullable<Varchar>,
+        users -> Array<Text>,
+        cargo_name -> Nullable<Varchar>,
+        port -> Nullable<Int4>,
+        path -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use crate::models::exports::*;
+
+    cluster_cargo_networks (key) {
+        key -> Varchar,
+        cluster_cargo_key -> Varchar,
+        network_key -> Varchar,
+        is_primary -> Bool,
+        ipv4_address -> Nullable<Varchar>,
+        ipv6_address -> Nullable<Varchar>,
     }
 }
 
@@ -35,6 +97,31 @@ table! {
     }
 }
 
+table! {
+    use crate::models::exports::*;
+
+    cluster_maintenances (cluster_key) {
+        cluster_key -> Varchar,
+        enabled -> Bool,
+        body -> Nullable<Text>,
+        allow -> Array<Text>,
+        bad_gateway_body -> Nullable<Text>,
+        gateway_timeout_body -> Nullable<Text>,
+    }
+}
+
+table! {
+    use crate::models::exports::*;
+
+    cluster_network_policies (key) {
+        key -> Varchar,
+        cluster_key -> Varchar,
+        network_key -> Varchar,
+        allowed_networks -> Array<Text>,
+        allow_egress -> Bool,
+    }
+}
+
 table! {
     use crate::models::exports::*;
 
@@ -45,6 +132,30 @@ table! {
         docker_network_id -> Varchar,
         default_gateway -> Varchar,
         cluster_key -> Varchar,
+        subnet -> Nullable<Varchar>,
+        ip_range -> Nullable<Varchar>,
+        internal -> Bool,
+        ipv6_subnet -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use crate::models::exports::*;
+
+    cluster_routes (key) {
+        key -> Varchar,
+        cluster_key -> Varchar,
+        name -> Varchar,
+        domain -> Varchar,
+        path -> Varchar,
+        cargo_name -> Nullable<Varchar>,
+        port -> Nullable<Int4>,
+        tls -> Bool,
+        redirect_to -> Nullable<Varchar>,
+        headers -> Array<Text>,
+        websocket -> Bool,
+        connect_timeout -> Nullable<Int4>,
+        read_timeout -> Nullable<Int4>,
     }
 }
 
@@ -67,6 +178,22 @@ table! {
         name -> Varchar,
         namespace -> Varchar,
         proxy_templates -> Array<Text>,
+        domains -> Array<Text>,
+    }
+}
+
+table! {
+    use crate::models::exports::*;
+
+    events (key) {
+        key -> Uuid,
+        cluster_key -> Varchar,
+        kind -> Event_kind,
+        actor -> Varchar,
+        target -> Nullable<Varchar>,
+        outcome -> Event_outcome,
+        error -> Nullable<Text>,
+        created_at -> Timestamptz,
     }
 }
 
@@ -92,6 +219,20 @@ table! {
     }
 }
 
+table! {
+    use crate::models::exports::*;
+
+    namespace_quotas (namespace_name) {
+        namespace_name -> Varchar,
+        max_cargoes -> Nullable<Int8>,
+        max_clusters -> Nullable<Int8>,
+        max_networks -> Nullable<Int8>,
+        max_replicas -> Nullable<Int8>,
+        max_memory -> Nullable<Int8>,
+        max_cpu -> Nullable<Int8>,
+    }
+}
+
 table! {
     use crate::models::exports::*;
 
@@ -127,6 +268,18 @@ table! {
     }
 }
 
+table! {
+    use crate::models::exports::*;
+
+    nginx_template_revisions (template_name, revision) {
+        template_name -> Varchar,
+        revision -> Int4,
+        mode -> Nginx_template_modes,
+        content -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     use crate::models::exports::*;
 
@@ -138,21 +291,41 @@ table! {
 }
 
 joinable!(cargoes -> namespaces (namespace_name));
+joinable!(cluster_cargo_networks -> cluster_cargoes (cluster_cargo_key));
+joinable!(cluster_cargo_networks -> cluster_networks (network_key));
 joinable!(cluster_cargoes -> cargoes (cargo_key));
 joinable!(cluster_cargoes -> cluster_networks (network_key));
 joinable!(cluster_cargoes -> clusters (cluster_key));
+joinable!(cluster_network_policies -> cluster_networks (network_key));
+joinable!(cluster_network_policies -> clusters (cluster_key));
 joinable!(cluster_networks -> clusters (cluster_key));
+joinable!(cluster_access_policies -> clusters (cluster_key));
+joinable!(cluster_auth_policies -> clusters (cluster_key));
+joinable!(cluster_maintenances -> clusters (cluster_key));
+joinable!(cluster_routes -> clusters (cluster_key));
+joinable!(namespace_quotas -> namespaces (namespace_name));
+joinable!(nginx_template_revisions -> nginx_templates (template_name));
 
 allow_tables_to_appear_in_same_query!(
     cargo_environnements,
     cargoes,
+    certificates,
+    cluster_access_policies,
+    cluster_auth_policies,
+    cluster_cargo_networks,
     cluster_cargoes,
+    cluster_maintenances,
+    cluster_network_policies,
     cluster_networks,
+    cluster_routes,
     cluster_variables,
     clusters,
+    events,
     git_repositories,
     git_repository_branches,
+    namespace_quotas,
     namespaces,
     nginx_logs,
+    nginx_template_revisions,
     nginx_templates,
 );
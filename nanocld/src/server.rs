@@ -43,12 +43,21 @@ pub async fn start<'a>(
       .configure(controllers::cluster::ntex_config)
       // bind controller cluster variables
       .configure(controllers::cluster_variable::ntex_config)
+      // bind controller cluster routes
+      .configure(controllers::cluster_route::ntex_config)
+      .configure(controllers::cluster_access_policy::ntex_config)
+      .configure(controllers::cluster_auth_policy::ntex_config)
+      .configure(controllers::cluster_maintenance::ntex_config)
       // bind controller cluster network
       .configure(controllers::cluster_network::ntex_config)
+      // bind controller cluster network policy
+      .configure(controllers::cluster_network_policy::ntex_config)
       // bind controller cluster cargo
       .configure(controllers::cluster_cargo::ntex_config)
       // bind controller nginx template
       .configure(controllers::nginx_template::ntex_config)
+      // bind controller certificate
+      .configure(controllers::certificate::ntex_config)
       // bind controller container
       .configure(controllers::container::ntex_config)
       // bind controller cargo
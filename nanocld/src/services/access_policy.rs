@@ -0,0 +1,406 @@
+//! Access policies of the domains of a cluster
+//! Policies are rendered as nginx allow, deny, limit_req and limit_conn
+//! directives. Zones of the limits are declared in a config file applied
+//! with the proxy templates, directives are given to the templates
+//! by domain and added to the servers of the routes.
+use ntex::web;
+use ntex::http::StatusCode;
+use thiserror::Error;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+use crate::models::{
+  Pool, ClusterItem, ClusterAccessPolicyItem, ClusterAccessPolicyPartial,
+  PgDeleteGeneric,
+};
+
+use super::acme::template_key;
+use super::ipam::{self, Cidr};
+use super::route::is_valid_domain;
+
+/// Size of the shared memory zones keeping the state of clients
+const ZONE_SIZE: &str = "10m";
+
+#[derive(Debug, Error)]
+pub enum AccessPolicyError {
+  #[error("invalid access policy")]
+  Invalid(String),
+}
+
+impl IntoHttpResponseError for AccessPolicyError {
+  fn to_http_error(&self) -> HttpResponseError {
+    match self {
+      AccessPolicyError::Invalid(msg) => HttpResponseError {
+        msg: format!("invalid access policy {}", msg),
+        status: StatusCode::BAD_REQUEST,
+      },
+    }
+  }
+}
+
+/// Name of the config file declaring the zones of the policies of a cluster
+pub fn config_file_name(cluster_key: &str) -> String {
+  format!("{}.nanocl-access", cluster_key)
+}
+
+/// Path of the config file declaring the zones of the policies of a cluster
+pub fn config_file_path(cluster_key: &str, state_dir: &str) -> PathBuf {
+  Path::new(state_dir)
+    .join("nginx/sites-enabled")
+    .join(format!("{}.conf", config_file_name(cluster_key)))
+}
+
+/// Address or network in the form expected by nginx
+pub fn normalize_address(value: &str) -> Result<String, AccessPolicyError> {
+  let invalid = || AccessPolicyError::Invalid(format!("address {}", value));
+  if value.contains('/') {
+    let cidr = value.parse::<Cidr>().map_err(|_| invalid())?;
+    return Ok(cidr.to_string());
+  }
+  let addr = ipam::parse_address(value).map_err(|_| invalid())?;
+  Ok(addr.to_string())
+}
+
+/// Ensure a policy can be rendered as nginx directives
+/// and return his addresses normalized
+pub fn validate(
+  item: &ClusterAccessPolicyPartial,
+) -> Result<(Vec<String>, Vec<String>), AccessPolicyError> {
+  let is_valid_name = !item.name.is_empty()
+    && item
+      .name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+  if !is_valid_name {
+    return Err(AccessPolicyError::Invalid(format!(
+      "name {} must only contain letters, digits, '-' or '_'",
+      item.name
+    )));
+  }
+  if let Some(domain) = &item.domain {
+    if !is_valid_domain(domain) {
+      return Err(AccessPolicyError::Invalid(format!("domain {}", domain)));
+    }
+  }
+  let allow = item
+    .allow
+    .iter()
+    .flatten()
+    .map(|value| normalize_address(value))
+    .collect::<Result<Vec<_>, _>>()?;
+  let deny = item
+    .deny
+    .iter()
+    .flatten()
+    .map(|value| normalize_address(value))
+    .collect::<Result<Vec<_>, _>>()?;
+  if let Some(rate) = item.rate {
+    if rate <= 0 {
+      return Err(AccessPolicyError::Invalid(format!("rate {}", rate)));
+    }
+  }
+  match (item.rate, item.burst) {
+    (_, Some(burst)) if burst < 0 => {
+      return Err(AccessPolicyError::Invalid(format!("burst {}", burst)));
+    }
+    (None, Some(_)) => {
+      return Err(AccessPolicyError::Invalid(String::from(
+        "burst requires a rate",
+      )));
+    }
+    _ => {}
+  }
+  if let Some(connections) = item.connections {
+    if connections <= 0 {
+      return Err(AccessPolicyError::Invalid(format!(
+        "connections {}",
+        connections
+      )));
+    }
+  }
+  if allow.is_empty()
+    && deny.is_empty()
+    && item.rate.is_none()
+    && item.connections.is_none()
+  {
+    return Err(AccessPolicyError::Invalid(String::from(
+      "at least one of allow, deny, rate or connections must be set",
+    )));
+  }
+  Ok((allow, deny))
+}
+
+/// Name of the zones of a policy, nginx names can't contain dashes
+fn zone_name(policy: &ClusterAccessPolicyItem) -> String {
+  format!("nanocl_{}", policy.key.replace('-', "_"))
+}
+
+/// Generate the zones of the rate and connection limits of policies
+pub fn gen_zones(
+  cluster_key: &str,
+  policies: &[ClusterAccessPolicyItem],
+) -> String {
+  let mut output = format!(
+    "# generated by nanocl from the access policies of cluster {}\n",
+    cluster_key
+  );
+  for policy in policies {
+    let zone = zone_name(policy);
+    if let Some(rate) = policy.rate {
+      output += &format!(
+        "limit_req_zone $binary_remote_addr zone={}:{} rate={}r/s;\n",
+        zone, ZONE_SIZE, rate
+      );
+    }
+    if policy.connections.is_some() {
+      output += &format!(
+        "limit_conn_zone $binary_remote_addr zone={}_conn:{};\n",
+        zone, ZONE_SIZE
+      );
+    }
+  }
+  output
+}
+
+/// Directives of the policies applying to a domain for a server block
+/// denied addresses are checked before allowed ones
+fn gen_directives(
+  output: &mut String,
+  domain: &str,
+  policies: &[ClusterAccessPolicyItem],
+) -> std::fmt::Result {
+  let policies = policies
+    .iter()
+    .filter(|policy| {
+      policy.domain.is_none() || policy.domain.as_deref() == Some(domain)
+    })
+    .collect::<Vec<_>>();
+  for address in policies.iter().flat_map(|policy| &policy.deny) {
+    writeln!(output, "  deny {};", address)?;
+  }
+  for address in policies.iter().flat_map(|policy| &policy.allow) {
+    writeln!(output, "  allow {};", address)?;
+  }
+  if policies.iter().any(|policy| !policy.allow.is_empty()) {
+    writeln!(output, "  deny all;")?;
+  }
+  for policy in policies.iter().filter(|policy| policy.rate.is_some()) {
+    match policy.burst {
+      Some(burst) if burst > 0 => writeln!(
+        output,
+        "  limit_req zone={} burst={} nodelay;",
+        zone_name(policy),
+        burst
+      )?,
+      _ => writeln!(output, "  limit_req zone={};", zone_name(policy))?,
+    }
+  }
+  if policies.iter().any(|policy| policy.rate.is_some()) {
+    writeln!(output, "  limit_req_status 429;")?;
+  }
+  for policy in &policies {
+    if let Some(connections) = policy.connections {
+      writeln!(
+        output,
+        "  limit_conn {}_conn {};",
+        zone_name(policy),
+        connections
+      )?;
+    }
+  }
+  if policies.iter().any(|policy| policy.connections.is_some()) {
+    writeln!(output, "  limit_conn_status 429;")?;
+  }
+  Ok(())
+}
+
+/// Directives of the policies by template key of the given domains
+/// domains without policy are omitted
+pub fn gen_template_directives<'a>(
+  domains: impl Iterator<Item = &'a str>,
+  policies: &[ClusterAccessPolicyItem],
+) -> Result<HashMap<String, String>, AccessPolicyError> {
+  let mut directives = HashMap::new();
+  for domain in domains {
+    let mut output = String::new();
+    gen_directives(&mut output, domain, policies)
+      .map_err(|err| AccessPolicyError::Invalid(err.to_string()))?;
+    if !output.is_empty() {
+      directives.insert(template_key(domain), output);
+    }
+  }
+  Ok(directives)
+}
+
+/// Create a policy and start the cluster to render it
+/// the policy is removed if the cluster fail to render it
+pub async fn create(
+  cluster: &ClusterItem,
+  item: ClusterAccessPolicyPartial,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<ClusterAccessPolicyItem, HttpResponseError> {
+  let (allow, deny) = validate(&item).map_err(|err| err.to_http_error())?;
+  let key = format!("{}-{}", cluster.key, item.name);
+  if repositories::cluster_access_policy::find_by_key(key.to_owned(), pool)
+    .await
+    .is_ok()
+  {
+    return Err(HttpResponseError {
+      msg: format!("access policy {} already exists", item.name),
+      status: StatusCode::CONFLICT,
+    });
+  }
+  let policy = ClusterAccessPolicyItem {
+    key,
+    cluster_key: cluster.key.to_owned(),
+    name: item.name,
+    domain: item.domain,
+    allow,
+    deny,
+    rate: item.rate,
+    burst: item.burst,
+    connections: item.connections,
+  };
+  let policy =
+    repositories::cluster_access_policy::create(policy, pool).await?;
+  let res =
+    services::cluster::start(cluster, config, pool, docker_api, actor).await;
+  if let Err(err) = res {
+    repositories::cluster_access_policy::delete_by_key(policy.key, pool)
+      .await?;
+    return Err(err);
+  }
+  Ok(policy)
+}
+
+/// Delete a policy and render the cluster without it
+/// the zones file is removed with the last policy of the cluster
+pub async fn delete(
+  cluster: &ClusterItem,
+  name: &str,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  let key = format!("{}-{}", cluster.key, name);
+  repositories::cluster_access_policy::find_by_key(key.to_owned(), pool)
+    .await?;
+  let res =
+    repositories::cluster_access_policy::delete_by_key(key, pool).await?;
+  // Servers stop using the zones before they are removed
+  services::cluster::start(cluster, config, pool, docker_api, actor).await?;
+  let policies = repositories::cluster_access_policy::list_by_cluster(
+    cluster.key.to_owned(),
+    pool,
+  )
+  .await?;
+  if policies.is_empty() {
+    let path = config_file_path(&cluster.key, &config.state_dir);
+    services::nginx::remove_config_files(
+      &[path],
+      &config.state_dir,
+      docker_api,
+    )
+    .await
+    .map_err(|err| err.to_http_error())?;
+  }
+  Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  fn gen_policy(name: &str) -> ClusterAccessPolicyItem {
+    ClusterAccessPolicyItem {
+      key: format!("global-dev-{}", name),
+      cluster_key: String::from("global-dev"),
+      name: name.to_owned(),
+      domain: None,
+      allow: Vec::new(),
+      deny: Vec::new(),
+      rate: None,
+      burst: None,
+      connections: None,
+    }
+  }
+
+  #[test]
+  fn test_validate() {
+    let mut item = ClusterAccessPolicyPartial {
+      name: String::from("admin"),
+      domain: Some(String::from("admin.example.com")),
+      allow: Some(vec![String::from("10.1.2.3/8"), String::from("::1")]),
+      deny: None,
+      rate: None,
+      burst: None,
+      connections: None,
+    };
+    let (allow, deny) = validate(&item).unwrap();
+    assert_eq!(allow, vec!["10.0.0.0/8", "::1"]);
+    assert!(deny.is_empty());
+    item.allow = Some(vec![String::from("10.0.0.0/33")]);
+    assert!(validate(&item).is_err());
+    item.allow = Some(vec![String::from("all; include /etc/passwd")]);
+    assert!(validate(&item).is_err());
+    item.allow = None;
+    assert!(validate(&item).is_err());
+    item.burst = Some(10);
+    assert!(validate(&item).is_err());
+    item.rate = Some(5);
+    assert!(validate(&item).is_ok());
+    item.domain = Some(String::from("admin.example.com;"));
+    assert!(validate(&item).is_err());
+  }
+
+  #[test]
+  fn test_gen_config() {
+    let mut admin = gen_policy("admin");
+    admin.domain = Some(String::from("admin.example.com"));
+    admin.allow = vec![String::from("10.0.0.0/8")];
+    let mut abuse = gen_policy("abuse");
+    abuse.deny = vec![String::from("192.0.2.1")];
+    abuse.rate = Some(10);
+    abuse.burst = Some(20);
+    abuse.connections = Some(5);
+    let policies = vec![abuse, admin];
+
+    let zones = gen_zones("global-dev", &policies);
+    assert!(zones.contains(
+      "limit_req_zone $binary_remote_addr zone=nanocl_global_dev_abuse:10m rate=10r/s;"
+    ));
+    assert!(zones.contains(
+      "limit_conn_zone $binary_remote_addr zone=nanocl_global_dev_abuse_conn:10m;"
+    ));
+
+    let domains = ["admin.example.com", "www.example.com", "api.example.com"];
+    let directives =
+      gen_template_directives(domains.into_iter(), &policies).unwrap();
+    assert_eq!(
+      directives.get("admin_example_com").unwrap(),
+      "  deny 192.0.2.1;
+  allow 10.0.0.0/8;
+  deny all;
+  limit_req zone=nanocl_global_dev_abuse burst=20 nodelay;
+  limit_req_status 429;
+  limit_conn nanocl_global_dev_abuse_conn 5;
+  limit_conn_status 429;
+"
+    );
+    let www = directives.get("www_example_com").unwrap();
+    assert!(www.starts_with("  deny 192.0.2.1;\n  limit_req "));
+
+    let directives =
+      gen_template_directives(domains.into_iter(), &policies[1..]).unwrap();
+    assert_eq!(directives.len(), 1);
+  }
+}
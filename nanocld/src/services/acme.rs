@@ -0,0 +1,764 @@
+//! ACME client issuing certificates of cluster domains
+//! Challenges are http-01 served by the nginx container from
+//! `/etc/letsencrypt/acme-challenge` and certificates are written
+//! in `/etc/letsencrypt/live/{domain}` like certbot does.
+use ntex::web;
+use ntex::util::Bytes;
+use ntex::time::Millis;
+use ntex::http::StatusCode;
+use ntex::http::client::{Client, Connector};
+use chrono::Utc;
+use thiserror::Error;
+use std::fs;
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeSet, HashMap};
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use openssl::nid::Nid;
+use openssl::stack::Stack;
+use openssl::ecdsa::EcdsaSig;
+use openssl::asn1::Asn1Time;
+use openssl::pkey::{PKey, Private};
+use openssl::error::ErrorStack;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::{hash, MessageDigest};
+use openssl::bn::{BigNum, BigNumRef, BigNumContext};
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use openssl::x509::{X509, X509NameBuilder, X509Req};
+use openssl::x509::extension::SubjectAlternativeName;
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::models::{Pool, EventKind, CertificateRenewReport};
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+
+use super::history::SYSTEM_ACTOR;
+
+/// Certificates expiring in less days are renewed
+const RENEW_BEFORE_DAYS: u32 = 30;
+/// Number of times an order or authorization is polled
+const POLL_ATTEMPTS: usize = 10;
+/// Path of the letsencrypt directory inside the nginx container
+const NGINX_LETSENCRYPT_DIR: &str = "/etc/letsencrypt";
+/// Nginx location serving challenges, included by templates
+/// in the port 80 server of their domains
+const CHALLENGE_SNIPPET: &str = r#"location ^~ /.well-known/acme-challenge/ {
+  default_type "text/plain";
+  allow all;
+  auth_basic off;
+  auth_request off;
+  alias /etc/letsencrypt/acme-challenge/;
+}
+"#;
+
+#[derive(Debug, Error)]
+pub enum AcmeError {
+  #[error("acme io error")]
+  Io(#[from] IoError),
+  #[error("acme openssl error")]
+  Openssl(#[from] ErrorStack),
+  #[error("acme json error")]
+  Json(#[from] serde_json::Error),
+  #[error("acme request failed")]
+  Request(String),
+  #[error("acme server error")]
+  Problem(AcmeProblem),
+  #[error("acme protocol error")]
+  Protocol(String),
+}
+
+impl IntoHttpResponseError for AcmeError {
+  fn to_http_error(&self) -> HttpResponseError {
+    let msg = match self {
+      AcmeError::Io(err) => format!("acme io error {}", err),
+      AcmeError::Openssl(err) => format!("acme openssl error {}", err),
+      AcmeError::Json(err) => format!("acme json error {}", err),
+      AcmeError::Request(err) => format!("acme request failed {}", err),
+      AcmeError::Problem(problem) => {
+        format!("acme server error {} {}", problem.r#type, problem.detail)
+      }
+      AcmeError::Protocol(msg) => format!("acme protocol error {}", msg),
+    };
+    HttpResponseError {
+      msg,
+      status: StatusCode::INTERNAL_SERVER_ERROR,
+    }
+  }
+}
+
+/// Error document returned by the ACME server
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcmeProblem {
+  #[serde(default)]
+  pub(crate) r#type: String,
+  #[serde(default)]
+  pub(crate) detail: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AcmeDirectory {
+  new_nonce: String,
+  new_account: String,
+  new_order: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AcmeOrder {
+  status: String,
+  authorizations: Vec<String>,
+  finalize: String,
+  certificate: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AcmeChallenge {
+  r#type: String,
+  url: String,
+  token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AcmeAuthorization {
+  status: String,
+  challenges: Vec<AcmeChallenge>,
+}
+
+/// Response of a signed request
+struct AcmeResponse {
+  location: Option<String>,
+  body: Bytes,
+}
+
+/// Paths of a certificate available to proxy templates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateTemplateData {
+  pub(crate) certificate: String,
+  pub(crate) certificate_key: String,
+}
+
+/// Encode data in base64 url without padding as required by ACME
+pub fn b64(data: &[u8]) -> String {
+  base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+/// Key of a domain in template data
+/// mustache use dots to walk in objects so they are replaced
+pub fn template_key(domain: &str) -> String {
+  domain.replace('.', "_")
+}
+
+/// Content of the challenge file served for a token
+pub fn key_authorization(token: &str, thumbprint: &str) -> String {
+  format!("{}.{}", token, thumbprint)
+}
+
+fn gen_key() -> Result<EcKey<Private>, ErrorStack> {
+  let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+  EcKey::generate(&group)
+}
+
+/// Big endian bytes of a number left padded to the size of a P-256 field
+fn to_padded_bytes(number: &BigNumRef, size: usize) -> Vec<u8> {
+  let bytes = number.to_vec();
+  let mut padded = vec![0; size.saturating_sub(bytes.len())];
+  padded.extend(bytes);
+  padded
+}
+
+/// Public key of an account as json web key
+pub fn gen_jwk(key: &EcKey<Private>) -> Result<serde_json::Value, ErrorStack> {
+  let mut ctx = BigNumContext::new()?;
+  let mut x = BigNum::new()?;
+  let mut y = BigNum::new()?;
+  key.public_key().affine_coordinates_gfp(
+    key.group(),
+    &mut x,
+    &mut y,
+    &mut ctx,
+  )?;
+  Ok(json!({
+    "crv": "P-256",
+    "kty": "EC",
+    "x": b64(&to_padded_bytes(&x, 32)),
+    "y": b64(&to_padded_bytes(&y, 32)),
+  }))
+}
+
+/// Thumbprint of a json web key as defined by RFC 7638
+/// required members are serialized in lexicographic order
+pub fn jwk_thumbprint(jwk: &serde_json::Value) -> Result<String, AcmeError> {
+  let member = |name: &str| jwk[name].as_str().unwrap_or_default().to_owned();
+  let canonical = format!(
+    r#"{{"crv":"{}","kty":"{}","x":"{}","y":"{}"}}"#,
+    member("crv"),
+    member("kty"),
+    member("x"),
+    member("y"),
+  );
+  let digest = hash(MessageDigest::sha256(), canonical.as_bytes())?;
+  Ok(b64(&digest))
+}
+
+/// Sign a payload as a flattened json web signature using ES256
+pub fn sign_jws(
+  key: &EcKey<Private>,
+  protected: &serde_json::Value,
+  payload: &str,
+) -> Result<String, AcmeError> {
+  let protected = b64(protected.to_string().as_bytes());
+  let payload = b64(payload.as_bytes());
+  let data = format!("{}.{}", protected, payload);
+  let digest = hash(MessageDigest::sha256(), data.as_bytes())?;
+  let signature = EcdsaSig::sign(&digest, key)?;
+  let mut raw = to_padded_bytes(signature.r(), 32);
+  raw.extend(to_padded_bytes(signature.s(), 32));
+  let jws = json!({
+    "protected": protected,
+    "payload": payload,
+    "signature": b64(&raw),
+  });
+  Ok(jws.to_string())
+}
+
+/// Certificate signing request of a domain in DER format
+pub fn gen_csr(
+  domain: &str,
+  key: &PKey<Private>,
+) -> Result<Vec<u8>, ErrorStack> {
+  let mut name = X509NameBuilder::new()?;
+  name.append_entry_by_nid(Nid::COMMONNAME, domain)?;
+  let name = name.build();
+  let mut req = X509Req::builder()?;
+  req.set_subject_name(&name)?;
+  let mut extensions = Stack::new()?;
+  let san = SubjectAlternativeName::new()
+    .dns(domain)
+    .build(&req.x509v3_context(None))?;
+  extensions.push(san)?;
+  req.add_extensions(&extensions)?;
+  req.set_pubkey(key)?;
+  req.sign(key, MessageDigest::sha256())?;
+  req.build().to_der()
+}
+
+fn live_dir(domain: &str, state_dir: &str) -> PathBuf {
+  Path::new(state_dir)
+    .join("nginx/letsencrypt/live")
+    .join(domain)
+}
+
+fn challenge_dir(state_dir: &str) -> PathBuf {
+  Path::new(state_dir).join("nginx/letsencrypt/acme-challenge")
+}
+
+/// Path of the challenge snippet inside the nginx container
+pub fn challenge_snippet_path() -> String {
+  format!("{}/nanocl-acme.conf", NGINX_LETSENCRYPT_DIR)
+}
+
+/// Create the challenge directory and the nginx snippet serving it
+pub fn boot(state_dir: &str) -> Result<(), AcmeError> {
+  fs::create_dir_all(challenge_dir(state_dir))?;
+  let snippet_path =
+    Path::new(state_dir).join("nginx/letsencrypt/nanocl-acme.conf");
+  fs::write(snippet_path, CHALLENGE_SNIPPET)?;
+  Ok(())
+}
+
+/// Check if the certificate of a domain is missing or expire soon
+pub fn needs_renewal(domain: &str, state_dir: &str) -> Result<bool, AcmeError> {
+  let path = live_dir(domain, state_dir).join("fullchain.pem");
+  let pem = match fs::read(path) {
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(true),
+    Err(err) => return Err(err.into()),
+    Ok(pem) => pem,
+  };
+  let certificate = X509::from_pem(&pem)?;
+  let threshold = Asn1Time::days_from_now(RENEW_BEFORE_DAYS)?;
+  Ok(certificate.not_after() < threshold)
+}
+
+/// Certificates issued for the given domains by template key
+pub fn list_template_certificates(
+  domains: &[String],
+  state_dir: &str,
+) -> HashMap<String, CertificateTemplateData> {
+  domains
+    .iter()
+    .filter(|domain| live_dir(domain, state_dir).join("fullchain.pem").exists())
+    .map(|domain| {
+      let dir_path = format!("{}/live/{}", NGINX_LETSENCRYPT_DIR, domain);
+      (
+        template_key(domain),
+        CertificateTemplateData {
+          certificate: format!("{}/fullchain.pem", dir_path),
+          certificate_key: format!("{}/privkey.pem", dir_path),
+        },
+      )
+    })
+    .collect()
+}
+
+/// Load the account key or create it on first use
+fn load_account_key(state_dir: &str) -> Result<EcKey<Private>, AcmeError> {
+  let dir_path = Path::new(state_dir).join("acme");
+  let key_path = dir_path.join("account.pem");
+  if key_path.exists() {
+    let pem = fs::read(key_path)?;
+    return Ok(EcKey::private_key_from_pem(&pem)?);
+  }
+  services::utils::create_private_dir(&dir_path)?;
+  let key = gen_key()?;
+  services::utils::write_private_file(
+    &key_path,
+    &key.private_key_to_pem()?,
+    0o600,
+  )?;
+  Ok(key)
+}
+
+struct AcmeClient {
+  client: Client,
+  directory: AcmeDirectory,
+  key: EcKey<Private>,
+  jwk: serde_json::Value,
+  thumbprint: String,
+  kid: Option<String>,
+  nonce: Option<String>,
+}
+
+impl AcmeClient {
+  async fn connect(
+    directory_url: &str,
+    config: &DaemonConfig,
+  ) -> Result<Self, AcmeError> {
+    let mut ssl = SslConnector::builder(SslMethod::tls())?;
+    if config.acme_insecure {
+      ssl.set_verify(SslVerifyMode::NONE);
+    }
+    let client = Client::build()
+      .connector(Connector::default().openssl(ssl.build()).finish())
+      .timeout(Millis::from_secs(30))
+      .finish();
+    let mut res = client
+      .get(directory_url)
+      .send()
+      .await
+      .map_err(|err| AcmeError::Request(err.to_string()))?;
+    let directory = res
+      .json::<AcmeDirectory>()
+      .await
+      .map_err(|err| AcmeError::Request(err.to_string()))?;
+    let key = load_account_key(&config.state_dir)?;
+    let jwk = gen_jwk(&key)?;
+    let thumbprint = jwk_thumbprint(&jwk)?;
+    Ok(AcmeClient {
+      client,
+      directory,
+      key,
+      jwk,
+      thumbprint,
+      kid: None,
+      nonce: None,
+    })
+  }
+
+  async fn get_nonce(&mut self) -> Result<String, AcmeError> {
+    if let Some(nonce) = self.nonce.take() {
+      return Ok(nonce);
+    }
+    let res = self
+      .client
+      .head(&self.directory.new_nonce)
+      .send()
+      .await
+      .map_err(|err| AcmeError::Request(err.to_string()))?;
+    res
+      .headers()
+      .get("replay-nonce")
+      .and_then(|nonce| nonce.to_str().ok())
+      .map(|nonce| nonce.to_owned())
+      .ok_or_else(|| AcmeError::Protocol(String::from("missing nonce")))
+  }
+
+  /// Send a signed request, a None payload is a POST-as-GET
+  async fn post(
+    &mut self,
+    url: &str,
+    payload: Option<serde_json::Value>,
+  ) -> Result<AcmeResponse, AcmeError> {
+    let payload = payload.map(|payload| payload.to_string());
+    // A bad nonce is retried once with the fresh nonce of the error
+    let mut retried = false;
+    loop {
+      let nonce = self.get_nonce().await?;
+      let mut protected = json!({
+        "alg": "ES256",
+        "nonce": nonce,
+        "url": url,
+      });
+      match &self.kid {
+        None => protected["jwk"] = self.jwk.to_owned(),
+        Some(kid) => protected["kid"] = json!(kid),
+      }
+      let body =
+        sign_jws(&self.key, &protected, payload.as_deref().unwrap_or(""))?;
+      let mut res = self
+        .client
+        .post(url)
+        .content_type("application/jose+json")
+        .send_body(body)
+        .await
+        .map_err(|err| AcmeError::Request(err.to_string()))?;
+      let header = |name: &str| {
+        res
+          .headers()
+          .get(name)
+          .and_then(|value| value.to_str().ok())
+          .map(|value| value.to_owned())
+      };
+      self.nonce = header("replay-nonce");
+      let location = header("location");
+      let status = res.status();
+      let body = res
+        .body()
+        .limit(1 << 20)
+        .await
+        .map_err(|err| AcmeError::Request(err.to_string()))?;
+      if status.is_success() {
+        return Ok(AcmeResponse { location, body });
+      }
+      let problem = serde_json::from_slice::<AcmeProblem>(&body)?;
+      if problem.r#type == "urn:ietf:params:acme:error:badNonce" && !retried {
+        retried = true;
+        continue;
+      }
+      return Err(AcmeError::Problem(problem));
+    }
+  }
+
+  async fn register(&mut self, email: Option<&str>) -> Result<(), AcmeError> {
+    let mut payload = json!({ "termsOfServiceAgreed": true });
+    if let Some(email) = email {
+      payload["contact"] = json!([format!("mailto:{}", email)]);
+    }
+    let url = self.directory.new_account.to_owned();
+    let res = self.post(&url, Some(payload)).await?;
+    let kid = res.location.ok_or_else(|| {
+      AcmeError::Protocol(String::from("missing account location"))
+    })?;
+    self.kid = Some(kid);
+    Ok(())
+  }
+
+  async fn get_order(&mut self, url: &str) -> Result<AcmeOrder, AcmeError> {
+    let res = self.post(url, None).await?;
+    Ok(serde_json::from_slice(&res.body)?)
+  }
+
+  /// Answer the http-01 challenge of an authorization and wait for it
+  async fn authorize(
+    &mut self,
+    url: &str,
+    state_dir: &str,
+  ) -> Result<(), AcmeError> {
+    let res = self.post(url, None).await?;
+    let authorization = serde_json::from_slice::<AcmeAuthorization>(&res.body)?;
+    if authorization.status == "valid" {
+      return Ok(());
+    }
+    let challenge = authorization
+      .challenges
+      .into_iter()
+      .find(|challenge| challenge.r#type == "http-01")
+      .ok_or_else(|| {
+        AcmeError::Protocol(String::from("no http-01 challenge offered"))
+      })?;
+    let token_path = challenge_dir(state_dir).join(&challenge.token);
+    fs::write(
+      &token_path,
+      key_authorization(&challenge.token, &self.thumbprint),
+    )?;
+    let res = self.wait_authorization(url, &challenge.url).await;
+    if let Err(err) = fs::remove_file(&token_path) {
+      log::warn!("unable to remove acme challenge {}", err);
+    }
+    res
+  }
+
+  async fn wait_authorization(
+    &mut self,
+    url: &str,
+    challenge_url: &str,
+  ) -> Result<(), AcmeError> {
+    self.post(challenge_url, Some(json!({}))).await?;
+    for _ in 0..POLL_ATTEMPTS {
+      ntex::time::sleep(Millis::from_secs(2)).await;
+      let res = self.post(url, None).await?;
+      let authorization =
+        serde_json::from_slice::<AcmeAuthorization>(&res.body)?;
+      match authorization.status.as_str() {
+        "valid" => return Ok(()),
+        "pending" | "processing" => continue,
+        status => {
+          return Err(AcmeError::Protocol(format!(
+            "authorization is {}",
+            status
+          )))
+        }
+      }
+    }
+    Err(AcmeError::Protocol(String::from("authorization timed out")))
+  }
+
+  /// Order, validate and download a certificate for a domain
+  async fn issue(
+    &mut self,
+    domain: &str,
+    state_dir: &str,
+  ) -> Result<(), AcmeError> {
+    let payload = json!({
+      "identifiers": [{ "type": "dns", "value": domain }],
+    });
+    let url = self.directory.new_order.to_owned();
+    let res = self.post(&url, Some(payload)).await?;
+    let order_url = res.location.ok_or_else(|| {
+      AcmeError::Protocol(String::from("missing order location"))
+    })?;
+    let order = serde_json::from_slice::<AcmeOrder>(&res.body)?;
+    for authorization in &order.authorizations {
+      self.authorize(authorization, state_dir).await?;
+    }
+
+    let key = PKey::from_ec_key(gen_key()?)?;
+    let csr = gen_csr(domain, &key)?;
+    self
+      .post(&order.finalize, Some(json!({ "csr": b64(&csr) })))
+      .await?;
+    let mut certificate_url = None;
+    for _ in 0..POLL_ATTEMPTS {
+      let order = self.get_order(&order_url).await?;
+      match order.status.as_str() {
+        "valid" => {
+          certificate_url = order.certificate;
+          break;
+        }
+        "pending" | "ready" | "processing" => {
+          ntex::time::sleep(Millis::from_secs(2)).await;
+        }
+        status => {
+          return Err(AcmeError::Protocol(format!("order is {}", status)))
+        }
+      }
+    }
+    let certificate_url = certificate_url.ok_or_else(|| {
+      AcmeError::Protocol(String::from("certificate not issued in time"))
+    })?;
+    let res = self.post(&certificate_url, None).await?;
+
+    let dir_path = live_dir(domain, state_dir);
+    services::utils::create_private_dir(&dir_path)?;
+    services::utils::write_private_file(
+      &dir_path.join("privkey.pem"),
+      &key.private_key_to_pem_pkcs8()?,
+      0o600,
+    )?;
+    services::utils::write_private_file(
+      &dir_path.join("fullchain.pem"),
+      &res.body,
+      0o644,
+    )?;
+    Ok(())
+  }
+}
+
+/// Issue certificates of cluster domains missing one or expiring soon
+/// and render again the proxy templates of their clusters
+pub async fn renew(
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+) -> Result<CertificateRenewReport, HttpResponseError> {
+  let directory_url =
+    config
+      .acme_directory
+      .to_owned()
+      .ok_or_else(|| HttpResponseError {
+        msg: String::from("acme directory is not configured"),
+        status: StatusCode::BAD_REQUEST,
+      })?;
+  let mut report = CertificateRenewReport::default();
+  let clusters = repositories::cluster::list_all(pool).await?;
+  let mut domains = BTreeSet::new();
+  for domain in clusters.iter().flat_map(|cluster| &cluster.domains) {
+    match needs_renewal(domain, &config.state_dir) {
+      Err(err) => report.errors.push(format!(
+        "unable to read certificate of {}: {}",
+        domain,
+        err.to_http_error().msg
+      )),
+      Ok(true) => {
+        domains.insert(domain.to_owned());
+      }
+      Ok(false) => {}
+    }
+  }
+
+  if !domains.is_empty() {
+    let client = match AcmeClient::connect(&directory_url, config).await {
+      Err(err) => Err(err),
+      Ok(mut client) => client
+        .register(config.acme_email.as_deref())
+        .await
+        .map(|_| client),
+    };
+    let mut client = client.map_err(|err| err.to_http_error())?;
+    for domain in domains {
+      match client.issue(&domain, &config.state_dir).await {
+        Err(err) => report.errors.push(format!(
+          "unable to issue certificate of {}: {}",
+          domain,
+          err.to_http_error().msg
+        )),
+        Ok(_) => report.issued.push(domain),
+      }
+    }
+  }
+
+  for cluster in clusters {
+    let is_renewed = cluster
+      .domains
+      .iter()
+      .any(|domain| report.issued.contains(domain));
+    if !is_renewed {
+      continue;
+    }
+    let res = services::cluster::start(
+      &cluster,
+      config,
+      pool,
+      docker_api,
+      SYSTEM_ACTOR,
+    )
+    .await;
+    services::history::record(
+      &cluster.key,
+      EventKind::Start,
+      SYSTEM_ACTOR,
+      None,
+      &res,
+      pool,
+    )
+    .await;
+    match res {
+      Err(err) => report.errors.push(format!(
+        "unable to start cluster {}: {}",
+        cluster.key, err.msg
+      )),
+      Ok(_) => report.rendered.push(cluster.key),
+    }
+  }
+
+  report.finished_at = Some(Utc::now());
+  Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  /// Decode a base64 url member of a flattened json web signature
+  fn decode_member(jws: &serde_json::Value, name: &str) -> Vec<u8> {
+    base64::decode_config(
+      jws[name].as_str().unwrap_or_default(),
+      base64::URL_SAFE_NO_PAD,
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn test_encoding() {
+    assert_eq!(template_key("api.example.com"), "api_example_com");
+    assert_eq!(key_authorization("token", "thumb"), "token.thumb");
+    assert_eq!(b64(&[251, 255]), "-_8");
+  }
+
+  #[test]
+  fn test_gen_jwk() -> Result<(), AcmeError> {
+    let key = gen_key()?;
+    let jwk = gen_jwk(&key)?;
+    assert_eq!(jwk["kty"], "EC");
+    assert_eq!(jwk["crv"], "P-256");
+    for coordinate in ["x", "y"] {
+      let bytes = decode_member(&jwk, coordinate);
+      assert_eq!(bytes.len(), 32);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_jwk_thumbprint() -> Result<(), AcmeError> {
+    let jwk = json!({
+      "kty": "EC",
+      "y": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0",
+      "x": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+      "crv": "P-256",
+      "kid": "ignored",
+    });
+    assert_eq!(
+      jwk_thumbprint(&jwk)?,
+      "oKIywvGUpTVTyxMQ3bwIIeQUudfr_CkLMjCE19ECD-U"
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_jws_protected_header() -> Result<(), AcmeError> {
+    let key = gen_key()?;
+    let protected = json!({ "alg": "ES256", "url": "https://acme/order" });
+    let jws = sign_jws(&key, &protected, r#"{"status":"valid"}"#)?;
+    let jws = serde_json::from_str::<serde_json::Value>(&jws)?;
+    let header = decode_member(&jws, "protected");
+    let header = serde_json::from_slice::<serde_json::Value>(&header)?;
+    assert_eq!(header, protected);
+    assert_eq!(decode_member(&jws, "payload"), br#"{"status":"valid"}"#);
+    let jws = sign_jws(&key, &protected, "")?;
+    let jws = serde_json::from_str::<serde_json::Value>(&jws)?;
+    assert_eq!(jws["payload"], "");
+    Ok(())
+  }
+
+  #[test]
+  fn test_jws_signature() -> Result<(), AcmeError> {
+    let key = gen_key()?;
+    let protected = json!({ "alg": "ES256", "url": "https://acme/order" });
+    let jws = sign_jws(&key, &protected, "")?;
+    let jws = serde_json::from_str::<serde_json::Value>(&jws)?;
+    let signature = decode_member(&jws, "signature");
+    assert_eq!(signature.len(), 64);
+    let data = format!(
+      "{}.{}",
+      jws["protected"].as_str().unwrap_or_default(),
+      jws["payload"].as_str().unwrap_or_default()
+    );
+    let digest = hash(MessageDigest::sha256(), data.as_bytes())?;
+    let r = BigNum::from_slice(&signature[..32])?;
+    let s = BigNum::from_slice(&signature[32..])?;
+    let signature = EcdsaSig::from_private_components(r, s)?;
+    assert!(signature.verify(&digest, &key)?);
+    let other_key = gen_key()?;
+    assert!(!signature.verify(&digest, &other_key)?);
+    Ok(())
+  }
+
+  #[test]
+  fn test_gen_csr() -> Result<(), AcmeError> {
+    let key = PKey::from_ec_key(gen_key()?)?;
+    let csr = X509Req::from_der(&gen_csr("api.example.com", &key)?)?;
+    assert!(csr.verify(&key)?);
+    Ok(())
+  }
+}
@@ -0,0 +1,684 @@
+//! Authentication required by the proxy for a domain or a route of a cluster
+//! Basic auth users are stored hashed and written as htpasswd files in
+//! `state_dir/nginx/htpasswd` mounted in the nginx container.
+//! Forward auth send a subrequest to a cargo with `auth_request`
+//! and only let through requests it answered with a 2xx status.
+use ntex::web;
+use ntex::http::StatusCode;
+use thiserror::Error;
+use std::fs;
+use std::fmt::Write;
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap};
+use pwhash::HashSetup;
+use pwhash::sha512_crypt;
+use pwhash::error::Error as PwhashError;
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+use crate::models::{
+  Pool, ClusterItem, ClusterRouteItem, ClusterAuthKind, ClusterAuthPolicyItem,
+  ClusterAuthPolicyPartial, PgDeleteGeneric,
+};
+
+use super::acme::template_key;
+use super::route::{is_safe_value, is_valid_domain, RouteDirectives};
+
+/// Path of the htpasswd directory inside the nginx container
+pub const NGINX_HTPASSWD_DIR: &str = "/etc/nginx/htpasswd";
+const DEFAULT_REALM: &str = "Restricted";
+/// Rounds of sha512 crypt, nginx check the hash on every request
+const HASH_ROUNDS: u32 = 10000;
+/// Group of the nginx workers reading the htpasswd files
+const NGINX_GID: u32 = 101;
+
+#[derive(Debug, Error)]
+pub enum AuthPolicyError {
+  #[error("auth policy io error")]
+  Io(#[from] IoError),
+  #[error("auth policy hash error")]
+  Hash(#[from] PwhashError),
+  #[error("invalid auth policy")]
+  Invalid(String),
+  #[error("unable to render auth policies")]
+  Render(String),
+}
+
+impl IntoHttpResponseError for AuthPolicyError {
+  fn to_http_error(&self) -> HttpResponseError {
+    match self {
+      AuthPolicyError::Io(err) => HttpResponseError {
+        msg: format!("auth policy io error {}", err),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+      AuthPolicyError::Hash(err) => HttpResponseError {
+        msg: format!("auth policy hash error {}", err),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+      AuthPolicyError::Invalid(msg) => HttpResponseError {
+        msg: format!("invalid auth policy {}", msg),
+        status: StatusCode::BAD_REQUEST,
+      },
+      AuthPolicyError::Render(msg) => HttpResponseError {
+        msg: format!("unable to render auth policies {}", msg),
+        status: StatusCode::BAD_REQUEST,
+      },
+    }
+  }
+}
+
+/// Name of the config file declaring the forward auth upstreams of a cluster
+pub fn config_file_name(cluster_key: &str) -> String {
+  format!("{}.nanocl-auth", cluster_key)
+}
+
+/// Path of the config file declaring the forward auth upstreams of a cluster
+pub fn config_file_path(cluster_key: &str, state_dir: &str) -> PathBuf {
+  Path::new(state_dir)
+    .join("nginx/sites-enabled")
+    .join(format!("{}.conf", config_file_name(cluster_key)))
+}
+
+fn htpasswd_dir(state_dir: &str) -> PathBuf {
+  Path::new(state_dir).join("nginx/htpasswd")
+}
+
+/// Path of the htpasswd file of a basic auth policy
+pub fn htpasswd_path(policy_key: &str, state_dir: &str) -> PathBuf {
+  htpasswd_dir(state_dir).join(policy_key)
+}
+
+/// Hash a password with a random salt in the sha512 crypt format
+/// understood by nginx with the glibc `crypt` of its image
+pub fn hash_password(password: &str) -> Result<String, AuthPolicyError> {
+  let setup = HashSetup {
+    salt: None,
+    rounds: Some(HASH_ROUNDS),
+  };
+  Ok(sha512_crypt::hash_with(setup, password)?)
+}
+
+/// Split a basic auth user formatted as `name:password`
+fn parse_user(user: &str) -> Option<(&str, &str)> {
+  let (name, password) = user.split_once(':')?;
+  let is_valid_name = !name.is_empty()
+    && name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@'));
+  if !is_valid_name || password.is_empty() {
+    return None;
+  }
+  Some((name, password))
+}
+
+/// Ensure a policy can be rendered as nginx directives
+/// `routes` are the routes of the cluster a policy can be attached to
+pub fn validate(
+  item: &ClusterAuthPolicyPartial,
+  routes: &[ClusterRouteItem],
+) -> Result<(), AuthPolicyError> {
+  let is_valid_name = !item.name.is_empty()
+    && item
+      .name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+  if !is_valid_name {
+    return Err(AuthPolicyError::Invalid(format!(
+      "name {} must only contain letters, digits, '-' or '_'",
+      item.name
+    )));
+  }
+  match (&item.domain, &item.route_name) {
+    (Some(domain), None) => {
+      if !is_valid_domain(domain) {
+        return Err(AuthPolicyError::Invalid(format!("domain {}", domain)));
+      }
+    }
+    (None, Some(route_name)) => {
+      if !routes.iter().any(|route| &route.name == route_name) {
+        return Err(AuthPolicyError::Invalid(format!(
+          "route {} doesn't exist",
+          route_name
+        )));
+      }
+    }
+    _ => {
+      return Err(AuthPolicyError::Invalid(String::from(
+        "either domain or route_name must be set",
+      )))
+    }
+  }
+  match item.kind {
+    ClusterAuthKind::Basic => {
+      let users = item.users.to_owned().unwrap_or_default();
+      if users.is_empty() {
+        return Err(AuthPolicyError::Invalid(String::from(
+          "basic auth requires at least one user",
+        )));
+      }
+      for user in &users {
+        if parse_user(user).is_none() {
+          return Err(AuthPolicyError::Invalid(String::from(
+            "users must be formatted as 'name:password'",
+          )));
+        }
+      }
+      if let Some(realm) = &item.realm {
+        let is_valid_realm = !realm.is_empty()
+          && !realm
+            .chars()
+            .any(|c| c.is_control() || matches!(c, '"' | '\\' | ';'));
+        if !is_valid_realm {
+          return Err(AuthPolicyError::Invalid(format!("realm {}", realm)));
+        }
+      }
+      if item.cargo_name.is_some() || item.port.is_some() || item.path.is_some()
+      {
+        return Err(AuthPolicyError::Invalid(String::from(
+          "cargo_name, port and path are only used by forward auth",
+        )));
+      }
+    }
+    ClusterAuthKind::Forward => {
+      match (&item.cargo_name, item.port) {
+        (Some(cargo_name), Some(port)) => {
+          if !is_safe_value(cargo_name) {
+            return Err(AuthPolicyError::Invalid(format!(
+              "cargo_name {}",
+              cargo_name
+            )));
+          }
+          if !(1..=65535).contains(&port) {
+            return Err(AuthPolicyError::Invalid(format!("port {}", port)));
+          }
+        }
+        _ => {
+          return Err(AuthPolicyError::Invalid(String::from(
+            "forward auth requires a cargo_name and a port",
+          )))
+        }
+      }
+      if let Some(path) = &item.path {
+        if !path.starts_with('/') || !is_safe_value(path) {
+          return Err(AuthPolicyError::Invalid(format!("path {}", path)));
+        }
+      }
+      if item.users.is_some() || item.realm.is_some() {
+        return Err(AuthPolicyError::Invalid(String::from(
+          "users and realm are only used by basic auth",
+        )));
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Htpasswd lines of the users of a basic auth policy
+pub fn hash_users(users: &[String]) -> Result<Vec<String>, AuthPolicyError> {
+  users
+    .iter()
+    .filter_map(|user| parse_user(user))
+    .map(|(name, password)| {
+      Ok(format!("{}:{}", name, hash_password(password)?))
+    })
+    .collect()
+}
+
+/// Write the htpasswd files of the basic auth policies of a cluster
+pub fn write_htpasswd_files(
+  policies: &[ClusterAuthPolicyItem],
+  state_dir: &str,
+) -> Result<(), AuthPolicyError> {
+  let policies = policies
+    .iter()
+    .filter(|policy| policy.kind == ClusterAuthKind::Basic)
+    .collect::<Vec<_>>();
+  if policies.is_empty() {
+    return Ok(());
+  }
+  fs::create_dir_all(htpasswd_dir(state_dir))?;
+  for policy in policies {
+    let mut content = policy.users.join("\n");
+    content.push('\n');
+    let path = htpasswd_path(&policy.key, state_dir);
+    services::utils::write_private_file(&path, content.as_bytes(), 0o640)?;
+    if let Err(err) = std::os::unix::fs::chown(&path, None, Some(NGINX_GID)) {
+      log::warn!(
+        "unable to give htpasswd file {} to nginx group {}",
+        path.display(),
+        err
+      );
+    }
+  }
+  Ok(())
+}
+
+fn upstream_name(policy: &ClusterAuthPolicyItem) -> String {
+  format!("nanocl-auth-{}", policy.key)
+}
+
+/// Internal location answering the auth subrequests of a forward policy
+fn auth_location(policy: &ClusterAuthPolicyItem) -> String {
+  format!("/_nanocl_auth_{}", policy.key)
+}
+
+/// Generate the upstreams of the forward auth policies
+/// `targets` are the ips of the containers of each cargo by cargo name
+pub fn gen_upstreams(
+  cluster_key: &str,
+  policies: &[ClusterAuthPolicyItem],
+  targets: &HashMap<String, Vec<String>>,
+) -> Result<Option<String>, AuthPolicyError> {
+  let mut upstreams = BTreeMap::new();
+  for policy in policies {
+    let (cargo_name, port) = match (&policy.cargo_name, policy.port) {
+      (Some(cargo_name), Some(port)) => (cargo_name, port),
+      _ => continue,
+    };
+    let ips = targets
+      .get(cargo_name)
+      .filter(|ips| !ips.is_empty())
+      .ok_or_else(|| {
+        AuthPolicyError::Render(format!(
+          "auth policy {} cargo {} has no running container in the cluster",
+          policy.name, cargo_name
+        ))
+      })?;
+    upstreams.insert(upstream_name(policy), (ips, port));
+  }
+  if upstreams.is_empty() {
+    return Ok(None);
+  }
+  let mut output = format!(
+    "# generated by nanocl from the auth policies of cluster {}\n",
+    cluster_key
+  );
+  for (name, (ips, port)) in upstreams {
+    output += &format!("\nupstream {} {{\n", name);
+    for ip in ips {
+      output += &format!("  server {}:{};\n", ip, port);
+    }
+    output += "}\n";
+  }
+  Ok(Some(output))
+}
+
+/// Directives requiring the authentication of a policy
+fn gen_auth_directives(
+  output: &mut String,
+  policy: &ClusterAuthPolicyItem,
+  indent: &str,
+) -> std::fmt::Result {
+  match policy.kind {
+    ClusterAuthKind::Basic => {
+      writeln!(
+        output,
+        "{}auth_basic \"{}\";",
+        indent,
+        policy.realm.as_deref().unwrap_or(DEFAULT_REALM)
+      )?;
+      writeln!(
+        output,
+        "{}auth_basic_user_file {}/{};",
+        indent, NGINX_HTPASSWD_DIR, policy.key
+      )
+    }
+    ClusterAuthKind::Forward => {
+      writeln!(output, "{}auth_request {};", indent, auth_location(policy))
+    }
+  }
+}
+
+/// Internal location of a forward policy in the servers of his domain
+fn gen_auth_location(
+  output: &mut String,
+  policy: &ClusterAuthPolicyItem,
+) -> std::fmt::Result {
+  if policy.kind != ClusterAuthKind::Forward {
+    return Ok(());
+  }
+  writeln!(output, "  location = {} {{", auth_location(policy))?;
+  writeln!(output, "    internal;")?;
+  writeln!(output, "    auth_basic off;")?;
+  writeln!(output, "    auth_request off;")?;
+  writeln!(
+    output,
+    "    proxy_pass http://{}{};",
+    upstream_name(policy),
+    policy.path.as_deref().unwrap_or("/")
+  )?;
+  writeln!(output, "    proxy_pass_request_body off;")?;
+  writeln!(output, "    proxy_set_header Content-Length \"\";")?;
+  writeln!(output, "    proxy_set_header Host $host;")?;
+  writeln!(output, "    proxy_set_header X-Original-URI $request_uri;")?;
+  writeln!(
+    output,
+    "    proxy_set_header X-Original-Method $request_method;"
+  )?;
+  writeln!(output, "    proxy_set_header X-Real-IP $remote_addr;")?;
+  writeln!(output, "  }}")
+}
+
+/// Generate the directives of the policies for the given domains and routes
+/// domains and routes without policy are omitted
+pub fn gen_directives<'a>(
+  domains: impl Iterator<Item = &'a str>,
+  routes: &[ClusterRouteItem],
+  policies: &[ClusterAuthPolicyItem],
+) -> Result<RouteDirectives, AuthPolicyError> {
+  let render_error = |err: std::fmt::Error| {
+    AuthPolicyError::Render(format!("unable to write directives {}", err))
+  };
+  let mut directives = RouteDirectives::default();
+  for domain in domains {
+    let mut output = String::new();
+    for policy in policies {
+      if policy.domain.as_deref() == Some(domain) {
+        gen_auth_directives(&mut output, policy, "  ").map_err(render_error)?;
+        gen_auth_location(&mut output, policy).map_err(render_error)?;
+        continue;
+      }
+      let is_route_domain = routes.iter().any(|route| {
+        Some(&route.name) == policy.route_name.as_ref()
+          && route.domain == domain
+      });
+      if is_route_domain {
+        gen_auth_location(&mut output, policy).map_err(render_error)?;
+      }
+    }
+    if !output.is_empty() {
+      directives.servers.insert(template_key(domain), output);
+    }
+  }
+  for policy in policies {
+    let route_name = match &policy.route_name {
+      None => continue,
+      Some(route_name) => route_name,
+    };
+    let output = directives
+      .locations
+      .entry(route_name.to_owned())
+      .or_default();
+    gen_auth_directives(output, policy, "    ").map_err(render_error)?;
+  }
+  Ok(directives)
+}
+
+/// Create a policy and start the cluster to render it
+/// the policy is removed if the cluster fail to render it
+pub async fn create(
+  cluster: &ClusterItem,
+  item: ClusterAuthPolicyPartial,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<ClusterAuthPolicyItem, HttpResponseError> {
+  let routes =
+    repositories::cluster_route::list_by_cluster(cluster.key.to_owned(), pool)
+      .await?;
+  validate(&item, &routes).map_err(|err| err.to_http_error())?;
+  let policies = repositories::cluster_auth_policy::list_by_cluster(
+    cluster.key.to_owned(),
+    pool,
+  )
+  .await?;
+  let conflict = policies.iter().find(|policy| {
+    policy.name == item.name
+      || (policy.domain.is_some() && policy.domain == item.domain)
+      || (policy.route_name.is_some() && policy.route_name == item.route_name)
+  });
+  if let Some(policy) = conflict {
+    return Err(HttpResponseError {
+      msg: format!("auth policy {} already exists", policy.name),
+      status: StatusCode::CONFLICT,
+    });
+  }
+  let users = hash_users(&item.users.unwrap_or_default())
+    .map_err(|err| err.to_http_error())?;
+  let policy = ClusterAuthPolicyItem {
+    key: format!("{}-{}", cluster.key, item.name),
+    cluster_key: cluster.key.to_owned(),
+    name: item.name,
+    kind: item.kind,
+    domain: item.domain,
+    route_name: item.route_name,
+    realm: item.realm,
+    users,
+    cargo_name: item.cargo_name,
+    port: item.port,
+    path: item.path,
+  };
+  let policy = repositories::cluster_auth_policy::create(policy, pool).await?;
+  let res =
+    services::cluster::start(cluster, config, pool, docker_api, actor).await;
+  if let Err(err) = res {
+    repositories::cluster_auth_policy::delete_by_key(
+      policy.key.to_owned(),
+      pool,
+    )
+    .await?;
+    remove_htpasswd_file(&policy.key, &config.state_dir)
+      .map_err(|err| err.to_http_error())?;
+    return Err(err);
+  }
+  Ok(policy)
+}
+
+/// Remove the htpasswd file of a policy if it exists
+pub fn remove_htpasswd_file(
+  policy_key: &str,
+  state_dir: &str,
+) -> Result<(), AuthPolicyError> {
+  match fs::remove_file(htpasswd_path(policy_key, state_dir)) {
+    Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+    _ => Ok(()),
+  }
+}
+
+/// Delete a policy and render the cluster without it
+/// the upstreams file is removed with the last forward policy of the cluster
+pub async fn delete(
+  cluster: &ClusterItem,
+  name: &str,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  let key = format!("{}-{}", cluster.key, name);
+  repositories::cluster_auth_policy::find_by_key(key.to_owned(), pool).await?;
+  let res =
+    repositories::cluster_auth_policy::delete_by_key(key.to_owned(), pool)
+      .await?;
+  // Servers stop using the files before they are removed
+  services::cluster::start(cluster, config, pool, docker_api, actor).await?;
+  remove_htpasswd_file(&key, &config.state_dir)
+    .map_err(|err| err.to_http_error())?;
+  let policies = repositories::cluster_auth_policy::list_by_cluster(
+    cluster.key.to_owned(),
+    pool,
+  )
+  .await?;
+  let has_forward = policies
+    .iter()
+    .any(|policy| policy.kind == ClusterAuthKind::Forward);
+  if !has_forward {
+    let path = config_file_path(&cluster.key, &config.state_dir);
+    services::nginx::remove_config_files(
+      &[path],
+      &config.state_dir,
+      docker_api,
+    )
+    .await
+    .map_err(|err| err.to_http_error())?;
+  }
+  Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  fn gen_partial(kind: ClusterAuthKind) -> ClusterAuthPolicyPartial {
+    ClusterAuthPolicyPartial {
+      name: String::from("admin"),
+      kind,
+      domain: Some(String::from("admin.example.com")),
+      route_name: None,
+      realm: None,
+      users: None,
+      cargo_name: None,
+      port: None,
+      path: None,
+    }
+  }
+
+  fn gen_policy(name: &str, kind: ClusterAuthKind) -> ClusterAuthPolicyItem {
+    ClusterAuthPolicyItem {
+      key: format!("global-dev-{}", name),
+      cluster_key: String::from("global-dev"),
+      name: name.to_owned(),
+      kind,
+      domain: None,
+      route_name: None,
+      realm: None,
+      users: Vec::new(),
+      cargo_name: None,
+      port: None,
+      path: None,
+    }
+  }
+
+  fn gen_route(name: &str, domain: &str) -> ClusterRouteItem {
+    ClusterRouteItem {
+      key: format!("global-dev-{}", name),
+      cluster_key: String::from("global-dev"),
+      name: name.to_owned(),
+      domain: domain.to_owned(),
+      path: String::from("/"),
+      cargo_name: Some(String::from("api")),
+      port: Some(8080),
+      tls: false,
+      redirect_to: None,
+      headers: Vec::new(),
+      websocket: false,
+      connect_timeout: None,
+      read_timeout: None,
+    }
+  }
+
+  #[test]
+  fn test_validate() {
+    let routes = vec![gen_route("api", "api.example.com")];
+    let mut item = gen_partial(ClusterAuthKind::Basic);
+    assert!(validate(&item, &routes).is_err());
+    item.users = Some(vec![String::from("alice:secret")]);
+    assert!(validate(&item, &routes).is_ok());
+    item.users = Some(vec![String::from("alice")]);
+    assert!(validate(&item, &routes).is_err());
+    item.users = Some(vec![String::from("alice:secret")]);
+    item.realm = Some(String::from("admin\"; deny all"));
+    assert!(validate(&item, &routes).is_err());
+    item.realm = None;
+    item.route_name = Some(String::from("api"));
+    assert!(validate(&item, &routes).is_err());
+    item.domain = None;
+    assert!(validate(&item, &routes).is_ok());
+    item.route_name = Some(String::from("web"));
+    assert!(validate(&item, &routes).is_err());
+
+    let mut item = gen_partial(ClusterAuthKind::Forward);
+    assert!(validate(&item, &routes).is_err());
+    item.cargo_name = Some(String::from("auth"));
+    item.port = Some(4180);
+    item.path = Some(String::from("/oauth2/auth"));
+    assert!(validate(&item, &routes).is_ok());
+    item.path = Some(String::from("/auth; return 200"));
+    assert!(validate(&item, &routes).is_err());
+    item.path = None;
+    item.users = Some(vec![String::from("alice:secret")]);
+    assert!(validate(&item, &routes).is_err());
+  }
+
+  #[test]
+  fn test_hash_users() -> Result<(), AuthPolicyError> {
+    let users = vec![String::from("alice:secret")];
+    let lines = hash_users(&users)?;
+    let (name, hash) = lines[0].split_once(':').unwrap();
+    assert_eq!(name, "alice");
+    assert!(hash.starts_with(&format!("$6$rounds={}$", HASH_ROUNDS)));
+    assert!(sha512_crypt::verify("secret", hash));
+    assert!(!sha512_crypt::verify("other", hash));
+    assert_ne!(hash_users(&users)?, lines);
+    Ok(())
+  }
+
+  #[test]
+  fn test_write_htpasswd_files() -> Result<(), AuthPolicyError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir_path = std::env::temp_dir()
+      .join(format!("nanocl-htpasswd-{}", uuid::Uuid::new_v4()));
+    let state_dir = dir_path.to_string_lossy().to_string();
+    let mut admin = gen_policy("admin", ClusterAuthKind::Basic);
+    admin.users = vec![String::from("alice:$6$rounds=10000$abc$def")];
+    write_htpasswd_files(&[admin.to_owned()], &state_dir)?;
+    let path = htpasswd_path(&admin.key, &state_dir);
+    assert_eq!(
+      fs::read_to_string(&path)?,
+      "alice:$6$rounds=10000$abc$def\n"
+    );
+    let mode = fs::metadata(&path)?.permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+    fs::remove_dir_all(dir_path)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_gen_directives() -> Result<(), AuthPolicyError> {
+    let mut admin = gen_policy("admin", ClusterAuthKind::Basic);
+    admin.domain = Some(String::from("admin.example.com"));
+    admin.users = vec![String::from("alice:$6$rounds=10000$abc$def")];
+    let mut api = gen_policy("api", ClusterAuthKind::Forward);
+    api.route_name = Some(String::from("api"));
+    api.cargo_name = Some(String::from("auth"));
+    api.port = Some(4180);
+    api.path = Some(String::from("/check"));
+    let policies = vec![admin, api];
+    let routes = vec![gen_route("api", "api.example.com")];
+    let domains = ["admin.example.com", "api.example.com", "www.example.com"];
+
+    let directives = gen_directives(domains.into_iter(), &routes, &policies)?;
+    assert_eq!(
+      directives.servers.get("admin_example_com").unwrap(),
+      "  auth_basic \"Restricted\";
+  auth_basic_user_file /etc/nginx/htpasswd/global-dev-admin;
+"
+    );
+    let api_server = directives.servers.get("api_example_com").unwrap();
+    assert!(
+      api_server.starts_with("  location = /_nanocl_auth_global-dev-api {")
+    );
+    assert!(api_server
+      .contains("    proxy_pass http://nanocl-auth-global-dev-api/check;"));
+    assert!(!directives.servers.contains_key("www_example_com"));
+    assert_eq!(
+      directives.locations.get("api").unwrap(),
+      "    auth_request /_nanocl_auth_global-dev-api;\n"
+    );
+
+    let targets = HashMap::new();
+    assert!(gen_upstreams("global-dev", &policies, &targets).is_err());
+    let targets =
+      HashMap::from([(String::from("auth"), vec![String::from("10.1.0.4")])]);
+    let upstreams = gen_upstreams("global-dev", &policies, &targets)?.unwrap();
+    assert!(upstreams.contains("upstream nanocl-auth-global-dev-api {"));
+    assert!(upstreams.contains("  server 10.1.0.4:4180;"));
+    assert!(gen_upstreams("global-dev", &policies[..1], &targets)?.is_none());
+    Ok(())
+  }
+}
@@ -75,6 +75,8 @@ pub async fn create_containers<'a>(
       // This remove internet inside the container need to find a workarround
       network_mode: Some(opts.network_key.to_owned()),
       // network_mode: Some(String::from("none")),
+      memory: opts.cargo.memory_limit,
+      nano_cpus: opts.cargo.cpu_limit,
       ..Default::default()
     }),
     ..Default::default()
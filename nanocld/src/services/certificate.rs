@@ -0,0 +1,337 @@
+//! Certificates uploaded by users and installed on the proxy
+//! Files are written in `state_dir/nginx/ssl` mounted
+//! in the nginx container as `/etc/nginx/ssl`.
+use ntex::web;
+use ntex::http::StatusCode;
+use chrono::{DateTime, TimeZone, Utc};
+use thiserror::Error;
+use std::fs;
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::x509::{X509, X509NameRef};
+use openssl::error::ErrorStack;
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+use crate::models::{Pool, CertificateItem, CertificatePartial, PgDeleteGeneric};
+
+use super::acme::{template_key, CertificateTemplateData};
+
+/// Path of the ssl directory inside the nginx container
+const NGINX_SSL_DIR: &str = "/etc/nginx/ssl";
+
+#[derive(Debug, Error)]
+pub enum CertificateError {
+  #[error("certificate io error")]
+  Io(#[from] IoError),
+  #[error("certificate openssl error")]
+  Openssl(#[from] ErrorStack),
+  #[error("invalid certificate")]
+  Invalid(String),
+  #[error("certificate in use")]
+  InUse(Vec<String>),
+}
+
+impl IntoHttpResponseError for CertificateError {
+  fn to_http_error(&self) -> HttpResponseError {
+    match self {
+      CertificateError::Io(err) => HttpResponseError {
+        msg: format!("certificate io error {}", err),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+      CertificateError::Openssl(err) => HttpResponseError {
+        msg: format!("invalid certificate {}", err),
+        status: StatusCode::BAD_REQUEST,
+      },
+      CertificateError::Invalid(msg) => HttpResponseError {
+        msg: format!("invalid certificate {}", msg),
+        status: StatusCode::BAD_REQUEST,
+      },
+      CertificateError::InUse(files) => HttpResponseError {
+        msg: format!("certificate is used by {}", files.join(", ")),
+        status: StatusCode::CONFLICT,
+      },
+    }
+  }
+}
+
+/// Names are used as file names so only a safe subset is allowed
+fn validate_name(name: &str) -> Result<(), CertificateError> {
+  let is_valid = !name.is_empty()
+    && !name.starts_with('.')
+    && name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+  if !is_valid {
+    return Err(CertificateError::Invalid(format!(
+      "name {} must only contain letters, digits, '-', '_' or '.'",
+      name
+    )));
+  }
+  Ok(())
+}
+
+fn to_datetime(time: &Asn1TimeRef) -> Result<DateTime<Utc>, ErrorStack> {
+  let epoch = Asn1Time::from_unix(0)?;
+  let diff = epoch.diff(time)?;
+  Ok(Utc.timestamp(diff.days as i64 * 86400 + diff.secs as i64, 0))
+}
+
+/// Readable form of a certificate issuer like `CN=Internal CA, O=Corp`
+fn name_to_string(name: &X509NameRef) -> String {
+  name
+    .entries()
+    .map(|entry| {
+      let key = entry.object().nid().short_name().unwrap_or("?");
+      let value = entry
+        .data()
+        .as_utf8()
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+      format!("{}={}", key, value)
+    })
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// Domains of a certificate from his subject alternative names
+/// or his common name when there is none
+fn certificate_domains(certificate: &X509) -> Vec<String> {
+  let domains = certificate
+    .subject_alt_names()
+    .map(|names| {
+      names
+        .iter()
+        .filter_map(|name| name.dnsname().map(String::from))
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+  if !domains.is_empty() {
+    return domains;
+  }
+  certificate
+    .subject_name()
+    .entries_by_nid(Nid::COMMONNAME)
+    .filter_map(|entry| entry.data().as_utf8().ok())
+    .map(|value| value.to_string())
+    .collect()
+}
+
+/// Validate a certificate with his key and extract his informations
+pub fn parse(
+  item: &CertificatePartial,
+) -> Result<CertificateItem, CertificateError> {
+  validate_name(&item.name)?;
+  let chain = X509::stack_from_pem(item.certificate.as_bytes())?;
+  let certificate = chain.first().ok_or_else(|| {
+    CertificateError::Invalid(String::from("no certificate found"))
+  })?;
+  let key = PKey::private_key_from_pem(item.certificate_key.as_bytes())?;
+  if !certificate.public_key()?.public_eq(&key) {
+    return Err(CertificateError::Invalid(String::from(
+      "private key doesn't match the certificate",
+    )));
+  }
+  if certificate.not_after() < Asn1Time::days_from_now(0)? {
+    return Err(CertificateError::Invalid(String::from(
+      "certificate is expired",
+    )));
+  }
+  let domains = certificate_domains(certificate);
+  if domains.is_empty() {
+    return Err(CertificateError::Invalid(String::from(
+      "certificate has no domain",
+    )));
+  }
+  Ok(CertificateItem {
+    name: item.name.to_owned(),
+    domains,
+    issuer: name_to_string(certificate.issuer_name()),
+    not_before: to_datetime(certificate.not_before())?,
+    not_after: to_datetime(certificate.not_after())?,
+    created_at: Utc::now(),
+  })
+}
+
+fn ssl_dir(state_dir: &str) -> PathBuf {
+  Path::new(state_dir).join("nginx/ssl")
+}
+
+/// Paths of a certificate and his key inside the nginx container
+fn template_data(name: &str) -> CertificateTemplateData {
+  CertificateTemplateData {
+    certificate: format!("{}/{}.crt", NGINX_SSL_DIR, name),
+    certificate_key: format!("{}/{}.key", NGINX_SSL_DIR, name),
+  }
+}
+
+/// Rendered proxy files referencing the certificate or his key
+pub fn find_references(
+  name: &str,
+  state_dir: &str,
+) -> Result<Vec<String>, IoError> {
+  let data = template_data(name);
+  let mut files = Vec::new();
+  for dir_path in ["nginx/sites-enabled", "nginx/streams-enabled"] {
+    let dir_path = Path::new(state_dir).join(dir_path);
+    if !dir_path.exists() {
+      continue;
+    }
+    for entry in fs::read_dir(dir_path)? {
+      let path = entry?.path();
+      let content = fs::read_to_string(&path)?;
+      if content.contains(&data.certificate)
+        || content.contains(&data.certificate_key)
+      {
+        files.push(path.display().to_string());
+      }
+    }
+  }
+  files.sort();
+  Ok(files)
+}
+
+/// Validate and store a certificate then write his files for the proxy
+pub async fn create(
+  item: CertificatePartial,
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+) -> Result<CertificateItem, HttpResponseError> {
+  let certificate = parse(&item).map_err(|err| err.to_http_error())?;
+  let certificate =
+    repositories::certificate::create(certificate, pool).await?;
+  let dir_path = ssl_dir(&config.state_dir);
+  let res = fs::create_dir_all(&dir_path)
+    .and_then(|_| {
+      fs::write(
+        dir_path.join(format!("{}.crt", item.name)),
+        &item.certificate,
+      )
+    })
+    .and_then(|_| {
+      services::utils::write_private_file(
+        &dir_path.join(format!("{}.key", item.name)),
+        item.certificate_key.as_bytes(),
+        0o600,
+      )
+    });
+  if let Err(err) = res {
+    repositories::certificate::delete_by_name(item.name, pool).await?;
+    return Err(CertificateError::Io(err).to_http_error());
+  }
+  Ok(certificate)
+}
+
+/// Delete a certificate unless a rendered template still use it
+pub async fn delete(
+  name: String,
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  repositories::certificate::find_by_name(name.to_owned(), pool).await?;
+  let files = find_references(&name, &config.state_dir)
+    .map_err(|err| CertificateError::Io(err).to_http_error())?;
+  if !files.is_empty() {
+    return Err(CertificateError::InUse(files).to_http_error());
+  }
+  let dir_path = ssl_dir(&config.state_dir);
+  for file_name in [format!("{}.crt", name), format!("{}.key", name)] {
+    match fs::remove_file(dir_path.join(file_name)) {
+      Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+        return Err(CertificateError::Io(err).to_http_error())
+      }
+      _ => {}
+    }
+  }
+  repositories::certificate::delete_by_name(name, pool).await
+}
+
+/// Uploaded certificates by template key of their domains
+/// when several certificates match a domain the one expiring last is used
+pub async fn list_template_certificates(
+  pool: &web::types::State<Pool>,
+) -> Result<HashMap<String, CertificateTemplateData>, HttpResponseError> {
+  let mut certificates = repositories::certificate::list(pool).await?;
+  certificates.sort_by_key(|certificate| certificate.not_after);
+  let mut data = HashMap::new();
+  for certificate in certificates {
+    for domain in &certificate.domains {
+      data.insert(template_key(domain), template_data(&certificate.name));
+    }
+  }
+  Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  use openssl::bn::BigNum;
+  use openssl::hash::MessageDigest;
+  use openssl::rsa::Rsa;
+  use openssl::x509::{X509Builder, X509NameBuilder};
+  use openssl::x509::extension::SubjectAlternativeName;
+
+  use crate::utils::test::*;
+
+  fn gen_certificate(name: &str) -> Result<CertificatePartial, ErrorStack> {
+    let key = PKey::from_rsa(Rsa::generate(2048)?)?;
+    let mut subject = X509NameBuilder::new()?;
+    subject.append_entry_by_text("CN", "Internal CA")?;
+    let subject = subject.build();
+    let mut builder = X509Builder::new()?;
+    builder.set_version(2)?;
+    builder.set_serial_number(&BigNum::from_u32(1)?.to_asn1_integer()?)?;
+    builder.set_subject_name(&subject)?;
+    builder.set_issuer_name(&subject)?;
+    builder.set_pubkey(&key)?;
+    builder.set_not_before(&Asn1Time::days_from_now(0)?)?;
+    builder.set_not_after(&Asn1Time::days_from_now(90)?)?;
+    let san = SubjectAlternativeName::new()
+      .dns("api.example.com")
+      .dns("*.api.example.com")
+      .build(&builder.x509v3_context(None, None))?;
+    builder.append_extension(san)?;
+    builder.sign(&key, MessageDigest::sha256())?;
+    let certificate = builder.build();
+    Ok(CertificatePartial {
+      name: name.to_owned(),
+      certificate: String::from_utf8_lossy(&certificate.to_pem()?).to_string(),
+      certificate_key: String::from_utf8_lossy(
+        &key.private_key_to_pem_pkcs8()?,
+      )
+      .to_string(),
+    })
+  }
+
+  #[test]
+  fn test_parse() -> TestReturn {
+    let item = gen_certificate("internal-api")?;
+    let certificate = parse(&item)?;
+    assert_eq!(
+      certificate.domains,
+      vec!["api.example.com", "*.api.example.com"]
+    );
+    assert_eq!(certificate.issuer, "CN=Internal CA");
+    assert!(certificate.not_after > certificate.not_before);
+
+    let other = gen_certificate("other")?;
+    let mismatch = CertificatePartial {
+      certificate_key: other.certificate_key,
+      ..item.to_owned()
+    };
+    assert!(parse(&mismatch).is_err());
+    let bad_name = CertificatePartial {
+      name: String::from("../internal-api"),
+      ..item
+    };
+    assert!(parse(&bad_name).is_err());
+    Ok(())
+  }
+}
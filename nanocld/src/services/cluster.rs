@@ -1,28 +1,52 @@
 use ntex::web;
 use ntex::http::StatusCode;
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
 use serde::{Serialize, Deserialize};
 use futures::{StreamExt, stream};
 use futures::stream::FuturesUnordered;
+use futures::lock::{Mutex as AsyncMutex, MutexGuard};
+use once_cell::sync::Lazy;
 
 use crate::config::DaemonConfig;
 use crate::utils::render_template;
 use crate::{services, repositories};
 use crate::models::{
   Pool, ClusterItem, CargoItem, ClusterNetworkItem, ClusterCargoPartial,
-  CargoEnvItem, NginxTemplateModes, ClusterCargoItem,
+  CargoEnvItem, ClusterCargoItem, PgDeleteGeneric, ClusterCargoNetworkPartial,
+  EventKind, ClusterRouteItem, NginxTemplateModes, ClusterAccessPolicyItem,
+  ClusterAuthPolicyItem, ClusterMaintenanceItem, NginxTemplateItem,
 };
 
 use crate::errors::{HttpResponseError, IntoHttpResponseError};
 
+use super::ipam::{Cidr, IpamError};
 use super::cargo::CreateCargoContainerOpts;
+use super::nginx::NginxConfigFile;
+use super::acme::{template_key, CertificateTemplateData};
+use super::route::RouteDirectives;
+
+/// Held while containers of cluster cargoes are created or removed
+/// so a reconcile never sees a join, unjoin or redeploy half done
+static CLUSTER_CARGO_LOCK: Lazy<AsyncMutex<()>> =
+  Lazy::new(|| AsyncMutex::new(()));
+
+/// Wait until no other join, unjoin, redeploy or reconcile is running
+pub async fn lock_cluster_cargoes() -> MutexGuard<'static, ()> {
+  CLUSTER_CARGO_LOCK.lock().await
+}
 
 #[derive(Debug)]
 pub struct JoinCargoOptions {
   pub(crate) cluster: ClusterItem,
   pub(crate) cargo: CargoItem,
+  /// Primary network used for target_ip
   pub(crate) network: ClusterNetworkItem,
+  /// Additional networks to connect the containers to
+  pub(crate) networks: Vec<ClusterNetworkItem>,
+  /// Fixed ipv4 address in the primary network
+  pub(crate) ipv4_address: Option<String>,
+  /// Fixed ipv6 address in the primary network
+  pub(crate) ipv6_address: Option<String>,
   pub(crate) is_creating_relation: bool,
 }
 
@@ -36,6 +60,23 @@ pub struct TemplateData {
   vars: Option<HashMap<String, String>>,
   cargoes: HashMap<String, CargoTemplateData>,
   networks: Option<HashMap<String, NetworkTemplateData>>,
+  /// Certificates of the cluster domains with dots replaced by underscores
+  certificates: HashMap<String, CertificateTemplateData>,
+  /// Nginx snippet to include in port 80 servers to answer acme challenges
+  acme_challenge: Option<String>,
+  /// Access policy directives of the domains with dots replaced by underscores
+  access_policies: HashMap<String, String>,
+  /// Auth policy directives of the domains with dots replaced by underscores
+  auth_policies: HashMap<String, String>,
+  /// Nginx snippet http servers must include with `{{{error_pages}}}`
+  /// to serve maintenance and error pages
+  error_pages: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CargoNetworkTemplateData {
+  target_ip: String,
+  target_ips: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +85,7 @@ pub struct CargoTemplateData {
   target_ip: String,
   dns_entry: Option<String>,
   target_ips: Vec<String>,
+  networks: HashMap<String, CargoNetworkTemplateData>,
 }
 
 pub async fn delete_networks(
@@ -98,27 +140,32 @@ pub async fn list_containers(
   Ok(containers)
 }
 
+/// Ips of containers by network key
+/// stopped containers are started or skipped when not starting
 async fn start_containers(
   containers: Vec<bollard::models::ContainerSummary>,
-  network_key: &str,
+  network_keys: &[String],
+  is_starting: bool,
   docker_api: &web::types::State<bollard::Docker>,
-) -> Result<Vec<String>, HttpResponseError> {
-  log::info!("Starting cargoes");
-  let target_ips = containers
+) -> Result<Vec<HashMap<String, String>>, HttpResponseError> {
+  let container_ips = containers
     .into_iter()
     .map(|container| async move {
       let container_id = container.id.unwrap_or_default();
-      log::info!("starting container {}", &container_id);
       let state = container.state.unwrap_or_default();
       if state != "running" {
+        if !is_starting {
+          return Ok(None);
+        }
+        log::info!("starting container {}", &container_id);
         docker_api
           .start_container(
             &container_id,
             None::<bollard::container::StartContainerOptions<String>>,
           )
           .await?;
+        log::info!("successfully started container {}", &container_id);
       }
-      log::info!("successfully started container {}", &container_id);
       let container = docker_api.inspect_container(&container_id, None).await?;
       let networks = container
         .network_settings
@@ -137,42 +184,116 @@ async fn start_containers(
           ),
           status: StatusCode::INTERNAL_SERVER_ERROR,
         })?;
-      let network = networks.get(network_key).ok_or(HttpResponseError {
-        msg: format!(
-          "unable to get network {} for container {}",
-          &network_key, &container_id
-        ),
-        status: StatusCode::INTERNAL_SERVER_ERROR,
-      })?;
-      let ip_address =
-        network.ip_address.as_ref().ok_or(HttpResponseError {
-          msg: format!(
-            "unable to get ip_address of container {}",
-            &container_id
-          ),
-          status: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
-      Ok::<String, HttpResponseError>(ip_address.into())
+      network_keys
+        .iter()
+        .map(|network_key| {
+          let network = networks.get(network_key).ok_or(HttpResponseError {
+            msg: format!(
+              "unable to get network {} for container {}",
+              &network_key, &container_id
+            ),
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+          })?;
+          let ip_address =
+            network.ip_address.as_ref().ok_or(HttpResponseError {
+              msg: format!(
+                "unable to get ip_address of container {}",
+                &container_id
+              ),
+              status: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+          Ok((network_key.to_owned(), ip_address.to_owned()))
+        })
+        .collect::<Result<HashMap<String, String>, HttpResponseError>>()
+        .map(Some)
     })
     .collect::<FuturesUnordered<_>>()
     .collect::<Vec<_>>()
     .await
     .into_iter()
-    .collect::<Result<Vec<String>, HttpResponseError>>()?;
-  log::info!("all cargo started");
-  Ok(target_ips)
+    .collect::<Result<Vec<_>, HttpResponseError>>()?;
+  Ok(container_ips.into_iter().flatten().collect())
 }
 
-async fn start_cluster_cargoes(
-  cluster_cargoes: Vec<ClusterCargoItem>,
+/// Keys of the networks a cargo is joined to, the primary one first
+async fn list_network_keys(
+  cluster_cargo: &ClusterCargoItem,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<String>, HttpResponseError> {
+  let mut network_keys = vec![cluster_cargo.network_key.to_owned()];
+  repositories::cluster_cargo_network::list_by_cluster_cargo_key(
+    cluster_cargo.key.to_owned(),
+    pool,
+  )
+  .await?
+  .into_iter()
+  .filter(|network| !network.is_primary)
+  .for_each(|network| network_keys.push(network.network_key));
+  Ok(network_keys)
+}
+
+/// Template data of a joined cargo from the ips of his containers
+/// by network key
+fn gen_cargo_template_data(
+  cargo: CargoItem,
+  cluster_cargo: &ClusterCargoItem,
+  network_keys: &[String],
+  container_ips: &[HashMap<String, String>],
+) -> CargoTemplateData {
+  let target_ips = container_ips
+    .iter()
+    .filter_map(|ips| ips.get(&cluster_cargo.network_key).cloned())
+    .collect::<Vec<String>>();
+  let target_ip = target_ips.get(0).cloned().unwrap_or_default();
+  let network_prefix = format!("{}-", &cluster_cargo.cluster_key);
+  let networks = network_keys
+    .iter()
+    .map(|network_key| {
+      let target_ips = container_ips
+        .iter()
+        .filter_map(|ips| ips.get(network_key).cloned())
+        .collect::<Vec<String>>();
+      let target_ip = target_ips.get(0).cloned().unwrap_or_default();
+      let network_name = network_key
+        .strip_prefix(&network_prefix)
+        .unwrap_or(network_key)
+        .to_owned();
+      let network = CargoNetworkTemplateData {
+        target_ip,
+        target_ips,
+      };
+      (network_name, network)
+    })
+    .collect::<HashMap<String, CargoNetworkTemplateData>>();
+  CargoTemplateData {
+    name: cargo.name,
+    dns_entry: cargo.dns_entry,
+    target_ip,
+    target_ips,
+    networks,
+  }
+}
+
+/// Template data of the cargoes of a cluster by cargo name
+/// containers are started or only the running ones are used when not starting
+async fn gen_cargoes(
+  cluster: &ClusterItem,
+  is_starting: bool,
   docker_api: &web::types::State<bollard::Docker>,
   pool: &web::types::State<Pool>,
-) -> Result<Vec<CargoTemplateData>, HttpResponseError> {
-  cluster_cargoes
+) -> Result<HashMap<String, CargoTemplateData>, HttpResponseError> {
+  let cluster_cargoes = repositories::cluster_cargo::get_by_cluster_key(
+    cluster.key.to_owned(),
+    pool,
+  )
+  .await?;
+  if is_starting {
+    log::info!("starting cargoes of cluster {}", &cluster.key);
+  }
+  let cargoes = cluster_cargoes
     .into_iter()
     .map(|cluster_cargo| async move {
       let cargo_key = &cluster_cargo.cargo_key;
-      let network_key = &cluster_cargo.network_key;
       let containers = list_containers(
         &cluster_cargo.cluster_key,
         &cluster_cargo.cargo_key,
@@ -182,164 +303,631 @@ async fn start_cluster_cargoes(
 
       let cargo =
         repositories::cargo::find_by_key(cargo_key.to_owned(), pool).await?;
+      let network_keys = list_network_keys(&cluster_cargo, pool).await?;
 
-      let mut target_ips =
-        start_containers(containers, network_key, docker_api).await?;
-      target_ips.reverse();
-      let target_ip = match target_ips.get(0) {
-        None => String::new(),
-        Some(target_ip) => target_ip.to_owned(),
-      };
-      let cargo_template_data = CargoTemplateData {
-        name: cargo.name,
-        dns_entry: cargo.dns_entry,
-        target_ip,
-        target_ips,
-      };
-      Ok::<CargoTemplateData, HttpResponseError>(cargo_template_data)
+      let mut container_ips =
+        start_containers(containers, &network_keys, is_starting, docker_api)
+          .await?;
+      container_ips.reverse();
+      Ok::<CargoTemplateData, HttpResponseError>(gen_cargo_template_data(
+        cargo,
+        &cluster_cargo,
+        &network_keys,
+        &container_ips,
+      ))
     })
     .collect::<FuturesUnordered<_>>()
     .collect::<Vec<_>>()
     .await
     .into_iter()
-    .collect::<Result<Vec<CargoTemplateData>, HttpResponseError>>()
+    .collect::<Result<Vec<CargoTemplateData>, HttpResponseError>>()?
+    .into_iter()
+    .map(|item| (item.name.to_owned(), item))
+    .collect::<HashMap<String, CargoTemplateData>>();
+  Ok(cargoes)
 }
 
-pub async fn start(
+/// Template data of the cargoes of a cluster by cargo name without the
+/// ignored joins, only the database is read so container ips are left empty
+async fn gen_dns_cargoes(
   cluster: &ClusterItem,
-  config: &DaemonConfig,
+  ignored_joins: &[String],
   pool: &web::types::State<Pool>,
-  docker_api: &web::types::State<bollard::Docker>,
-) -> Result<(), HttpResponseError> {
+) -> Result<HashMap<String, CargoTemplateData>, HttpResponseError> {
   let cluster_cargoes = repositories::cluster_cargo::get_by_cluster_key(
     cluster.key.to_owned(),
     pool,
   )
   .await?;
-
-  let cargoes = start_cluster_cargoes(cluster_cargoes, docker_api, pool)
-    .await?
-    .into_iter()
-    .fold(HashMap::new(), |mut acc, item| {
-      acc.insert(item.name.to_owned(), item);
-      acc
-    });
-
-  if !cluster.proxy_templates.is_empty() {
-    let cluster_vars = repositories::cluster_variable::list_by_cluster(
-      cluster.key.to_owned(),
+  let mut cargoes = HashMap::new();
+  for cluster_cargo in cluster_cargoes {
+    if ignored_joins.contains(&cluster_cargo.key) {
+      continue;
+    }
+    let cargo = repositories::cargo::find_by_key(
+      cluster_cargo.cargo_key.to_owned(),
       pool,
     )
     .await?;
-    let vars =
-      services::cluster_variable::cluster_vars_to_hashmap(cluster_vars);
+    let network_keys = list_network_keys(&cluster_cargo, pool).await?;
+    let item =
+      gen_cargo_template_data(cargo, &cluster_cargo, &network_keys, &[]);
+    cargoes.insert(item.name.to_owned(), item);
+  }
+  Ok(cargoes)
+}
 
-    let networks =
-      repositories::cluster_network::list_for_cluster(cluster.to_owned(), pool)
-        .await?
-        .into_iter()
-        .fold(HashMap::new(), |mut acc, network| {
-          acc.insert(
-            network.name.to_owned(),
-            NetworkTemplateData {
-              gateway: network.default_gateway,
-            },
-          );
-          acc
-        });
+/// Data given to the templates of a cluster
+/// with the policies and maintenance it is made of
+struct RenderData {
+  template_data: TemplateData,
+  access_policies: Vec<ClusterAccessPolicyItem>,
+  auth_policies: Vec<ClusterAuthPolicyItem>,
+  auth_directives: RouteDirectives,
+  maintenance: Option<ClusterMaintenanceItem>,
+}
 
-    let mut templates = stream::iter(&cluster.proxy_templates);
+/// Gather the data given to the templates of a cluster
+async fn gen_render_data(
+  cluster: &ClusterItem,
+  routes: &[ClusterRouteItem],
+  cargoes: HashMap<String, CargoTemplateData>,
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+) -> Result<RenderData, HttpResponseError> {
+  let cluster_vars = repositories::cluster_variable::list_by_cluster(
+    cluster.key.to_owned(),
+    pool,
+  )
+  .await?;
+  let vars = services::cluster_variable::cluster_vars_to_hashmap(cluster_vars);
 
-    while let Some(template_name) = templates.next().await {
-      let template = repositories::nginx_template::get_by_name(
-        template_name.to_owned(),
-        pool,
-      )
-      .await?;
-      let file_path = Path::new(&config.state_dir);
-      let file_path = match template.mode {
-        NginxTemplateModes::Http => file_path.join("nginx/sites-enabled"),
-        NginxTemplateModes::Stream => file_path.join("nginx/streams-enabled"),
-      };
-      let file_path = file_path.join(format!(
-        "{name}.conf",
-        name = format!("{}.{}", &cluster.key, &template.name)
-      ));
-      let template_data = TemplateData {
-        vars: Some(vars.to_owned()),
-        networks: Some(networks.to_owned()),
-        cargoes: cargoes.to_owned(),
-      };
+  let networks =
+    repositories::cluster_network::list_for_cluster(cluster.to_owned(), pool)
+      .await?
+      .into_iter()
+      .fold(HashMap::new(), |mut acc, network| {
+        acc.insert(
+          network.name.to_owned(),
+          NetworkTemplateData {
+            gateway: network.default_gateway,
+          },
+        );
+        acc
+      });
 
-      let config_file = render_template(template.content, &template_data)?;
-      std::fs::write(&file_path, config_file).map_err(|err| {
-        HttpResponseError {
-          msg: format!(
-            "Unable to write config file {} {}",
-            &file_path.display(),
-            err
-          ),
-          status: StatusCode::INTERNAL_SERVER_ERROR,
-        }
-      })?;
+  // Uploaded certificates take precedence over the ones issued by acme
+  let mut certificates = services::acme::list_template_certificates(
+    &cluster.domains,
+    &config.state_dir,
+  );
+  certificates
+    .extend(services::certificate::list_template_certificates(pool).await?);
+  let acme_challenge = config
+    .acme_directory
+    .as_ref()
+    .map(|_| services::acme::challenge_snippet_path());
 
-      let mut cargoes = stream::iter(&cargoes);
+  let policies = repositories::cluster_access_policy::list_by_cluster(
+    cluster.key.to_owned(),
+    pool,
+  )
+  .await?;
+  let auth_policies = repositories::cluster_auth_policy::list_by_cluster(
+    cluster.key.to_owned(),
+    pool,
+  )
+  .await?;
+  let policy_domains = cluster
+    .domains
+    .iter()
+    .chain(routes.iter().map(|route| &route.domain))
+    .chain(policies.iter().filter_map(|policy| policy.domain.as_ref()))
+    .chain(
+      auth_policies
+        .iter()
+        .filter_map(|policy| policy.domain.as_ref()),
+    )
+    .map(|domain| domain.as_str())
+    .collect::<HashSet<_>>();
+  let access_policies = services::access_policy::gen_template_directives(
+    policy_domains.iter().copied(),
+    &policies,
+  )
+  .map_err(|err| err.to_http_error())?;
+  let auth_directives = services::auth_policy::gen_directives(
+    policy_domains.iter().copied(),
+    routes,
+    &auth_policies,
+  )
+  .map_err(|err| err.to_http_error())?;
+  let maintenance = repositories::cluster_maintenance::find_by_cluster_key(
+    cluster.key.to_owned(),
+    pool,
+  )
+  .await?;
 
-      println!("{:#?}", &networks);
+  let template_data = TemplateData {
+    vars: Some(vars),
+    networks: Some(networks),
+    cargoes,
+    certificates,
+    acme_challenge,
+    access_policies,
+    auth_policies: auth_directives.servers.to_owned(),
+    error_pages: maintenance
+      .as_ref()
+      .and_then(services::maintenance::gen_directives),
+  };
+  Ok(RenderData {
+    template_data,
+    access_policies: policies,
+    auth_policies,
+    auth_directives,
+    maintenance,
+  })
+}
 
-      while let Some((_, item)) = cargoes.next().await {
-        if None == item.dns_entry {
-          continue;
-        }
-        let item_string =
-          serde_json::to_string(&item).map_err(|err| HttpResponseError {
-            msg: format!("{}", err),
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-          })?;
+/// Render the dns entry of a cargo as its ip and domain
+fn render_dns_entry(
+  item: &CargoTemplateData,
+  template_data: &TemplateData,
+) -> Result<Option<(String, String)>, HttpResponseError> {
+  if item.dns_entry.is_none() {
+    return Ok(None);
+  }
+  let item_string =
+    serde_json::to_string(item).map_err(|err| HttpResponseError {
+      msg: format!("{}", err),
+      status: StatusCode::INTERNAL_SERVER_ERROR,
+    })?;
 
-        let item: CargoTemplateData =
-          serde_json::from_str(&render_template(item_string, &template_data)?)
-            .map_err(|err| HttpResponseError {
-              msg: format!("{}", err),
-              status: StatusCode::INTERNAL_SERVER_ERROR,
-            })?;
+  let item: CargoTemplateData =
+    serde_json::from_str(&render_template(item_string, template_data)?)
+      .map_err(|err| HttpResponseError {
+        msg: format!("{}", err),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      })?;
 
-        let domain = item.dns_entry.ok_or(HttpResponseError {
-          msg: String::from("Unexpected error domain should not be null"),
-          status: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+  let domain = item.dns_entry.ok_or(HttpResponseError {
+    msg: String::from("Unexpected error domain should not be null"),
+    status: StatusCode::INTERNAL_SERVER_ERROR,
+  })?;
 
-        let dns_settings = domain.split(':').collect::<Vec<_>>();
+  match domain.split_once(':') {
+    Some((ip, domain)) if !domain.contains(':') => {
+      Ok(Some((ip.to_owned(), domain.to_owned())))
+    }
+    _ => Err(HttpResponseError {
+      msg: String::from("Error dns settings have incorrect format"),
+      status: StatusCode::BAD_REQUEST,
+    }),
+  }
+}
 
-        if dns_settings.len() != 2 {
-          return Err(HttpResponseError {
-            msg: String::from("Error dns settings have incorrect format"),
-            status: StatusCode::BAD_REQUEST,
-          });
-        }
+/// Domains of the dns entries of cargoes by cargo name
+/// rendered with the template data of their cluster
+async fn render_cargo_dns_domains(
+  cluster: &ClusterItem,
+  cargoes: HashMap<String, CargoTemplateData>,
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+) -> Result<HashMap<String, String>, HttpResponseError> {
+  if cargoes.values().all(|cargo| cargo.dns_entry.is_none()) {
+    return Ok(HashMap::new());
+  }
+  let routes =
+    repositories::cluster_route::list_by_cluster(cluster.key.to_owned(), pool)
+      .await?;
+  let RenderData { template_data, .. } =
+    gen_render_data(cluster, &routes, cargoes, config, pool).await?;
+  let mut domains = HashMap::new();
+  for (name, item) in &template_data.cargoes {
+    if let Some((_, domain)) = render_dns_entry(item, &template_data)? {
+      domains.insert(name.to_owned(), domain);
+    }
+  }
+  Ok(domains)
+}
 
-        services::dnsmasq::add_dns_entry(
-          dns_settings[1],
-          dns_settings[0],
-          &config.state_dir,
+/// Domains of the dns entries of the cargoes of a cluster by cargo name
+/// rendered like a start does without starting the containers
+pub async fn render_dns_domains(
+  cluster: &ClusterItem,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+) -> Result<HashMap<String, String>, HttpResponseError> {
+  let cargoes = gen_cargoes(cluster, false, docker_api, pool).await?;
+  render_cargo_dns_domains(cluster, cargoes, config, pool).await
+}
+
+/// Check if a dns domain is still rendered by a cargo joined to a cluster
+/// other than the ignored joins, only the database is read
+/// so domains are expected to not depend on container ips
+/// a cluster failing to render is considered to use it
+pub async fn is_dns_domain_used(
+  domain: &str,
+  ignored_joins: &[String],
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+) -> Result<bool, HttpResponseError> {
+  let cluster_keys = repositories::cluster_cargo::list_all(pool)
+    .await?
+    .into_iter()
+    .filter(|cluster_cargo| !ignored_joins.contains(&cluster_cargo.key))
+    .map(|cluster_cargo| cluster_cargo.cluster_key)
+    .collect::<HashSet<_>>();
+  for cluster_key in cluster_keys {
+    let cluster = repositories::cluster::find_by_key(cluster_key, pool).await?;
+    let cargoes = gen_dns_cargoes(&cluster, ignored_joins, pool).await?;
+    match render_cargo_dns_domains(&cluster, cargoes, config, pool).await {
+      Err(err) => {
+        log::warn!(
+          "unable to render dns entries of cluster {} {}",
+          &cluster.key,
+          err.msg
+        );
+        return Ok(true);
+      }
+      Ok(domains) if domains.values().any(|used| used == domain) => {
+        return Ok(true)
+      }
+      Ok(_) => {}
+    }
+  }
+  Ok(false)
+}
+
+/// Config files of the templates and routes of a cluster
+/// the given revision is used instead of the stored template with its name
+async fn gen_config_files(
+  cluster: &ClusterItem,
+  routes: &[ClusterRouteItem],
+  render_data: &RenderData,
+  revision: Option<&NginxTemplateItem>,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<NginxConfigFile>, HttpResponseError> {
+  let RenderData {
+    template_data,
+    access_policies: policies,
+    auth_policies,
+    auth_directives,
+    maintenance,
+  } = render_data;
+  let targets = template_data
+    .cargoes
+    .iter()
+    .map(|(name, cargo)| (name.to_owned(), cargo.target_ips.to_owned()))
+    .collect::<HashMap<String, Vec<String>>>();
+  let mut config_files = Vec::new();
+  // Zones and upstreams used by the servers of the templates and routes
+  if !policies.is_empty() {
+    config_files.push(NginxConfigFile {
+      name: services::access_policy::config_file_name(&cluster.key),
+      mode: NginxTemplateModes::Http,
+      content: services::access_policy::gen_zones(&cluster.key, policies),
+    });
+  }
+  let auth_upstreams =
+    services::auth_policy::gen_upstreams(&cluster.key, auth_policies, &targets)
+      .map_err(|err| err.to_http_error())?;
+  if let Some(content) = auth_upstreams {
+    config_files.push(NginxConfigFile {
+      name: services::auth_policy::config_file_name(&cluster.key),
+      mode: NginxTemplateModes::Http,
+      content,
+    });
+  }
+  if let Some(content) = maintenance
+    .as_ref()
+    .and_then(services::maintenance::gen_variables)
+  {
+    config_files.push(NginxConfigFile {
+      name: services::maintenance::config_file_name(&cluster.key),
+      mode: NginxTemplateModes::Http,
+      content,
+    });
+  }
+  let mut templates = stream::iter(&cluster.proxy_templates);
+
+  while let Some(template_name) = templates.next().await {
+    let template = match revision {
+      Some(revision) if revision.name == *template_name => revision.to_owned(),
+      _ => {
+        repositories::nginx_template::get_by_name(
+          template_name.to_owned(),
+          pool,
         )
-        .map_err(|err| err.to_http_error())?;
+        .await?
       }
+    };
+    let content = render_template(template.content, template_data)?;
+    config_files.push(NginxConfigFile {
+      name: format!("{}.{}", &cluster.key, &template.name),
+      mode: template.mode,
+      content,
+    });
+  }
 
-      services::dnsmasq::restart(docker_api)
-        .await
+  if !routes.is_empty() {
+    // Access directives are checked before the authentication
+    let mut directives = auth_directives.to_owned();
+    for (key, access) in &template_data.access_policies {
+      let server = directives.servers.entry(key.to_owned()).or_default();
+      server.insert_str(0, access);
+    }
+    if let Some(error_pages) = &template_data.error_pages {
+      let domains = routes
+        .iter()
+        .map(|route| template_key(&route.domain))
+        .collect::<HashSet<_>>();
+      for key in domains {
+        directives
+          .servers
+          .entry(key)
+          .or_default()
+          .push_str(error_pages);
+      }
+    }
+    let route_config = services::route::gen_config(
+      &cluster.key,
+      routes,
+      &targets,
+      &template_data.certificates,
+      &directives,
+      template_data.acme_challenge.as_deref(),
+    )
+    .map_err(|err| err.to_http_error())?;
+    for warning in &route_config.warnings {
+      log::warn!("routes of cluster {} {}", &cluster.key, warning);
+    }
+    config_files.push(NginxConfigFile {
+      name: services::route::config_file_name(&cluster.key),
+      mode: NginxTemplateModes::Http,
+      content: route_config.content,
+    });
+  }
+  Ok(config_files)
+}
+
+/// Render the proxy templates of a cluster and register dns entries
+/// of his cargoes
+async fn render_proxy_templates(
+  cluster: &ClusterItem,
+  routes: &[ClusterRouteItem],
+  cargoes: HashMap<String, CargoTemplateData>,
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+  docker_api: &web::types::State<bollard::Docker>,
+) -> Result<(), HttpResponseError> {
+  let render_data =
+    gen_render_data(cluster, routes, cargoes, config, pool).await?;
+  services::auth_policy::write_htpasswd_files(
+    &render_data.auth_policies,
+    &config.state_dir,
+  )
+  .map_err(|err| err.to_http_error())?;
+  if let Some(maintenance) = &render_data.maintenance {
+    services::maintenance::write_pages(maintenance, &config.state_dir)
+      .map_err(|err| err.to_http_error())?;
+  }
+
+  let config_files =
+    gen_config_files(cluster, routes, &render_data, None, pool).await?;
+  services::nginx::apply_config_files(
+    config_files,
+    &config.state_dir,
+    docker_api,
+  )
+  .await
+  .map_err(|err| err.to_http_error())?;
+
+  let template_data = &render_data.template_data;
+  for item in template_data.cargoes.values() {
+    if let Some((ip, domain)) = render_dns_entry(item, template_data)? {
+      services::dnsmasq::add_dns_entry(&domain, &ip, &config.state_dir)
         .map_err(|err| err.to_http_error())?;
-      services::nginx::reload_config(docker_api).await?;
     }
   }
+
+  services::dnsmasq::restart(docker_api)
+    .await
+    .map_err(|err| err.to_http_error())?;
+  Ok(())
+}
+
+/// Render a cluster with a new revision of one of his templates
+/// and test it with `nginx -t` without applying anything
+pub async fn test_template(
+  cluster: &ClusterItem,
+  template: &NginxTemplateItem,
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+  docker_api: &web::types::State<bollard::Docker>,
+) -> Result<(), HttpResponseError> {
+  let cargoes = gen_cargoes(cluster, false, docker_api, pool).await?;
+  let routes =
+    repositories::cluster_route::list_by_cluster(cluster.key.to_owned(), pool)
+      .await?;
+  let render_data =
+    gen_render_data(cluster, &routes, cargoes, config, pool).await?;
+  let config_files =
+    gen_config_files(cluster, &routes, &render_data, Some(template), pool)
+      .await?;
+  services::nginx::test_config_files(
+    &config_files,
+    &config.state_dir,
+    docker_api,
+  )
+  .await
+  .map_err(|err| err.to_http_error())
+}
+
+/// Render the proxy templates and routes of a cluster if it has some
+async fn render_cluster(
+  cluster: &ClusterItem,
+  cargoes: HashMap<String, CargoTemplateData>,
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+  docker_api: &web::types::State<bollard::Docker>,
+  actor: &str,
+) -> Result<(), HttpResponseError> {
+  let routes =
+    repositories::cluster_route::list_by_cluster(cluster.key.to_owned(), pool)
+      .await?;
+
+  if !cluster.proxy_templates.is_empty() || !routes.is_empty() {
+    let mut targets = cluster.proxy_templates.to_owned();
+    targets.extend(routes.iter().map(|route| format!("route {}", route.name)));
+    let res = render_proxy_templates(
+      cluster, &routes, cargoes, config, pool, docker_api,
+    )
+    .await;
+    services::history::record(
+      &cluster.key,
+      EventKind::Template,
+      actor,
+      Some(targets.join(", ")),
+      &res,
+      pool,
+    )
+    .await;
+    res?;
+  }
   Ok(())
 }
 
+/// Start the containers of the cargoes of a cluster and render it
+pub async fn start(
+  cluster: &ClusterItem,
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+  docker_api: &web::types::State<bollard::Docker>,
+  actor: &str,
+) -> Result<(), HttpResponseError> {
+  let cargoes = gen_cargoes(cluster, true, docker_api, pool).await?;
+  render_cluster(cluster, cargoes, config, pool, docker_api, actor).await
+}
+
+/// Render a cluster with the containers already running
+/// stopped containers are left as they are
+pub async fn render(
+  cluster: &ClusterItem,
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+  docker_api: &web::types::State<bollard::Docker>,
+  actor: &str,
+) -> Result<(), HttpResponseError> {
+  let cargoes = gen_cargoes(cluster, false, docker_api, pool).await?;
+  render_cluster(cluster, cargoes, config, pool, docker_api, actor).await
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MustacheData {
   pub(crate) vars: HashMap<String, String>,
 }
 
+/// Subnets configured in the ipam of a docker network
+fn parse_network_subnets(network: bollard::models::Network) -> Vec<Cidr> {
+  network
+    .ipam
+    .and_then(|ipam| ipam.config)
+    .unwrap_or_default()
+    .into_iter()
+    .filter_map(|config| config.subnet?.parse::<Cidr>().ok())
+    .collect::<Vec<Cidr>>()
+}
+
+/// List subnets configured on given cluster network from docker
+pub async fn get_network_subnets(
+  network: &ClusterNetworkItem,
+  docker_api: &web::types::State<bollard::Docker>,
+) -> Result<Vec<Cidr>, HttpResponseError> {
+  let network = docker_api
+    .inspect_network(
+      &network.docker_network_id,
+      None::<bollard::network::InspectNetworkOptions<String>>,
+    )
+    .await?;
+  Ok(parse_network_subnets(network))
+}
+
+/// Ensure fixed addresses of a join are part of the primary network subnets
+/// and are not already allocated to another cargo
+async fn validate_static_addresses(
+  opts: &JoinCargoOptions,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+) -> Result<(), HttpResponseError> {
+  let addresses = [(&opts.ipv4_address, true), (&opts.ipv6_address, false)];
+  if addresses.iter().all(|(address, _)| address.is_none()) {
+    return Ok(());
+  }
+  let network = docker_api
+    .inspect_network(
+      &opts.network.docker_network_id,
+      None::<bollard::network::InspectNetworkOptions<String>>,
+    )
+    .await?;
+  // Docker never gives an ipv6 address in a network without ipv6
+  if opts.ipv6_address.is_some() && network.enable_ipv6 != Some(true) {
+    return Err(HttpResponseError {
+      msg: format!(
+        "network {} doesn't have ipv6 enabled to use an ipv6 address",
+        opts.network.name
+      ),
+      status: StatusCode::BAD_REQUEST,
+    });
+  }
+  let subnets = parse_network_subnets(network);
+  for (address, is_ipv4) in addresses {
+    let address = match address {
+      None => continue,
+      Some(address) => address,
+    };
+    let ip = services::ipam::parse_address(address)
+      .map_err(|err| err.to_http_error())?;
+    if ip.is_ipv4() != is_ipv4 {
+      return Err(
+        IpamError::InvalidAddress(address.to_owned()).to_http_error(),
+      );
+    }
+    if !subnets.iter().any(|subnet| subnet.contains(&ip)) {
+      let subnets = subnets
+        .iter()
+        .map(|subnet| subnet.to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+      return Err(
+        IpamError::OutOfSubnet(address.to_owned(), subnets).to_http_error(),
+      );
+    }
+    if *address == opts.network.default_gateway {
+      return Err(HttpResponseError {
+        msg: format!("address {} is the gateway of the network", address),
+        status: StatusCode::CONFLICT,
+      });
+    }
+    let existing = repositories::cluster_cargo_network::find_by_address(
+      opts.network.key.to_owned(),
+      address.to_owned(),
+      pool,
+    )
+    .await?;
+    if let Some(existing) = existing {
+      return Err(HttpResponseError {
+        msg: format!(
+          "address {} is already allocated to {}",
+          address, existing.cluster_cargo_key
+        ),
+        status: StatusCode::CONFLICT,
+      });
+    }
+  }
+  Ok(())
+}
+
 pub async fn join_cargo(
   opts: &JoinCargoOptions,
   docker_api: &web::types::State<bollard::Docker>,
@@ -397,6 +985,9 @@ pub async fn join_cargo(
       acc
     })
     .to_vec();
+  if opts.is_creating_relation {
+    validate_static_addresses(opts, docker_api, pool).await?;
+  }
   let create_opts = CreateCargoContainerOpts {
     cargo: &opts.cargo,
     network_key: &opts.network.key,
@@ -408,17 +999,30 @@ pub async fn join_cargo(
   let container_ids =
     services::cargo::create_containers(create_opts, docker_api).await?;
 
+  let networks = std::iter::once(&opts.network)
+    .chain(opts.networks.iter())
+    .collect::<Vec<&ClusterNetworkItem>>();
+  let networks = &networks;
+
   container_ids
     .clone()
     .into_iter()
     .map(|container_name| async move {
-      let config = bollard::network::ConnectNetworkOptions {
-        container: container_name.to_owned(),
-        ..Default::default()
-      };
-      docker_api
-        .connect_network(&opts.network.key, config)
-        .await?;
+      for network in networks {
+        let mut config = bollard::network::ConnectNetworkOptions {
+          container: container_name.to_owned(),
+          ..Default::default()
+        };
+        if network.key == opts.network.key {
+          config.endpoint_config.ipam_config =
+            Some(bollard::models::EndpointIpamConfig {
+              ipv4_address: opts.ipv4_address.to_owned(),
+              ipv6_address: opts.ipv6_address.to_owned(),
+              ..Default::default()
+            });
+        }
+        docker_api.connect_network(&network.key, config).await?;
+      }
       Ok::<(), HttpResponseError>(())
     })
     .collect::<FuturesUnordered<_>>()
@@ -428,8 +1032,176 @@ pub async fn join_cargo(
     .collect::<Result<Vec<()>, HttpResponseError>>()?;
 
   if opts.is_creating_relation {
-    repositories::cluster_cargo::create(cluster_cargo, pool).await?;
+    let cluster_cargo =
+      repositories::cluster_cargo::create(cluster_cargo, pool).await?;
+    let cargo_networks = networks
+      .iter()
+      .map(|network| {
+        let is_primary = network.key == opts.network.key;
+        ClusterCargoNetworkPartial {
+          cluster_cargo_key: cluster_cargo.key.to_owned(),
+          network_key: network.key.to_owned(),
+          is_primary,
+          ipv4_address: opts.ipv4_address.to_owned().filter(|_| is_primary),
+          ipv6_address: opts.ipv6_address.to_owned().filter(|_| is_primary),
+        }
+      })
+      .collect::<Vec<ClusterCargoNetworkPartial>>();
+    repositories::cluster_cargo_network::create_many(cargo_networks, pool)
+      .await?;
   }
 
   Ok(container_ids)
 }
+
+/// Recreate the containers of an existing cluster cargo
+/// Old containers are removed once the new ones are started
+/// unless the join use fixed addresses that would conflict
+pub async fn redeploy_cargo(
+  cluster_cargo: ClusterCargoItem,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<(), HttpResponseError> {
+  let cluster = repositories::cluster::find_by_key(
+    cluster_cargo.cluster_key.to_owned(),
+    pool,
+  )
+  .await?;
+  let cargo =
+    repositories::cargo::find_by_key(cluster_cargo.cargo_key.to_owned(), pool)
+      .await?;
+  let network = repositories::cluster_network::find_by_key(
+    cluster_cargo.network_key.to_owned(),
+    pool,
+  )
+  .await?;
+  let cargo_networks =
+    repositories::cluster_cargo_network::list_by_cluster_cargo_key(
+      cluster_cargo.key.to_owned(),
+      pool,
+    )
+    .await?;
+  let mut opts = JoinCargoOptions {
+    cluster: cluster.to_owned(),
+    cargo,
+    network,
+    networks: Vec::new(),
+    ipv4_address: None,
+    ipv6_address: None,
+    is_creating_relation: false,
+  };
+  for cargo_network in cargo_networks {
+    if cargo_network.is_primary {
+      opts.ipv4_address = cargo_network.ipv4_address;
+      opts.ipv6_address = cargo_network.ipv6_address;
+      continue;
+    }
+    let network = repositories::cluster_network::find_by_key(
+      cargo_network.network_key,
+      pool,
+    )
+    .await?;
+    opts.networks.push(network);
+  }
+
+  let mut cnt_to_remove = list_containers(
+    &cluster_cargo.cluster_key,
+    &cluster_cargo.cargo_key,
+    docker_api,
+  )
+  .await?;
+  log::debug!("container to remove {:#?}", &cnt_to_remove);
+
+  let options = Some(bollard::container::RemoveContainerOptions {
+    force: true,
+    ..Default::default()
+  });
+  // Fixed addresses can't be used by two containers at the same time
+  if opts.ipv4_address.is_some() || opts.ipv6_address.is_some() {
+    for container in cnt_to_remove.drain(..) {
+      docker_api
+        .remove_container(&container.id.unwrap_or_default(), options)
+        .await?;
+    }
+  }
+
+  join_cargo(&opts, docker_api, pool).await?;
+  start(&cluster, config, pool, docker_api, actor).await?;
+
+  for container in cnt_to_remove {
+    log::debug!("removing container {:#?}", &container);
+    docker_api
+      .remove_container(&container.id.unwrap_or_default(), options)
+      .await?;
+  }
+  Ok(())
+}
+
+/// Remove a cargo from a cluster
+/// His containers are removed with his dns entries
+/// and the proxy templates of the cluster are rendered again
+pub async fn unjoin_cargo(
+  cluster: &ClusterItem,
+  cargo: &CargoItem,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  let _lock = lock_cluster_cargoes().await;
+  // The dns entry is rendered while the containers of the cargo still exist
+  let dns_domain = match cargo.dns_entry {
+    None => None,
+    Some(_) => render_dns_domains(cluster, config, docker_api, pool)
+      .await?
+      .remove(&cargo.name),
+  };
+  let containers =
+    list_containers(&cluster.key, &cargo.key, docker_api).await?;
+
+  containers
+    .into_iter()
+    .map(|container| async move {
+      let id = container.id.ok_or(HttpResponseError {
+        msg: String::from("unable to get container id"),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      })?;
+      let options = Some(bollard::container::RemoveContainerOptions {
+        force: true,
+        ..Default::default()
+      });
+      docker_api.remove_container(&id, options).await?;
+      Ok::<_, HttpResponseError>(())
+    })
+    .collect::<FuturesUnordered<_>>()
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .collect::<Result<Vec<()>, HttpResponseError>>()?;
+
+  let cluster_cargo_key = format!("{}-{}", &cluster.key, &cargo.key);
+  repositories::cluster_cargo_network::delete_by_cluster_cargo_key(
+    cluster_cargo_key.to_owned(),
+    pool,
+  )
+  .await?;
+  let res =
+    repositories::cluster_cargo::delete_by_key(cluster_cargo_key, pool).await?;
+
+  // Another cargo may still resolve the same domain
+  if let Some(domain) = dns_domain {
+    if !is_dns_domain_used(&domain, &[], config, pool).await? {
+      services::dnsmasq::remove_dns_entry(&domain, &config.state_dir)
+        .map_err(|err| err.to_http_error())?;
+      services::dnsmasq::restart(docker_api)
+        .await
+        .map_err(|err| err.to_http_error())?;
+    }
+  }
+
+  start(cluster, config, pool, docker_api, actor).await?;
+
+  Ok(res)
+}
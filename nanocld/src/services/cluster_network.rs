@@ -0,0 +1,190 @@
+use ntex::web;
+use std::net::IpAddr;
+use std::collections::HashMap;
+use bollard::errors::Error as DockerError;
+use bollard::container::ListContainersOptions;
+use bollard::network::InspectNetworkOptions;
+
+use crate::repositories;
+use crate::errors::HttpResponseError;
+use crate::models::{
+  Pool, ClusterNetworkItem, ClusterNetworkInspect, ClusterNetworkContainer,
+};
+
+use super::ipam::{self, Cidr};
+
+/// Remove the prefix length docker add to endpoint addresses
+fn strip_prefix_len(address: Option<String>) -> Option<String> {
+  let address = address?;
+  let address = address.split('/').next().unwrap_or_default().to_owned();
+  if address.is_empty() {
+    return None;
+  }
+  Some(address)
+}
+
+/// List cargo keys of the containers connected to a docker network
+async fn list_container_cargoes(
+  docker_network_id: &str,
+  docker_api: &web::types::State<bollard::Docker>,
+) -> Result<HashMap<String, String>, HttpResponseError> {
+  let mut filters = HashMap::new();
+  filters.insert("network", vec![docker_network_id]);
+  let options = Some(ListContainersOptions {
+    all: true,
+    filters,
+    ..Default::default()
+  });
+  let containers = docker_api.list_containers(options).await?;
+  let cargoes = containers
+    .into_iter()
+    .filter_map(|container| {
+      let cargo_key = container.labels?.get("cargo")?.to_owned();
+      Some((container.id?, cargo_key))
+    })
+    .collect::<HashMap<String, String>>();
+  Ok(cargoes)
+}
+
+/// Inspect a cluster network with his live docker state
+/// Differences between the database and docker are reported as warnings
+pub async fn inspect(
+  network: ClusterNetworkItem,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+) -> Result<ClusterNetworkInspect, HttpResponseError> {
+  let mut warnings = Vec::new();
+  let docker_network = match docker_api
+    .inspect_network(
+      &network.docker_network_id,
+      None::<InspectNetworkOptions<String>>,
+    )
+    .await
+  {
+    Err(DockerError::DockerResponseServerError {
+      status_code: 404, ..
+    }) => {
+      warnings.push(format!(
+        "docker network {} no longer exists",
+        &network.docker_network_id
+      ));
+      return Ok(ClusterNetworkInspect {
+        network,
+        subnets: Vec::new(),
+        containers: Vec::new(),
+        free_addresses: 0,
+        warnings,
+      });
+    }
+    Err(err) => return Err(err.into()),
+    Ok(docker_network) => docker_network,
+  };
+
+  if docker_network.internal.unwrap_or(false) != network.internal {
+    warnings.push(format!(
+      "docker network internal flag is {} but {} is expected",
+      !network.internal, network.internal
+    ));
+  }
+
+  let ipam_configs = docker_network
+    .ipam
+    .and_then(|ipam| ipam.config)
+    .unwrap_or_default();
+  let subnets = ipam_configs
+    .iter()
+    .filter_map(|config| config.subnet.to_owned())
+    .collect::<Vec<String>>();
+  if let Some(subnet) = &network.subnet {
+    if !subnets.contains(subnet) {
+      warnings.push(format!(
+        "subnet {} is expected but docker network use {}",
+        subnet,
+        subnets.join(", ")
+      ));
+    }
+  }
+
+  let cargoes =
+    list_container_cargoes(&network.docker_network_id, docker_api).await?;
+  let containers = docker_network
+    .containers
+    .unwrap_or_default()
+    .into_iter()
+    .map(|(id, container)| ClusterNetworkContainer {
+      cargo_key: cargoes.get(&id).cloned(),
+      name: container.name.unwrap_or_default(),
+      ipv4_address: strip_prefix_len(container.ipv4_address),
+      ipv6_address: strip_prefix_len(container.ipv6_address),
+      id,
+    })
+    .collect::<Vec<ClusterNetworkContainer>>();
+
+  // Ensure fixed addresses are the one used by the containers
+  let cargo_networks =
+    repositories::cluster_cargo_network::list_by_network_key(
+      network.key.to_owned(),
+      pool,
+    )
+    .await?;
+  let cluster_prefix = network.cluster_key.to_owned() + "-";
+  for cargo_network in cargo_networks {
+    let cargo_key = cargo_network
+      .cluster_cargo_key
+      .trim_start_matches(&cluster_prefix);
+    let expected = [cargo_network.ipv4_address, cargo_network.ipv6_address];
+    for container in containers
+      .iter()
+      .filter(|c| c.cargo_key.as_deref() == Some(cargo_key))
+    {
+      let current = [&container.ipv4_address, &container.ipv6_address];
+      for (expected, current) in expected.iter().zip(current) {
+        if expected.is_some() && expected != current {
+          warnings.push(format!(
+            "container {} use address {} but {} is expected",
+            container.name,
+            current.to_owned().unwrap_or_default(),
+            expected.to_owned().unwrap_or_default(),
+          ));
+        }
+      }
+    }
+  }
+
+  let used = containers
+    .iter()
+    .flat_map(|c| [&c.ipv4_address, &c.ipv6_address])
+    .filter_map(|address| address.as_ref()?.parse::<IpAddr>().ok())
+    .collect::<Vec<IpAddr>>();
+  let free_addresses = ipam_configs
+    .iter()
+    .filter_map(|config| {
+      let subnet = config.subnet.as_ref()?.parse::<Cidr>().ok()?;
+      if !subnet.addr.is_ipv4() {
+        return None;
+      }
+      let ip_range = config
+        .ip_range
+        .as_ref()
+        .and_then(|ip_range| ip_range.parse::<Cidr>().ok());
+      let gateway = config
+        .gateway
+        .as_ref()
+        .and_then(|gateway| gateway.parse::<IpAddr>().ok());
+      Some(ipam::count_free_addresses(
+        &subnet,
+        ip_range.as_ref(),
+        gateway.as_ref(),
+        &used,
+      ))
+    })
+    .sum::<u128>();
+
+  Ok(ClusterNetworkInspect {
+    network,
+    subnets,
+    containers,
+    free_addresses: u64::try_from(free_addresses).unwrap_or(u64::MAX),
+    warnings,
+  })
+}
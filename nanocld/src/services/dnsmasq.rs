@@ -91,6 +91,29 @@ pub fn add_dns_entry(
   Ok(())
 }
 
+/// # Remove a dns entry from dnsmasq
+///
+/// # Arguments
+/// - [domain_name](str) domain name of the entry to remove
+/// - [state_dir](str) nanocld state directory
+pub fn remove_dns_entry(
+  domain_name: &str,
+  state_dir: &str,
+) -> Result<(), DnsmasqError> {
+  let file_path = Path::new(state_dir).join("dnsmasq/dnsmasq.d/dns_entry.conf");
+  let content = fs::read_to_string(&file_path)?;
+  let reg_expr =
+    r"address=/\.".to_owned() + &regex::escape(domain_name) + "/.*\n?";
+
+  let reg = Regex::new(&reg_expr)?;
+  if reg.is_match(&content) {
+    let new_content = reg.replace_all(&content, "").to_string();
+    write_dns_entry_conf(&file_path, &new_content)?;
+  }
+
+  Ok(())
+}
+
 pub async fn restart(docker_api: &Docker) -> Result<(), DnsmasqError> {
   docker_api
     .restart_container("nanocl-dns-dnsmasq", None)
@@ -220,4 +243,23 @@ mod tests {
     write_dns_entry_conf(&file_path, &saved_content)?;
     Ok(())
   }
+
+  #[ntex::test]
+  async fn test_remove_dns_entry() -> TestReturn {
+    const STATE_DIR: &str = "/var/lib/nanocl";
+    let file_path =
+      Path::new(STATE_DIR).join("dnsmasq/dnsmasq.d/dns_entry.conf");
+    let saved_content = fs::read_to_string(&file_path)?;
+    write_dns_entry_conf(&file_path, "")?;
+    add_dns_entry("test.com", "141.0.0.1", STATE_DIR)?;
+    add_dns_entry("test2.com", "122.0.0.1", STATE_DIR)?;
+    remove_dns_entry("test.com", STATE_DIR)?;
+    let content = fs::read_to_string(&file_path)?;
+    assert_eq!(content, "address=/.test2.com/122.0.0.1\n");
+    remove_dns_entry("test.com", STATE_DIR)?;
+    let content = fs::read_to_string(&file_path)?;
+    assert_eq!(content, "address=/.test2.com/122.0.0.1\n");
+    write_dns_entry_conf(&file_path, &saved_content)?;
+    Ok(())
+  }
 }
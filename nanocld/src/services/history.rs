@@ -0,0 +1,77 @@
+//! Record actions made on clusters so their history can be inspected
+use ntex::web;
+use uuid::Uuid;
+use chrono::Utc;
+
+use crate::repositories;
+use crate::errors::HttpResponseError;
+use crate::models::{Pool, EventItem, EventKind, EventOutcome};
+
+/// Actor of the actions made by the daemon itself
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// Create an event from the error of an action if there is one
+pub fn gen_event(
+  cluster_key: &str,
+  kind: EventKind,
+  actor: &str,
+  target: Option<String>,
+  error: Option<String>,
+) -> EventItem {
+  let outcome = match error {
+    None => EventOutcome::Success,
+    Some(_) => EventOutcome::Failure,
+  };
+  EventItem {
+    key: Uuid::new_v4(),
+    cluster_key: cluster_key.to_owned(),
+    kind,
+    actor: actor.to_owned(),
+    target,
+    outcome,
+    error,
+    created_at: Utc::now(),
+  }
+}
+
+/// Record the result of an action in the history of a cluster
+/// Failing to record is only logged to never fail the action itself
+pub async fn record<T>(
+  cluster_key: &str,
+  kind: EventKind,
+  actor: &str,
+  target: Option<String>,
+  result: &Result<T, HttpResponseError>,
+  pool: &web::types::State<Pool>,
+) {
+  let error = result.as_ref().err().map(|err| err.msg.to_owned());
+  let event = gen_event(cluster_key, kind, actor, target, error);
+  if let Err(err) = repositories::event::create(event, pool).await {
+    log::warn!(
+      "unable to record event for cluster {}: {}",
+      cluster_key,
+      err
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_gen_event() {
+    let event = gen_event("global-dev", EventKind::Start, "api", None, None);
+    assert_eq!(event.outcome, EventOutcome::Success);
+    let event = gen_event(
+      "global-dev",
+      EventKind::Join,
+      "api",
+      Some(String::from("global-api")),
+      Some(String::from("network not found")),
+    );
+    assert_eq!(event.outcome, EventOutcome::Failure);
+    assert_eq!(event.error.as_deref(), Some("network not found"));
+  }
+}
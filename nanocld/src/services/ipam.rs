@@ -0,0 +1,427 @@
+use std::fs;
+use std::fmt;
+use std::str::FromStr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use thiserror::Error;
+use ntex::http::StatusCode;
+use bollard::{Docker, errors::Error as DockerError, network::ListNetworksOptions};
+
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+use crate::services::errors::docker_error_ref;
+
+/// Subnets that must never be allocated to a cluster network
+/// 155.0.0.0/24 is used by the ipsec vpn network nanoclvpn0
+const RESERVED_SUBNETS: [&str; 1] = ["155.0.0.0/24"];
+
+/// Prefix length of automatically allocated subnets
+const DEFAULT_PREFIX: u8 = 24;
+
+#[derive(Debug, Error)]
+pub enum IpamError {
+  #[error("invalid cidr {0}")]
+  InvalidCidr(String),
+  #[error("invalid ip address {0}")]
+  InvalidAddress(String),
+  #[error("{0} is not in subnet {1}")]
+  OutOfSubnet(String, String),
+  #[error("subnet {0} overlap with {1}")]
+  Overlap(String, String),
+  #[error("subnet {0} is not an {1} subnet")]
+  WrongFamily(String, String),
+  #[error("no free subnet available in network pools")]
+  PoolExhausted,
+  #[error("ipam docker_api error")]
+  Docker(#[from] DockerError),
+}
+
+impl IntoHttpResponseError for IpamError {
+  fn to_http_error(&self) -> HttpResponseError {
+    match self {
+      IpamError::Docker(err) => docker_error_ref(err),
+      IpamError::PoolExhausted => HttpResponseError {
+        msg: format!("{}", self),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+      _ => HttpResponseError {
+        msg: format!("{}", self),
+        status: StatusCode::BAD_REQUEST,
+      },
+    }
+  }
+}
+
+/// An ipv4 or ipv6 network in cidr notation eg: 10.0.0.0/24
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cidr {
+  pub(crate) addr: IpAddr,
+  pub(crate) prefix: u8,
+}
+
+fn ip_to_u128(addr: &IpAddr) -> u128 {
+  match addr {
+    IpAddr::V4(addr) => u32::from(*addr) as u128,
+    IpAddr::V6(addr) => u128::from(*addr),
+  }
+}
+
+fn u128_to_ip(value: u128, is_v4: bool) -> IpAddr {
+  if is_v4 {
+    IpAddr::V4(Ipv4Addr::from(value as u32))
+  } else {
+    IpAddr::V6(Ipv6Addr::from(value))
+  }
+}
+
+impl Cidr {
+  fn bits(&self) -> u8 {
+    if self.addr.is_ipv4() {
+      32
+    } else {
+      128
+    }
+  }
+
+  fn mask(&self) -> u128 {
+    let host_bits = (self.bits() - self.prefix) as u32;
+    let all = if self.addr.is_ipv4() {
+      u32::MAX as u128
+    } else {
+      u128::MAX
+    };
+    all.checked_shl(host_bits).unwrap_or(0) & all
+  }
+
+  fn first(&self) -> u128 {
+    ip_to_u128(&self.addr) & self.mask()
+  }
+
+  fn last(&self) -> u128 {
+    self.first() | !self.mask() & self.last_of_family()
+  }
+
+  fn last_of_family(&self) -> u128 {
+    if self.addr.is_ipv4() {
+      u32::MAX as u128
+    } else {
+      u128::MAX
+    }
+  }
+
+  /// Number of addresses in the network
+  pub fn size(&self) -> u128 {
+    (self.last() - self.first()).saturating_add(1)
+  }
+
+  /// Check if given address is part of the network
+  pub fn contains(&self, addr: &IpAddr) -> bool {
+    if addr.is_ipv4() != self.addr.is_ipv4() {
+      return false;
+    }
+    let value = ip_to_u128(addr);
+    value >= self.first() && value <= self.last()
+  }
+
+  /// Check if both networks share at least one address
+  pub fn overlaps(&self, other: &Cidr) -> bool {
+    if self.addr.is_ipv4() != other.addr.is_ipv4() {
+      return false;
+    }
+    self.first() <= other.last() && other.first() <= self.last()
+  }
+
+  /// Check if given network is entirely part of this one
+  pub fn includes(&self, other: &Cidr) -> bool {
+    self.contains(&other.addr)
+      && other.prefix >= self.prefix
+      && other.last() <= self.last()
+  }
+
+  /// Iterate over the subnets of given prefix length inside this network
+  fn subnets(&self, prefix: u8) -> impl Iterator<Item = Cidr> {
+    let is_v4 = self.addr.is_ipv4();
+    let prefix = prefix.max(self.prefix).min(self.bits());
+    let step = 1u128
+      .checked_shl((self.bits() - prefix) as u32)
+      .unwrap_or(u128::MAX);
+    let first = self.first();
+    let last = self.last();
+    (0u128..)
+      .map(move |index| first.checked_add(index.checked_mul(step)?))
+      .take_while(move |value| matches!(value, Some(value) if *value <= last))
+      .flatten()
+      .map(move |value| Cidr {
+        addr: u128_to_ip(value, is_v4),
+        prefix,
+      })
+  }
+}
+
+impl FromStr for Cidr {
+  type Err = IpamError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (addr, prefix) = s
+      .split_once('/')
+      .ok_or_else(|| IpamError::InvalidCidr(s.to_owned()))?;
+    let addr = addr
+      .parse::<IpAddr>()
+      .map_err(|_| IpamError::InvalidCidr(s.to_owned()))?;
+    let prefix = prefix
+      .parse::<u8>()
+      .map_err(|_| IpamError::InvalidCidr(s.to_owned()))?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    if prefix > max {
+      return Err(IpamError::InvalidCidr(s.to_owned()));
+    }
+    let cidr = Cidr { addr, prefix };
+    Ok(Cidr {
+      addr: u128_to_ip(cidr.first(), addr.is_ipv4()),
+      prefix,
+    })
+  }
+}
+
+impl fmt::Display for Cidr {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}/{}", self.addr, self.prefix)
+  }
+}
+
+/// Parse an ip address and return a ipam error if it's not valid
+pub fn parse_address(addr: &str) -> Result<IpAddr, IpamError> {
+  addr
+    .parse::<IpAddr>()
+    .map_err(|_| IpamError::InvalidAddress(addr.to_owned()))
+}
+
+/// Ensure given address is part of given subnet
+pub fn ensure_in_subnet(addr: &str, subnet: &str) -> Result<IpAddr, IpamError> {
+  let ip = parse_address(addr)?;
+  let cidr = subnet.parse::<Cidr>()?;
+  if !cidr.contains(&ip) {
+    return Err(IpamError::OutOfSubnet(addr.to_owned(), subnet.to_owned()));
+  }
+  Ok(ip)
+}
+
+/// Count addresses of a subnet that can still be given to a container
+/// The network, broadcast and gateway addresses are never given
+pub fn count_free_addresses(
+  subnet: &Cidr,
+  ip_range: Option<&Cidr>,
+  gateway: Option<&IpAddr>,
+  used: &[IpAddr],
+) -> u128 {
+  let pool = ip_range.unwrap_or(subnet);
+  let is_v4 = subnet.addr.is_ipv4();
+  let mut reserved = vec![u128_to_ip(subnet.first(), is_v4)];
+  if is_v4 && subnet.prefix < 31 {
+    reserved.push(u128_to_ip(subnet.last(), is_v4));
+  }
+  if let Some(gateway) = gateway {
+    reserved.push(*gateway);
+  }
+  reserved.extend(used.iter().copied());
+  reserved.sort();
+  reserved.dedup();
+  let taken = reserved.iter().filter(|ip| pool.contains(ip)).count() as u128;
+  pool.size().saturating_sub(taken)
+}
+
+/// Read ipv4 routes of the host from /proc/net/route
+/// the default route is ignored
+fn host_routes() -> Vec<Cidr> {
+  let content = match fs::read_to_string("/proc/net/route") {
+    Err(_) => return Vec::new(),
+    Ok(content) => content,
+  };
+  content
+    .lines()
+    .skip(1)
+    .filter_map(|line| {
+      let fields = line.split_whitespace().collect::<Vec<&str>>();
+      let dest = u32::from_str_radix(fields.get(1)?, 16).ok()?;
+      let mask = u32::from_str_radix(fields.get(7)?, 16).ok()?;
+      // Values are printed as raw network byte order integers
+      let dest = Ipv4Addr::from(u32::from_be(dest));
+      let prefix = mask.count_ones() as u8;
+      if prefix == 0 {
+        return None;
+      }
+      Some(Cidr {
+        addr: IpAddr::V4(dest),
+        prefix,
+      })
+    })
+    .collect()
+}
+
+/// List subnets already used by docker networks
+async fn docker_subnets(docker_api: &Docker) -> Result<Vec<Cidr>, IpamError> {
+  let networks = docker_api
+    .list_networks(None::<ListNetworksOptions<String>>)
+    .await?;
+  let subnets = networks
+    .into_iter()
+    .filter_map(|network| network.ipam?.config)
+    .flatten()
+    .filter_map(|config| config.subnet?.parse::<Cidr>().ok())
+    .collect::<Vec<Cidr>>();
+  Ok(subnets)
+}
+
+/// Subnets a cluster network must never overlap with
+async fn used_subnets(docker_api: &Docker) -> Result<Vec<Cidr>, IpamError> {
+  let mut used = RESERVED_SUBNETS
+    .iter()
+    .filter_map(|subnet| subnet.parse::<Cidr>().ok())
+    .collect::<Vec<Cidr>>();
+  used.extend(host_routes());
+  used.extend(docker_subnets(docker_api).await?);
+  Ok(used)
+}
+
+fn find_free_subnet(pools: &[Cidr], used: &[Cidr]) -> Option<Cidr> {
+  pools.iter().find_map(|pool| {
+    pool
+      .subnets(DEFAULT_PREFIX)
+      .find(|subnet| !used.iter().any(|used| used.overlaps(subnet)))
+  })
+}
+
+/// Allocate a free subnet from the daemon network pools
+/// that doesn't overlap with host routes, docker networks or reserved subnets
+pub async fn allocate_subnet(
+  network_pools: &[String],
+  docker_api: &Docker,
+) -> Result<Cidr, IpamError> {
+  let pools = network_pools
+    .iter()
+    .map(|pool| pool.parse::<Cidr>())
+    .collect::<Result<Vec<Cidr>, IpamError>>()?;
+  let used = used_subnets(docker_api).await?;
+  find_free_subnet(&pools, &used).ok_or(IpamError::PoolExhausted)
+}
+
+/// Ensure the optional ip range and gateway of a network are in his subnet
+pub fn validate_range(
+  subnet: &Cidr,
+  ip_range: Option<&str>,
+  gateway: Option<&str>,
+) -> Result<(), IpamError> {
+  if let Some(ip_range) = ip_range {
+    let range = ip_range.parse::<Cidr>()?;
+    if !subnet.includes(&range) {
+      return Err(IpamError::OutOfSubnet(
+        ip_range.to_owned(),
+        subnet.to_string(),
+      ));
+    }
+  }
+  if let Some(gateway) = gateway {
+    ensure_in_subnet(gateway, &subnet.to_string())?;
+  }
+  Ok(())
+}
+
+/// Ensure a subnet is an ipv4 or an ipv6 one
+pub fn ensure_family(subnet: &Cidr, ipv6: bool) -> Result<(), IpamError> {
+  if subnet.addr.is_ipv6() != ipv6 {
+    let family = if ipv6 { "ipv6" } else { "ipv4" };
+    return Err(IpamError::WrongFamily(
+      subnet.to_string(),
+      family.to_owned(),
+    ));
+  }
+  Ok(())
+}
+
+/// Validate a user defined subnet with it's optional ip range and gateway
+pub async fn validate_subnet(
+  subnet: &str,
+  ip_range: Option<&str>,
+  gateway: Option<&str>,
+  docker_api: &Docker,
+) -> Result<Cidr, IpamError> {
+  let cidr = subnet.parse::<Cidr>()?;
+  validate_range(&cidr, ip_range, gateway)?;
+  let used = used_subnets(docker_api).await?;
+  if let Some(used) = used.iter().find(|used| used.overlaps(&cidr)) {
+    return Err(IpamError::Overlap(subnet.to_owned(), used.to_string()));
+  }
+  Ok(cidr)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_cidr() {
+    let cidr = "10.0.1.12/24".parse::<Cidr>().unwrap();
+    assert_eq!(cidr.to_string(), "10.0.1.0/24");
+    assert_eq!(cidr.size(), 256);
+    assert!(cidr.contains(&"10.0.1.254".parse().unwrap()));
+    assert!(!cidr.contains(&"10.0.2.1".parse().unwrap()));
+    assert!(cidr.overlaps(&"10.0.0.0/16".parse().unwrap()));
+    assert!(!cidr.overlaps(&"155.0.0.0/24".parse().unwrap()));
+    assert!(cidr.includes(&"10.0.1.128/25".parse().unwrap()));
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    let cidr = "fd00::/64".parse::<Cidr>().unwrap();
+    assert!(cidr.contains(&"fd00::42".parse().unwrap()));
+    assert!(!cidr.contains(&"10.0.1.1".parse().unwrap()));
+  }
+
+  #[test]
+  fn test_count_free_addresses() {
+    let subnet = "10.0.1.0/24".parse::<Cidr>().unwrap();
+    let gateway = "10.0.1.1".parse::<IpAddr>().unwrap();
+    let used = vec![
+      "10.0.1.2".parse::<IpAddr>().unwrap(),
+      "10.0.1.1".parse::<IpAddr>().unwrap(),
+    ];
+    let free = count_free_addresses(&subnet, None, Some(&gateway), &used);
+    assert_eq!(free, 252);
+    let ip_range = "10.0.1.128/25".parse::<Cidr>().unwrap();
+    let free =
+      count_free_addresses(&subnet, Some(&ip_range), Some(&gateway), &used);
+    assert_eq!(free, 127);
+  }
+
+  #[test]
+  fn test_find_free_subnet() {
+    let pools = vec!["155.0.0.0/23".parse::<Cidr>().unwrap()];
+    let used = vec!["155.0.0.0/24".parse::<Cidr>().unwrap()];
+    let subnet = find_free_subnet(&pools, &used).unwrap();
+    assert_eq!(subnet.to_string(), "155.0.1.0/24");
+    let used = vec!["155.0.0.0/16".parse::<Cidr>().unwrap()];
+    assert!(find_free_subnet(&pools, &used).is_none());
+  }
+
+  #[test]
+  fn test_validate_range() {
+    let subnet = "172.28.3.0/24".parse::<Cidr>().unwrap();
+    assert!(validate_range(&subnet, None, None).is_ok());
+    assert!(validate_range(
+      &subnet,
+      Some("172.28.3.128/25"),
+      Some("172.28.3.1")
+    )
+    .is_ok());
+    assert!(validate_range(&subnet, Some("10.0.0.0/25"), None).is_err());
+    assert!(validate_range(&subnet, None, Some("10.0.0.1")).is_err());
+    assert!(validate_range(&subnet, None, Some("fd00::1")).is_err());
+  }
+
+  #[test]
+  fn test_ensure_family() {
+    let subnet = "172.28.3.0/24".parse::<Cidr>().unwrap();
+    assert!(ensure_family(&subnet, false).is_ok());
+    assert!(ensure_family(&subnet, true).is_err());
+    let subnet = "fd00:28::/64".parse::<Cidr>().unwrap();
+    assert!(ensure_family(&subnet, true).is_ok());
+    assert!(ensure_family(&subnet, false).is_err());
+  }
+}
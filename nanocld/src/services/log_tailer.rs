@@ -0,0 +1,233 @@
+//! Follow a log file written by another process
+//! Complete lines are read from the last offset, the file is identified
+//! by his inode to detect rotations and truncations are detected
+//! when the file become smaller than the offset.
+//! The position is persisted to resume after a restart.
+use std::fs::{self, File};
+use std::io::{Error as IoError, ErrorKind, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use serde::{Serialize, Deserialize};
+
+/// Maximum number of bytes read at once
+const READ_CHUNK: u64 = 1024 * 1024;
+
+/// Position of the tailer after the last complete line read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogTailerPosition {
+  inode: u64,
+  offset: u64,
+}
+
+pub struct LogTailer {
+  path: PathBuf,
+  position_path: PathBuf,
+  /// Persisted position used when the file is opened the first time
+  resume: Option<LogTailerPosition>,
+  file: Option<File>,
+  inode: u64,
+  /// Offset of the end of the data read
+  offset: u64,
+  /// Data read after the last complete line
+  pending: Vec<u8>,
+  saved: Option<LogTailerPosition>,
+}
+
+impl LogTailer {
+  /// Create a tailer resuming from the position stored in `position_path`
+  /// without position only lines written from now are read
+  pub fn new(path: PathBuf, position_path: PathBuf) -> Self {
+    let resume = fs::read(&position_path)
+      .ok()
+      .and_then(|content| serde_json::from_slice(&content).ok());
+    LogTailer {
+      path,
+      position_path,
+      resume,
+      file: None,
+      inode: 0,
+      offset: 0,
+      pending: Vec::new(),
+      saved: None,
+    }
+  }
+
+  fn open(&mut self, inode: u64, offset: u64) -> Result<(), IoError> {
+    let mut file = File::open(&self.path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    self.file = Some(file);
+    self.inode = inode;
+    self.offset = offset;
+    self.pending.clear();
+    Ok(())
+  }
+
+  /// Read at most `READ_CHUNK` bytes of the opened file
+  /// and move complete lines in `lines`
+  /// returns true when the end of the file is reached
+  fn read_available(
+    &mut self,
+    lines: &mut Vec<String>,
+  ) -> Result<bool, IoError> {
+    let file = match self.file.as_mut() {
+      None => return Ok(true),
+      Some(file) => file,
+    };
+    let read = file.take(READ_CHUNK).read_to_end(&mut self.pending)?;
+    self.offset += read as u64;
+    while let Some(end) = self.pending.iter().position(|byte| *byte == b'\n') {
+      let line = self.pending.drain(..=end).collect::<Vec<_>>();
+      let line = String::from_utf8_lossy(&line[..end]);
+      let line = line.trim_end_matches('\r');
+      if !line.is_empty() {
+        lines.push(line.to_owned());
+      }
+    }
+    Ok((read as u64) < READ_CHUNK)
+  }
+
+  /// Complete lines written since the last call
+  /// the file is read by chunks, `lag` tells when more lines are available
+  pub fn read_lines(&mut self) -> Result<Vec<String>, IoError> {
+    let mut lines = Vec::new();
+    let metadata = match fs::metadata(&self.path) {
+      Err(err) if err.kind() == ErrorKind::NotFound => None,
+      Err(err) => return Err(err),
+      Ok(metadata) => Some(metadata),
+    };
+    match metadata {
+      // Rotated and not created again yet
+      // lines can still be written in the rotated file
+      None => {
+        self.read_available(&mut lines)?;
+      }
+      Some(metadata) if self.file.is_none() => {
+        let offset = match self.resume.take() {
+          None => metadata.len(),
+          Some(position)
+            if position.inode == metadata.ino()
+              && position.offset <= metadata.len() =>
+          {
+            position.offset
+          }
+          Some(_) => 0,
+        };
+        self.open(metadata.ino(), offset)?;
+        self.read_available(&mut lines)?;
+      }
+      Some(metadata) if metadata.ino() != self.inode => {
+        // End of the rotated file before reading the new one
+        if self.read_available(&mut lines)? {
+          self.open(metadata.ino(), 0)?;
+          self.read_available(&mut lines)?;
+        }
+      }
+      Some(metadata) => {
+        if metadata.len() < self.offset {
+          self.open(self.inode, 0)?;
+        }
+        self.read_available(&mut lines)?;
+      }
+    }
+    Ok(lines)
+  }
+
+  /// Bytes of the file not read yet
+  pub fn lag(&self) -> u64 {
+    match fs::metadata(&self.path) {
+      Err(_) => 0,
+      Ok(metadata) if self.file.is_some() && metadata.ino() == self.inode => {
+        metadata.len().saturating_sub(self.offset)
+      }
+      Ok(metadata) => metadata.len(),
+    }
+  }
+
+  /// Position after the last complete line read
+  pub fn position(&self) -> Option<LogTailerPosition> {
+    self.file.as_ref()?;
+    Some(LogTailerPosition {
+      inode: self.inode,
+      offset: self.offset - self.pending.len() as u64,
+    })
+  }
+
+  /// Persist the position to resume from it after a restart
+  /// nothing is written when it didn't change since the last save
+  pub fn save_position(&mut self) -> Result<(), IoError> {
+    let position = match self.position() {
+      None => return Ok(()),
+      Some(position) => position,
+    };
+    if self.saved.as_ref() == Some(&position) {
+      return Ok(());
+    }
+    let content = serde_json::to_vec(&position)?;
+    let tmp_path = self.position_path.with_extension("tmp");
+    if let Some(dir_path) = self.position_path.parent() {
+      fs::create_dir_all(dir_path)?;
+    }
+    fs::write(&tmp_path, content)?;
+    fs::rename(tmp_path, &self.position_path)?;
+    self.saved = Some(position);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::Write;
+  use std::fs::OpenOptions;
+  use std::path::Path;
+  use uuid::Uuid;
+
+  use crate::utils::test::*;
+
+  fn append(path: &Path, content: &str) -> Result<(), IoError> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(content.as_bytes())
+  }
+
+  #[test]
+  fn test_read_lines() -> TestReturn {
+    let dir_path = std::env::temp_dir()
+      .join(format!("nanocl-tailer-test-{}", Uuid::new_v4()));
+    fs::create_dir_all(&dir_path)?;
+    let path = dir_path.join("access.log");
+    let position_path = dir_path.join("access.log.position");
+    append(&path, "before start\n")?;
+
+    let mut tailer = LogTailer::new(path.to_owned(), position_path.to_owned());
+    assert!(tailer.read_lines()?.is_empty());
+
+    append(&path, "first\nsecond\nthi")?;
+    assert_eq!(tailer.read_lines()?, vec!["first", "second"]);
+    append(&path, "rd\n")?;
+    assert_eq!(tailer.read_lines()?, vec!["third"]);
+
+    // Rotation with lines written in the old file before the new one
+    fs::rename(&path, dir_path.join("access.log.1"))?;
+    append(&dir_path.join("access.log.1"), "fourth\n")?;
+    assert_eq!(tailer.read_lines()?, vec!["fourth"]);
+    append(&path, "fifth\n")?;
+    assert_eq!(tailer.read_lines()?, vec!["fifth"]);
+
+    // Truncation
+    fs::write(&path, "")?;
+    append(&path, "six\n")?;
+    assert_eq!(tailer.read_lines()?, vec!["six"]);
+
+    // Resume after a restart without the incomplete line
+    append(&path, "seventh\neig")?;
+    assert_eq!(tailer.read_lines()?, vec!["seventh"]);
+    tailer.save_position()?;
+    append(&path, "hth\n")?;
+    let mut tailer = LogTailer::new(path.to_owned(), position_path.to_owned());
+    assert_eq!(tailer.read_lines()?, vec!["eighth"]);
+
+    fs::remove_dir_all(dir_path)?;
+    Ok(())
+  }
+}
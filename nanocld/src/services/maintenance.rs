@@ -0,0 +1,460 @@
+//! Maintenance mode and custom error pages of a cluster
+//! Pages are written in `state_dir/nginx/ssl/pages/{cluster_key}` mounted in
+//! the nginx container and served by named locations of the cluster servers.
+//! During maintenance every request not coming from an allowed address
+//! is answered with the maintenance page and a 503 status.
+//! Route servers include the pages, http templates must render them
+//! in their servers with `{{{error_pages}}}`.
+use ntex::web;
+use ntex::http::StatusCode;
+use thiserror::Error;
+use std::fs;
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+use crate::models::{
+  Pool, ClusterItem, ClusterMaintenanceItem, ClusterMaintenancePartial,
+  NginxTemplateModes,
+};
+
+use super::access_policy::normalize_address;
+
+/// Path of the pages directory inside the nginx container
+const NGINX_PAGES_DIR: &str = "/etc/nginx/ssl/pages";
+const DEFAULT_MAINTENANCE_BODY: &str = "<!DOCTYPE html>
+<html>
+<head><title>Maintenance</title></head>
+<body>
+<h1>Maintenance</h1>
+<p>This service is under maintenance, please try again later.</p>
+</body>
+</html>
+";
+
+#[derive(Debug, Error)]
+pub enum MaintenanceError {
+  #[error("maintenance io error")]
+  Io(#[from] IoError),
+  #[error("invalid maintenance")]
+  Invalid(String),
+}
+
+impl IntoHttpResponseError for MaintenanceError {
+  fn to_http_error(&self) -> HttpResponseError {
+    match self {
+      MaintenanceError::Io(err) => HttpResponseError {
+        msg: format!("maintenance io error {}", err),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+      MaintenanceError::Invalid(msg) => HttpResponseError {
+        msg: format!("invalid maintenance {}", msg),
+        status: StatusCode::BAD_REQUEST,
+      },
+    }
+  }
+}
+
+/// Name of the config file declaring the maintenance variable of a cluster
+pub fn config_file_name(cluster_key: &str) -> String {
+  format!("{}.nanocl-maintenance", cluster_key)
+}
+
+/// Path of the config file declaring the maintenance variable of a cluster
+pub fn config_file_path(cluster_key: &str, state_dir: &str) -> PathBuf {
+  Path::new(state_dir)
+    .join("nginx/sites-enabled")
+    .join(format!("{}.conf", config_file_name(cluster_key)))
+}
+
+/// Directory of the error pages of a cluster
+pub fn pages_dir(cluster_key: &str, state_dir: &str) -> PathBuf {
+  Path::new(state_dir)
+    .join("nginx/ssl/pages")
+    .join(cluster_key)
+}
+
+fn variable_name(cluster_key: &str) -> String {
+  format!("nanocl_maintenance_{}", cluster_key.replace('-', "_"))
+}
+
+/// Status, named location and body of the pages served for a cluster
+fn pages(item: &ClusterMaintenanceItem) -> Vec<(u16, &str, &str)> {
+  let mut pages = Vec::new();
+  if item.enabled {
+    let body = item.body.as_deref().unwrap_or(DEFAULT_MAINTENANCE_BODY);
+    pages.push((503, "nanocl_maintenance", body));
+  }
+  if let Some(body) = &item.bad_gateway_body {
+    pages.push((502, "nanocl_bad_gateway", body.as_str()));
+  }
+  if let Some(body) = &item.gateway_timeout_body {
+    pages.push((504, "nanocl_gateway_timeout", body.as_str()));
+  }
+  pages
+}
+
+/// Apply a partial on the current maintenance of a cluster
+/// unset fields keep their value and empty bodies are removed
+pub fn merge(
+  cluster_key: &str,
+  current: Option<ClusterMaintenanceItem>,
+  item: ClusterMaintenancePartial,
+) -> Result<ClusterMaintenanceItem, MaintenanceError> {
+  let current = current.unwrap_or_else(|| ClusterMaintenanceItem {
+    cluster_key: cluster_key.to_owned(),
+    enabled: false,
+    body: None,
+    allow: Vec::new(),
+    bad_gateway_body: None,
+    gateway_timeout_body: None,
+  });
+  let merge_body = |body: Option<String>, current: Option<String>| match body {
+    None => current,
+    Some(body) if body.trim().is_empty() => None,
+    Some(body) => Some(body),
+  };
+  let allow = match item.allow {
+    None => current.allow,
+    Some(allow) => allow
+      .iter()
+      .map(|address| {
+        normalize_address(address).map_err(|_| {
+          MaintenanceError::Invalid(format!("address {}", address))
+        })
+      })
+      .collect::<Result<Vec<_>, _>>()?,
+  };
+  Ok(ClusterMaintenanceItem {
+    cluster_key: current.cluster_key,
+    enabled: item.enabled,
+    body: merge_body(item.body, current.body),
+    allow,
+    bad_gateway_body: merge_body(
+      item.bad_gateway_body,
+      current.bad_gateway_body,
+    ),
+    gateway_timeout_body: merge_body(
+      item.gateway_timeout_body,
+      current.gateway_timeout_body,
+    ),
+  })
+}
+
+/// Write the pages served for a cluster and remove the unused ones
+pub fn write_pages(
+  item: &ClusterMaintenanceItem,
+  state_dir: &str,
+) -> Result<(), MaintenanceError> {
+  let dir = pages_dir(&item.cluster_key, state_dir);
+  fs::create_dir_all(&dir)?;
+  let pages = pages(item);
+  for status in [502, 503, 504] {
+    let path = dir.join(format!("{}.html", status));
+    match pages
+      .iter()
+      .find(|(page_status, _, _)| *page_status == status)
+    {
+      Some((_, _, body)) => fs::write(path, body)?,
+      None => match fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+          return Err(err.into())
+        }
+        _ => {}
+      },
+    }
+  }
+  Ok(())
+}
+
+/// Remove the pages of a cluster if there are some
+pub fn remove_pages(
+  cluster_key: &str,
+  state_dir: &str,
+) -> Result<(), MaintenanceError> {
+  match fs::remove_dir_all(pages_dir(cluster_key, state_dir)) {
+    Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+    _ => Ok(()),
+  }
+}
+
+/// Http directives telling if a request must get the maintenance page
+/// allowed addresses and acme challenges still reach the cargoes
+pub fn gen_variables(item: &ClusterMaintenanceItem) -> Option<String> {
+  if !item.enabled {
+    return None;
+  }
+  let variable = variable_name(&item.cluster_key);
+  let mut output = format!(
+    "# generated by nanocl from the maintenance of cluster {}\n",
+    item.cluster_key
+  );
+  output += &format!("geo ${}_client {{\n  default 1;\n", variable);
+  for address in &item.allow {
+    output += &format!("  {} 0;\n", address);
+  }
+  output += "}\n\n";
+  output += &format!(
+    "map ${}_client$uri ${} {{\n  default 0;\n  \
+     ~^1/\\.well-known/acme-challenge/ 0;\n  ~^1 1;\n}}\n",
+    variable, variable
+  );
+  Some(output)
+}
+
+/// Server directives serving the pages of a cluster
+pub fn gen_directives(item: &ClusterMaintenanceItem) -> Option<String> {
+  let pages = pages(item);
+  if pages.is_empty() {
+    return None;
+  }
+  let mut output = String::new();
+  for (status, location, _) in &pages {
+    output += &format!("  error_page {} @{};\n", status, location);
+  }
+  if item.enabled {
+    output += &format!(
+      "  if (${}) {{\n    return 503;\n  }}\n",
+      variable_name(&item.cluster_key)
+    );
+  }
+  for (status, location, _) in &pages {
+    output += &format!(
+      "  location @{} {{\n    root {}/{};\n    try_files /{}.html ={};\n  }}\n",
+      location, NGINX_PAGES_DIR, item.cluster_key, status, status
+    );
+  }
+  Some(output)
+}
+
+/// Whether a template renders the error pages in its servers
+pub fn includes_error_pages(content: &str) -> bool {
+  let content = content
+    .chars()
+    .filter(|c| !c.is_whitespace())
+    .collect::<String>();
+  ["{{{error_pages}}}", "{{&error_pages}}", "{{error_pages}}"]
+    .iter()
+    .any(|tag| content.contains(tag))
+}
+
+/// Ensure the pages of a maintenance are served by at least one server
+/// of the cluster, a route or an http template including them
+async fn check_served(
+  cluster: &ClusterItem,
+  item: &ClusterMaintenanceItem,
+  pool: &web::types::State<Pool>,
+) -> Result<(), HttpResponseError> {
+  if gen_directives(item).is_none() {
+    return Ok(());
+  }
+  let routes =
+    repositories::cluster_route::list_by_cluster(cluster.key.to_owned(), pool)
+      .await?;
+  if !routes.is_empty() {
+    return Ok(());
+  }
+  for template_name in &cluster.proxy_templates {
+    let template =
+      repositories::nginx_template::get_by_name(template_name.to_owned(), pool)
+        .await?;
+    if template.mode == NginxTemplateModes::Http
+      && includes_error_pages(&template.content)
+    {
+      return Ok(());
+    }
+  }
+  Err(
+    MaintenanceError::Invalid(format!(
+      "cluster {} has no route nor http template including \
+       {{{{{{error_pages}}}}}} to serve the pages",
+      cluster.key
+    ))
+    .to_http_error(),
+  )
+}
+
+/// Update the maintenance of a cluster and start it to render the pages
+/// pages no server of the cluster would serve are refused
+/// the previous maintenance is restored if the cluster fail to render
+pub async fn update(
+  cluster: &ClusterItem,
+  item: ClusterMaintenancePartial,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<ClusterMaintenanceItem, HttpResponseError> {
+  let current = repositories::cluster_maintenance::find_by_cluster_key(
+    cluster.key.to_owned(),
+    pool,
+  )
+  .await?;
+  let maintenance = merge(&cluster.key, current.to_owned(), item)
+    .map_err(|err| err.to_http_error())?;
+  check_served(cluster, &maintenance, pool).await?;
+  let maintenance =
+    repositories::cluster_maintenance::upsert(maintenance, pool).await?;
+  let res =
+    services::cluster::start(cluster, config, pool, docker_api, actor).await;
+  if let Err(err) = res {
+    match current {
+      Some(current) => {
+        write_pages(&current, &config.state_dir)
+          .map_err(|err| err.to_http_error())?;
+        repositories::cluster_maintenance::upsert(current, pool).await?;
+      }
+      None => {
+        remove_pages(&cluster.key, &config.state_dir)
+          .map_err(|err| err.to_http_error())?;
+        repositories::cluster_maintenance::delete_by_cluster_key(
+          cluster.key.to_owned(),
+          pool,
+        )
+        .await?;
+      }
+    }
+    return Err(err);
+  }
+  // Servers stop using the variable before his file is removed
+  if !maintenance.enabled {
+    let path = config_file_path(&cluster.key, &config.state_dir);
+    services::nginx::remove_config_files(
+      &[path],
+      &config.state_dir,
+      docker_api,
+    )
+    .await
+    .map_err(|err| err.to_http_error())?;
+  }
+  Ok(maintenance)
+}
+
+/// Maintenance of a cluster, disabled when it was never set
+pub async fn inspect(
+  cluster: &ClusterItem,
+  pool: &web::types::State<Pool>,
+) -> Result<ClusterMaintenanceItem, HttpResponseError> {
+  let current = repositories::cluster_maintenance::find_by_cluster_key(
+    cluster.key.to_owned(),
+    pool,
+  )
+  .await?;
+  merge(&cluster.key, current, ClusterMaintenancePartial::default())
+    .map_err(|err| err.to_http_error())
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  fn gen_item() -> ClusterMaintenanceItem {
+    ClusterMaintenanceItem {
+      cluster_key: String::from("global-dev"),
+      enabled: true,
+      body: None,
+      allow: vec![String::from("10.0.0.0/8")],
+      bad_gateway_body: None,
+      gateway_timeout_body: None,
+    }
+  }
+
+  #[test]
+  fn test_merge() {
+    let item = merge(
+      "global-dev",
+      None,
+      ClusterMaintenancePartial {
+        enabled: true,
+        body: Some(String::from("<h1>Down</h1>")),
+        allow: Some(vec![String::from("192.168.1.10")]),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+    assert!(item.enabled);
+    assert_eq!(item.body.as_deref(), Some("<h1>Down</h1>"));
+    assert_eq!(item.allow, vec![String::from("192.168.1.10")]);
+
+    // Unset fields are kept and empty bodies removed
+    let item = merge(
+      "global-dev",
+      Some(item),
+      ClusterMaintenancePartial {
+        enabled: false,
+        body: None,
+        bad_gateway_body: Some(String::from("<h1>Bad gateway</h1>")),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+    assert!(!item.enabled);
+    assert_eq!(item.body.as_deref(), Some("<h1>Down</h1>"));
+    assert_eq!(item.allow, vec![String::from("192.168.1.10")]);
+    let item = merge(
+      "global-dev",
+      Some(item),
+      ClusterMaintenancePartial {
+        body: Some(String::new()),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+    assert_eq!(item.body, None);
+    assert_eq!(
+      item.bad_gateway_body.as_deref(),
+      Some("<h1>Bad gateway</h1>")
+    );
+
+    let res = merge(
+      "global-dev",
+      None,
+      ClusterMaintenancePartial {
+        allow: Some(vec![String::from("10.0.0.0/33")]),
+        ..Default::default()
+      },
+    );
+    assert!(res.is_err());
+  }
+
+  #[test]
+  fn test_gen_directives() {
+    let mut item = gen_item();
+    let variables = gen_variables(&item).unwrap();
+    assert!(variables.contains("geo $nanocl_maintenance_global_dev_client {"));
+    assert!(variables.contains("  10.0.0.0/8 0;\n"));
+    assert!(variables.contains(
+      "map $nanocl_maintenance_global_dev_client$uri $nanocl_maintenance_global_dev {"
+    ));
+    let directives = gen_directives(&item).unwrap();
+    assert!(directives.contains("  error_page 503 @nanocl_maintenance;\n"));
+    assert!(directives.contains("  if ($nanocl_maintenance_global_dev) {\n"));
+    assert!(directives.contains("    root /etc/nginx/ssl/pages/global-dev;\n"));
+    assert!(directives.contains("    try_files /503.html =503;\n"));
+    assert!(!directives.contains("error_page 502"));
+
+    // Custom error pages are served outside of maintenance
+    item.enabled = false;
+    item.gateway_timeout_body = Some(String::from("<h1>Timeout</h1>"));
+    assert_eq!(gen_variables(&item), None);
+    let directives = gen_directives(&item).unwrap();
+    assert!(directives.contains("  error_page 504 @nanocl_gateway_timeout;\n"));
+    assert!(!directives.contains("return 503"));
+    assert!(!directives.contains("error_page 503"));
+
+    item.gateway_timeout_body = None;
+    assert_eq!(gen_directives(&item), None);
+  }
+
+  #[test]
+  fn test_includes_error_pages() {
+    assert!(includes_error_pages("server {\n  {{{ error_pages }}}\n}"));
+    assert!(includes_error_pages("server {\n  {{& error_pages}}\n}"));
+    assert!(!includes_error_pages(
+      "server {\n  {{{access_policies}}}\n}"
+    ));
+  }
+}
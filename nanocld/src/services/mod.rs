@@ -1,12 +1,29 @@
 pub mod utils;
 pub mod errors;
 
+pub mod ipam;
 pub mod ipsec;
 pub mod cargo;
 pub mod nginx;
+pub mod nginx_template;
+pub mod nginx_log;
+pub mod log_tailer;
+pub mod nginx_log_ingestion;
+pub mod network_policy;
+pub mod quota;
+pub mod namespace;
+pub mod acme;
+pub mod certificate;
+pub mod route;
+pub mod access_policy;
+pub mod auth_policy;
+pub mod maintenance;
+pub mod history;
+pub mod reconcile;
 pub mod docker;
 pub mod github;
 pub mod cluster;
+pub mod cluster_network;
 pub mod dnsmasq;
 pub mod postgresql;
 pub mod git_repository;
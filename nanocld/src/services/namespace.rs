@@ -0,0 +1,350 @@
+//! Cascading deletion of a namespace with everything it owns
+use ntex::web;
+use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::config::DaemonConfig;
+use crate::{services, repositories};
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+use crate::models::{
+  Pool, ClusterItem, ClusterCargoItem, EventKind, NamespaceDeleteReport,
+};
+
+use super::nginx::NginxError;
+use super::reconcile::container_name;
+
+/// Resources of a namespace to remove
+struct DeletePlan {
+  report: NamespaceDeleteReport,
+  clusters: Vec<ClusterItem>,
+  nginx_files: Vec<PathBuf>,
+  /// Clusters of other namespaces that had a cargo of the namespace joined
+  /// they are started again to render their templates without it
+  foreign_clusters: BTreeSet<String>,
+}
+
+/// Collect everything a namespace own without removing anything
+async fn plan_delete(
+  name: &str,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+) -> Result<DeletePlan, HttpResponseError> {
+  let namespace =
+    repositories::namespace::find_by_name(name.to_owned(), pool).await?;
+  let cargoes = repositories::cargo::find_by_namespace(namespace, pool).await?;
+  let clusters =
+    repositories::cluster::find_by_namespace(name.to_owned(), pool).await?;
+  let mut report = NamespaceDeleteReport::default();
+
+  let mut joins: BTreeMap<String, ClusterCargoItem> = BTreeMap::new();
+  for cluster in &clusters {
+    let items = repositories::cluster_cargo::get_by_cluster_key(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    for item in items {
+      joins.insert(item.key.to_owned(), item);
+    }
+  }
+  for cargo in &cargoes {
+    let items = repositories::cluster_cargo::find_by_cargo_key(
+      cargo.key.to_owned(),
+      pool,
+    )
+    .await?;
+    for item in items {
+      joins.insert(item.key.to_owned(), item);
+    }
+  }
+
+  let mut containers = BTreeSet::new();
+  for join in joins.values() {
+    let items = services::cluster::list_containers(
+      &join.cluster_key,
+      &join.cargo_key,
+      docker_api,
+    )
+    .await?;
+    containers.extend(items.iter().map(container_name));
+  }
+  for cargo in &cargoes {
+    let items =
+      services::cargo::list_containers(cargo.key.to_owned(), docker_api)
+        .await?;
+    containers.extend(items.iter().map(container_name));
+  }
+  report.containers = containers.into_iter().collect();
+
+  let mut dns_entries = BTreeSet::new();
+  let mut foreign_clusters = BTreeSet::new();
+  let mut dns_clusters: BTreeMap<String, Vec<String>> = BTreeMap::new();
+  for join in joins.values() {
+    if !clusters
+      .iter()
+      .any(|cluster| cluster.key == join.cluster_key)
+    {
+      foreign_clusters.insert(join.cluster_key.to_owned());
+    }
+    let cargo = match cargoes.iter().find(|cargo| cargo.key == join.cargo_key) {
+      Some(cargo) => cargo.to_owned(),
+      None => {
+        repositories::cargo::find_by_key(join.cargo_key.to_owned(), pool)
+          .await?
+      }
+    };
+    if cargo.dns_entry.is_some() {
+      dns_clusters
+        .entry(join.cluster_key.to_owned())
+        .or_default()
+        .push(cargo.name);
+    }
+  }
+  // Entries are rendered like a start does while the containers still exist
+  let ignored_joins = joins.keys().cloned().collect::<Vec<_>>();
+  for (cluster_key, cargo_names) in dns_clusters {
+    let cluster = repositories::cluster::find_by_key(cluster_key, pool).await?;
+    let mut domains =
+      services::cluster::render_dns_domains(&cluster, config, docker_api, pool)
+        .await?;
+    for cargo_name in cargo_names {
+      let domain = match domains.remove(&cargo_name) {
+        None => continue,
+        Some(domain) => domain,
+      };
+      // Another cluster may still resolve the same domain
+      if !services::cluster::is_dns_domain_used(
+        &domain,
+        &ignored_joins,
+        config,
+        pool,
+      )
+      .await?
+      {
+        dns_entries.insert(domain);
+      }
+    }
+  }
+  report.joins = joins.into_keys().collect();
+  report.dns_entries = dns_entries.into_iter().collect();
+
+  for cargo in &cargoes {
+    let items =
+      repositories::cargo_env::list_by_cargo_key(cargo.key.to_owned(), pool)
+        .await?;
+    report
+      .cargo_envs
+      .extend(items.into_iter().map(|item| item.key));
+    report.cargoes.push(cargo.key.to_owned());
+  }
+
+  let mut nginx_files = Vec::new();
+  for cluster in &clusters {
+    let networks =
+      repositories::cluster_network::list_for_cluster(cluster.to_owned(), pool)
+        .await?;
+    report
+      .networks
+      .extend(networks.into_iter().map(|network| network.key));
+    let variables = repositories::cluster_variable::list_by_cluster(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    report
+      .cluster_variables
+      .extend(variables.into_iter().map(|variable| variable.key));
+    let routes = repositories::cluster_route::list_by_cluster(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    report
+      .cluster_routes
+      .extend(routes.into_iter().map(|route| route.key));
+    let policies = repositories::cluster_access_policy::list_by_cluster(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    report
+      .cluster_access_policies
+      .extend(policies.into_iter().map(|policy| policy.key));
+    let auth_policies = repositories::cluster_auth_policy::list_by_cluster(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    report
+      .cluster_auth_policies
+      .extend(auth_policies.into_iter().map(|policy| policy.key));
+    let maintenance = repositories::cluster_maintenance::find_by_cluster_key(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    if maintenance.is_some() {
+      report.cluster_maintenances.push(cluster.key.to_owned());
+    }
+    report.clusters.push(cluster.key.to_owned());
+    let files = services::nginx::list_cluster_config_files(
+      &cluster.key,
+      &config.state_dir,
+    )
+    .map_err(|err| NginxError::Io(err).to_http_error())?;
+    nginx_files.extend(files);
+  }
+  report.nginx_files = nginx_files
+    .iter()
+    .map(|path| path.display().to_string())
+    .collect();
+
+  Ok(DeletePlan {
+    report,
+    clusters,
+    nginx_files,
+    foreign_clusters,
+  })
+}
+
+/// Remove the resources of a plan in dependency order
+async fn apply_delete(
+  name: &str,
+  plan: &DeletePlan,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<(), HttpResponseError> {
+  let report = &plan.report;
+  for container in &report.containers {
+    let options = Some(bollard::container::RemoveContainerOptions {
+      force: true,
+      ..Default::default()
+    });
+    docker_api.remove_container(container, options).await?;
+  }
+  for join in &report.joins {
+    repositories::cluster_cargo_network::delete_by_cluster_cargo_key(
+      join.to_owned(),
+      pool,
+    )
+    .await?;
+    repositories::cluster_cargo::delete_by_key(join.to_owned(), pool).await?;
+  }
+  for cargo in &report.cargoes {
+    repositories::cargo_env::delete_by_cargo_key(cargo.to_owned(), pool)
+      .await?;
+  }
+  for cargo in &report.cargoes {
+    repositories::cargo::delete_by_key(cargo.to_owned(), pool).await?;
+  }
+  for cluster in &plan.clusters {
+    repositories::cluster_network_policy::delete_by_cluster_key(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    services::cluster::delete_networks(cluster.to_owned(), docker_api, pool)
+      .await?;
+  }
+  services::network_policy::reconcile(docker_api, pool).await?;
+  for cluster in &plan.clusters {
+    repositories::cluster_variable::delete_by_cluster_key(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    repositories::cluster_route::delete_by_cluster_key(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    repositories::cluster_access_policy::delete_by_cluster_key(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    repositories::cluster_auth_policy::delete_by_cluster_key(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+    repositories::cluster_maintenance::delete_by_cluster_key(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?;
+  }
+  for cluster in &plan.clusters {
+    repositories::cluster::delete_by_key(cluster.key.to_owned(), pool).await?;
+  }
+  services::nginx::remove_config_files(
+    &plan.nginx_files,
+    &config.state_dir,
+    docker_api,
+  )
+  .await
+  .map_err(|err| err.to_http_error())?;
+  for policy in &report.cluster_auth_policies {
+    services::auth_policy::remove_htpasswd_file(policy, &config.state_dir)
+      .map_err(|err| err.to_http_error())?;
+  }
+  for cluster in &report.cluster_maintenances {
+    services::maintenance::remove_pages(cluster, &config.state_dir)
+      .map_err(|err| err.to_http_error())?;
+  }
+  for domain in &report.dns_entries {
+    services::dnsmasq::remove_dns_entry(domain, &config.state_dir)
+      .map_err(|err| err.to_http_error())?;
+  }
+  if !report.dns_entries.is_empty() {
+    services::dnsmasq::restart(docker_api)
+      .await
+      .map_err(|err| err.to_http_error())?;
+  }
+  for cluster_key in &plan.foreign_clusters {
+    let cluster =
+      repositories::cluster::find_by_key(cluster_key.to_owned(), pool).await?;
+    let res =
+      services::cluster::start(&cluster, config, pool, docker_api, actor).await;
+    services::history::record(
+      cluster_key,
+      EventKind::Start,
+      actor,
+      None,
+      &res,
+      pool,
+    )
+    .await;
+    res?;
+  }
+  repositories::namespace_quota::delete_by_namespace(name.to_owned(), pool)
+    .await?;
+  repositories::namespace::delete_by_name(name.to_owned(), pool).await?;
+  Ok(())
+}
+
+/// Delete a namespace with his containers, joins, cargo envs, cargoes,
+/// networks, cluster variables, cluster routes, clusters, nginx files
+/// and dns entries
+/// when dry_run is true only list what would be removed
+pub async fn delete_cascade(
+  name: &str,
+  dry_run: bool,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<NamespaceDeleteReport, HttpResponseError> {
+  // Held from the plan to the end of the removal so a reconcile never
+  // redeploys a cargo or a join being removed
+  let _lock = services::cluster::lock_cluster_cargoes().await;
+  let mut plan = plan_delete(name, config, docker_api, pool).await?;
+  if !dry_run {
+    apply_delete(name, &plan, config, docker_api, pool, actor).await?;
+  }
+  plan.report.dry_run = dry_run;
+  Ok(plan.report)
+}
@@ -0,0 +1,224 @@
+//! Enforce cluster network policies with a dedicated iptables chain
+//! jumped to from docker's `DOCKER-USER` chain.
+//! Rules are loaded with iptables-restore so they work with both
+//! the legacy and the nftables backend of iptables.
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::collections::HashMap;
+
+use ntex::web;
+use thiserror::Error;
+use ntex::http::StatusCode;
+use std::io::Error as IoError;
+
+use crate::{services, repositories};
+use crate::models::{Pool, ClusterNetworkPolicyItem};
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+
+use super::ipam::Cidr;
+
+const POLICY_CHAIN: &str = "NANOCL-POLICIES";
+const DOCKER_USER_CHAIN: &str = "DOCKER-USER";
+
+#[derive(Debug, Error)]
+pub enum NetworkPolicyError {
+  #[error("network policy io error")]
+  Io(#[from] IoError),
+  #[error("network policy command error {0}")]
+  Command(String),
+}
+
+impl IntoHttpResponseError for NetworkPolicyError {
+  fn to_http_error(&self) -> HttpResponseError {
+    match self {
+      NetworkPolicyError::Io(err) => HttpResponseError {
+        msg: format!("network policy io error {:#?}", err),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+      NetworkPolicyError::Command(output) => HttpResponseError {
+        msg: format!("unable to apply network policies: {}", output),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+    }
+  }
+}
+
+/// A network policy with networks resolved to their subnets
+#[derive(Debug)]
+pub struct PolicyRules {
+  pub(crate) sources: Vec<Cidr>,
+  pub(crate) allowed: Vec<Cidr>,
+  pub(crate) allow_egress: bool,
+}
+
+/// Generate the iptables-restore input for given policies
+/// Traffic of a network without policy is left to docker defaults
+pub fn gen_restore_rules(policies: &[PolicyRules]) -> String {
+  let mut lines = vec![
+    String::from("*filter"),
+    format!(":{} - [0:0]", POLICY_CHAIN),
+    format!(
+      "-A {} -m conntrack --ctstate RELATED,ESTABLISHED -j ACCEPT",
+      POLICY_CHAIN
+    ),
+  ];
+  for policy in policies {
+    for source in policy.sources.iter().filter(|c| c.addr.is_ipv4()) {
+      lines.push(format!(
+        "-A {} -s {} -d {} -j RETURN",
+        POLICY_CHAIN, source, source
+      ));
+      for allowed in policy.allowed.iter().filter(|c| c.addr.is_ipv4()) {
+        lines.push(format!(
+          "-A {} -s {} -d {} -j ACCEPT",
+          POLICY_CHAIN, source, allowed
+        ));
+      }
+      if !policy.allow_egress {
+        lines.push(format!("-A {} -s {} -j DROP", POLICY_CHAIN, source));
+      }
+    }
+  }
+  lines.push(format!("-A {} -j RETURN", POLICY_CHAIN));
+  lines.push(String::from("COMMIT"));
+  lines.join("\n") + "\n"
+}
+
+fn exec_iptables(args: &[&str]) -> Result<bool, NetworkPolicyError> {
+  let output = Command::new("iptables").args(args).output()?;
+  Ok(output.status.success())
+}
+
+/// Load rules in the policy chain and ensure docker jump to it
+fn apply_restore_rules(rules: &str) -> Result<(), NetworkPolicyError> {
+  let mut child = Command::new("iptables-restore")
+    .arg("--noflush")
+    .stdin(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+  if let Some(stdin) = child.stdin.as_mut() {
+    stdin.write_all(rules.as_bytes())?;
+  }
+  let output = child.wait_with_output()?;
+  if !output.status.success() {
+    return Err(NetworkPolicyError::Command(
+      String::from_utf8_lossy(&output.stderr).to_string(),
+    ));
+  }
+  let exists = exec_iptables(&["-C", DOCKER_USER_CHAIN, "-j", POLICY_CHAIN])?;
+  if !exists
+    && !exec_iptables(&["-I", DOCKER_USER_CHAIN, "1", "-j", POLICY_CHAIN])?
+  {
+    return Err(NetworkPolicyError::Command(format!(
+      "unable to insert jump to {} in {}",
+      POLICY_CHAIN, DOCKER_USER_CHAIN
+    )));
+  }
+  Ok(())
+}
+
+/// Resolve policies networks to their subnets
+/// networks that doesn't exist anymore are ignored
+async fn resolve_policies(
+  policies: Vec<ClusterNetworkPolicyItem>,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<PolicyRules>, HttpResponseError> {
+  let mut subnets: HashMap<String, Vec<Cidr>> = HashMap::new();
+  let mut rules = Vec::new();
+  for policy in policies {
+    let keys = std::iter::once(&policy.network_key)
+      .chain(policy.allowed_networks.iter())
+      .collect::<Vec<&String>>();
+    for key in keys {
+      if subnets.contains_key(key) {
+        continue;
+      }
+      let network =
+        match repositories::cluster_network::find_by_key(key.to_owned(), pool)
+          .await
+        {
+          Err(_) => {
+            log::warn!("network policy reference unknown network {}", key);
+            subnets.insert(key.to_owned(), Vec::new());
+            continue;
+          }
+          Ok(network) => network,
+        };
+      let network_subnets =
+        services::cluster::get_network_subnets(&network, docker_api).await?;
+      subnets.insert(key.to_owned(), network_subnets);
+    }
+    let allowed = policy
+      .allowed_networks
+      .iter()
+      .filter_map(|key| subnets.get(key))
+      .flatten()
+      .copied()
+      .collect::<Vec<Cidr>>();
+    rules.push(PolicyRules {
+      sources: subnets
+        .get(&policy.network_key)
+        .cloned()
+        .unwrap_or_default(),
+      allowed,
+      allow_egress: policy.allow_egress,
+    });
+  }
+  Ok(rules)
+}
+
+/// Reconcile host firewall rules with network policies stored in database
+pub async fn reconcile(
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+) -> Result<(), HttpResponseError> {
+  let policies = repositories::cluster_network_policy::list_all(pool).await?;
+  let policies = resolve_policies(policies, docker_api, pool).await?;
+  let rules = gen_restore_rules(&policies);
+  log::debug!("applying network policies\n{}", &rules);
+  web::block(move || apply_restore_rules(&rules))
+    .await
+    .map_err(|err| match err {
+      web::error::BlockingError::Error(err) => err.to_http_error(),
+      web::error::BlockingError::Canceled => HttpResponseError {
+        msg: String::from("network policies reconciliation canceled"),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+    })?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_gen_restore_rules() {
+    let policies = vec![
+      PolicyRules {
+        sources: vec!["10.1.0.0/24".parse().unwrap()],
+        allowed: vec!["10.2.0.0/24".parse().unwrap()],
+        allow_egress: false,
+      },
+      PolicyRules {
+        sources: vec!["10.3.0.0/24".parse().unwrap()],
+        allowed: vec![],
+        allow_egress: true,
+      },
+    ];
+    let rules = gen_restore_rules(&policies);
+    let expected = "*filter
+:NANOCL-POLICIES - [0:0]
+-A NANOCL-POLICIES -m conntrack --ctstate RELATED,ESTABLISHED -j ACCEPT
+-A NANOCL-POLICIES -s 10.1.0.0/24 -d 10.1.0.0/24 -j RETURN
+-A NANOCL-POLICIES -s 10.1.0.0/24 -d 10.2.0.0/24 -j ACCEPT
+-A NANOCL-POLICIES -s 10.1.0.0/24 -j DROP
+-A NANOCL-POLICIES -s 10.3.0.0/24 -d 10.3.0.0/24 -j RETURN
+-A NANOCL-POLICIES -j RETURN
+COMMIT
+";
+    assert_eq!(rules, expected);
+  }
+}
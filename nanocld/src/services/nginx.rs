@@ -1,52 +1,430 @@
 use futures::{
-  SinkExt,
+  SinkExt, StreamExt,
   channel::mpsc::{unbounded, UnboundedReceiver},
 };
 use ntex::{web, rt};
-use std::path::Path;
-use std::sync::mpsc::channel;
-use notify::{Watcher, RecursiveMode, RawEvent, raw_watcher, Op};
+use ntex::http::StatusCode;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::Utc;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use notify::{Watcher, RecursiveMode, RawEvent, raw_watcher};
+use uuid::Uuid;
+use once_cell::sync::Lazy;
+use futures::lock::Mutex as AsyncMutex;
+use thiserror::Error;
+use std::io::Error as IoError;
 
 use bollard::{
   Docker,
   models::HostConfig,
   errors::Error as DockerError,
   container::{CreateContainerOptions, Config},
-  exec::{CreateExecOptions, StartExecOptions},
+  exec::{CreateExecOptions, StartExecOptions, StartExecResults},
 };
 
-use crate::repositories;
 use crate::config::DaemonConfig;
-use crate::models::{Pool, NginxLogPartial, NginxLogItem};
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+use crate::models::{Pool, NginxLogItem, NginxTemplateModes};
 
 use super::utils::*;
+use super::log_tailer::LogTailer;
+use super::auth_policy::NGINX_HTPASSWD_DIR;
+use super::nginx_log_ingestion::{
+  self, NginxLogIngestion, SharedNginxLogIngestionMetrics,
+};
+
+use crate::services::errors::docker_error_ref;
+
+const NGINX_CONTAINER_NAME: &str = "nanocl-proxy-nginx";
+/// Seconds between two reads of the access log without notification
+const NGINX_LOG_POLL_INTERVAL: u64 = 5;
+
+#[derive(Debug, Error)]
+pub enum NginxError {
+  #[error("nginx io error")]
+  Io(#[from] IoError),
+  #[error("nginx docker_api error")]
+  Docker(#[from] DockerError),
+  #[error("nginx configuration test failed")]
+  InvalidConfig(String),
+  #[error("nginx command failed")]
+  Command(String),
+}
 
-pub async fn reload_config(docker_api: &Docker) -> Result<(), DockerError> {
-  let container_name = "nanocl-proxy-nginx";
+impl IntoHttpResponseError for NginxError {
+  fn to_http_error(&self) -> HttpResponseError {
+    match self {
+      NginxError::Io(err) => HttpResponseError {
+        msg: format!("nginx io error {:#?}", err),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+      NginxError::Docker(err) => docker_error_ref(err),
+      NginxError::InvalidConfig(output) => HttpResponseError {
+        msg: format!("nginx configuration test failed: {}", output),
+        status: StatusCode::BAD_REQUEST,
+      },
+      NginxError::Command(output) => HttpResponseError {
+        msg: format!("nginx command failed: {}", output),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+    }
+  }
+}
+
+/// Rendered nginx config file waiting to be applied
+#[derive(Debug)]
+pub struct NginxConfigFile {
+  pub(crate) name: String,
+  pub(crate) mode: NginxTemplateModes,
+  pub(crate) content: String,
+}
+
+impl NginxConfigFile {
+  fn file_name(&self) -> String {
+    format!("{}.conf", &self.name)
+  }
+
+  /// Name of the proxy directory of the file
+  fn dir_name(&self) -> &'static str {
+    match self.mode {
+      NginxTemplateModes::Http => "sites-enabled",
+      NginxTemplateModes::Stream => "streams-enabled",
+    }
+  }
+
+  fn target_path(&self, state_dir: &str) -> PathBuf {
+    Path::new(state_dir)
+      .join("nginx")
+      .join(self.dir_name())
+      .join(self.file_name())
+  }
+}
+
+/// Content of a config file before it was replaced
+/// None means the file didn't exist
+type ConfigBackup = (PathBuf, Option<Vec<u8>>);
+
+/// Proxy directories of the config files in the state dir and in nginx
+const NGINX_CONFIG_DIRS: [(&str, &str); 2] = [
+  ("nginx/sites-enabled", "sites-enabled"),
+  ("nginx/streams-enabled", "streams-enabled"),
+];
+/// Staging directory in the state dir and in nginx
+/// each apply gets its own directory with the test config
+const NGINX_STAGING_DIRS: (&str, &str) =
+  ("nginx/staging", "/etc/nginx/staging");
+
+/// Applies are serialized so a reload never picks up files of another test
+static NGINX_CONFIG_LOCK: Lazy<AsyncMutex<()>> =
+  Lazy::new(|| AsyncMutex::new(()));
+
+/// Execute a command inside the nginx container
+/// and return his exit code with his output
+async fn exec_cmd(
+  cmd: Vec<&str>,
+  docker_api: &Docker,
+) -> Result<(i64, String), DockerError> {
   let config = CreateExecOptions {
-    cmd: Some(vec!["nginx", "-s", "reload"]),
+    cmd: Some(cmd),
     attach_stdout: Some(true),
     attach_stderr: Some(true),
     ..Default::default()
   };
-  let res = docker_api.create_exec(container_name, config).await?;
+  let res = docker_api.create_exec(NGINX_CONTAINER_NAME, config).await?;
   let config = StartExecOptions { detach: false };
-  docker_api.start_exec(&res.id, Some(config)).await?;
+  let mut output = String::new();
+  if let StartExecResults::Attached {
+    output: mut stream, ..
+  } = docker_api.start_exec(&res.id, Some(config)).await?
+  {
+    while let Some(log) = stream.next().await {
+      output += &log?.to_string();
+    }
+  }
+  let exec = docker_api.inspect_exec(&res.id).await?;
+  Ok((exec.exit_code.unwrap_or_default(), output))
+}
+
+/// Config used to test a staging directory with `nginx -t`
+/// it only loads the staged proxy directories
+fn gen_test_config(stage_id: &str) -> String {
+  let (_, staging_dir) = NGINX_STAGING_DIRS;
+  let include = |dir_name: &str| {
+    format!("  include {}/{}/{}/*;\n", staging_dir, stage_id, dir_name)
+  };
+  format!(
+    "events {{}}\n\nhttp {{\n  include /etc/nginx/mime.types;\n{}}}\n\n\
+     stream {{\n{}}}\n",
+    include("sites-enabled"),
+    include("streams-enabled"),
+  )
+}
+
+/// Test the config files of a staging directory with `nginx -t`
+async fn test_staged_config(
+  stage_id: &str,
+  docker_api: &Docker,
+) -> Result<(), NginxError> {
+  let (_, staging_dir) = NGINX_STAGING_DIRS;
+  let conf = format!("{}/{}/nginx.conf", staging_dir, stage_id);
+  let (exit_code, output) =
+    exec_cmd(vec!["nginx", "-t", "-c", &conf], docker_api).await?;
+  if exit_code != 0 {
+    return Err(NginxError::InvalidConfig(output));
+  }
+  Ok(())
+}
+
+pub async fn reload_config(docker_api: &Docker) -> Result<(), NginxError> {
+  let (exit_code, output) =
+    exec_cmd(vec!["nginx", "-s", "reload"], docker_api).await?;
+  if exit_code != 0 {
+    return Err(NginxError::Command(output));
+  }
+  Ok(())
+}
+
+fn backup_config_files(
+  paths: &[PathBuf],
+) -> Result<Vec<ConfigBackup>, IoError> {
+  paths
+    .iter()
+    .map(|path| match fs::read(path) {
+      Ok(content) => Ok((path.to_owned(), Some(content))),
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+        Ok((path.to_owned(), None))
+      }
+      Err(err) => Err(err),
+    })
+    .collect()
+}
+
+fn restore_config_files(backups: &[ConfigBackup]) {
+  for (path, content) in backups {
+    let res = match content {
+      None => match fs::remove_file(path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        res => res,
+      },
+      Some(content) => fs::write(path, content),
+    };
+    if let Err(err) = res {
+      log::error!("unable to restore nginx config {} {}", path.display(), err);
+    }
+  }
+}
+
+/// Copy the live proxy directories in a staging directory
+/// without the removed files and with the rendered ones
+fn stage_config_files(
+  files: &[NginxConfigFile],
+  removed: &[PathBuf],
+  stage_id: &str,
+  stage_dir: &Path,
+  state_dir: &str,
+) -> Result<(), IoError> {
+  for (dir_path, dir_name) in NGINX_CONFIG_DIRS {
+    let staged_dir = stage_dir.join(dir_name);
+    fs::create_dir_all(&staged_dir)?;
+    let live_dir = Path::new(state_dir).join(dir_path);
+    if !live_dir.exists() {
+      continue;
+    }
+    for entry in fs::read_dir(live_dir)? {
+      let path = entry?.path();
+      if !path.is_file() || removed.contains(&path) {
+        continue;
+      }
+      if let Some(file_name) = path.file_name() {
+        fs::copy(&path, staged_dir.join(file_name))?;
+      }
+    }
+  }
+  for file in files {
+    let staged_path = stage_dir.join(file.dir_name()).join(file.file_name());
+    fs::write(staged_path, &file.content)?;
+  }
+  fs::write(stage_dir.join("nginx.conf"), gen_test_config(stage_id))?;
+  Ok(())
+}
+
+/// Put the tested files in the live proxy directories
+/// previous files are restored if one of them can't be written
+fn swap_config_files(
+  files: &[NginxConfigFile],
+  removed: &[PathBuf],
+  state_dir: &str,
+) -> Result<(), IoError> {
+  let paths = files
+    .iter()
+    .map(|file| file.target_path(state_dir))
+    .chain(removed.iter().cloned())
+    .collect::<Vec<_>>();
+  let backups = backup_config_files(&paths)?;
+  for file in files {
+    if let Err(err) = fs::write(file.target_path(state_dir), &file.content) {
+      restore_config_files(&backups);
+      return Err(err);
+    }
+  }
+  for path in removed {
+    match fs::remove_file(path) {
+      Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+        restore_config_files(&backups);
+        return Err(err);
+      }
+      _ => {}
+    }
+  }
   Ok(())
 }
 
+/// Test the proxy config with the changes in a staging directory
+/// the config lock must be held
+async fn test_changes(
+  files: &[NginxConfigFile],
+  removed: &[PathBuf],
+  state_dir: &str,
+  docker_api: &Docker,
+) -> Result<(), NginxError> {
+  let stage_id = Uuid::new_v4().to_string();
+  let (staging_path, _) = NGINX_STAGING_DIRS;
+  let stage_dir = Path::new(state_dir).join(staging_path).join(&stage_id);
+  let res = match stage_config_files(
+    files, removed, &stage_id, &stage_dir, state_dir,
+  ) {
+    Err(err) => Err(err.into()),
+    Ok(_) => test_staged_config(&stage_id, docker_api).await,
+  };
+  if let Err(err) = fs::remove_dir_all(&stage_dir) {
+    log::warn!(
+      "unable to remove nginx staging directory {} {}",
+      stage_dir.display(),
+      err
+    );
+  }
+  res
+}
+
+/// Test the proxy config with the changes in a staging directory
+/// and only put them in place and reload nginx when it's valid
+async fn apply_changes(
+  files: &[NginxConfigFile],
+  removed: &[PathBuf],
+  state_dir: &str,
+  docker_api: &Docker,
+) -> Result<(), NginxError> {
+  let _lock = NGINX_CONFIG_LOCK.lock().await;
+  test_changes(files, removed, state_dir, docker_api).await?;
+  swap_config_files(files, removed, state_dir)?;
+  reload_config(docker_api).await
+}
+
+/// Files with the same name as the rendered ones in the other proxy directory
+/// they were rendered before a template changed of mode
+fn list_other_mode_files(
+  files: &[NginxConfigFile],
+  state_dir: &str,
+) -> Vec<PathBuf> {
+  files
+    .iter()
+    .flat_map(|file| {
+      NGINX_CONFIG_DIRS
+        .iter()
+        .filter(move |(_, dir_name)| *dir_name != file.dir_name())
+        .map(move |(dir_path, _)| {
+          Path::new(state_dir).join(dir_path).join(file.file_name())
+        })
+    })
+    .filter(|path| path.exists())
+    .collect()
+}
+
+/// Apply rendered config files to the proxy
+/// Files are tested with the current ones in a staging directory
+/// and only swapped in place if `nginx -t` succeed
+/// a file rendered in another mode before is removed
+pub async fn apply_config_files(
+  files: Vec<NginxConfigFile>,
+  state_dir: &str,
+  docker_api: &Docker,
+) -> Result<(), NginxError> {
+  let removed = list_other_mode_files(&files, state_dir);
+  apply_changes(&files, &removed, state_dir, docker_api).await
+}
+
+/// Test rendered config files with the current ones in a staging directory
+/// without applying them
+pub async fn test_config_files(
+  files: &[NginxConfigFile],
+  state_dir: &str,
+  docker_api: &Docker,
+) -> Result<(), NginxError> {
+  let _lock = NGINX_CONFIG_LOCK.lock().await;
+  let removed = list_other_mode_files(files, state_dir);
+  test_changes(files, &removed, state_dir, docker_api).await
+}
+
+/// List config files rendered for a cluster in the proxy directories
+pub fn list_cluster_config_files(
+  cluster_key: &str,
+  state_dir: &str,
+) -> Result<Vec<PathBuf>, IoError> {
+  let prefix = format!("{}.", cluster_key);
+  let mut paths = Vec::new();
+  for dir_path in ["nginx/sites-enabled", "nginx/streams-enabled"] {
+    let dir_path = Path::new(state_dir).join(dir_path);
+    if !dir_path.exists() {
+      continue;
+    }
+    for entry in fs::read_dir(dir_path)? {
+      let path = entry?.path();
+      let is_cluster_file = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with(&prefix))
+        .unwrap_or(false);
+      if is_cluster_file {
+        paths.push(path);
+      }
+    }
+  }
+  paths.sort();
+  Ok(paths)
+}
+
+/// Remove config files from the proxy and reload it
+/// files are only removed if `nginx -t` succeed without them
+pub async fn remove_config_files(
+  paths: &[PathBuf],
+  state_dir: &str,
+  docker_api: &Docker,
+) -> Result<(), NginxError> {
+  if paths.is_empty() {
+    return Ok(());
+  }
+  apply_changes(&[], paths, state_dir, docker_api).await
+}
+
 fn gen_nginx_host_conf(config: &DaemonConfig) -> HostConfig {
   let sites_path = Path::new(&config.state_dir).join("nginx/sites-enabled");
   let stream_path = Path::new(&config.state_dir).join("nginx/streams-enabled");
   let log_path = Path::new(&config.state_dir).join("nginx/log");
   let ssl_path = Path::new(&config.state_dir).join("nginx/ssl");
   let letsencrypt_path = Path::new(&config.state_dir).join("nginx/letsencrypt");
+  let htpasswd_path = Path::new(&config.state_dir).join("nginx/htpasswd");
+  let (staging_path, staging_dir) = NGINX_STAGING_DIRS;
+  let staging_path = Path::new(&config.state_dir).join(staging_path);
   let binds = Some(vec![
     format!("{}:/etc/nginx/sites-enabled", sites_path.display()),
     format!("{}:/var/log/nginx", log_path.display()),
     format!("{}:/etc/nginx/ssl", ssl_path.display()),
     format!("{}:/etc/nginx/streams-enabled", stream_path.display()),
     format!("{}:/etc/letsencrypt", letsencrypt_path.display()),
+    format!("{}:{}", staging_path.display(), staging_dir),
+    format!("{}:{}", htpasswd_path.display(), NGINX_HTPASSWD_DIR),
   ]);
   let network_mode = Some(String::from("host"));
   HostConfig {
@@ -78,9 +456,12 @@ async fn create_nginx_container(
   Ok(())
 }
 
+/// Ingest the access log of the proxy in database
+/// logs are sent on the returned channel once written
 pub fn watch_nginx_logs(
-  state_dir: String,
+  config: DaemonConfig,
   pool: web::types::State<Pool>,
+  metrics: SharedNginxLogIngestionMetrics,
 ) -> UnboundedReceiver<NginxLogItem> {
   // Create a channel to receive the events.
   let (mut tx, rx) = unbounded::<NginxLogItem>();
@@ -89,54 +470,75 @@ pub fn watch_nginx_logs(
   // The notification back-end is selected based on the platform.
   rt::Arbiter::new().exec_fn(move || {
     rt::spawn(async move {
+      let state_dir = Path::new(&config.state_dir);
       let mut watcher = raw_watcher(wtx).unwrap();
       // Add a path to be watched. All files and directories at that path and
       // below will be monitored for changes.
-      let dir_path = Path::new(&state_dir).join("nginx/log");
-      watcher.watch(dir_path, RecursiveMode::Recursive).unwrap();
+      let dir_path = state_dir.join("nginx/log");
+      watcher.watch(&dir_path, RecursiveMode::Recursive).unwrap();
+      // The position is stored outside of the watched directory
+      // to not be notified when it's saved
+      let mut tailer = LogTailer::new(
+        dir_path.join("access.log"),
+        state_dir.join("nginx/access.log.position"),
+      );
+      let mut ingestion = NginxLogIngestion::new(&config, metrics);
+      let mut partitions_from = None;
       loop {
-        match wrx.recv() {
+        let today = Utc::today().naive_utc();
+        if partitions_from != Some(today) {
+          nginx_log_ingestion::create_partitions(today, &pool).await;
+          partitions_from = Some(today);
+        }
+        // Events can be missed during a rotation so the file is also read
+        // periodically
+        let poll_interval = Duration::from_secs(NGINX_LOG_POLL_INTERVAL);
+        let timeout = if tailer.lag() > 0 && !ingestion.is_full() {
+          Duration::ZERO
+        } else {
+          ingestion
+            .flush_in()
+            .map_or(poll_interval, |flush_in| flush_in.min(poll_interval))
+        };
+        match wrx.recv_timeout(timeout) {
           Ok(RawEvent {
             path: Some(path),
             op: Ok(op),
             cookie,
           }) => {
             log::debug!("watcher event {:?} {:?} ({:?})", op, path, cookie);
-            if path.to_string_lossy() == "/var/lib/nanocl/nginx/log/access.log"
-              && op == Op::WRITE
-            {
-              let output = std::process::Command::new("tail")
-                .args(["-n", "1", "/var/lib/nanocl/nginx/log/access.log"])
-                .output()
-                .expect("unable to get last nginx log entry.");
-              let str = String::from_utf8(output.stdout).unwrap();
-              let json_result = serde_json::from_str::<NginxLogPartial>(&str);
-              match json_result {
-                Err(err) => {
-                  log::error!("Parsing nginx log fail {}", err);
-                }
-                Ok(partial) => {
-                  match repositories::nginx_log::create_log(partial, &pool)
-                    .await
-                  {
-                    Err(err) => {
-                      log::error!("Unable to create nginx log entry {}", err);
-                    }
-                    Ok(entry) => {
-                      if let Err(err) = tx.send(entry).await {
-                        log::error!(
-                          "Error while sending nginx log event {:#?}",
-                          err
-                        );
-                      }
-                    }
-                  }
-                }
-              }
-            }
           }
           Ok(event) => log::warn!("Received broken event {:#?}", event),
-          Err(e) => log::error!("Received error event {}", e),
+          Err(RecvTimeoutError::Timeout) => {}
+          Err(err) => {
+            log::error!("Nginx log watcher stopped {}", err);
+            break;
+          }
+        }
+        if !ingestion.is_full() {
+          match tailer.read_lines() {
+            Err(err) => log::error!("Unable to read nginx logs {}", err),
+            Ok(lines) => ingestion.push_lines(lines),
+          }
+        }
+        ingestion.set_lag(tailer.lag());
+        let items = match ingestion.flush(&pool).await {
+          Err(err) => {
+            log::error!("Unable to create nginx log entries {}", err.msg);
+            continue;
+          }
+          Ok(items) => items,
+        };
+        // Lines read are all written so reading can resume after them
+        if ingestion.is_empty() {
+          if let Err(err) = tailer.save_position() {
+            log::error!("Unable to save nginx log position {}", err);
+          }
+        }
+        for item in items {
+          if let Err(err) = tx.send(item).await {
+            log::error!("Error while sending nginx log event {:#?}", err);
+          }
         }
       }
     });
@@ -144,12 +546,40 @@ pub fn watch_nginx_logs(
   rx
 }
 
+/// Check the proxy container has every mount of the current version
+/// proxies created before a mount was added have to be created again
+async fn has_current_binds(
+  config: &DaemonConfig,
+  docker_api: &Docker,
+) -> Result<bool, DockerError> {
+  let container = docker_api
+    .inspect_container(NGINX_CONTAINER_NAME, None)
+    .await?;
+  let binds = container
+    .host_config
+    .and_then(|host_config| host_config.binds)
+    .unwrap_or_default();
+  let expected = gen_nginx_host_conf(config).binds.unwrap_or_default();
+  Ok(expected.iter().all(|bind| binds.contains(bind)))
+}
+
 pub async fn boot(
   config: &DaemonConfig,
   docker_api: &Docker,
 ) -> Result<(), DockerError> {
-  let container_name = "nanocl-proxy-nginx";
-  let s_state = get_service_state(container_name, docker_api).await;
+  let container_name = NGINX_CONTAINER_NAME;
+  let mut s_state = get_service_state(container_name, docker_api).await;
+  if s_state != ServiceState::Uninstalled
+    && !has_current_binds(config, docker_api).await?
+  {
+    log::info!("recreating {} with the current mounts", container_name);
+    let options = Some(bollard::container::RemoveContainerOptions {
+      force: true,
+      ..Default::default()
+    });
+    docker_api.remove_container(container_name, options).await?;
+    s_state = ServiceState::Uninstalled;
+  }
   if s_state == ServiceState::Uninstalled {
     create_nginx_container(container_name, config, docker_api).await?;
   }
@@ -160,3 +590,119 @@ pub async fn boot(
   }
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::utils::test::*;
+
+  #[test]
+  fn test_stage_swap_and_restore_config_files() -> TestReturn {
+    let state_dir = std::env::temp_dir()
+      .join(format!("nanocl-nginx-test-{}", Uuid::new_v4()));
+    let stage_dir = state_dir.join("nginx/staging/test");
+    fs::create_dir_all(state_dir.join("nginx/sites-enabled"))?;
+    fs::create_dir_all(state_dir.join("nginx/streams-enabled"))?;
+    let state_dir_str = state_dir.to_string_lossy().to_string();
+    let files = vec![
+      NginxConfigFile {
+        name: String::from("global-dev.http"),
+        mode: NginxTemplateModes::Http,
+        content: String::from("server {}"),
+      },
+      NginxConfigFile {
+        name: String::from("global-dev.stream"),
+        mode: NginxTemplateModes::Stream,
+        content: String::from("server {}"),
+      },
+    ];
+    let http_path = files[0].target_path(&state_dir_str);
+    let stream_path = files[1].target_path(&state_dir_str);
+    let other_path = state_dir.join("nginx/sites-enabled/global-other.conf");
+    let removed_path = state_dir.join("nginx/sites-enabled/global-old.conf");
+    fs::write(&http_path, "previous")?;
+    fs::write(&other_path, "other")?;
+    fs::write(&removed_path, "old")?;
+    let removed = vec![removed_path.to_owned()];
+
+    // Staging leaves the live directories untouched
+    stage_config_files(&files, &removed, "test", &stage_dir, &state_dir_str)?;
+    let staged_http = stage_dir.join("sites-enabled");
+    assert_eq!(
+      fs::read_to_string(staged_http.join("global-dev.http.conf"))?,
+      "server {}"
+    );
+    assert_eq!(
+      fs::read_to_string(staged_http.join("global-other.conf"))?,
+      "other"
+    );
+    assert!(!staged_http.join("global-old.conf").exists());
+    assert!(stage_dir
+      .join("streams-enabled/global-dev.stream.conf")
+      .exists());
+    assert_eq!(
+      fs::read_to_string(stage_dir.join("nginx.conf"))?,
+      gen_test_config("test")
+    );
+    assert_eq!(fs::read_to_string(&http_path)?, "previous");
+    assert!(!stream_path.exists());
+
+    let paths = vec![http_path.to_owned(), stream_path.to_owned()];
+    let backups = backup_config_files(&paths)?;
+    swap_config_files(&files, &removed, &state_dir_str)?;
+    assert_eq!(fs::read_to_string(&http_path)?, "server {}");
+    assert_eq!(fs::read_to_string(&stream_path)?, "server {}");
+    assert!(!removed_path.exists());
+    restore_config_files(&backups);
+    assert_eq!(fs::read_to_string(&http_path)?, "previous");
+    assert!(!stream_path.exists());
+    fs::remove_dir_all(&state_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_list_other_mode_files() -> TestReturn {
+    let state_dir = std::env::temp_dir()
+      .join(format!("nanocl-nginx-test-{}", Uuid::new_v4()));
+    fs::create_dir_all(state_dir.join("nginx/sites-enabled"))?;
+    fs::create_dir_all(state_dir.join("nginx/streams-enabled"))?;
+    let state_dir_str = state_dir.to_string_lossy().to_string();
+    let previous_path =
+      state_dir.join("nginx/sites-enabled/global-dev.tcp.conf");
+    fs::write(&previous_path, "server {}")?;
+    let stream_file = NginxConfigFile {
+      name: String::from("global-dev.tcp"),
+      mode: NginxTemplateModes::Stream,
+      content: String::from("server {}"),
+    };
+    let http_file = NginxConfigFile {
+      name: String::from("global-dev.tcp"),
+      mode: NginxTemplateModes::Http,
+      content: String::from("server {}"),
+    };
+    assert_eq!(
+      list_other_mode_files(&[stream_file], &state_dir_str),
+      vec![previous_path]
+    );
+    assert!(list_other_mode_files(&[http_file], &state_dir_str).is_empty());
+    fs::remove_dir_all(&state_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_gen_test_config() {
+    let expected = r#"events {}
+
+http {
+  include /etc/nginx/mime.types;
+  include /etc/nginx/staging/abc/sites-enabled/*;
+}
+
+stream {
+  include /etc/nginx/staging/abc/streams-enabled/*;
+}
+"#;
+    assert_eq!(gen_test_config("abc"), expected);
+  }
+}
@@ -0,0 +1,682 @@
+//! Search in access logs stored by the proxy
+//! Pages are fetched with an opaque cursor made of the sorted value
+//! and the key of the last log returned.
+//! Traffic statistics are aggregated from the same logs.
+//! Old logs are pruned in batches and can be archived before.
+//! Subscribers of the log stream only receive the logs matching their filter.
+use ntex::web;
+use ntex::http::StatusCode;
+use chrono::{DateTime, Duration, NaiveDate, SecondsFormat, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use thiserror::Error;
+use uuid::Uuid;
+use std::fs::{self, File};
+use std::io::{Error as IoError, Write};
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeSet, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use regex::Regex;
+
+use crate::repositories;
+use crate::config::DaemonConfig;
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+use crate::models::{
+  Pool, NginxLogItem, NginxLogSearchQuery, NginxLogSearchResult, NginxLogSort,
+  NginxLogCursor, NginxStats, NginxStatsQuery, NginxLogStreamQuery,
+};
+
+/// Number of logs returned when no limit is given
+const DEFAULT_LIMIT: i64 = 100;
+/// Maximum number of logs returned in a page
+const MAX_LIMIT: i64 = 1000;
+/// Window of statistics in minutes when no start is given
+const DEFAULT_STATS_WINDOW: i64 = 60;
+/// Maximum window of statistics in days
+const MAX_STATS_WINDOW: i64 = 31;
+/// Number of uris and user agents in tops when none is given
+const DEFAULT_TOP: i64 = 10;
+const MAX_TOP: i64 = 100;
+/// Number of logs archived and deleted at once when pruning
+const PRUNE_BATCH: i64 = 10_000;
+
+#[derive(Debug, Error)]
+pub enum NginxLogError {
+  #[error("invalid cursor")]
+  InvalidCursor,
+  #[error("invalid query")]
+  InvalidQuery(String),
+  #[error("nginx log archive error")]
+  Io(#[from] IoError),
+}
+
+impl IntoHttpResponseError for NginxLogError {
+  fn to_http_error(&self) -> HttpResponseError {
+    match self {
+      NginxLogError::InvalidCursor => HttpResponseError {
+        msg: String::from("invalid cursor for the given sort"),
+        status: StatusCode::BAD_REQUEST,
+      },
+      NginxLogError::InvalidQuery(msg) => HttpResponseError {
+        msg: format!("invalid query {}", msg),
+        status: StatusCode::BAD_REQUEST,
+      },
+      NginxLogError::Io(err) => HttpResponseError {
+        msg: format!("nginx log archive error {}", err),
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+      },
+    }
+  }
+}
+
+/// Cursor pointing after the given log
+pub fn encode_cursor(sort: NginxLogSort, item: &NginxLogItem) -> String {
+  let value = match sort {
+    NginxLogSort::DateDesc | NginxLogSort::DateAsc => {
+      item.date_gmt.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+    }
+    NginxLogSort::RequestTimeDesc | NginxLogSort::RequestTimeAsc => {
+      item.request_time.to_string()
+    }
+  };
+  base64::encode_config(
+    format!("{}|{}", value, item.key),
+    base64::URL_SAFE_NO_PAD,
+  )
+}
+
+/// Read a cursor created by `encode_cursor` with the same sort
+pub fn decode_cursor(
+  sort: NginxLogSort,
+  cursor: &str,
+) -> Result<NginxLogCursor, NginxLogError> {
+  let bytes = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+    .map_err(|_| NginxLogError::InvalidCursor)?;
+  let cursor =
+    String::from_utf8(bytes).map_err(|_| NginxLogError::InvalidCursor)?;
+  let (value, key) = cursor
+    .rsplit_once('|')
+    .ok_or(NginxLogError::InvalidCursor)?;
+  let key = Uuid::parse_str(key).map_err(|_| NginxLogError::InvalidCursor)?;
+  match sort {
+    NginxLogSort::DateDesc | NginxLogSort::DateAsc => {
+      let date = DateTime::parse_from_rfc3339(value)
+        .map_err(|_| NginxLogError::InvalidCursor)?;
+      Ok(NginxLogCursor::Date(date.with_timezone(&Utc), key))
+    }
+    NginxLogSort::RequestTimeDesc | NginxLogSort::RequestTimeAsc => {
+      let time = value
+        .parse::<f64>()
+        .map_err(|_| NginxLogError::InvalidCursor)?;
+      Ok(NginxLogCursor::RequestTime(time, key))
+    }
+  }
+}
+
+fn validate(query: &NginxLogSearchQuery) -> Result<(), NginxLogError> {
+  if let (Some(since), Some(until)) = (query.since, query.until) {
+    if since >= until {
+      return Err(NginxLogError::InvalidQuery(String::from(
+        "since must be before until",
+      )));
+    }
+  }
+  if let (Some(min), Some(max)) = (query.status_min, query.status_max) {
+    if min > max {
+      return Err(NginxLogError::InvalidQuery(String::from(
+        "status_min must not be greater than status_max",
+      )));
+    }
+  }
+  match query.limit {
+    Some(limit) if !(1..=MAX_LIMIT).contains(&limit) => {
+      Err(NginxLogError::InvalidQuery(format!(
+        "limit must be between 1 and {}",
+        MAX_LIMIT
+      )))
+    }
+    _ => Ok(()),
+  }
+}
+
+/// Search a page of stored logs
+pub async fn search(
+  mut query: NginxLogSearchQuery,
+  pool: &web::types::State<Pool>,
+) -> Result<NginxLogSearchResult, HttpResponseError> {
+  validate(&query).map_err(|err| err.to_http_error())?;
+  let sort = query.sort.unwrap_or(NginxLogSort::DateDesc);
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+  let cursor = match query.cursor.take() {
+    None => None,
+    Some(cursor) => {
+      Some(decode_cursor(sort, &cursor).map_err(|err| err.to_http_error())?)
+    }
+  };
+  // One more log is fetched to know if there is a next page
+  let mut items =
+    repositories::nginx_log::search(query, sort, cursor, limit + 1, pool)
+      .await?;
+  let next_cursor = if items.len() as i64 > limit {
+    items.truncate(limit as usize);
+    items.last().map(|item| encode_cursor(sort, item))
+  } else {
+    None
+  };
+  Ok(NginxLogSearchResult { items, next_cursor })
+}
+
+/// Window of statistics from the query
+fn stats_window(
+  query: &NginxStatsQuery,
+  now: DateTime<Utc>,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), NginxLogError> {
+  let until = query.until.unwrap_or(now);
+  let since = query
+    .since
+    .unwrap_or_else(|| until - Duration::minutes(DEFAULT_STATS_WINDOW));
+  if since >= until {
+    return Err(NginxLogError::InvalidQuery(String::from(
+      "since must be before until",
+    )));
+  }
+  if until - since > Duration::days(MAX_STATS_WINDOW) {
+    return Err(NginxLogError::InvalidQuery(format!(
+      "window must not be longer than {} days",
+      MAX_STATS_WINDOW
+    )));
+  }
+  Ok((since, until))
+}
+
+/// Aggregate traffic of a host, of a cluster or of all hosts
+/// the traffic of a cluster is the one of his domains and route domains
+pub async fn stats(
+  query: NginxStatsQuery,
+  cluster_key: Option<String>,
+  pool: &web::types::State<Pool>,
+) -> Result<NginxStats, HttpResponseError> {
+  let (since, until) =
+    stats_window(&query, Utc::now()).map_err(|err| err.to_http_error())?;
+  let top = query.top.unwrap_or(DEFAULT_TOP);
+  if !(1..=MAX_TOP).contains(&top) {
+    return Err(
+      NginxLogError::InvalidQuery(format!(
+        "top must be between 1 and {}",
+        MAX_TOP
+      ))
+      .to_http_error(),
+    );
+  }
+  let hosts = scope_hosts(query.host, cluster_key, pool).await?;
+  repositories::nginx_log::stats(since, until, hosts, top, pool).await
+}
+
+/// Hosts of a host or of a cluster, none when neither is given
+/// the hosts of a cluster are his domains and route domains
+async fn scope_hosts(
+  host: Option<String>,
+  cluster_key: Option<String>,
+  pool: &web::types::State<Pool>,
+) -> Result<Option<Vec<String>>, HttpResponseError> {
+  match (host, cluster_key) {
+    (Some(_), Some(_)) => Err(
+      NginxLogError::InvalidQuery(String::from(
+        "host and cluster cannot be used together",
+      ))
+      .to_http_error(),
+    ),
+    (Some(host), None) => Ok(Some(vec![host])),
+    (None, Some(cluster_key)) => {
+      let cluster =
+        repositories::cluster::find_by_key(cluster_key.to_owned(), pool)
+          .await?;
+      let routes =
+        repositories::cluster_route::list_by_cluster(cluster_key, pool).await?;
+      let hosts = cluster
+        .domains
+        .into_iter()
+        .chain(routes.into_iter().map(|route| route.domain))
+        .collect::<BTreeSet<_>>();
+      Ok(Some(hosts.into_iter().collect()))
+    }
+    (None, None) => Ok(None),
+  }
+}
+
+/// Filter of a subscription to the stream of access logs
+#[derive(Debug, Clone)]
+pub struct NginxLogFilter {
+  hosts: Option<HashSet<String>>,
+  status_min: Option<i32>,
+  status_max: Option<i32>,
+  path: Option<Regex>,
+  sample: f64,
+  /// Number of matching logs used to sample them
+  matched: Arc<AtomicU64>,
+}
+
+impl Default for NginxLogFilter {
+  fn default() -> Self {
+    NginxLogFilter {
+      hosts: None,
+      status_min: None,
+      status_max: None,
+      path: None,
+      sample: 1.0,
+      matched: Arc::new(AtomicU64::new(0)),
+    }
+  }
+}
+
+impl NginxLogFilter {
+  fn new(
+    query: NginxLogStreamQuery,
+    hosts: Option<Vec<String>>,
+  ) -> Result<Self, NginxLogError> {
+    if let (Some(min), Some(max)) = (query.status_min, query.status_max) {
+      if min > max {
+        return Err(NginxLogError::InvalidQuery(String::from(
+          "status_min must be lower than status_max",
+        )));
+      }
+    }
+    let sample = query.sample.unwrap_or(1.0);
+    if !(sample > 0.0 && sample <= 1.0) {
+      return Err(NginxLogError::InvalidQuery(String::from(
+        "sample must be greater than 0 and lower or equal to 1",
+      )));
+    }
+    let path = query
+      .path
+      .map(|path| Regex::new(&path))
+      .transpose()
+      .map_err(|err| {
+        NginxLogError::InvalidQuery(format!("invalid path regex {}", err))
+      })?;
+    Ok(NginxLogFilter {
+      hosts: hosts.map(|hosts| hosts.into_iter().collect()),
+      status_min: query.status_min,
+      status_max: query.status_max,
+      path,
+      sample,
+      ..Default::default()
+    })
+  }
+
+  /// The log match the filters and is part of the sample
+  /// one log of every `1 / sample` matching logs is kept
+  pub fn matches(&self, item: &NginxLogItem) -> bool {
+    if let Some(hosts) = &self.hosts {
+      if !hosts.contains(&item.host) {
+        return false;
+      }
+    }
+    if self.status_min.map_or(false, |min| item.status < min)
+      || self.status_max.map_or(false, |max| item.status > max)
+    {
+      return false;
+    }
+    if let Some(path) = &self.path {
+      if !path.is_match(&item.uri) {
+        return false;
+      }
+    }
+    if self.sample >= 1.0 {
+      return true;
+    }
+    let count = self.matched.fetch_add(1, Ordering::Relaxed) as f64;
+    ((count + 1.0) * self.sample).floor() > (count * self.sample).floor()
+  }
+}
+
+/// Build the filter of a subscription to the stream of access logs
+pub async fn stream_filter(
+  query: NginxLogStreamQuery,
+  cluster_key: Option<String>,
+  pool: &web::types::State<Pool>,
+) -> Result<NginxLogFilter, HttpResponseError> {
+  let hosts = scope_hosts(query.host.to_owned(), cluster_key, pool).await?;
+  NginxLogFilter::new(query, hosts).map_err(|err| err.to_http_error())
+}
+
+/// Logs removed by a pruning
+#[derive(Debug, Default)]
+pub struct NginxLogPruneReport {
+  pub(crate) deleted: usize,
+  /// Days of the partitions dropped with their logs
+  pub(crate) partitions: Vec<NaiveDate>,
+  /// Files where the deleted logs were exported, one by batch
+  pub(crate) archives: Vec<PathBuf>,
+}
+
+/// Gzipped ndjson file receiving pruned logs
+struct Archive {
+  path: PathBuf,
+  encoder: GzEncoder<File>,
+}
+
+impl Archive {
+  /// Create an archive named after the date and the key of its first log
+  fn create(
+    state_dir: &str,
+    now: DateTime<Utc>,
+    key: &Uuid,
+  ) -> Result<Self, IoError> {
+    let dir_path = Path::new(state_dir).join("nginx/archives");
+    fs::create_dir_all(&dir_path)?;
+    let path = dir_path.join(format!(
+      "nginx-logs-{}-{}.ndjson.gz",
+      now.format("%Y%m%dT%H%M%SZ"),
+      key.to_simple()
+    ));
+    let encoder = GzEncoder::new(File::create(&path)?, Compression::default());
+    Ok(Archive { path, encoder })
+  }
+
+  fn write(&mut self, items: &[NginxLogItem]) -> Result<(), IoError> {
+    for item in items {
+      serde_json::to_writer(&mut self.encoder, item)?;
+      self.encoder.write_all(b"\n")?;
+    }
+    Ok(())
+  }
+
+  /// Complete the archive and sync it on disk with its directory entry
+  fn finish(self) -> Result<PathBuf, IoError> {
+    let file = self.encoder.finish()?;
+    file.sync_all()?;
+    if let Some(dir_path) = self.path.parent() {
+      File::open(dir_path)?.sync_all()?;
+    }
+    Ok(self.path)
+  }
+}
+
+/// Export a batch of logs in its own archive synced on disk
+fn archive_batch(
+  items: &[NginxLogItem],
+  state_dir: &str,
+) -> Result<Option<PathBuf>, IoError> {
+  let first = match items.first() {
+    None => return Ok(None),
+    Some(first) => first,
+  };
+  let mut archive = Archive::create(state_dir, Utc::now(), &first.key)?;
+  archive.write(items)?;
+  archive.finish().map(Some)
+}
+
+/// Delete the oldest logs in batches
+/// only the ones before the given date and at most the given number
+/// Each batch is archived on disk before being deleted
+async fn prune_oldest(
+  before: Option<DateTime<Utc>>,
+  mut limit: Option<i64>,
+  report: &mut NginxLogPruneReport,
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+) -> Result<(), HttpResponseError> {
+  loop {
+    let batch = limit.map_or(PRUNE_BATCH, |limit| limit.min(PRUNE_BATCH));
+    if batch <= 0 {
+      return Ok(());
+    }
+    let items =
+      repositories::nginx_log::list_oldest(before, batch, pool).await?;
+    if config.nginx_log_archive {
+      let path = archive_batch(&items, &config.state_dir)
+        .map_err(|err| NginxLogError::Io(err).to_http_error())?;
+      report.archives.extend(path);
+    }
+    let keys = items.iter().map(|item| item.key).collect::<Vec<_>>();
+    let res = repositories::nginx_log::delete_by_keys(keys, pool).await?;
+    report.deleted += res.count;
+    if let Some(limit) = limit.as_mut() {
+      *limit -= items.len() as i64;
+    }
+    if (items.len() as i64) < batch {
+      return Ok(());
+    }
+  }
+}
+
+/// Days of the partitions only holding logs before the given date
+fn expired_partitions(
+  days: Vec<NaiveDate>,
+  before: DateTime<Utc>,
+) -> Vec<NaiveDate> {
+  let mut days = days
+    .into_iter()
+    .filter(|day| day.succ().and_hms(0, 0, 0) <= before.naive_utc())
+    .collect::<Vec<_>>();
+  days.sort();
+  days
+}
+
+/// Drop the daily partitions of logs older than the given date
+async fn drop_partitions(
+  before: DateTime<Utc>,
+  report: &mut NginxLogPruneReport,
+  pool: &web::types::State<Pool>,
+) -> Result<(), HttpResponseError> {
+  let days = repositories::nginx_log::list_partitions(pool).await?;
+  for day in expired_partitions(days, before) {
+    repositories::nginx_log::drop_partition(day, pool).await?;
+    report.partitions.push(day);
+  }
+  Ok(())
+}
+
+/// Delete logs older than the retention and the oldest ones
+/// above the maximum number of logs
+/// Partitions older than the retention are dropped at once
+/// after their logs are archived
+pub async fn prune(
+  config: &DaemonConfig,
+  pool: &web::types::State<Pool>,
+) -> Result<NginxLogPruneReport, HttpResponseError> {
+  let mut report = NginxLogPruneReport::default();
+  if config.nginx_log_retention > 0 {
+    let before =
+      Utc::now() - Duration::days(i64::from(config.nginx_log_retention));
+    if !config.nginx_log_archive {
+      drop_partitions(before, &mut report, pool).await?;
+    }
+    prune_oldest(Some(before), None, &mut report, config, pool).await?;
+    if config.nginx_log_archive {
+      drop_partitions(before, &mut report, pool).await?;
+    }
+  }
+  if config.nginx_log_max_rows > 0 {
+    let count = repositories::nginx_log::count(pool).await?;
+    let excess = count - config.nginx_log_max_rows;
+    prune_oldest(None, Some(excess), &mut report, config, pool).await?;
+  }
+  Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  fn gen_log(date_gmt: &str, request_time: f64) -> NginxLogItem {
+    NginxLogItem {
+      key: Uuid::new_v4(),
+      date_gmt: DateTime::parse_from_rfc3339(date_gmt)
+        .unwrap()
+        .with_timezone(&Utc),
+      uri: String::from("/"),
+      host: String::from("example.com"),
+      remote_addr: String::from("10.0.0.1"),
+      realip_remote_addr: String::from("10.0.0.1"),
+      server_protocol: String::from("HTTP/1.1"),
+      request_method: String::from("GET"),
+      content_length: 0,
+      status: 200,
+      request_time,
+      body_bytes_sent: 0,
+      proxy_host: None,
+      upstream_addr: None,
+      query_string: None,
+      request_body: None,
+      content_type: None,
+      http_user_agent: None,
+      http_referrer: None,
+      http_accept_language: None,
+    }
+  }
+
+  #[test]
+  fn test_cursor() {
+    let item = gen_log("2022-08-08T10:20:30.123456+02:00", 0.042);
+
+    let cursor = encode_cursor(NginxLogSort::DateDesc, &item);
+    assert_eq!(
+      decode_cursor(NginxLogSort::DateAsc, &cursor).unwrap(),
+      NginxLogCursor::Date(item.date_gmt, item.key)
+    );
+    assert!(decode_cursor(NginxLogSort::RequestTimeAsc, &cursor).is_err());
+
+    let cursor = encode_cursor(NginxLogSort::RequestTimeDesc, &item);
+    assert_eq!(
+      decode_cursor(NginxLogSort::RequestTimeDesc, &cursor).unwrap(),
+      NginxLogCursor::RequestTime(0.042, item.key)
+    );
+    assert!(decode_cursor(NginxLogSort::DateDesc, "not a cursor").is_err());
+  }
+
+  #[test]
+  fn test_validate() {
+    let query = NginxLogSearchQuery {
+      status_min: Some(500),
+      status_max: Some(400),
+      ..Default::default()
+    };
+    assert!(validate(&query).is_err());
+    let query = NginxLogSearchQuery {
+      limit: Some(0),
+      ..Default::default()
+    };
+    assert!(validate(&query).is_err());
+    let query = NginxLogSearchQuery {
+      status_min: Some(400),
+      status_max: Some(499),
+      limit: Some(50),
+      ..Default::default()
+    };
+    assert!(validate(&query).is_ok());
+  }
+
+  #[test]
+  fn test_archive() -> Result<(), IoError> {
+    use std::io::Read;
+    use flate2::read::GzDecoder;
+
+    let state_dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    let items = vec![
+      gen_log("2022-08-08T10:20:30Z", 0.1),
+      gen_log("2022-08-08T10:20:31Z", 0.2),
+    ];
+    assert!(archive_batch(&[], &state_dir.to_string_lossy())?.is_none());
+    let path = archive_batch(&items, &state_dir.to_string_lossy())?.unwrap();
+    assert!(path
+      .to_string_lossy()
+      .ends_with(&format!("-{}.ndjson.gz", items[0].key.to_simple())));
+    let mut content = String::new();
+    GzDecoder::new(File::open(&path)?).read_to_string(&mut content)?;
+    let items = content
+      .lines()
+      .map(serde_json::from_str::<NginxLogItem>)
+      .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[1].request_time, 0.2);
+    fs::remove_dir_all(state_dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_expired_partitions() {
+    let day =
+      |value: &str| NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap();
+    let before = DateTime::parse_from_rfc3339("2022-08-10T12:00:00Z")
+      .unwrap()
+      .with_timezone(&Utc);
+    let days = vec![
+      day("2022-08-10"),
+      day("2022-08-08"),
+      day("2022-08-09"),
+      day("2022-08-11"),
+    ];
+    assert_eq!(
+      expired_partitions(days.to_owned(), before),
+      vec![day("2022-08-08"), day("2022-08-09")]
+    );
+    let midnight = DateTime::parse_from_rfc3339("2022-08-10T00:00:00Z")
+      .unwrap()
+      .with_timezone(&Utc);
+    assert_eq!(
+      expired_partitions(days, midnight),
+      vec![day("2022-08-08"), day("2022-08-09")]
+    );
+  }
+
+  #[test]
+  fn test_stats_window() {
+    let now = Utc::now();
+    let (since, until) =
+      stats_window(&NginxStatsQuery::default(), now).unwrap();
+    assert_eq!(until, now);
+    assert_eq!(until - since, Duration::minutes(DEFAULT_STATS_WINDOW));
+    let query = NginxStatsQuery {
+      since: Some(now - Duration::days(MAX_STATS_WINDOW + 1)),
+      ..Default::default()
+    };
+    assert!(stats_window(&query, now).is_err());
+    let query = NginxStatsQuery {
+      since: Some(now),
+      until: Some(now - Duration::minutes(1)),
+      ..Default::default()
+    };
+    assert!(stats_window(&query, now).is_err());
+  }
+
+  #[test]
+  fn test_stream_filter() {
+    let query = NginxLogStreamQuery {
+      status_min: Some(400),
+      path: Some(String::from("^/api/")),
+      sample: Some(0.5),
+      ..Default::default()
+    };
+    let filter =
+      NginxLogFilter::new(query, Some(vec![String::from("example.com")]))
+        .unwrap();
+    let mut item = gen_log("2022-08-12T10:00:00Z", 0.1);
+    item.uri = String::from("/api/users");
+    item.status = 404;
+    let matched = (0..4).filter(|_| filter.matches(&item)).count();
+    assert_eq!(matched, 2);
+    item.status = 200;
+    assert!(!filter.matches(&item));
+    item.status = 500;
+    item.host = String::from("other.com");
+    assert!(!filter.matches(&item));
+    item.host = String::from("example.com");
+    item.uri = String::from("/");
+    assert!(!filter.matches(&item));
+
+    let query = NginxLogStreamQuery {
+      path: Some(String::from("(")),
+      ..Default::default()
+    };
+    assert!(NginxLogFilter::new(query, None).is_err());
+    let query = NginxLogStreamQuery {
+      sample: Some(0.0),
+      ..Default::default()
+    };
+    assert!(NginxLogFilter::new(query, None).is_err());
+  }
+}
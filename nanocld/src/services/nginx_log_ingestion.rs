@@ -0,0 +1,237 @@
+//! Write access logs read by the proxy in database by batches
+//! Logs are buffered until the batch is full or the flush interval elapsed.
+//! Reading is paused when too many logs wait to be written,
+//! the access log file then hold the logs not ingested yet.
+use ntex::web;
+use chrono::{NaiveDate, Utc};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::repositories;
+use crate::config::DaemonConfig;
+use crate::errors::HttpResponseError;
+use crate::models::{Pool, NginxLogItem, NginxLogPartial, NginxLogIngestionMetrics};
+
+/// Number of batches which can wait to be written before reading is paused
+const BUFFERED_BATCHES: usize = 10;
+/// Number of days of partitions created in advance
+const PARTITIONS_AHEAD: u32 = 2;
+
+pub type SharedNginxLogIngestionMetrics = Arc<Mutex<NginxLogIngestionMetrics>>;
+
+pub struct NginxLogIngestion {
+  batch_size: usize,
+  capacity: usize,
+  flush_interval: Duration,
+  buffer: Vec<NginxLogItem>,
+  /// When buffered logs must be written
+  deadline: Option<Instant>,
+  /// The last write failed, the next one waits for the flush interval
+  retrying: bool,
+  metrics: SharedNginxLogIngestionMetrics,
+}
+
+impl NginxLogIngestion {
+  pub fn new(
+    config: &DaemonConfig,
+    metrics: SharedNginxLogIngestionMetrics,
+  ) -> Self {
+    let batch_size = config.nginx_log_batch_size.max(1);
+    let ingestion = NginxLogIngestion {
+      batch_size,
+      capacity: batch_size * BUFFERED_BATCHES,
+      flush_interval: Duration::from_millis(config.nginx_log_flush_interval),
+      buffer: Vec::new(),
+      deadline: None,
+      retrying: false,
+      metrics,
+    };
+    ingestion.update_metrics(|metrics| {
+      metrics.batch_size = ingestion.batch_size;
+      metrics.capacity = ingestion.capacity;
+    });
+    ingestion
+  }
+
+  fn update_metrics<F>(&self, update: F)
+  where
+    F: FnOnce(&mut NginxLogIngestionMetrics),
+  {
+    match self.metrics.lock() {
+      Err(err) => log::error!("Unable to lock nginx log metrics {:#?}", err),
+      Ok(mut metrics) => {
+        update(&mut metrics);
+        metrics.buffered = self.buffer.len();
+        metrics.throttled = self.is_full();
+      }
+    }
+  }
+
+  /// Parse and buffer lines of the access log
+  pub fn push_lines(&mut self, lines: Vec<String>) {
+    if lines.is_empty() {
+      return;
+    }
+    let received = lines.len() as u64;
+    let mut parse_errors = 0;
+    for line in lines {
+      match serde_json::from_str::<NginxLogPartial>(&line) {
+        Err(err) => {
+          log::error!("Parsing nginx log fail {}", err);
+          parse_errors += 1;
+        }
+        Ok(partial) => self.buffer.push(NginxLogItem::from(partial)),
+      }
+    }
+    if !self.buffer.is_empty() && self.deadline.is_none() {
+      self.deadline = Some(Instant::now() + self.flush_interval);
+    }
+    if self.buffer.len() >= self.batch_size && !self.retrying {
+      self.deadline = Some(Instant::now());
+    }
+    self.update_metrics(|metrics| {
+      metrics.received += received;
+      metrics.parse_errors += parse_errors;
+    });
+  }
+
+  /// Too many logs wait to be written to read more
+  pub fn is_full(&self) -> bool {
+    self.buffer.len() >= self.capacity
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.buffer.is_empty()
+  }
+
+  /// Time before buffered logs must be written, none when there is none
+  pub fn flush_in(&self) -> Option<Duration> {
+    self
+      .deadline
+      .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+  }
+
+  pub fn set_lag(&self, lag: u64) {
+    self.update_metrics(|metrics| metrics.lag_bytes = lag);
+  }
+
+  /// Write buffered logs in database when their deadline is reached
+  /// and return them once written
+  pub async fn flush(
+    &mut self,
+    pool: &web::types::State<Pool>,
+  ) -> Result<Vec<NginxLogItem>, HttpResponseError> {
+    if self.flush_in() != Some(Duration::ZERO) {
+      return Ok(Vec::new());
+    }
+    let started = Instant::now();
+    let res =
+      repositories::nginx_log::create_logs(self.buffer.to_owned(), pool).await;
+    let duration = started.elapsed().as_millis() as u64;
+    match res {
+      Err(err) => {
+        self.retrying = true;
+        self.deadline = Some(Instant::now() + self.flush_interval);
+        self.update_metrics(|metrics| {
+          metrics.failed_batches += 1;
+          metrics.last_flush_duration = duration;
+          metrics.last_error = Some(err.msg.to_owned());
+        });
+        Err(err)
+      }
+      Ok(count) => {
+        let items = std::mem::take(&mut self.buffer);
+        self.retrying = false;
+        self.deadline = None;
+        self.update_metrics(|metrics| {
+          metrics.inserted += count as u64;
+          metrics.batches += 1;
+          metrics.last_flush_at = Some(Utc::now());
+          metrics.last_flush_duration = duration;
+          metrics.last_error = None;
+        });
+        Ok(items)
+      }
+    }
+  }
+}
+
+/// Create the daily partitions of logs from the given day
+/// logs of a day without partition are stored in the default one
+pub async fn create_partitions(
+  from: NaiveDate,
+  pool: &web::types::State<Pool>,
+) {
+  let mut day = from;
+  for _ in 0..=PARTITIONS_AHEAD {
+    if let Err(err) = repositories::nginx_log::create_partition(day, pool).await
+    {
+      log::warn!(
+        "Unable to create nginx log partition of {} {}",
+        day,
+        err.msg
+      );
+    }
+    day = day.succ();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  use crate::utils::test::*;
+
+  fn gen_line(status: &str) -> String {
+    serde_json::json!({
+      "date_gmt": "2022-08-11T10:20:30+02:00",
+      "uri": "/",
+      "host": "example.com",
+      "remote_addr": "10.0.0.1",
+      "realip_remote_addr": "10.0.0.1",
+      "server_protocol": "HTTP/1.1",
+      "request_method": "GET",
+      "content_length": "0",
+      "status": status,
+      "request_time": "0.042",
+      "body_bytes_sent": "12",
+      "proxy_host": "",
+      "upstream_addr": "",
+      "query_string": "",
+      "request_body": "",
+      "content_type": "",
+      "http_user_agent": "",
+      "http_referrer": "",
+      "http_accept_language": "",
+    })
+    .to_string()
+  }
+
+  #[test]
+  fn test_push_lines() {
+    let mut config = gen_daemon_config();
+    config.nginx_log_batch_size = 2;
+    config.nginx_log_flush_interval = 60_000;
+    let metrics = SharedNginxLogIngestionMetrics::default();
+    let mut ingestion = NginxLogIngestion::new(&config, metrics.clone());
+    assert_eq!(ingestion.flush_in(), None);
+
+    ingestion.push_lines(vec![gen_line("200"), String::from("not a log")]);
+    assert!(ingestion.flush_in().unwrap() > Duration::ZERO);
+    ingestion.push_lines(vec![gen_line("404")]);
+    assert_eq!(ingestion.flush_in(), Some(Duration::ZERO));
+    assert!(!ingestion.is_full());
+
+    let lines = (0..18).map(|_| gen_line("200")).collect::<Vec<_>>();
+    ingestion.push_lines(lines);
+    assert!(ingestion.is_full());
+
+    let metrics = metrics.lock().unwrap();
+    assert_eq!(metrics.received, 21);
+    assert_eq!(metrics.parse_errors, 1);
+    assert_eq!(metrics.buffered, 20);
+    assert_eq!(metrics.capacity, 20);
+    assert!(metrics.throttled);
+  }
+}
@@ -0,0 +1,686 @@
+//! Revisions of nginx templates
+//! Every update is stored as a new revision and clusters using the template
+//! are rendered again without starting their containers.
+//! Contents are checked and rendered with `nginx -t` for every cluster
+//! before being stored, tags are matched against the data given to templates
+//! when a cluster is started.
+use ntex::web;
+use ntex::http::StatusCode;
+use thiserror::Error;
+use std::collections::HashSet;
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+use crate::models::{
+  Pool, NginxTemplateDiff, NginxTemplateUpdateBody, NginxTemplateUpdateReport,
+  PgDeleteGeneric, NginxTemplateItem, NginxTemplateDiagnostic,
+  NginxTemplateDiagnosticLevel,
+};
+
+#[derive(Debug, Error)]
+pub enum NginxTemplateError {
+  #[error("invalid nginx template")]
+  Invalid(Vec<NginxTemplateDiagnostic>),
+  #[error("unable to render nginx template")]
+  Render(Vec<String>),
+}
+
+impl IntoHttpResponseError for NginxTemplateError {
+  fn to_http_error(&self) -> HttpResponseError {
+    match self {
+      NginxTemplateError::Invalid(diagnostics) => HttpResponseError {
+        msg: format!(
+          "invalid nginx template\n{}",
+          diagnostics
+            .iter()
+            .map(|diagnostic| format!(
+              "{}:{} {}",
+              diagnostic.line, diagnostic.column, diagnostic.message
+            ))
+            .collect::<Vec<_>>()
+            .join("\n")
+        ),
+        status: StatusCode::BAD_REQUEST,
+      },
+      NginxTemplateError::Render(errors) => HttpResponseError {
+        msg: format!("unable to render nginx template\n{}", errors.join("\n")),
+        status: StatusCode::BAD_REQUEST,
+      },
+    }
+  }
+}
+
+/// Shape of the data given to templates
+/// it must follow `TemplateData` in the cluster service
+#[derive(Debug, Clone)]
+enum Schema {
+  Value,
+  List(Box<Schema>),
+  /// Object with keys only known when rendering
+  Map(Box<Schema>),
+  /// Variables of the cluster checked against the attached clusters
+  Vars,
+  Object(Vec<(&'static str, Schema)>),
+}
+
+impl Schema {
+  fn child(&self, key: &str) -> Option<Schema> {
+    match self {
+      Schema::Object(fields) => fields
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, schema)| schema.to_owned()),
+      Schema::Map(schema) => Some(*schema.to_owned()),
+      Schema::Vars => Some(Schema::Value),
+      Schema::Value | Schema::List(_) => None,
+    }
+  }
+}
+
+fn template_data_schema() -> Schema {
+  let list = || Schema::List(Box::new(Schema::Value));
+  let cargo_network =
+    Schema::Object(vec![("target_ip", Schema::Value), ("target_ips", list())]);
+  let cargo = Schema::Object(vec![
+    ("name", Schema::Value),
+    ("target_ip", Schema::Value),
+    ("dns_entry", Schema::Value),
+    ("target_ips", list()),
+    ("networks", Schema::Map(Box::new(cargo_network))),
+  ]);
+  let network = Schema::Object(vec![("gateway", Schema::Value)]);
+  let certificate = Schema::Object(vec![
+    ("certificate", Schema::Value),
+    ("certificate_key", Schema::Value),
+  ]);
+  Schema::Object(vec![
+    ("vars", Schema::Vars),
+    ("cargoes", Schema::Map(Box::new(cargo))),
+    ("networks", Schema::Map(Box::new(network))),
+    ("certificates", Schema::Map(Box::new(certificate))),
+    ("acme_challenge", Schema::Value),
+    ("access_policies", Schema::Map(Box::new(Schema::Value))),
+    ("auth_policies", Schema::Map(Box::new(Schema::Value))),
+    ("error_pages", Schema::Value),
+  ])
+}
+
+/// Line and column of a byte offset
+fn position(content: &str, offset: usize) -> (usize, usize) {
+  let before = &content[..offset];
+  let line = before.matches('\n').count() + 1;
+  let column = before
+    .rsplit('\n')
+    .next()
+    .unwrap_or_default()
+    .chars()
+    .count();
+  (line, column + 1)
+}
+
+/// Line and column of the first directive only found in http servers
+fn find_http_server(content: &str) -> Option<(usize, usize)> {
+  content
+    .lines()
+    .enumerate()
+    .find(|(_, line)| {
+      let directive = line.trim_start();
+      ["server_name ", "location "]
+        .iter()
+        .any(|name| directive.starts_with(name))
+    })
+    .map(|(index, line)| (index + 1, line.len() - line.trim_start().len() + 1))
+}
+
+struct Section {
+  name: String,
+  /// Whether the section pushed a context on the stack
+  is_pushing: bool,
+  line: usize,
+  column: usize,
+}
+
+struct Linter<'a> {
+  /// Variable names by cluster key
+  variables: &'a [(String, HashSet<String>)],
+  stack: Vec<Schema>,
+  diagnostics: Vec<NginxTemplateDiagnostic>,
+}
+
+impl<'a> Linter<'a> {
+  fn push(
+    &mut self,
+    level: NginxTemplateDiagnosticLevel,
+    line: usize,
+    column: usize,
+    message: String,
+  ) {
+    self.diagnostics.push(NginxTemplateDiagnostic {
+      level,
+      line,
+      column,
+      message,
+    });
+  }
+
+  /// Resolve a name like mustache by looking for his first part
+  /// from the innermost context
+  fn resolve(
+    &mut self,
+    name: &str,
+    line: usize,
+    column: usize,
+  ) -> Option<Schema> {
+    if name == "." {
+      return self.stack.last().cloned();
+    }
+    let mut parts = name.split('.');
+    let first = parts.next().unwrap_or_default();
+    let found = self.stack.iter().rev().find_map(|context| {
+      context.child(first).map(|schema| {
+        let var = matches!(context, Schema::Vars).then(|| first.to_owned());
+        (schema, var)
+      })
+    });
+    let (mut schema, mut var) = match found {
+      None => {
+        self.push(
+          NginxTemplateDiagnosticLevel::Error,
+          line,
+          column,
+          format!("unknown name {}", name),
+        );
+        return None;
+      }
+      Some(found) => found,
+    };
+    for part in parts {
+      if let Schema::Vars = schema {
+        var = Some(part.to_owned());
+      }
+      schema = match schema.child(part) {
+        None => {
+          self.push(
+            NginxTemplateDiagnosticLevel::Error,
+            line,
+            column,
+            format!("unknown name {}", name),
+          );
+          return None;
+        }
+        Some(schema) => schema,
+      };
+    }
+    if let Some(var) = var {
+      for (cluster_key, names) in self.variables {
+        if !names.contains(&var) {
+          self.push(
+            NginxTemplateDiagnosticLevel::Warning,
+            line,
+            column,
+            format!(
+              "variable {} is not defined in cluster {}",
+              var, cluster_key
+            ),
+          );
+        }
+      }
+    }
+    Some(schema)
+  }
+}
+
+/// Check the tags of a template and the names they reference
+/// variables are checked against the given variable names of each cluster
+pub fn lint_content(
+  content: &str,
+  variables: &[(String, HashSet<String>)],
+) -> Vec<NginxTemplateDiagnostic> {
+  let mut linter = Linter {
+    variables,
+    stack: vec![template_data_schema()],
+    diagnostics: Vec::new(),
+  };
+  let mut sections: Vec<Section> = Vec::new();
+  let mut has_error_pages = false;
+  let (mut open, mut close) = (String::from("{{"), String::from("}}"));
+  let mut offset = 0;
+  while let Some(start) = content[offset..].find(&open) {
+    let start = offset + start;
+    let (line, column) = position(content, start);
+    let mut tag_start = start + open.len();
+    let mut tag_close = close.to_owned();
+    // Unescaped variables use a third brace with the default delimiters
+    if open == "{{" && content[tag_start..].starts_with('{') {
+      tag_start += 1;
+      tag_close = format!("}}{}", close);
+    }
+    let end = match content[tag_start..].find(&tag_close) {
+      None => {
+        linter.push(
+          NginxTemplateDiagnosticLevel::Error,
+          line,
+          column,
+          String::from("unclosed tag"),
+        );
+        break;
+      }
+      Some(end) => tag_start + end,
+    };
+    offset = end + tag_close.len();
+    let tag = content[tag_start..end].trim();
+    let (sigil, name) = match tag.chars().next() {
+      Some(sigil @ ('#' | '^' | '/' | '!' | '>' | '&' | '=')) => {
+        (Some(sigil), tag[1..].trim())
+      }
+      _ => (None, tag),
+    };
+    match sigil {
+      Some('!') => {}
+      Some('=') => {
+        let delimiters = tag.trim_matches('=').split_whitespace();
+        match delimiters.collect::<Vec<_>>()[..] {
+          [new_open, new_close] => {
+            open = new_open.to_owned();
+            close = new_close.to_owned();
+          }
+          _ => linter.push(
+            NginxTemplateDiagnosticLevel::Error,
+            line,
+            column,
+            format!("invalid delimiters {}", tag),
+          ),
+        }
+      }
+      _ if name.is_empty() => linter.push(
+        NginxTemplateDiagnosticLevel::Error,
+        line,
+        column,
+        String::from("empty tag"),
+      ),
+      Some('>') => linter.push(
+        NginxTemplateDiagnosticLevel::Error,
+        line,
+        column,
+        format!("partial {} is not supported", name),
+      ),
+      Some('/') => match sections.pop() {
+        None => linter.push(
+          NginxTemplateDiagnosticLevel::Error,
+          line,
+          column,
+          format!("closing tag {} without section", name),
+        ),
+        Some(section) => {
+          if section.is_pushing {
+            linter.stack.pop();
+          }
+          if section.name != name {
+            linter.push(
+              NginxTemplateDiagnosticLevel::Error,
+              line,
+              column,
+              format!(
+                "closing tag {} does not match section {} opened at {}:{}",
+                name, section.name, section.line, section.column
+              ),
+            );
+          }
+        }
+      },
+      Some(sigil @ ('#' | '^')) => {
+        has_error_pages |= name == "error_pages";
+        let schema = linter.resolve(name, line, column);
+        // Inverted sections are rendered without context
+        let is_pushing = sigil == '#' && schema.is_some();
+        if let Some(schema) = schema.filter(|_| is_pushing) {
+          linter.stack.push(match schema {
+            Schema::List(item) => *item,
+            schema => schema,
+          });
+        }
+        sections.push(Section {
+          name: name.to_owned(),
+          is_pushing,
+          line,
+          column,
+        });
+      }
+      _ => {
+        has_error_pages |= name == "error_pages";
+        linter.resolve(name, line, column);
+      }
+    }
+  }
+  // Maintenance and error pages are only served by http servers including them
+  if !has_error_pages {
+    if let Some((line, column)) = find_http_server(content) {
+      linter.push(
+        NginxTemplateDiagnosticLevel::Warning,
+        line,
+        column,
+        String::from(
+          "http servers must include {{{error_pages}}} \
+           to serve the maintenance and error pages",
+        ),
+      );
+    }
+  }
+  for section in sections {
+    linter.push(
+      NginxTemplateDiagnosticLevel::Error,
+      section.line,
+      section.column,
+      format!("section {} is not closed", section.name),
+    );
+  }
+  let has_error = linter
+    .diagnostics
+    .iter()
+    .any(|diagnostic| diagnostic.level == NginxTemplateDiagnosticLevel::Error);
+  // The linter may accept a template the mustache parser refuses
+  if let (false, Err(err)) = (has_error, mustache::compile_str(content)) {
+    linter.push(
+      NginxTemplateDiagnosticLevel::Error,
+      1,
+      1,
+      format!("{}", err),
+    );
+  }
+  linter.diagnostics
+}
+
+/// Check a template against the variables of the clusters using it
+pub async fn lint(
+  name: &str,
+  content: &str,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<NginxTemplateDiagnostic>, HttpResponseError> {
+  let clusters =
+    repositories::cluster::list_by_proxy_template(name.to_owned(), pool)
+      .await?;
+  let mut variables = Vec::new();
+  for cluster in clusters {
+    let names = repositories::cluster_variable::list_by_cluster(
+      cluster.key.to_owned(),
+      pool,
+    )
+    .await?
+    .into_iter()
+    .map(|variable| variable.name)
+    .collect::<HashSet<_>>();
+    variables.push((cluster.key, names));
+  }
+  Ok(lint_content(content, &variables))
+}
+
+/// Lint a template and refuse it when an error is found
+/// warnings are returned
+async fn check(
+  name: &str,
+  content: &str,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<NginxTemplateDiagnostic>, HttpResponseError> {
+  let diagnostics = lint(name, content, pool).await?;
+  let (errors, warnings): (Vec<_>, Vec<_>) =
+    diagnostics.into_iter().partition(|diagnostic| {
+      diagnostic.level == NginxTemplateDiagnosticLevel::Error
+    });
+  if !errors.is_empty() {
+    return Err(NginxTemplateError::Invalid(errors).to_http_error());
+  }
+  Ok(warnings)
+}
+
+/// Create a template after checking his content
+pub async fn create(
+  item: NginxTemplateItem,
+  pool: &web::types::State<Pool>,
+) -> Result<NginxTemplateItem, HttpResponseError> {
+  check(&item.name, &item.content, pool).await?;
+  repositories::nginx_template::create(item, pool).await
+}
+
+/// Line diff of two contents based on their longest common subsequence
+pub fn diff_lines(from: &str, to: &str) -> Vec<String> {
+  let from = from.lines().collect::<Vec<_>>();
+  let to = to.lines().collect::<Vec<_>>();
+  // lengths[i][j] is the length of the longest common subsequence
+  // of from[i..] and to[j..]
+  let mut lengths = vec![vec![0; to.len() + 1]; from.len() + 1];
+  for i in (0..from.len()).rev() {
+    for j in (0..to.len()).rev() {
+      lengths[i][j] = if from[i] == to[j] {
+        lengths[i + 1][j + 1] + 1
+      } else {
+        lengths[i + 1][j].max(lengths[i][j + 1])
+      };
+    }
+  }
+  let (mut i, mut j) = (0, 0);
+  let mut lines = Vec::new();
+  while i < from.len() && j < to.len() {
+    if from[i] == to[j] {
+      lines.push(format!(" {}", from[i]));
+      i += 1;
+      j += 1;
+    } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+      lines.push(format!("-{}", from[i]));
+      i += 1;
+    } else {
+      lines.push(format!("+{}", to[j]));
+      j += 1;
+    }
+  }
+  lines.extend(from[i..].iter().map(|line| format!("-{}", line)));
+  lines.extend(to[j..].iter().map(|line| format!("+{}", line)));
+  lines
+}
+
+/// Diff between two revisions of a template
+pub async fn diff(
+  name: String,
+  from: i32,
+  to: i32,
+  pool: &web::types::State<Pool>,
+) -> Result<NginxTemplateDiff, HttpResponseError> {
+  let from_revision =
+    repositories::nginx_template_revision::find(name.to_owned(), from, pool)
+      .await?;
+  let to_revision =
+    repositories::nginx_template_revision::find(name, to, pool).await?;
+  Ok(NginxTemplateDiff {
+    from,
+    to,
+    lines: diff_lines(&from_revision.content, &to_revision.content),
+  })
+}
+
+/// Store a new revision of a template and render again
+/// every cluster using it without starting their containers
+/// the revision is only stored when every cluster pass `nginx -t` with it
+pub async fn update(
+  name: String,
+  item: NginxTemplateUpdateBody,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<NginxTemplateUpdateReport, HttpResponseError> {
+  let warnings = check(&name, &item.content, pool).await?;
+  let current =
+    repositories::nginx_template::get_by_name(name.to_owned(), pool).await?;
+  let template = NginxTemplateItem {
+    name: name.to_owned(),
+    mode: item.mode.to_owned().unwrap_or(current.mode),
+    content: item.content.to_owned(),
+  };
+  let clusters =
+    repositories::cluster::list_by_proxy_template(name.to_owned(), pool)
+      .await?;
+  let mut errors = Vec::new();
+  for cluster in &clusters {
+    let res = services::cluster::test_template(
+      cluster, &template, config, pool, docker_api,
+    )
+    .await;
+    if let Err(err) = res {
+      errors.push(format!(
+        "unable to render cluster {}: {}",
+        cluster.key, err.msg
+      ));
+    }
+  }
+  if !errors.is_empty() {
+    return Err(NginxTemplateError::Render(errors).to_http_error());
+  }
+  let revision = repositories::nginx_template::update(name, item, pool).await?;
+  let mut report = NginxTemplateUpdateReport {
+    revision,
+    rendered: Vec::new(),
+    errors: Vec::new(),
+    warnings,
+  };
+  for cluster in clusters {
+    let res =
+      services::cluster::render(&cluster, config, pool, docker_api, actor)
+        .await;
+    match res {
+      Err(err) => report.errors.push(format!(
+        "unable to render cluster {}: {}",
+        cluster.key, err.msg
+      )),
+      Ok(_) => report.rendered.push(cluster.key),
+    }
+  }
+  Ok(report)
+}
+
+/// Store the content of a previous revision as a new revision
+pub async fn rollback(
+  name: String,
+  revision: i32,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<NginxTemplateUpdateReport, HttpResponseError> {
+  let revision = repositories::nginx_template_revision::find(
+    name.to_owned(),
+    revision,
+    pool,
+  )
+  .await?;
+  let item = NginxTemplateUpdateBody {
+    mode: Some(revision.mode),
+    content: revision.content,
+  };
+  update(name, item, config, docker_api, pool, actor).await
+}
+
+/// Delete a template with his revisions unless a cluster still use it
+pub async fn delete(
+  name: String,
+  pool: &web::types::State<Pool>,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  let clusters =
+    repositories::cluster::list_by_proxy_template(name.to_owned(), pool)
+      .await?;
+  if !clusters.is_empty() {
+    let keys = clusters
+      .into_iter()
+      .map(|cluster| cluster.key)
+      .collect::<Vec<_>>();
+    return Err(HttpResponseError {
+      msg: format!(
+        "nginx template {} is used by clusters {}",
+        name,
+        keys.join(", ")
+      ),
+      status: StatusCode::CONFLICT,
+    });
+  }
+  repositories::nginx_template::delete_by_name(name, pool).await
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_diff_lines() {
+    let from = "server {\n  listen 80;\n  return 404;\n}";
+    let to = "server {\n  listen 80;\n  listen 443 ssl;\n}\n";
+    assert_eq!(
+      diff_lines(from, to),
+      vec![
+        " server {",
+        "   listen 80;",
+        "-  return 404;",
+        "+  listen 443 ssl;",
+        " }",
+      ]
+    );
+    assert_eq!(diff_lines("", "a"), vec!["+a"]);
+    assert_eq!(diff_lines("a", ""), vec!["-a"]);
+  }
+
+  fn messages(diagnostics: Vec<NginxTemplateDiagnostic>) -> Vec<String> {
+    diagnostics
+      .into_iter()
+      .map(|diagnostic| {
+        format!(
+          "{:?} {}:{} {}",
+          diagnostic.level,
+          diagnostic.line,
+          diagnostic.column,
+          diagnostic.message
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_lint_content() {
+    let variables = vec![(
+      String::from("global-dev"),
+      HashSet::from([String::from("domain")]),
+    )];
+    let content = "server {
+  server_name {{vars.domain}} {{vars.alias}};
+  {{#cargoes.api.target_ips}}
+  server {{.}}:8080;
+  {{/cargoes.api.target_ips}}
+  {{#cargoes.api}}proxy_pass http://{{target_ip}};{{/cargoes.api}}
+  {{{networks.front.gateway}}} {{cargoes.api.ip}} {{cargos}}
+  {{! comment }}{{^acme_challenge}}{{/acme_challenge}}
+  {{{error_pages}}}
+}";
+    assert_eq!(
+      messages(lint_content(content, &variables)),
+      vec![
+        "Warning 2:31 variable alias is not defined in cluster global-dev",
+        "Error 7:32 unknown name cargoes.api.ip",
+        "Error 7:51 unknown name cargos",
+      ]
+    );
+    assert_eq!(
+      messages(lint_content("{{#vars}}\n{{/cargoes}}\n{{name", &[])),
+      vec![
+        "Error 2:1 closing tag cargoes does not match section vars opened at 1:1",
+        "Error 3:1 unclosed tag",
+      ]
+    );
+    assert_eq!(
+      messages(lint_content("{{#cargoes}}{{web.name}}", &[])),
+      vec!["Error 1:1 section cargoes is not closed"]
+    );
+    assert!(lint_content("{{=<% %>=}}<% vars.domain %> {{", &[]).is_empty());
+    assert_eq!(
+      messages(lint_content("server {\n  location / {}\n}", &[])),
+      vec!["Warning 2:3 http servers must include {{{error_pages}}} to serve the maintenance and error pages"]
+    );
+  }
+}
@@ -0,0 +1,225 @@
+//! Enforce namespace resource quotas
+//! A replica is a container of a cargo joined to a cluster
+use ntex::web;
+use ntex::http::StatusCode;
+
+use crate::repositories;
+use crate::errors::HttpResponseError;
+use crate::models::{Pool, CargoItem, NamespaceQuotaItem, NamespaceUsage};
+
+/// Compute resources used by a namespace
+pub async fn get_usage(
+  namespace: &str,
+  pool: &web::types::State<Pool>,
+) -> Result<NamespaceUsage, HttpResponseError> {
+  let cargoes = repositories::cargo::count(namespace.to_owned(), pool).await?;
+  let clusters =
+    repositories::cluster::count(namespace.to_owned(), pool).await?;
+  let networks = repositories::cluster_network::count_by_namespace(
+    namespace.to_owned(),
+    pool,
+  )
+  .await?;
+  let replicas = repositories::namespace_quota::list_replica_limits(
+    namespace.to_owned(),
+    pool,
+  )
+  .await?;
+  Ok(NamespaceUsage {
+    cargoes: cargoes.count,
+    clusters: clusters.count,
+    networks: networks.count,
+    replicas: replicas.len() as i64,
+    memory: replicas.iter().filter_map(|(memory, _)| *memory).sum(),
+    cpu: replicas.iter().filter_map(|(_, cpu)| *cpu).sum(),
+  })
+}
+
+/// Ensure requested resources added to the usage stay in the quota
+pub fn check_quota(
+  quota: &NamespaceQuotaItem,
+  usage: &NamespaceUsage,
+  requested: &NamespaceUsage,
+) -> Result<(), HttpResponseError> {
+  let limits = [
+    (
+      "cargoes",
+      quota.max_cargoes,
+      usage.cargoes,
+      requested.cargoes,
+    ),
+    (
+      "clusters",
+      quota.max_clusters,
+      usage.clusters,
+      requested.clusters,
+    ),
+    (
+      "networks",
+      quota.max_networks,
+      usage.networks,
+      requested.networks,
+    ),
+    (
+      "replicas",
+      quota.max_replicas,
+      usage.replicas,
+      requested.replicas,
+    ),
+    ("memory", quota.max_memory, usage.memory, requested.memory),
+    ("cpu", quota.max_cpu, usage.cpu, requested.cpu),
+  ];
+  for (name, max, used, requested) in limits {
+    let max = match max {
+      None => continue,
+      Some(max) => max,
+    };
+    if requested > 0 && used + requested > max {
+      return Err(HttpResponseError {
+        msg: format!(
+          "namespace {} quota exceeded: {} used {} requested {} limit {}",
+          &quota.namespace_name, name, used, requested, max
+        ),
+        status: StatusCode::FORBIDDEN,
+      });
+    }
+  }
+  Ok(())
+}
+
+/// Ensure a cargo set the limits required by a quota
+pub fn check_cargo_limits(
+  quota: &NamespaceQuotaItem,
+  memory_limit: Option<i64>,
+  cpu_limit: Option<i64>,
+) -> Result<(), HttpResponseError> {
+  let required = [
+    ("memory", quota.max_memory, memory_limit),
+    ("cpu", quota.max_cpu, cpu_limit),
+  ];
+  for (name, max, limit) in required {
+    if max.is_some() && limit.is_none() {
+      return Err(HttpResponseError {
+        msg: format!(
+          "namespace {} quota require cargoes to set a {} limit",
+          &quota.namespace_name, name
+        ),
+        status: StatusCode::FORBIDDEN,
+      });
+    }
+  }
+  Ok(())
+}
+
+/// Ensure the namespace can hold the requested resources
+/// namespaces without quota are unlimited
+pub async fn ensure_available(
+  namespace: &str,
+  requested: NamespaceUsage,
+  pool: &web::types::State<Pool>,
+) -> Result<(), HttpResponseError> {
+  let quota = repositories::namespace_quota::find_by_namespace(
+    namespace.to_owned(),
+    pool,
+  )
+  .await?;
+  let quota = match quota {
+    None => return Ok(()),
+    Some(quota) => quota,
+  };
+  let usage = get_usage(namespace, pool).await?;
+  check_quota(&quota, &usage, &requested)
+}
+
+/// Ensure a new cargo fit in the quota of his namespace
+pub async fn ensure_cargo_available(
+  namespace: &str,
+  memory_limit: Option<i64>,
+  cpu_limit: Option<i64>,
+  pool: &web::types::State<Pool>,
+) -> Result<(), HttpResponseError> {
+  if let Some(quota) =
+    repositories::namespace_quota::find_by_namespace(namespace.to_owned(), pool)
+      .await?
+  {
+    check_cargo_limits(&quota, memory_limit, cpu_limit)?;
+  }
+  let requested = NamespaceUsage {
+    cargoes: 1,
+    ..Default::default()
+  };
+  ensure_available(namespace, requested, pool).await
+}
+
+/// Ensure a new replica of a cargo fit in the quota of his namespace
+pub async fn ensure_replica_available(
+  cargo: &CargoItem,
+  pool: &web::types::State<Pool>,
+) -> Result<(), HttpResponseError> {
+  if let Some(quota) = repositories::namespace_quota::find_by_namespace(
+    cargo.namespace_name.to_owned(),
+    pool,
+  )
+  .await?
+  {
+    check_cargo_limits(&quota, cargo.memory_limit, cargo.cpu_limit)?;
+  }
+  let requested = NamespaceUsage {
+    replicas: 1,
+    memory: cargo.memory_limit.unwrap_or_default(),
+    cpu: cargo.cpu_limit.unwrap_or_default(),
+    ..Default::default()
+  };
+  ensure_available(&cargo.namespace_name, requested, pool).await
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_check_quota() {
+    let quota = NamespaceQuotaItem {
+      namespace_name: String::from("test"),
+      max_cargoes: Some(2),
+      max_clusters: None,
+      max_networks: None,
+      max_replicas: Some(4),
+      max_memory: Some(1024),
+      max_cpu: None,
+    };
+    let usage = NamespaceUsage {
+      cargoes: 2,
+      clusters: 10,
+      replicas: 3,
+      memory: 512,
+      ..Default::default()
+    };
+    let requested = NamespaceUsage {
+      clusters: 1,
+      ..Default::default()
+    };
+    assert!(check_quota(&quota, &usage, &requested).is_ok());
+    let requested = NamespaceUsage {
+      cargoes: 1,
+      ..Default::default()
+    };
+    let err = check_quota(&quota, &usage, &requested).unwrap_err();
+    assert_eq!(err.status, StatusCode::FORBIDDEN);
+    let requested = NamespaceUsage {
+      replicas: 1,
+      memory: 512,
+      ..Default::default()
+    };
+    assert!(check_quota(&quota, &usage, &requested).is_ok());
+    let requested = NamespaceUsage {
+      replicas: 1,
+      memory: 513,
+      ..Default::default()
+    };
+    assert!(check_quota(&quota, &usage, &requested).is_err());
+    assert!(check_cargo_limits(&quota, None, None).is_err());
+    assert!(check_cargo_limits(&quota, Some(256), None).is_ok());
+  }
+}
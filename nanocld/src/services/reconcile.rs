@@ -0,0 +1,350 @@
+//! Reconcile cluster containers with the state stored in database
+//! Missing containers are created again, stray ones are removed,
+//! networks are connected back and proxy templates rendered again.
+use ntex::web;
+use chrono::Utc;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use bollard::models::ContainerSummary;
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::errors::HttpResponseError;
+use crate::models::{
+  Pool, ClusterCargoItem, ClusterCargoNetworkItem, EventKind, ReconcileReport,
+};
+
+use super::history::SYSTEM_ACTOR;
+
+/// Seconds a container without a cluster cargo is kept before being removed
+const STRAY_CONTAINER_GRACE_PERIOD: i64 = 300;
+
+pub fn container_name(container: &ContainerSummary) -> String {
+  container
+    .names
+    .as_ref()
+    .and_then(|names| names.get(0))
+    .map(|name| name.trim_start_matches('/').to_owned())
+    .or_else(|| container.id.to_owned())
+    .unwrap_or_default()
+}
+
+/// Whether a container was created during the grace period
+fn is_recent(container: &ContainerSummary, now: i64) -> bool {
+  container
+    .created
+    .map(|created| now - created < STRAY_CONTAINER_GRACE_PERIOD)
+    .unwrap_or(false)
+}
+
+/// List every container created for a cluster
+async fn list_cluster_containers(
+  docker_api: &web::types::State<bollard::Docker>,
+) -> Result<Vec<ContainerSummary>, HttpResponseError> {
+  let mut filters = HashMap::new();
+  filters.insert("label", vec!["cluster"]);
+  let options = Some(bollard::container::ListContainersOptions {
+    all: true,
+    filters,
+    ..Default::default()
+  });
+  let containers = docker_api.list_containers(options).await?;
+  Ok(containers)
+}
+
+/// Networks of a cluster cargo a container is not connected to
+/// Joins made before networks were stored only have a primary network
+pub fn missing_networks(
+  cluster_cargo: &ClusterCargoItem,
+  mut cargo_networks: Vec<ClusterCargoNetworkItem>,
+  connected: &HashSet<String>,
+) -> Vec<ClusterCargoNetworkItem> {
+  if !cargo_networks.iter().any(|network| network.is_primary) {
+    cargo_networks.push(ClusterCargoNetworkItem {
+      key: format!("{}-{}", &cluster_cargo.key, &cluster_cargo.network_key),
+      cluster_cargo_key: cluster_cargo.key.to_owned(),
+      network_key: cluster_cargo.network_key.to_owned(),
+      is_primary: true,
+      ipv4_address: None,
+      ipv6_address: None,
+    });
+  }
+  cargo_networks
+    .into_iter()
+    .filter(|network| !connected.contains(&network.network_key))
+    .collect()
+}
+
+/// Connect a container back to the networks of his cluster cargo
+/// Return the name of the networks connected
+async fn reconnect_networks(
+  container: &ContainerSummary,
+  cluster_cargo: &ClusterCargoItem,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+) -> Result<Vec<String>, HttpResponseError> {
+  let connected = container
+    .network_settings
+    .as_ref()
+    .and_then(|settings| settings.networks.as_ref())
+    .map(|networks| networks.keys().cloned().collect::<HashSet<String>>())
+    .unwrap_or_default();
+  let cargo_networks =
+    repositories::cluster_cargo_network::list_by_cluster_cargo_key(
+      cluster_cargo.key.to_owned(),
+      pool,
+    )
+    .await?;
+  let mut reconnected = Vec::new();
+  for network in missing_networks(cluster_cargo, cargo_networks, &connected) {
+    let mut config = bollard::network::ConnectNetworkOptions {
+      container: container.id.to_owned().unwrap_or_default(),
+      ..Default::default()
+    };
+    if network.is_primary {
+      config.endpoint_config.ipam_config =
+        Some(bollard::models::EndpointIpamConfig {
+          ipv4_address: network.ipv4_address,
+          ipv6_address: network.ipv6_address,
+          ..Default::default()
+        });
+    }
+    docker_api
+      .connect_network(&network.network_key, config)
+      .await?;
+    reconnected.push(network.network_key);
+  }
+  Ok(reconnected)
+}
+
+/// Compare cluster cargoes stored in database with containers labelled
+/// by nanocl and fix the differences
+pub async fn reconcile(
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+) -> Result<ReconcileReport, HttpResponseError> {
+  let _lock = services::cluster::lock_cluster_cargoes().await;
+  let now = Utc::now().timestamp();
+  let mut report = ReconcileReport::default();
+  let cluster_cargoes = repositories::cluster_cargo::list_all(pool).await?;
+  let expected = cluster_cargoes
+    .iter()
+    .map(|item| (item.cluster_key.to_owned(), item.cargo_key.to_owned()))
+    .collect::<HashSet<(String, String)>>();
+  // Clusters to start again to render their templates with the new state
+  let mut clusters_to_start = BTreeSet::new();
+  let mut containers: HashMap<(String, String), Vec<ContainerSummary>> =
+    HashMap::new();
+
+  for container in list_cluster_containers(docker_api).await? {
+    let labels = container.labels.to_owned().unwrap_or_default();
+    let key = match (labels.get("cluster"), labels.get("cargo")) {
+      (Some(cluster), Some(cargo)) => (cluster.to_owned(), cargo.to_owned()),
+      _ => continue,
+    };
+    if expected.contains(&key) {
+      containers.entry(key).or_default().push(container);
+      continue;
+    }
+    // Give a container being created the time to get his cluster cargo stored
+    if is_recent(&container, now) {
+      continue;
+    }
+    let name = container_name(&container);
+    let options = Some(bollard::container::RemoveContainerOptions {
+      force: true,
+      ..Default::default()
+    });
+    match docker_api
+      .remove_container(&container.id.unwrap_or_default(), options)
+      .await
+    {
+      Err(err) => report
+        .errors
+        .push(format!("unable to remove container {}: {}", name, err)),
+      Ok(_) => {
+        report.removed.push(name);
+        clusters_to_start.insert(key.0);
+      }
+    }
+  }
+
+  let mut missing = Vec::new();
+  for cluster_cargo in cluster_cargoes {
+    let cluster_key = cluster_cargo.cluster_key.to_owned();
+    let key = (cluster_key.to_owned(), cluster_cargo.cargo_key.to_owned());
+    let cargo_containers = match containers.remove(&key) {
+      Some(cargo_containers) => cargo_containers,
+      None => {
+        missing.push(cluster_cargo);
+        continue;
+      }
+    };
+    for container in cargo_containers {
+      let name = container_name(&container);
+      if container.state.as_deref() != Some("running") {
+        report.started.push(name.to_owned());
+        clusters_to_start.insert(cluster_key.to_owned());
+      }
+      match reconnect_networks(&container, &cluster_cargo, docker_api, pool)
+        .await
+      {
+        Err(err) => report.errors.push(format!(
+          "unable to reconnect networks of container {}: {}",
+          name, err.msg
+        )),
+        Ok(networks) => {
+          if !networks.is_empty() {
+            clusters_to_start.insert(cluster_key.to_owned());
+          }
+          for network in networks {
+            report.reconnected.push(format!("{} {}", name, network));
+          }
+        }
+      }
+    }
+  }
+
+  // A redeploy starts the cluster of the cargo so it's done last
+  // and the cluster isn't started a second time
+  for cluster_cargo in missing {
+    let cluster_key = cluster_cargo.cluster_key.to_owned();
+    let cargo_key = cluster_cargo.cargo_key.to_owned();
+    let cluster_cargo_key = cluster_cargo.key.to_owned();
+    let res = services::cluster::redeploy_cargo(
+      cluster_cargo,
+      config,
+      docker_api,
+      pool,
+      SYSTEM_ACTOR,
+    )
+    .await;
+    services::history::record(
+      &cluster_key,
+      EventKind::Deploy,
+      SYSTEM_ACTOR,
+      Some(cargo_key),
+      &res,
+      pool,
+    )
+    .await;
+    match res {
+      Err(err) => report.errors.push(format!(
+        "unable to create containers of {}: {}",
+        cluster_cargo_key, err.msg
+      )),
+      Ok(_) => {
+        report.created.push(cluster_cargo_key);
+        clusters_to_start.remove(&cluster_key);
+      }
+    }
+  }
+
+  for cluster_key in clusters_to_start {
+    let cluster =
+      match repositories::cluster::find_by_key(cluster_key.to_owned(), pool)
+        .await
+      {
+        Err(err) => {
+          report.errors.push(format!(
+            "unable to find cluster {}: {}",
+            cluster_key, err.msg
+          ));
+          continue;
+        }
+        Ok(cluster) => cluster,
+      };
+    let res = services::cluster::start(
+      &cluster,
+      config,
+      pool,
+      docker_api,
+      SYSTEM_ACTOR,
+    )
+    .await;
+    services::history::record(
+      &cluster.key,
+      EventKind::Start,
+      SYSTEM_ACTOR,
+      None,
+      &res,
+      pool,
+    )
+    .await;
+    match res {
+      Err(err) => report.errors.push(format!(
+        "unable to start cluster {}: {}",
+        cluster.key, err.msg
+      )),
+      Ok(_) => {
+        if !cluster.proxy_templates.is_empty() {
+          report.rendered.push(cluster.key);
+        }
+      }
+    }
+  }
+
+  report.finished_at = Some(Utc::now());
+  Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_is_recent() {
+    let now = 1_660_000_000;
+    let mut container = ContainerSummary {
+      created: Some(now - 10),
+      ..Default::default()
+    };
+    assert!(is_recent(&container, now));
+    container.created = Some(now - STRAY_CONTAINER_GRACE_PERIOD);
+    assert!(!is_recent(&container, now));
+    container.created = None;
+    assert!(!is_recent(&container, now));
+  }
+
+  #[test]
+  fn test_missing_networks() {
+    let cluster_cargo = ClusterCargoItem {
+      key: String::from("global-dev-global-api"),
+      cargo_key: String::from("global-api"),
+      cluster_key: String::from("global-dev"),
+      network_key: String::from("global-dev-front"),
+    };
+    let connected = HashSet::from([String::from("global-dev-front")]);
+    let missing = missing_networks(&cluster_cargo, Vec::new(), &connected);
+    assert!(missing.is_empty());
+
+    let cargo_networks = vec![
+      ClusterCargoNetworkItem {
+        key: String::from("global-dev-global-api-global-dev-front"),
+        cluster_cargo_key: cluster_cargo.key.to_owned(),
+        network_key: String::from("global-dev-front"),
+        is_primary: true,
+        ipv4_address: Some(String::from("10.1.0.10")),
+        ipv6_address: None,
+      },
+      ClusterCargoNetworkItem {
+        key: String::from("global-dev-global-api-global-dev-back"),
+        cluster_cargo_key: cluster_cargo.key.to_owned(),
+        network_key: String::from("global-dev-back"),
+        is_primary: false,
+        ipv4_address: None,
+        ipv6_address: None,
+      },
+    ];
+    let missing =
+      missing_networks(&cluster_cargo, cargo_networks.to_owned(), &connected);
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0].network_key, "global-dev-back");
+
+    let missing =
+      missing_networks(&cluster_cargo, cargo_networks, &HashSet::new());
+    assert_eq!(missing.len(), 2);
+    assert_eq!(missing[0].ipv4_address.as_deref(), Some("10.1.0.10"));
+  }
+}
@@ -0,0 +1,596 @@
+//! Declarative http routes of a cluster
+//! Routes are rendered as nginx server and upstream blocks in a config file
+//! applied with the proxy templates when the cluster is started.
+use ntex::web;
+use ntex::http::StatusCode;
+use thiserror::Error;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{services, repositories};
+use crate::config::DaemonConfig;
+use crate::errors::{HttpResponseError, IntoHttpResponseError};
+use crate::models::{
+  Pool, ClusterItem, ClusterRouteItem, ClusterRoutePartial, PgDeleteGeneric,
+};
+
+use super::acme::{template_key, CertificateTemplateData};
+
+#[derive(Debug, Error)]
+pub enum RouteError {
+  #[error("invalid route")]
+  Invalid(String),
+  #[error("unable to render routes")]
+  Render(String),
+}
+
+impl IntoHttpResponseError for RouteError {
+  fn to_http_error(&self) -> HttpResponseError {
+    match self {
+      RouteError::Invalid(msg) => HttpResponseError {
+        msg: format!("invalid route {}", msg),
+        status: StatusCode::BAD_REQUEST,
+      },
+      RouteError::Render(msg) => HttpResponseError {
+        msg: format!("unable to render routes {}", msg),
+        status: StatusCode::BAD_REQUEST,
+      },
+    }
+  }
+}
+
+/// Name of the config file generated for the routes of a cluster
+pub fn config_file_name(cluster_key: &str) -> String {
+  format!("{}.nanocl-routes", cluster_key)
+}
+
+/// Path of the config file generated for the routes of a cluster
+pub fn config_file_path(cluster_key: &str, state_dir: &str) -> PathBuf {
+  Path::new(state_dir)
+    .join("nginx/sites-enabled")
+    .join(format!("{}.conf", config_file_name(cluster_key)))
+}
+
+/// Values are written in nginx directives so they must not be able
+/// to end a directive or a block
+pub fn is_safe_value(value: &str) -> bool {
+  !value.is_empty()
+    && !value.chars().any(|c| {
+      c.is_whitespace() || matches!(c, ';' | '{' | '}' | '"' | '\'' | '\\')
+    })
+}
+
+pub fn is_valid_domain(domain: &str) -> bool {
+  !domain.is_empty()
+    && domain
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '*'))
+}
+
+/// Split a header formatted as `Name: value`
+fn parse_header(header: &str) -> Option<(&str, &str)> {
+  let (name, value) = header.split_once(':')?;
+  let value = value.trim();
+  let is_valid_name = !name.is_empty()
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+  let is_valid_value = !value.is_empty()
+    && !value.chars().any(|c| matches!(c, '"' | '\\' | '\n' | '\r'));
+  if !is_valid_name || !is_valid_value {
+    return None;
+  }
+  Some((name, value))
+}
+
+/// Ensure a route can be rendered as nginx directives
+pub fn validate(item: &ClusterRoutePartial) -> Result<(), RouteError> {
+  let is_valid_name = !item.name.is_empty()
+    && item
+      .name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+  if !is_valid_name {
+    return Err(RouteError::Invalid(format!(
+      "name {} must only contain letters, digits, '-' or '_'",
+      item.name
+    )));
+  }
+  if !is_valid_domain(&item.domain) {
+    return Err(RouteError::Invalid(format!("domain {}", item.domain)));
+  }
+  if let Some(path) = &item.path {
+    if !path.starts_with('/') || !is_safe_value(path) {
+      return Err(RouteError::Invalid(format!("path {}", path)));
+    }
+  }
+  match (&item.redirect_to, &item.cargo_name, item.port) {
+    (Some(redirect_to), None, None) => {
+      if !is_safe_value(redirect_to) {
+        return Err(RouteError::Invalid(format!(
+          "redirect_to {}",
+          redirect_to
+        )));
+      }
+    }
+    (None, Some(cargo_name), Some(port)) => {
+      if !is_safe_value(cargo_name) {
+        return Err(RouteError::Invalid(format!("cargo_name {}", cargo_name)));
+      }
+      if !(1..=65535).contains(&port) {
+        return Err(RouteError::Invalid(format!("port {}", port)));
+      }
+    }
+    _ => {
+      return Err(RouteError::Invalid(String::from(
+        "either redirect_to or cargo_name and port must be set",
+      )))
+    }
+  }
+  for header in item.headers.iter().flatten() {
+    if parse_header(header).is_none() {
+      return Err(RouteError::Invalid(format!(
+        "header {} must be formatted as 'Name: value'",
+        header
+      )));
+    }
+  }
+  for timeout in [item.connect_timeout, item.read_timeout].iter().flatten() {
+    if *timeout <= 0 {
+      return Err(RouteError::Invalid(format!("timeout {}", timeout)));
+    }
+  }
+  Ok(())
+}
+
+/// Directives of the policies added to the servers and locations of routes
+#[derive(Debug, Clone, Default)]
+pub struct RouteDirectives {
+  /// Directives of the servers by template key of their domain
+  pub(crate) servers: HashMap<String, String>,
+  /// Directives of the locations by route name
+  pub(crate) locations: HashMap<String, String>,
+}
+
+/// Nginx config of the routes of a cluster
+/// with warnings about the routes that can't reach their cargo
+#[derive(Debug)]
+pub struct RouteConfig {
+  pub(crate) content: String,
+  pub(crate) warnings: Vec<String>,
+}
+
+fn upstream_name(cluster_key: &str, route: &ClusterRouteItem) -> String {
+  format!(
+    "{}-{}-{}",
+    cluster_key,
+    route.cargo_name.to_owned().unwrap_or_default(),
+    route.port.unwrap_or_default()
+  )
+}
+
+/// Directives of the location block of a route
+/// `upstreams` are the upstream names by route name,
+/// routes without upstream answer with a bad gateway
+fn gen_location(
+  output: &mut String,
+  route: &ClusterRouteItem,
+  upstreams: &HashMap<String, String>,
+  directives: &RouteDirectives,
+) -> std::fmt::Result {
+  writeln!(output, "  location {} {{", route.path)?;
+  if let Some(redirect_to) = &route.redirect_to {
+    writeln!(output, "    return 301 {};", redirect_to)?;
+    return writeln!(output, "  }}");
+  }
+  let upstream = match upstreams.get(&route.name) {
+    None => {
+      writeln!(output, "    return 502;")?;
+      return writeln!(output, "  }}");
+    }
+    Some(upstream) => upstream,
+  };
+  if let Some(directives) = directives.locations.get(&route.name) {
+    write!(output, "{}", directives)?;
+  }
+  writeln!(output, "    proxy_pass http://{};", upstream)?;
+  writeln!(output, "    proxy_set_header Host $host;")?;
+  writeln!(output, "    proxy_set_header X-Real-IP $remote_addr;")?;
+  writeln!(
+    output,
+    "    proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;"
+  )?;
+  writeln!(output, "    proxy_set_header X-Forwarded-Proto $scheme;")?;
+  for (name, value) in route.headers.iter().filter_map(|h| parse_header(h)) {
+    writeln!(output, "    proxy_set_header {} \"{}\";", name, value)?;
+  }
+  if route.websocket {
+    writeln!(output, "    proxy_http_version 1.1;")?;
+    writeln!(output, "    proxy_set_header Upgrade $http_upgrade;")?;
+    writeln!(output, "    proxy_set_header Connection \"upgrade\";")?;
+  }
+  if let Some(timeout) = route.connect_timeout {
+    writeln!(output, "    proxy_connect_timeout {}s;", timeout)?;
+  }
+  if let Some(timeout) = route.read_timeout {
+    writeln!(output, "    proxy_read_timeout {}s;", timeout)?;
+    writeln!(output, "    proxy_send_timeout {}s;", timeout)?;
+  }
+  writeln!(output, "  }}")
+}
+
+/// Server blocks of a domain
+fn gen_servers(
+  output: &mut String,
+  domain: &str,
+  routes: &[&ClusterRouteItem],
+  upstreams: &HashMap<String, String>,
+  certificate: Option<&CertificateTemplateData>,
+  directives: &RouteDirectives,
+  acme_challenge: Option<&str>,
+) -> std::fmt::Result {
+  let server_directives = directives.servers.get(&template_key(domain));
+  writeln!(output, "\nserver {{")?;
+  writeln!(output, "  listen 80;")?;
+  writeln!(output, "  server_name {};", domain)?;
+  if let Some(directives) = server_directives {
+    write!(output, "{}", directives)?;
+  }
+  if let Some(snippet) = acme_challenge {
+    writeln!(output, "  include {};", snippet)?;
+  }
+  for route in routes.iter().filter(|route| !route.tls) {
+    gen_location(output, route, upstreams, directives)?;
+  }
+  for route in routes.iter().filter(|route| route.tls) {
+    writeln!(output, "  location {} {{", route.path)?;
+    writeln!(output, "    return 301 https://$host$request_uri;")?;
+    writeln!(output, "  }}")?;
+  }
+  writeln!(output, "}}")?;
+
+  let certificate = match certificate {
+    None => return Ok(()),
+    Some(certificate) => certificate,
+  };
+  writeln!(output, "\nserver {{")?;
+  writeln!(output, "  listen 443 ssl http2;")?;
+  writeln!(output, "  server_name {};", domain)?;
+  writeln!(output, "  ssl_certificate {};", certificate.certificate)?;
+  writeln!(
+    output,
+    "  ssl_certificate_key {};",
+    certificate.certificate_key
+  )?;
+  if let Some(directives) = server_directives {
+    write!(output, "{}", directives)?;
+  }
+  for route in routes.iter().filter(|route| route.tls) {
+    gen_location(output, route, upstreams, directives)?;
+  }
+  writeln!(output, "}}")
+}
+
+/// Generate the nginx config of the routes of a cluster
+/// `targets` are the ips of the containers of each cargo by cargo name
+/// `certificates` the certificates by template key of their domain
+/// and `directives` the directives of the policies of the cluster
+/// Routes of cargoes without running container answer with a bad gateway
+pub fn gen_config(
+  cluster_key: &str,
+  routes: &[ClusterRouteItem],
+  targets: &HashMap<String, Vec<String>>,
+  certificates: &HashMap<String, CertificateTemplateData>,
+  directives: &RouteDirectives,
+  acme_challenge: Option<&str>,
+) -> Result<RouteConfig, RouteError> {
+  let mut upstreams = BTreeMap::new();
+  let mut route_upstreams = HashMap::new();
+  let mut warnings = Vec::new();
+  let mut domains: BTreeMap<&str, Vec<&ClusterRouteItem>> = BTreeMap::new();
+  for route in routes {
+    domains
+      .entry(route.domain.as_str())
+      .or_default()
+      .push(route);
+    let (cargo_name, port) = match (&route.cargo_name, route.port) {
+      (Some(cargo_name), Some(port)) => (cargo_name, port),
+      _ => continue,
+    };
+    let ips = targets.get(cargo_name).ok_or_else(|| {
+      RouteError::Render(format!(
+        "route {} target cargo {} is not joined to the cluster",
+        route.name, cargo_name
+      ))
+    })?;
+    if ips.is_empty() {
+      warnings.push(format!(
+        "cargo {} of route {} has no running container",
+        cargo_name, route.name
+      ));
+      continue;
+    }
+    let servers = ips
+      .iter()
+      .map(|ip| format!("{}:{}", ip, port))
+      .collect::<Vec<_>>();
+    let name = upstream_name(cluster_key, route);
+    route_upstreams.insert(route.name.to_owned(), name.to_owned());
+    upstreams.insert(name, servers);
+  }
+
+  let mut output = format!(
+    "# generated by nanocl from the routes of cluster {}\n",
+    cluster_key
+  );
+  for (name, servers) in &upstreams {
+    output += &format!("\nupstream {} {{\n", name);
+    for server in servers {
+      output += &format!("  server {};\n", server);
+    }
+    output += "}\n";
+  }
+  for (domain, routes) in domains {
+    let certificate = if routes.iter().any(|route| route.tls) {
+      let certificate =
+        certificates.get(&template_key(domain)).ok_or_else(|| {
+          RouteError::Render(format!("no certificate found for {}", domain))
+        })?;
+      Some(certificate)
+    } else {
+      None
+    };
+    gen_servers(
+      &mut output,
+      domain,
+      &routes,
+      &route_upstreams,
+      certificate,
+      directives,
+      acme_challenge,
+    )
+    .map_err(|err| RouteError::Render(err.to_string()))?;
+  }
+  Ok(RouteConfig {
+    content: output,
+    warnings,
+  })
+}
+
+/// Create a route and start the cluster to render it
+/// the route is removed if the cluster fail to render it
+pub async fn create(
+  cluster: &ClusterItem,
+  item: ClusterRoutePartial,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<ClusterRouteItem, HttpResponseError> {
+  validate(&item).map_err(|err| err.to_http_error())?;
+  let path = item.path.unwrap_or_else(|| String::from("/"));
+  let tls = item.tls.unwrap_or_default();
+  let routes =
+    repositories::cluster_route::list_by_cluster(cluster.key.to_owned(), pool)
+      .await?;
+  // A location is rendered in both servers of a domain whatever his tls
+  let is_duplicate = routes
+    .iter()
+    .any(|route| route.domain == item.domain && route.path == path);
+  if is_duplicate {
+    return Err(HttpResponseError {
+      msg: format!("a route already match {}{}", item.domain, path),
+      status: StatusCode::CONFLICT,
+    });
+  }
+  let route = ClusterRouteItem {
+    key: format!("{}-{}", cluster.key, item.name),
+    cluster_key: cluster.key.to_owned(),
+    name: item.name,
+    domain: item.domain,
+    path,
+    cargo_name: item.cargo_name,
+    port: item.port,
+    tls,
+    redirect_to: item.redirect_to,
+    headers: item.headers.unwrap_or_default(),
+    websocket: item.websocket.unwrap_or_default(),
+    connect_timeout: item.connect_timeout,
+    read_timeout: item.read_timeout,
+  };
+  let route = repositories::cluster_route::create(route, pool).await?;
+  let res =
+    services::cluster::start(cluster, config, pool, docker_api, actor).await;
+  if let Err(err) = res {
+    repositories::cluster_route::delete_by_key(route.key, pool).await?;
+    return Err(err);
+  }
+  Ok(route)
+}
+
+/// Delete a route and render the remaining ones
+/// the config file is removed with the last route of the cluster
+pub async fn delete(
+  cluster: &ClusterItem,
+  name: &str,
+  config: &DaemonConfig,
+  docker_api: &web::types::State<bollard::Docker>,
+  pool: &web::types::State<Pool>,
+  actor: &str,
+) -> Result<PgDeleteGeneric, HttpResponseError> {
+  let key = format!("{}-{}", cluster.key, name);
+  repositories::cluster_route::find_by_key(key.to_owned(), pool).await?;
+  let res = repositories::cluster_route::delete_by_key(key, pool).await?;
+  let routes =
+    repositories::cluster_route::list_by_cluster(cluster.key.to_owned(), pool)
+      .await?;
+  if routes.is_empty() {
+    let path = config_file_path(&cluster.key, &config.state_dir);
+    services::nginx::remove_config_files(
+      &[path],
+      &config.state_dir,
+      docker_api,
+    )
+    .await
+    .map_err(|err| err.to_http_error())?;
+  } else {
+    services::cluster::start(cluster, config, pool, docker_api, actor).await?;
+  }
+  Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  fn gen_route(name: &str) -> ClusterRouteItem {
+    ClusterRouteItem {
+      key: format!("global-dev-{}", name),
+      cluster_key: String::from("global-dev"),
+      name: name.to_owned(),
+      domain: String::from("api.example.com"),
+      path: String::from("/"),
+      cargo_name: Some(String::from("api")),
+      port: Some(8080),
+      tls: false,
+      redirect_to: None,
+      headers: Vec::new(),
+      websocket: false,
+      connect_timeout: None,
+      read_timeout: None,
+    }
+  }
+
+  #[test]
+  fn test_validate() {
+    let mut item = ClusterRoutePartial {
+      name: String::from("api"),
+      domain: String::from("api.example.com"),
+      path: Some(String::from("/v1")),
+      cargo_name: Some(String::from("api")),
+      port: Some(8080),
+      tls: None,
+      redirect_to: None,
+      headers: Some(vec![String::from("X-Env: dev")]),
+      websocket: None,
+      connect_timeout: Some(5),
+      read_timeout: None,
+    };
+    assert!(validate(&item).is_ok());
+    item.redirect_to = Some(String::from("https://example.com"));
+    assert!(validate(&item).is_err());
+    item.cargo_name = None;
+    item.port = None;
+    assert!(validate(&item).is_ok());
+    item.path = Some(String::from("/; return 200"));
+    assert!(validate(&item).is_err());
+    item.path = None;
+    item.headers = Some(vec![String::from("X-Env")]);
+    assert!(validate(&item).is_err());
+  }
+
+  #[test]
+  fn test_gen_config() -> Result<(), RouteError> {
+    let mut api = gen_route("api");
+    api.tls = true;
+    api.websocket = true;
+    api.read_timeout = Some(60);
+    api.headers = vec![String::from("X-Env: dev")];
+    let mut legacy = gen_route("legacy");
+    legacy.path = String::from("/legacy");
+    legacy.cargo_name = None;
+    legacy.port = None;
+    legacy.redirect_to = Some(String::from("https://example.com"));
+    let routes = vec![api, legacy];
+    let targets = HashMap::from([(
+      String::from("api"),
+      vec![String::from("10.1.0.2"), String::from("10.1.0.3")],
+    )]);
+
+    let res = gen_config(
+      "global-dev",
+      &routes,
+      &targets,
+      &HashMap::new(),
+      &RouteDirectives::default(),
+      None,
+    );
+    assert!(res.is_err());
+
+    let certificates = HashMap::from([(
+      template_key("api.example.com"),
+      CertificateTemplateData {
+        certificate: String::from("/etc/nginx/ssl/api.crt"),
+        certificate_key: String::from("/etc/nginx/ssl/api.key"),
+      },
+    )]);
+    let directives = RouteDirectives {
+      servers: HashMap::from([(
+        template_key("api.example.com"),
+        String::from("  deny 192.0.2.1;\n"),
+      )]),
+      locations: HashMap::from([
+        (
+          String::from("api"),
+          String::from("    auth_request /auth;\n"),
+        ),
+        (
+          String::from("legacy"),
+          String::from("    auth_request /auth;\n"),
+        ),
+      ]),
+    };
+    let output = gen_config(
+      "global-dev",
+      &routes,
+      &targets,
+      &certificates,
+      &directives,
+      None,
+    )?;
+    assert!(output.warnings.is_empty());
+    let output = output.content;
+    assert!(output.contains("upstream global-dev-api-8080 {"));
+    assert!(output.contains("  server 10.1.0.3:8080;"));
+    assert!(output.contains("    return 301 https://$host$request_uri;"));
+    assert!(output.contains("    return 301 https://example.com;"));
+    assert!(output.contains("  ssl_certificate /etc/nginx/ssl/api.crt;"));
+    assert!(output.contains("    proxy_set_header Upgrade $http_upgrade;"));
+    assert!(output.contains("    proxy_set_header X-Env \"dev\";"));
+    assert!(output.contains("    proxy_read_timeout 60s;"));
+    assert_eq!(output.matches("  deny 192.0.2.1;\n").count(), 2);
+    assert_eq!(output.matches("    auth_request /auth;\n").count(), 1);
+
+    // Routes of a cargo without running container answer a bad gateway
+    let targets = HashMap::from([(String::from("api"), Vec::new())]);
+    let output = gen_config(
+      "global-dev",
+      &routes,
+      &targets,
+      &certificates,
+      &directives,
+      None,
+    )?;
+    assert_eq!(
+      output.warnings,
+      vec![String::from(
+        "cargo api of route api has no running container"
+      )]
+    );
+    assert!(!output.content.contains("upstream global-dev-api-8080"));
+    assert!(output.content.contains("    return 502;\n"));
+    assert!(!output.content.contains("proxy_pass"));
+
+    let targets = HashMap::new();
+    let res = gen_config(
+      "global-dev",
+      &routes,
+      &targets,
+      &certificates,
+      &directives,
+      None,
+    );
+    assert!(res.is_err());
+    Ok(())
+  }
+}
@@ -1,4 +1,8 @@
+use std::fs;
+use std::io::Write;
 use std::collections::HashMap;
+use std::path::Path;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
 use futures::StreamExt;
 use bollard::{
   Docker,
@@ -310,3 +314,89 @@ pub async fn get_service_state(
   }
   ServiceState::Stopped
 }
+
+/// # Create a directory only readable by its owner
+///
+/// # Arguments
+/// - [path](Path) the directory to create with its missing parents
+///
+/// # Return
+/// an [io error](std::io::Error) is returned if the directory can't be created
+pub fn create_private_dir(path: &Path) -> std::io::Result<()> {
+  fs::DirBuilder::new()
+    .recursive(true)
+    .mode(0o700)
+    .create(path)?;
+  fs::set_permissions(path, fs::Permissions::from_mode(0o700))
+}
+
+/// # Write a file with the given permissions
+/// The content is written in a temporary file created with the given mode
+/// and synced before being renamed, so the file is never readable by others
+/// nor partially written.
+///
+/// # Arguments
+/// - [path](Path) the file to write
+/// - [content](u8) the content of the file
+/// - [mode](u32) the permissions of the file
+///
+/// # Return
+/// an [io error](std::io::Error) is returned if the file can't be written
+///
+/// # Examples
+/// ```rust,norun
+/// use crate::services;
+///
+/// services::utils::write_private_file(&path, b"key", 0o600);
+/// ```
+pub fn write_private_file(
+  path: &Path,
+  content: &[u8],
+  mode: u32,
+) -> std::io::Result<()> {
+  let file_name = path.file_name().ok_or_else(|| {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, "missing file name")
+  })?;
+  let tmp_path =
+    path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+  let res = fs::OpenOptions::new()
+    .write(true)
+    .create(true)
+    .truncate(true)
+    .mode(mode)
+    .open(&tmp_path)
+    .and_then(|mut file| {
+      // The mode is only applied on creation
+      file.set_permissions(fs::Permissions::from_mode(mode))?;
+      file.write_all(content)?;
+      file.sync_all()
+    })
+    .and_then(|_| fs::rename(&tmp_path, path));
+  if res.is_err() {
+    let _ = fs::remove_file(&tmp_path);
+  }
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_write_private_file() {
+    let dir = std::env::temp_dir()
+      .join(format!("nanocl-private-{}", uuid::Uuid::new_v4()))
+      .join("live");
+    create_private_dir(&dir).unwrap();
+    let mode = fs::metadata(&dir).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+    let path = dir.join("privkey.pem");
+    fs::write(&path, "old").unwrap();
+    write_private_file(&path, b"key", 0o600).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "key");
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(!dir.join(".privkey.pem.tmp").exists());
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+  }
+}
@@ -59,6 +59,7 @@ pub mod test {
 
   use crate::services;
   use crate::models::Pool;
+  use crate::config::DaemonConfig;
 
   pub use ntex::web::test::TestServer;
 
@@ -75,6 +76,24 @@ pub mod test {
     .unwrap()
   }
 
+  pub fn gen_daemon_config() -> DaemonConfig {
+    DaemonConfig {
+      hosts: vec![String::from("unix:///run/nanocl/nanocl.sock")],
+      state_dir: String::from("/var/lib/nanocl"),
+      config_dir: String::from("/etc/nanocl"),
+      network_pools: vec![String::from("172.28.0.0/14")],
+      reconcile_interval: 0,
+      acme_directory: None,
+      acme_email: None,
+      acme_insecure: false,
+      nginx_log_retention: 0,
+      nginx_log_max_rows: 0,
+      nginx_log_archive: false,
+      nginx_log_batch_size: 500,
+      nginx_log_flush_interval: 1000,
+    }
+  }
+
   pub async fn gen_postgre_pool() -> Pool {
     let docker = gen_docker_client();
     let ip_addr = services::postgresql::get_postgres_ip(&docker)
@@ -96,6 +115,7 @@ pub mod test {
       App::new()
         .state(pool.clone())
         .state(docker.clone())
+        .state(gen_daemon_config())
         .configure(config)
     })
   }
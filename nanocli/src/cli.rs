@@ -1,14 +1,18 @@
 use std::io;
 use clap_complete::{generate, Generator};
-use clap::{App, AppSettings, Parser, Subcommand};
+use clap::{App, AppSettings, ArgEnum, Parser, Subcommand};
 
 use crate::nanocld::{
   git_repository::GitRepositoryPartial,
-  namespace::NamespacePartial,
-  cluster::{ClusterPartial, ClusterNetworkPartial},
+  namespace::{NamespacePartial, NamespaceQuotaPartial},
+  cluster::{
+    ClusterPartial, ClusterNetworkPartial, ClusterNetworkPolicyPartial,
+    ClusterRoutePartial, ClusterAccessPolicyPartial, ClusterAuthPolicyPartial,
+  },
   cargo::CargoPartial,
   container_image::ContainerImagePartial,
   nginx_template::NginxTemplateModes,
+  nginx_log::{NginxLogSearchQuery, NginxLogWatchQuery},
   container::ListContainerOptions,
 };
 
@@ -38,6 +42,44 @@ pub enum NamespaceCommands {
   /// List existing namespaces
   #[clap(alias("ls"))]
   List,
+  /// Inspect namespace with his quota and resource usage
+  Inspect(NamespaceInspectOptions),
+  /// Set resource quota of a namespace
+  SetQuota(NamespaceQuotaOptions),
+  /// Remove resource quota of a namespace
+  RemoveQuota(NamespaceInspectOptions),
+  /// Remove a namespace with everything it contains
+  #[clap(alias("rm"))]
+  Remove(NamespaceDeleteOptions),
+}
+
+/// Namespace delete options
+#[derive(Debug, Parser)]
+pub struct NamespaceDeleteOptions {
+  /// Name of the namespace
+  pub name: String,
+  /// Only list what would be removed
+  #[clap(long)]
+  pub dry_run: bool,
+  /// Remove without asking for confirmation
+  #[clap(short, long)]
+  pub yes: bool,
+}
+
+/// Namespace inspect options
+#[derive(Debug, Parser)]
+pub struct NamespaceInspectOptions {
+  /// Name of the namespace
+  pub name: String,
+}
+
+/// Namespace quota options
+#[derive(Debug, Parser)]
+pub struct NamespaceQuotaOptions {
+  /// Name of the namespace
+  pub name: String,
+  #[clap(flatten)]
+  pub quota: NamespaceQuotaPartial,
 }
 
 /// Git repository delete options
@@ -88,6 +130,202 @@ pub struct ClusterInspectOptions {
   pub(crate) name: String,
 }
 
+/// Cluster history options
+#[derive(Debug, Parser)]
+pub struct ClusterHistoryOptions {
+  /// Name of the cluster
+  pub(crate) name: String,
+  /// Maximum number of events to show
+  #[clap(long)]
+  pub(crate) limit: Option<i64>,
+}
+
+/// Cluster unjoin options
+#[derive(Debug, Parser)]
+pub struct ClusterUnjoinOptions {
+  /// Name of the cluster
+  pub(crate) name: String,
+  /// Name of the cargo to remove from the cluster
+  pub(crate) cargo: String,
+}
+
+/// Cluster route list options
+#[derive(Debug, Parser)]
+pub struct ClusterRouteListOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+}
+
+/// Cluster route create options
+#[derive(Debug, Parser)]
+pub struct ClusterRouteCreateOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+  #[clap(flatten)]
+  pub(crate) route: ClusterRoutePartial,
+}
+
+/// Cluster route delete options
+#[derive(Debug, Parser)]
+pub struct ClusterRouteDeleteOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+  /// Name of the route
+  pub(crate) name: String,
+}
+
+/// Cluster route sub commands
+#[derive(Debug, Subcommand)]
+pub enum ClusterRouteCommands {
+  /// List routes of a cluster
+  #[clap(alias("ls"))]
+  List(ClusterRouteListOptions),
+  /// Create a route and render it in the proxy
+  Create(ClusterRouteCreateOptions),
+  /// Remove a route from the proxy
+  #[clap(alias("rm"))]
+  Remove(ClusterRouteDeleteOptions),
+}
+
+/// Manage http routes of a cluster
+#[derive(Debug, Parser)]
+pub struct ClusterRouteArgs {
+  #[clap(subcommand)]
+  pub(crate) commands: ClusterRouteCommands,
+}
+
+/// Cluster access policy list options
+#[derive(Debug, Parser)]
+pub struct ClusterAccessPolicyListOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+}
+
+/// Cluster access policy create options
+#[derive(Debug, Parser)]
+pub struct ClusterAccessPolicyCreateOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+  #[clap(flatten)]
+  pub(crate) policy: ClusterAccessPolicyPartial,
+}
+
+/// Cluster access policy delete options
+#[derive(Debug, Parser)]
+pub struct ClusterAccessPolicyDeleteOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+  /// Name of the access policy
+  pub(crate) name: String,
+}
+
+/// Cluster access policy sub commands
+#[derive(Debug, Subcommand)]
+pub enum ClusterAccessPolicyCommands {
+  /// List access policies of a cluster
+  #[clap(alias("ls"))]
+  List(ClusterAccessPolicyListOptions),
+  /// Create an access policy and render it in the proxy
+  Create(ClusterAccessPolicyCreateOptions),
+  /// Remove an access policy from the proxy
+  #[clap(alias("rm"))]
+  Remove(ClusterAccessPolicyDeleteOptions),
+}
+
+/// Manage ip allow and deny lists and rate limits of a cluster
+#[derive(Debug, Parser)]
+pub struct ClusterAccessPolicyArgs {
+  #[clap(subcommand)]
+  pub(crate) commands: ClusterAccessPolicyCommands,
+}
+
+/// Cluster auth policy list options
+#[derive(Debug, Parser)]
+pub struct ClusterAuthPolicyListOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+}
+
+/// Cluster auth policy create options
+#[derive(Debug, Parser)]
+pub struct ClusterAuthPolicyCreateOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+  #[clap(flatten)]
+  pub(crate) policy: ClusterAuthPolicyPartial,
+}
+
+/// Cluster auth policy delete options
+#[derive(Debug, Parser)]
+pub struct ClusterAuthPolicyDeleteOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+  /// Name of the auth policy
+  pub(crate) name: String,
+}
+
+/// Cluster auth policy sub commands
+#[derive(Debug, Subcommand)]
+pub enum ClusterAuthPolicyCommands {
+  /// List auth policies of a cluster
+  #[clap(alias("ls"))]
+  List(ClusterAuthPolicyListOptions),
+  /// Create an auth policy and render it in the proxy
+  Create(ClusterAuthPolicyCreateOptions),
+  /// Remove an auth policy from the proxy
+  #[clap(alias("rm"))]
+  Remove(ClusterAuthPolicyDeleteOptions),
+}
+
+/// Manage basic and forward authentication of the domains and routes of a cluster
+#[derive(Debug, Parser)]
+pub struct ClusterAuthPolicyArgs {
+  #[clap(subcommand)]
+  pub(crate) commands: ClusterAuthPolicyCommands,
+}
+
+#[derive(Debug, Parser)]
+pub struct ClusterMaintenanceOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+  /// Path of the html page served during maintenance
+  #[clap(long)]
+  pub(crate) body_file: Option<String>,
+  /// Ip address or cidr still routed to the cargoes during maintenance
+  #[clap(long)]
+  pub(crate) allow: Option<Vec<String>>,
+  /// Path of the html page served when a cargo can't be reached
+  #[clap(long)]
+  pub(crate) bad_gateway_file: Option<String>,
+  /// Path of the html page served when a cargo takes too long to answer
+  #[clap(long)]
+  pub(crate) gateway_timeout_file: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct ClusterMaintenanceInspectOptions {
+  /// Name of the cluster
+  pub(crate) cluster: String,
+}
+
+/// Cluster maintenance sub commands
+#[derive(Debug, Subcommand)]
+pub enum ClusterMaintenanceCommands {
+  /// Serve the maintenance page instead of the cargoes
+  On(ClusterMaintenanceOptions),
+  /// Restore the routing to the cargoes
+  Off(ClusterMaintenanceOptions),
+  /// Show the maintenance and error pages of a cluster
+  Inspect(ClusterMaintenanceInspectOptions),
+}
+
+/// Manage maintenance mode and custom error pages of a cluster
+#[derive(Debug, Parser)]
+pub struct ClusterMaintenanceArgs {
+  #[clap(subcommand)]
+  pub(crate) commands: ClusterMaintenanceCommands,
+}
+
 /// Cluster sub commands
 #[derive(Debug, Subcommand)]
 pub enum ClusterCommands {
@@ -103,6 +341,18 @@ pub enum ClusterCommands {
   Start(ClusterStartOptions),
   /// Inspect cluster by it's name
   Inspect(ClusterInspectOptions),
+  /// Remove a cargo from a cluster
+  Unjoin(ClusterUnjoinOptions),
+  /// Show latest actions made on a cluster
+  History(ClusterHistoryOptions),
+  /// Manage http routes of a cluster
+  Route(ClusterRouteArgs),
+  /// Manage ip allow and deny lists and rate limits of a cluster
+  AccessPolicy(ClusterAccessPolicyArgs),
+  /// Manage basic and forward authentication of a cluster
+  AuthPolicy(ClusterAuthPolicyArgs),
+  /// Manage maintenance mode and custom error pages of a cluster
+  Maintenance(ClusterMaintenanceArgs),
 }
 
 /// Cluster network delete topions
@@ -133,6 +383,35 @@ pub enum ClusterNetworkCommands {
   /// Remove cluster network
   #[clap(alias("rm"))]
   Remove(ClusterNetworkDeleteOptions),
+  /// Inspect cluster network with his containers and ip usage
+  Inspect(ClusterNetworkInspectOptions),
+  /// Set the policy of a cluster network
+  SetPolicy(ClusterNetworkPolicyOptions),
+  /// Remove the policy of a cluster network
+  RemovePolicy(ClusterNetworkPolicyDeleteOptions),
+}
+
+/// Cluster network inspect options
+#[derive(Debug, Parser)]
+pub struct ClusterNetworkInspectOptions {
+  /// Name of the network
+  pub name: String,
+}
+
+/// Cluster network policy options
+#[derive(Debug, Parser)]
+pub struct ClusterNetworkPolicyOptions {
+  /// Name of the network
+  pub name: String,
+  #[clap(flatten)]
+  pub policy: ClusterNetworkPolicyPartial,
+}
+
+/// Cluster network policy delete options
+#[derive(Debug, Parser)]
+pub struct ClusterNetworkPolicyDeleteOptions {
+  /// Name of the network
+  pub name: String,
 }
 
 /// Cargo delete options
@@ -263,6 +542,51 @@ pub struct NginxTemplateCreateOptions {
   pub(crate) file_path: Option<String>,
 }
 
+#[derive(Debug, Parser)]
+pub struct NginxTemplateUpdateOptions {
+  /// Name of template to update
+  pub(crate) name: String,
+  /// New mode of template http|stream
+  #[clap(long, short)]
+  pub(crate) mode: Option<NginxTemplateModes>,
+  /// Update by reading stdi
+  #[clap(long = "stdi")]
+  pub(crate) is_reading_stdi: bool,
+  /// Update by reading a file
+  #[clap(short)]
+  pub(crate) file_path: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct NginxTemplateLintOptions {
+  /// Name of the template
+  pub(crate) name: String,
+  /// Lint by reading stdi
+  #[clap(long = "stdi")]
+  pub(crate) is_reading_stdi: bool,
+  /// Lint by reading a file
+  #[clap(short)]
+  pub(crate) file_path: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct NginxTemplateDiffOptions {
+  /// Name of the template
+  pub(crate) name: String,
+  /// Revision to compare from
+  pub(crate) from: i32,
+  /// Revision to compare to
+  pub(crate) to: i32,
+}
+
+#[derive(Debug, Parser)]
+pub struct NginxTemplateRollbackOptions {
+  /// Name of the template
+  pub(crate) name: String,
+  /// Revision to restore
+  pub(crate) revision: i32,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum NginxTemplateCommand {
   /// List existing template
@@ -273,6 +597,16 @@ pub enum NginxTemplateCommand {
   /// Remove a template
   #[clap(alias("rm"))]
   Remove(NginxTemplateOptions),
+  /// Update a template with a new revision
+  Update(NginxTemplateUpdateOptions),
+  /// Check a template content against the clusters using it
+  Lint(NginxTemplateLintOptions),
+  /// List revisions of a template
+  Revisions(NginxTemplateOptions),
+  /// Show the difference between two revisions
+  Diff(NginxTemplateDiffOptions),
+  /// Restore a previous revision as a new one
+  Rollback(NginxTemplateRollbackOptions),
   // Todo
   // Inspect(NginxTemplateOption),
 }
@@ -284,6 +618,77 @@ pub struct NginxTemplateArgs {
   pub(crate) commands: NginxTemplateCommand,
 }
 
+/// Format of a command output
+#[derive(Debug, Clone, ArgEnum)]
+pub enum OutputFormat {
+  Table,
+  Json,
+}
+
+#[derive(Debug, Parser)]
+pub struct NginxLogQueryOptions {
+  #[clap(flatten)]
+  pub(crate) query: NginxLogSearchQuery,
+  /// Output format
+  #[clap(long, short, arg_enum, default_value = "table")]
+  pub(crate) output: OutputFormat,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NginxLogCommands {
+  /// Stream logs as they are written
+  Watch(NginxLogWatchQuery),
+  /// Search in stored logs
+  Query(NginxLogQueryOptions),
+}
+
+/// Stream or search nginx access logs
+#[derive(Debug, Parser)]
+pub struct NginxLogArgs {
+  /// Logs are streamed when no command is given
+  #[clap(subcommand)]
+  pub(crate) commands: Option<NginxLogCommands>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CertificateOptions {
+  /// Name of the certificate
+  pub(crate) name: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct CertificateUploadOptions {
+  /// Name of the certificate
+  pub(crate) name: String,
+  /// Path of the certificate chain in PEM format
+  #[clap(long = "cert")]
+  pub(crate) certificate: String,
+  /// Path of the private key in PEM format
+  #[clap(long = "key")]
+  pub(crate) certificate_key: String,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CertificateCommands {
+  /// List installed certificates
+  #[clap(alias("ls"))]
+  List,
+  /// Upload a certificate with his private key
+  Upload(CertificateUploadOptions),
+  /// Show expiry date, domains and issuer of a certificate
+  Inspect(CertificateOptions),
+  /// Remove a certificate not used by a proxy template
+  #[clap(alias("rm"))]
+  Remove(CertificateOptions),
+}
+
+/// Manage certificates installed on the proxy
+#[derive(Debug, Parser)]
+pub struct CertificateArgs {
+  #[clap(subcommand)]
+  pub(crate) commands: CertificateCommands,
+}
+
 #[derive(Debug, Parser)]
 pub struct ContainerImageRemoveOpts {
   /// id or name of image to delete
@@ -313,6 +718,14 @@ pub struct ContainerImageArgs {
   pub(crate) commands: ContainerImageCommands,
 }
 
+/// Reconcile clusters with docker
+#[derive(Debug, Parser)]
+pub struct ReconcileOptions {
+  /// Show the report of the last reconciliation instead of running one
+  #[clap(long)]
+  pub(crate) last: bool,
+}
+
 /// Run a cargo in given environement
 #[derive(Debug, Parser)]
 pub struct RunArgs {
@@ -320,8 +733,10 @@ pub struct RunArgs {
   pub(crate) namespace: Option<String>,
   #[clap(long)]
   pub(crate) cluster: String,
-  #[clap(long)]
-  pub(crate) network: String,
+  /// Networks to join the cargo to, repeat the flag to join several
+  /// the first one is the primary network and is created
+  #[clap(long = "network", required = true)]
+  pub(crate) networks: Vec<String>,
   #[clap(long)]
   pub(crate) image: String,
   pub(crate) name: String,
@@ -337,13 +752,18 @@ pub enum Commands {
   Revert(RevertArgs),
   GitRepository(GitRepositoryArgs),
   NginxTemplate(NginxTemplateArgs),
+  Certificate(CertificateArgs),
   ClusterNetwork(ClusterNetworkArgs),
   ContainerImage(ContainerImageArgs),
   #[clap(name = "lsc")]
   ListContainer(ListContainerOptions),
   Run(RunArgs),
   /// Connect to nginx logging
-  NginxLog,
+  NginxLog(NginxLogArgs),
+  /// Recreate missing cluster containers and remove stray ones
+  Reconcile(ReconcileOptions),
+  /// Issue missing or expiring acme certificates of cluster domains
+  RenewCertificates,
   /// Show the Nanocl version information
   Version,
   // TODO shell ompletion
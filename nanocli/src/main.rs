@@ -4,9 +4,16 @@ use futures::StreamExt;
 use futures::stream::FuturesUnordered;
 use indicatif::{ProgressBar, ProgressStyle};
 use nanocld::{
-  nginx_template::NginxTemplatePartial,
-  cluster::{ClusterPartial, ClusterNetworkPartial, ClusterJoinPartial},
+  nginx_template::{
+    NginxTemplatePartial, NginxTemplateUpdateBody, NginxTemplateLintBody,
+  },
+  certificate::CertificatePartial,
+  cluster::{
+    ClusterPartial, ClusterNetworkPartial, ClusterJoinPartial,
+    ClusterMaintenancePartial,
+  },
   cargo::CargoPartial,
+  nginx_log::NginxLogWatchQuery,
   error::NanocldError,
 };
 use ntex::http::StatusCode;
@@ -14,7 +21,7 @@ use serde::{Serialize, Deserialize};
 
 use std::{
   process::{Command, Stdio},
-  io::BufRead,
+  io::{BufRead, Read},
 };
 
 use tabled::{
@@ -78,6 +85,25 @@ pub struct NamespaceWithCount {
   networks: usize,
 }
 
+/// Content of a template from stdin or from a file
+fn read_template_content(
+  is_reading_stdi: bool,
+  file_path: &Option<String>,
+) -> Result<String, CliError> {
+  match (is_reading_stdi, file_path) {
+    (true, None) => {
+      let mut content = String::new();
+      std::io::stdin().read_to_string(&mut content)?;
+      Ok(content)
+    }
+    (false, Some(file_path)) => Ok(std::fs::read_to_string(file_path)?),
+    _ => {
+      eprintln!("Expected one of --stdi or -f options use --help");
+      std::process::exit(1);
+    }
+  }
+}
+
 async fn execute_args(args: &Cli) -> Result<(), CliError> {
   let client = nanocld::client::Nanocld::connect_with_unix_default().await;
   match &args.command {
@@ -106,6 +132,7 @@ async fn execute_args(args: &Cli) -> Result<(), CliError> {
       let cluster = ClusterPartial {
         name: args.cluster.to_owned(),
         proxy_templates: None,
+        domains: None,
       };
       if let Err(err) = client
         .create_cluster(&cluster, args.namespace.to_owned())
@@ -121,8 +148,18 @@ async fn execute_args(args: &Cli) -> Result<(), CliError> {
           return Err(CliError::Client(err));
         }
       }
+      let (network, networks) = args
+        .networks
+        .split_first()
+        .expect("clap requires at least one network");
       let cluster_network = ClusterNetworkPartial {
-        name: args.network.to_owned(),
+        name: network.to_owned(),
+        subnet: None,
+        ip_range: None,
+        gateway: None,
+        ipv6_subnet: None,
+        internal: false,
+        driver_options: None,
       };
       client
         .create_cluster_network(
@@ -140,14 +177,19 @@ async fn execute_args(args: &Cli) -> Result<(), CliError> {
         domainname: None,
         hostname: None,
         environnements: None,
+        memory_limit: None,
+        cpu_limit: None,
       };
       client
         .create_cargo(&cargo, args.namespace.to_owned())
         .await?;
 
       let cluster_join = ClusterJoinPartial {
-        network: args.network.to_owned(),
+        network: network.to_owned(),
         cargo: args.name.to_owned(),
+        networks: (!networks.is_empty()).then(|| networks.to_vec()),
+        ipv4_address: None,
+        ipv6_address: None,
       };
       client
         .join_cluster_cargo(
@@ -193,6 +235,38 @@ async fn execute_args(args: &Cli) -> Result<(), CliError> {
         let item = client.create_namespace(&item.name).await?;
         println!("{}", item.name);
       }
+      NamespaceCommands::Inspect(options) => {
+        let item = client.inspect_namespace(&options.name).await?;
+        println!("=== NAMESPACE {} ===", item.name);
+        print_table(item.resources());
+      }
+      NamespaceCommands::SetQuota(options) => {
+        client
+          .put_namespace_quota(&options.name, &options.quota)
+          .await?;
+      }
+      NamespaceCommands::RemoveQuota(options) => {
+        client.delete_namespace_quota(&options.name).await?;
+      }
+      NamespaceCommands::Remove(options) => {
+        let report = client.delete_namespace(&options.name, true).await?;
+        report.print();
+        if options.dry_run {
+          return Ok(());
+        }
+        if !options.yes {
+          println!(
+            "Remove namespace {} and everything listed above ? [y/N]",
+            &options.name
+          );
+          let mut answer = String::new();
+          std::io::stdin().lock().read_line(&mut answer)?;
+          if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            return Ok(());
+          }
+        }
+        client.delete_namespace(&options.name, false).await?;
+      }
     },
     Commands::Cluster(args) => match &args.commands {
       ClusterCommands::List => {
@@ -223,8 +297,156 @@ async fn execute_args(args: &Cli) -> Result<(), CliError> {
         print_table(vec![&cluster]);
         println!("=== NETWORKS ===");
         print_table(cluster.networks.unwrap_or_default());
+        println!("=== NETWORK POLICIES ===");
+        print_table(cluster.network_policies.unwrap_or_default());
         println!("===============");
       }
+      ClusterCommands::Unjoin(options) => {
+        client
+          .unjoin_cluster_cargo(
+            &options.name,
+            &options.cargo,
+            args.namespace.to_owned(),
+          )
+          .await?;
+      }
+      ClusterCommands::History(options) => {
+        let items = client
+          .list_cluster_history(
+            &options.name,
+            options.limit,
+            args.namespace.to_owned(),
+          )
+          .await?;
+        print_table(items);
+      }
+      ClusterCommands::Route(route_args) => match &route_args.commands {
+        ClusterRouteCommands::List(options) => {
+          let items = client
+            .list_cluster_route(&options.cluster, args.namespace.to_owned())
+            .await?;
+          print_table(items);
+        }
+        ClusterRouteCommands::Create(options) => {
+          let item = client
+            .create_cluster_route(
+              &options.cluster,
+              &options.route,
+              args.namespace.to_owned(),
+            )
+            .await?;
+          println!("{}", item.name);
+        }
+        ClusterRouteCommands::Remove(options) => {
+          client
+            .delete_cluster_route(
+              &options.cluster,
+              &options.name,
+              args.namespace.to_owned(),
+            )
+            .await?;
+        }
+      },
+      ClusterCommands::AccessPolicy(policy_args) => {
+        match &policy_args.commands {
+          ClusterAccessPolicyCommands::List(options) => {
+            let items = client
+              .list_cluster_access_policy(
+                &options.cluster,
+                args.namespace.to_owned(),
+              )
+              .await?;
+            print_table(items);
+          }
+          ClusterAccessPolicyCommands::Create(options) => {
+            let item = client
+              .create_cluster_access_policy(
+                &options.cluster,
+                &options.policy,
+                args.namespace.to_owned(),
+              )
+              .await?;
+            println!("{}", item.name);
+          }
+          ClusterAccessPolicyCommands::Remove(options) => {
+            client
+              .delete_cluster_access_policy(
+                &options.cluster,
+                &options.name,
+                args.namespace.to_owned(),
+              )
+              .await?;
+          }
+        }
+      }
+      ClusterCommands::AuthPolicy(policy_args) => match &policy_args.commands {
+        ClusterAuthPolicyCommands::List(options) => {
+          let items = client
+            .list_cluster_auth_policy(
+              &options.cluster,
+              args.namespace.to_owned(),
+            )
+            .await?;
+          print_table(items);
+        }
+        ClusterAuthPolicyCommands::Create(options) => {
+          let item = client
+            .create_cluster_auth_policy(
+              &options.cluster,
+              &options.policy,
+              args.namespace.to_owned(),
+            )
+            .await?;
+          println!("{}", item.name);
+        }
+        ClusterAuthPolicyCommands::Remove(options) => {
+          client
+            .delete_cluster_auth_policy(
+              &options.cluster,
+              &options.name,
+              args.namespace.to_owned(),
+            )
+            .await?;
+        }
+      },
+      ClusterCommands::Maintenance(maintenance_args) => {
+        match &maintenance_args.commands {
+          ClusterMaintenanceCommands::On(options)
+          | ClusterMaintenanceCommands::Off(options) => {
+            let enabled = matches!(
+              maintenance_args.commands,
+              ClusterMaintenanceCommands::On(_)
+            );
+            let read_page = |path: &Option<String>| {
+              path.as_ref().map(std::fs::read_to_string).transpose()
+            };
+            let item = ClusterMaintenancePartial {
+              enabled,
+              body: read_page(&options.body_file)?,
+              allow: options.allow.to_owned(),
+              bad_gateway_body: read_page(&options.bad_gateway_file)?,
+              gateway_timeout_body: read_page(&options.gateway_timeout_file)?,
+            };
+            let item = client
+              .update_cluster_maintenance(
+                &options.cluster,
+                &item,
+                args.namespace.to_owned(),
+              )
+              .await?;
+            print_table(vec![item]);
+          }
+          ClusterMaintenanceCommands::Inspect(options) => {
+            let item = client
+              .inspect_cluster_maintenance(
+                &options.cluster,
+                args.namespace.to_owned(),
+              )
+              .await?;
+            print_table(vec![item]);
+          }
+        }
+      }
     },
     Commands::ClusterNetwork(args) => match &args.commands {
       ClusterNetworkCommands::List => {
@@ -252,6 +474,46 @@ async fn execute_args(args: &Cli) -> Result<(), CliError> {
           )
           .await?;
       }
+      ClusterNetworkCommands::Inspect(options) => {
+        let network = client
+          .inspect_cluster_network(
+            &args.cluster,
+            &options.name,
+            args.namespace.to_owned(),
+          )
+          .await?;
+        println!("=== NETWORK ===");
+        print_table(vec![&network]);
+        println!("=== CONTAINERS ===");
+        print_table(network.containers);
+        if !network.warnings.is_empty() {
+          println!("=== WARNINGS ===");
+          for warning in network.warnings {
+            println!("{}", warning);
+          }
+        }
+        println!("===============");
+      }
+      ClusterNetworkCommands::SetPolicy(options) => {
+        let item = client
+          .put_cluster_network_policy(
+            &args.cluster,
+            &options.name,
+            &options.policy,
+            args.namespace.to_owned(),
+          )
+          .await?;
+        print_table(vec![item]);
+      }
+      ClusterNetworkCommands::RemovePolicy(options) => {
+        client
+          .delete_cluster_network_policy(
+            &args.cluster,
+            &options.name,
+            args.namespace.to_owned(),
+          )
+          .await?;
+      }
     },
     Commands::GitRepository(args) => match &args.commands {
       GitRepositoryCommands::List => {
@@ -344,6 +606,71 @@ async fn execute_args(args: &Cli) -> Result<(), CliError> {
           println!("{}", &res.name);
         }
       }
+      NginxTemplateCommand::Update(options) => {
+        let content =
+          read_template_content(options.is_reading_stdi, &options.file_path)?;
+        let item = NginxTemplateUpdateBody {
+          mode: options.mode.to_owned(),
+          content,
+        };
+        let report = client.update_nginx_template(&options.name, &item).await?;
+        report.print();
+      }
+      NginxTemplateCommand::Lint(options) => {
+        let item = NginxTemplateLintBody {
+          name: options.name.to_owned(),
+          content: read_template_content(
+            options.is_reading_stdi,
+            &options.file_path,
+          )?,
+        };
+        let items = client.lint_nginx_template(&item).await?;
+        let has_error = items.iter().any(|item| item.level == "error");
+        print_table(items);
+        if has_error {
+          std::process::exit(1);
+        }
+      }
+      NginxTemplateCommand::Revisions(options) => {
+        let items = client.list_nginx_template_revision(&options.name).await?;
+        print_table(items);
+      }
+      NginxTemplateCommand::Diff(options) => {
+        let diff = client
+          .diff_nginx_template(&options.name, options.from, options.to)
+          .await?;
+        for line in diff.lines {
+          println!("{}", line);
+        }
+      }
+      NginxTemplateCommand::Rollback(options) => {
+        let report = client
+          .rollback_nginx_template(&options.name, options.revision)
+          .await?;
+        report.print();
+      }
+    },
+    Commands::Certificate(args) => match &args.commands {
+      CertificateCommands::List => {
+        let items = client.list_certificate().await?;
+        print_table(items);
+      }
+      CertificateCommands::Upload(options) => {
+        let item = CertificatePartial {
+          name: options.name.to_owned(),
+          certificate: std::fs::read_to_string(&options.certificate)?,
+          certificate_key: std::fs::read_to_string(&options.certificate_key)?,
+        };
+        let item = client.create_certificate(&item).await?;
+        println!("{}", item.name);
+      }
+      CertificateCommands::Inspect(options) => {
+        let item = client.inspect_certificate(&options.name).await?;
+        item.print();
+      }
+      CertificateCommands::Remove(options) => {
+        client.delete_certificate(&options.name).await?;
+      }
     },
     Commands::Apply(args) => {
       let mut file_path = std::env::current_dir()?;
@@ -408,8 +735,43 @@ async fn execute_args(args: &Cli) -> Result<(), CliError> {
         client.remove_container_image(&args.name).await?;
       }
     },
-    Commands::NginxLog => {
-      client.watch_nginx_logs().await?;
+    Commands::NginxLog(args) => match &args.commands {
+      None => {
+        client
+          .watch_nginx_logs(&NginxLogWatchQuery::default())
+          .await?;
+      }
+      Some(NginxLogCommands::Watch(query)) => {
+        client.watch_nginx_logs(query).await?;
+      }
+      Some(NginxLogCommands::Query(options)) => {
+        let result = client.search_nginx_logs(&options.query).await?;
+        match options.output {
+          OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&result)
+              .map_err(std::io::Error::from)?;
+            println!("{}", json);
+          }
+          OutputFormat::Table => {
+            print_table(result.items);
+            if let Some(cursor) = result.next_cursor {
+              eprintln!("next page with --cursor {}", cursor);
+            }
+          }
+        }
+      }
+    },
+    Commands::Reconcile(options) => {
+      let report = if options.last {
+        client.last_reconcile().await?
+      } else {
+        client.reconcile().await?
+      };
+      report.print();
+    }
+    Commands::RenewCertificates => {
+      let report = client.renew_certificates().await?;
+      report.print();
     }
     Commands::Version => {
       version::print_version();
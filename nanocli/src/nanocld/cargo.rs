@@ -31,6 +31,12 @@ pub struct CargoPartial {
   /// Environement variable
   #[clap(long = "-env")]
   pub(crate) environnements: Option<Vec<String>>,
+  /// Memory limit of each container in bytes
+  #[clap(long)]
+  pub(crate) memory_limit: Option<i64>,
+  /// Cpu limit of each container in units of 10^-9 cpus
+  #[clap(long)]
+  pub(crate) cpu_limit: Option<i64>,
 }
 
 /// Cargo item is an definition to container create image and start them
@@ -0,0 +1,98 @@
+use tabled::Tabled;
+use serde::{Serialize, Deserialize};
+
+use super::{
+  client::Nanocld,
+  error::{NanocldError, is_api_error},
+};
+
+fn tbd_vec_string(o: &[String]) -> String {
+  o.join(", ")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CertificatePartial {
+  pub(crate) name: String,
+  pub(crate) certificate: String,
+  pub(crate) certificate_key: String,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct CertificateItem {
+  pub(crate) name: String,
+  #[tabled(display_with = "tbd_vec_string")]
+  pub(crate) domains: Vec<String>,
+  pub(crate) issuer: String,
+  pub(crate) not_before: String,
+  pub(crate) not_after: String,
+  #[tabled(skip)]
+  pub(crate) created_at: String,
+}
+
+impl CertificateItem {
+  /// Print the certificate with one line per domain
+  pub fn print(&self) {
+    println!("=== CERTIFICATE {} ===", self.name);
+    println!("issuer {}", self.issuer);
+    println!("not before {}", self.not_before);
+    println!("not after {}", self.not_after);
+    println!("created at {}", self.created_at);
+    println!("=== DOMAINS ===");
+    for domain in &self.domains {
+      println!("{}", domain);
+    }
+  }
+}
+
+impl Nanocld {
+  pub async fn list_certificate(
+    &self,
+  ) -> Result<Vec<CertificateItem>, NanocldError> {
+    let mut res = self.get(String::from("/certificates")).send().await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let items = res.json::<Vec<CertificateItem>>().await?;
+    Ok(items)
+  }
+
+  pub async fn create_certificate(
+    &self,
+    item: &CertificatePartial,
+  ) -> Result<CertificateItem, NanocldError> {
+    let mut res = self
+      .post(String::from("/certificates"))
+      .send_json(item)
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<CertificateItem>().await?;
+    Ok(item)
+  }
+
+  pub async fn inspect_certificate(
+    &self,
+    name: &str,
+  ) -> Result<CertificateItem, NanocldError> {
+    let mut res = self
+      .get(format!("/certificates/{name}/inspect", name = name))
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<CertificateItem>().await?;
+    Ok(item)
+  }
+
+  pub async fn delete_certificate(
+    &self,
+    name: &str,
+  ) -> Result<(), NanocldError> {
+    let mut res = self
+      .delete(format!("/certificates/{name}", name = name))
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    Ok(())
+  }
+}
@@ -2,9 +2,13 @@ use ntex::rt;
 use ntex::http::Client;
 use ntex::http::client::{Connector, ClientRequest};
 
+/// Header used to tell the daemon who make the request
+const ACTOR_HEADER: &str = "x-nanocl-actor";
+
 pub struct Nanocld {
   client: Client,
   url: String,
+  actor: String,
 }
 
 impl Nanocld {
@@ -23,6 +27,7 @@ impl Nanocld {
     Nanocld {
       client,
       url: String::from("http://localhost"),
+      actor: std::env::var("USER").unwrap_or_default(),
     }
   }
 
@@ -31,14 +36,30 @@ impl Nanocld {
   }
 
   pub(crate) fn get(&self, url: String) -> ClientRequest {
-    self.client.get(self.gen_url(url))
+    self
+      .client
+      .get(self.gen_url(url))
+      .header(ACTOR_HEADER, &self.actor)
   }
 
   pub(crate) fn delete(&self, url: String) -> ClientRequest {
-    self.client.delete(self.gen_url(url))
+    self
+      .client
+      .delete(self.gen_url(url))
+      .header(ACTOR_HEADER, &self.actor)
   }
 
   pub(crate) fn post(&self, url: String) -> ClientRequest {
-    self.client.post(self.gen_url(url))
+    self
+      .client
+      .post(self.gen_url(url))
+      .header(ACTOR_HEADER, &self.actor)
+  }
+
+  pub(crate) fn put(&self, url: String) -> ClientRequest {
+    self
+      .client
+      .put(self.gen_url(url))
+      .header(ACTOR_HEADER, &self.actor)
   }
 }
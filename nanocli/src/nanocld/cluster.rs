@@ -1,4 +1,6 @@
-use clap::Parser;
+use std::collections::HashMap;
+
+use clap::{ArgEnum, Parser};
 use tabled::Tabled;
 use serde::{Serialize, Deserialize};
 
@@ -12,6 +14,41 @@ fn tbd_vec_string(o: &[String]) -> String {
   o.join(", ")
 }
 
+fn tbd_optional_string(s: &Option<String>) -> String {
+  match s {
+    None => String::from(""),
+    Some(s) => s.to_owned(),
+  }
+}
+
+fn tbd_optional_port(port: &Option<i32>) -> String {
+  match port {
+    None => String::from(""),
+    Some(port) => port.to_string(),
+  }
+}
+
+fn tbd_auth_kind(kind: &ClusterAuthKind) -> String {
+  match kind {
+    ClusterAuthKind::Basic => String::from("basic"),
+    ClusterAuthKind::Forward => String::from("forward"),
+  }
+}
+
+fn tbd_page(body: &Option<String>) -> String {
+  match body {
+    None => String::from("default"),
+    Some(_) => String::from("custom"),
+  }
+}
+
+fn tbd_optional_limit(limit: &Option<i32>) -> String {
+  match limit {
+    None => String::from(""),
+    Some(limit) => limit.to_string(),
+  }
+}
+
 #[derive(Debug, Tabled, Serialize, Deserialize)]
 pub struct ClusterItem {
   pub(crate) key: String,
@@ -19,6 +56,8 @@ pub struct ClusterItem {
   pub(crate) name: String,
   #[tabled(display_with = "tbd_vec_string")]
   pub(crate) proxy_templates: Vec<String>,
+  #[tabled(display_with = "tbd_vec_string")]
+  pub(crate) domains: Vec<String>,
   // #[tabled(display_with = "display_option")]
   // pub(crate) networks: Option<Vec<ClusterNetworkItem>>,
 }
@@ -31,8 +70,73 @@ pub struct ClusterItemWithRelation {
   pub(crate) namespace: String,
   #[tabled(display_with = "tbd_vec_string")]
   pub(crate) proxy_templates: Vec<String>,
+  #[tabled(display_with = "tbd_vec_string")]
+  pub(crate) domains: Vec<String>,
   #[tabled(skip)]
   pub(crate) networks: Option<Vec<ClusterNetworkItem>>,
+  #[tabled(skip)]
+  pub(crate) network_policies: Option<Vec<ClusterNetworkPolicyItem>>,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct ClusterNetworkContainer {
+  pub(crate) name: String,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) cargo_key: Option<String>,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) ipv4_address: Option<String>,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) ipv6_address: Option<String>,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct ClusterNetworkInspect {
+  #[serde(flatten)]
+  #[tabled(inline)]
+  pub(crate) network: ClusterNetworkItem,
+  #[tabled(display_with = "tbd_vec_string")]
+  pub(crate) subnets: Vec<String>,
+  pub(crate) free_addresses: u64,
+  #[tabled(skip)]
+  pub(crate) containers: Vec<ClusterNetworkContainer>,
+  #[tabled(skip)]
+  pub(crate) warnings: Vec<String>,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct ClusterNetworkPolicyItem {
+  pub(crate) network_key: String,
+  #[tabled(display_with = "tbd_vec_string")]
+  pub(crate) allowed_networks: Vec<String>,
+  pub(crate) allow_egress: bool,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct ClusterEventItem {
+  pub(crate) created_at: String,
+  pub(crate) kind: String,
+  pub(crate) actor: String,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) target: Option<String>,
+  pub(crate) outcome: String,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ClusterHistoryQuery {
+  namespace: Option<String>,
+  limit: Option<i64>,
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct ClusterNetworkPolicyPartial {
+  /// Network allowed to be reached as `network` or `cluster/network`
+  #[clap(long = "allow")]
+  pub(crate) allowed_networks: Vec<String>,
+  /// Allow traffic to the internet
+  #[clap(long)]
+  pub(crate) allow_egress: bool,
 }
 
 #[derive(Debug, Parser, Serialize, Deserialize)]
@@ -40,6 +144,9 @@ pub struct ClusterPartial {
   pub name: String,
   #[clap(long)]
   pub proxy_templates: Option<Vec<String>>,
+  /// Domain to issue a certificate for with ACME
+  #[clap(long = "domain")]
+  pub domains: Option<Vec<String>>,
 }
 
 #[derive(Debug, Tabled, Serialize, Deserialize)]
@@ -48,6 +155,11 @@ pub struct ClusterNetworkItem {
   pub(crate) name: String,
   pub(crate) cluster_key: String,
   pub(crate) default_gateway: String,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) subnet: Option<String>,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) ipv6_subnet: Option<String>,
+  pub(crate) internal: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -60,11 +172,244 @@ pub struct ClusterVarPartial {
 pub struct ClusterJoinPartial {
   pub(crate) network: String,
   pub(crate) cargo: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) networks: Option<Vec<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) ipv4_address: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) ipv6_address: Option<String>,
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct ClusterRoutePartial {
+  /// Name of the route
+  pub(crate) name: String,
+  /// Domain matched by the route
+  #[clap(long)]
+  pub(crate) domain: String,
+  /// Path prefix matched by the route default to /
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) path: Option<String>,
+  /// Name of the cargo receiving the traffic
+  #[clap(long = "cargo")]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) cargo_name: Option<String>,
+  /// Port of the cargo receiving the traffic
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) port: Option<i32>,
+  /// Serve the route in https and redirect http requests to it
+  #[clap(long)]
+  #[serde(default)]
+  pub(crate) tls: bool,
+  /// Answer with a permanent redirect to this url instead of a cargo
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) redirect_to: Option<String>,
+  /// Header added to the proxied request as 'Name: value'
+  #[clap(long = "header")]
+  #[serde(default)]
+  pub(crate) headers: Vec<String>,
+  /// Forward websocket upgrade requests
+  #[clap(long)]
+  #[serde(default)]
+  pub(crate) websocket: bool,
+  /// Timeout in seconds to connect to the cargo
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) connect_timeout: Option<i32>,
+  /// Timeout in seconds between two reads from the cargo
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) read_timeout: Option<i32>,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct ClusterRouteItem {
+  pub(crate) name: String,
+  pub(crate) domain: String,
+  pub(crate) path: String,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) cargo_name: Option<String>,
+  #[tabled(display_with = "tbd_optional_port")]
+  pub(crate) port: Option<i32>,
+  pub(crate) tls: bool,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) redirect_to: Option<String>,
+  #[tabled(skip)]
+  pub(crate) headers: Vec<String>,
+  pub(crate) websocket: bool,
+  #[tabled(skip)]
+  pub(crate) connect_timeout: Option<i32>,
+  #[tabled(skip)]
+  pub(crate) read_timeout: Option<i32>,
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct ClusterAccessPolicyPartial {
+  /// Name of the access policy
+  pub(crate) name: String,
+  /// Domain protected by the policy default to all domains of the cluster
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) domain: Option<String>,
+  /// Address or network in cidr notation allowed, others are denied
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) allow: Option<Vec<String>>,
+  /// Address or network in cidr notation denied
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) deny: Option<Vec<String>>,
+  /// Maximum number of requests per second of a client
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) rate: Option<i32>,
+  /// Number of requests of a client allowed over the rate
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) burst: Option<i32>,
+  /// Maximum number of simultaneous connections of a client
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) connections: Option<i32>,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct ClusterAccessPolicyItem {
+  pub(crate) name: String,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) domain: Option<String>,
+  #[tabled(display_with = "tbd_vec_string")]
+  pub(crate) allow: Vec<String>,
+  #[tabled(display_with = "tbd_vec_string")]
+  pub(crate) deny: Vec<String>,
+  #[tabled(display_with = "tbd_optional_limit")]
+  pub(crate) rate: Option<i32>,
+  #[tabled(display_with = "tbd_optional_limit")]
+  pub(crate) burst: Option<i32>,
+  #[tabled(display_with = "tbd_optional_limit")]
+  pub(crate) connections: Option<i32>,
+}
+
+/// Kind of authentication required by an auth policy
+#[derive(Debug, Clone, ArgEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterAuthKind {
+  Basic,
+  Forward,
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct ClusterAuthPolicyPartial {
+  /// Name of the auth policy
+  pub(crate) name: String,
+  /// Kind of authentication
+  #[clap(long, arg_enum)]
+  pub(crate) kind: ClusterAuthKind,
+  /// Domain protected by the policy
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) domain: Option<String>,
+  /// Route protected by the policy when no domain is given
+  #[clap(long = "route")]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) route_name: Option<String>,
+  /// Realm displayed by browsers for basic auth
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) realm: Option<String>,
+  /// Basic auth user as 'name:password'
+  #[clap(long = "user")]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) users: Option<Vec<String>>,
+  /// Name of the cargo answering forward auth requests
+  #[clap(long = "cargo")]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) cargo_name: Option<String>,
+  /// Port of the cargo answering forward auth requests
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) port: Option<i32>,
+  /// Path of the forward auth requests default to /
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) path: Option<String>,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct ClusterAuthPolicyItem {
+  pub(crate) name: String,
+  #[tabled(display_with = "tbd_auth_kind")]
+  pub(crate) kind: ClusterAuthKind,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) domain: Option<String>,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) route_name: Option<String>,
+  #[tabled(skip)]
+  pub(crate) realm: Option<String>,
+  #[tabled(display_with = "tbd_vec_string")]
+  pub(crate) users: Vec<String>,
+  #[tabled(display_with = "tbd_optional_string")]
+  pub(crate) cargo_name: Option<String>,
+  #[tabled(display_with = "tbd_optional_port")]
+  pub(crate) port: Option<i32>,
+  #[tabled(skip)]
+  pub(crate) path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClusterMaintenancePartial {
+  pub(crate) enabled: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) body: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) allow: Option<Vec<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) bad_gateway_body: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) gateway_timeout_body: Option<String>,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct ClusterMaintenanceItem {
+  pub(crate) enabled: bool,
+  #[tabled(display_with = "tbd_page")]
+  pub(crate) body: Option<String>,
+  #[tabled(display_with = "tbd_vec_string")]
+  pub(crate) allow: Vec<String>,
+  #[tabled(display_with = "tbd_page")]
+  pub(crate) bad_gateway_body: Option<String>,
+  #[tabled(display_with = "tbd_page")]
+  pub(crate) gateway_timeout_body: Option<String>,
 }
 
 #[derive(Debug, Parser, Serialize, Deserialize)]
 pub struct ClusterNetworkPartial {
   pub(crate) name: String,
+  /// Subnet in cidr notation eg: 10.1.0.0/24
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) subnet: Option<String>,
+  /// Range of the subnet where container ips are allocated
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) ip_range: Option<String>,
+  /// Gateway of the subnet
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) gateway: Option<String>,
+  /// Ipv6 subnet to make the network dual stack eg: fd00:1::/64
+  #[clap(long)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) ipv6_subnet: Option<String>,
+  /// Restrict external access to the network
+  #[clap(long)]
+  pub(crate) internal: bool,
+  #[clap(skip)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) driver_options: Option<HashMap<String, String>>,
 }
 
 impl Nanocld {
@@ -121,6 +466,25 @@ impl Nanocld {
     Ok(item)
   }
 
+  pub async fn list_cluster_history(
+    &self,
+    name: &str,
+    limit: Option<i64>,
+    namespace: Option<String>,
+  ) -> Result<Vec<ClusterEventItem>, NanocldError> {
+    let mut res = self
+      .get(format!("/clusters/{name}/history", name = name))
+      .query(&ClusterHistoryQuery { namespace, limit })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let items = res.json::<Vec<ClusterEventItem>>().await?;
+
+    Ok(items)
+  }
+
   pub async fn delete_cluster(
     &self,
     name: &str,
@@ -198,12 +562,59 @@ impl Nanocld {
     Ok(())
   }
 
+  pub async fn put_cluster_network_policy(
+    &self,
+    c_name: &str,
+    n_name: &str,
+    item: &ClusterNetworkPolicyPartial,
+    namespace: Option<String>,
+  ) -> Result<ClusterNetworkPolicyItem, NanocldError> {
+    let mut res = self
+      .put(format!(
+        "/clusters/{c_name}/networks/{n_name}/policy",
+        c_name = c_name,
+        n_name = n_name
+      ))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send_json(item)
+      .await
+      .map_err(NanocldError::SendRequest)?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<ClusterNetworkPolicyItem>().await?;
+
+    Ok(item)
+  }
+
+  pub async fn delete_cluster_network_policy(
+    &self,
+    c_name: &str,
+    n_name: &str,
+    namespace: Option<String>,
+  ) -> Result<(), NanocldError> {
+    let mut res = self
+      .delete(format!(
+        "/clusters/{c_name}/networks/{n_name}/policy",
+        c_name = c_name,
+        n_name = n_name
+      ))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+
+    Ok(())
+  }
+
   pub async fn inspect_cluster_network(
     &self,
     c_name: &str,
     n_name: &str,
     namespace: Option<String>,
-  ) -> Result<ClusterNetworkItem, NanocldError> {
+  ) -> Result<ClusterNetworkInspect, NanocldError> {
     let mut res = self
       .get(format!(
         "/clusters/{c_name}/networks/{n_name}/inspect",
@@ -217,7 +628,7 @@ impl Nanocld {
     let status = res.status();
     is_api_error(&mut res, &status).await?;
 
-    let item = res.json::<ClusterNetworkItem>().await?;
+    let item = res.json::<ClusterNetworkInspect>().await?;
     Ok(item)
   }
 
@@ -303,6 +714,248 @@ impl Nanocld {
     Ok(())
   }
 
+  pub async fn unjoin_cluster_cargo(
+    &self,
+    c_name: &str,
+    cargo_name: &str,
+    namespace: Option<String>,
+  ) -> Result<(), NanocldError> {
+    let mut res = self
+      .delete(format!(
+        "/clusters/{c_name}/cargoes/{cargo_name}",
+        c_name = c_name,
+        cargo_name = cargo_name
+      ))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+
+    Ok(())
+  }
+
+  pub async fn list_cluster_route(
+    &self,
+    c_name: &str,
+    namespace: Option<String>,
+  ) -> Result<Vec<ClusterRouteItem>, NanocldError> {
+    let mut res = self
+      .get(format!("/clusters/{c_name}/routes", c_name = c_name))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let items = res.json::<Vec<ClusterRouteItem>>().await?;
+
+    Ok(items)
+  }
+
+  pub async fn create_cluster_route(
+    &self,
+    c_name: &str,
+    item: &ClusterRoutePartial,
+    namespace: Option<String>,
+  ) -> Result<ClusterRouteItem, NanocldError> {
+    let mut res = self
+      .post(format!("/clusters/{c_name}/routes", c_name = c_name))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send_json(item)
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<ClusterRouteItem>().await?;
+
+    Ok(item)
+  }
+
+  pub async fn delete_cluster_route(
+    &self,
+    c_name: &str,
+    r_name: &str,
+    namespace: Option<String>,
+  ) -> Result<(), NanocldError> {
+    let mut res = self
+      .delete(format!(
+        "/clusters/{c_name}/routes/{r_name}",
+        c_name = c_name,
+        r_name = r_name
+      ))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+
+    Ok(())
+  }
+
+  pub async fn list_cluster_access_policy(
+    &self,
+    c_name: &str,
+    namespace: Option<String>,
+  ) -> Result<Vec<ClusterAccessPolicyItem>, NanocldError> {
+    let mut res = self
+      .get(format!(
+        "/clusters/{c_name}/access_policies",
+        c_name = c_name
+      ))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let items = res.json::<Vec<ClusterAccessPolicyItem>>().await?;
+
+    Ok(items)
+  }
+
+  pub async fn create_cluster_access_policy(
+    &self,
+    c_name: &str,
+    item: &ClusterAccessPolicyPartial,
+    namespace: Option<String>,
+  ) -> Result<ClusterAccessPolicyItem, NanocldError> {
+    let mut res = self
+      .post(format!(
+        "/clusters/{c_name}/access_policies",
+        c_name = c_name
+      ))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send_json(item)
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<ClusterAccessPolicyItem>().await?;
+
+    Ok(item)
+  }
+
+  pub async fn delete_cluster_access_policy(
+    &self,
+    c_name: &str,
+    p_name: &str,
+    namespace: Option<String>,
+  ) -> Result<(), NanocldError> {
+    let mut res = self
+      .delete(format!(
+        "/clusters/{c_name}/access_policies/{p_name}",
+        c_name = c_name,
+        p_name = p_name
+      ))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+
+    Ok(())
+  }
+
+  pub async fn list_cluster_auth_policy(
+    &self,
+    c_name: &str,
+    namespace: Option<String>,
+  ) -> Result<Vec<ClusterAuthPolicyItem>, NanocldError> {
+    let mut res = self
+      .get(format!("/clusters/{c_name}/auth_policies", c_name = c_name))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let items = res.json::<Vec<ClusterAuthPolicyItem>>().await?;
+
+    Ok(items)
+  }
+
+  pub async fn create_cluster_auth_policy(
+    &self,
+    c_name: &str,
+    item: &ClusterAuthPolicyPartial,
+    namespace: Option<String>,
+  ) -> Result<ClusterAuthPolicyItem, NanocldError> {
+    let mut res = self
+      .post(format!("/clusters/{c_name}/auth_policies", c_name = c_name))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send_json(item)
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<ClusterAuthPolicyItem>().await?;
+
+    Ok(item)
+  }
+
+  pub async fn delete_cluster_auth_policy(
+    &self,
+    c_name: &str,
+    p_name: &str,
+    namespace: Option<String>,
+  ) -> Result<(), NanocldError> {
+    let mut res = self
+      .delete(format!(
+        "/clusters/{c_name}/auth_policies/{p_name}",
+        c_name = c_name,
+        p_name = p_name
+      ))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+
+    Ok(())
+  }
+
+  pub async fn update_cluster_maintenance(
+    &self,
+    c_name: &str,
+    item: &ClusterMaintenancePartial,
+    namespace: Option<String>,
+  ) -> Result<ClusterMaintenanceItem, NanocldError> {
+    let mut res = self
+      .post(format!("/clusters/{c_name}/maintenance", c_name = c_name))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send_json(item)
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<ClusterMaintenanceItem>().await?;
+
+    Ok(item)
+  }
+
+  pub async fn inspect_cluster_maintenance(
+    &self,
+    c_name: &str,
+    namespace: Option<String>,
+  ) -> Result<ClusterMaintenanceItem, NanocldError> {
+    let mut res = self
+      .get(format!("/clusters/{c_name}/maintenance", c_name = c_name))
+      .query(&GenericNamespaceQuery { namespace })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<ClusterMaintenanceItem>().await?;
+
+    Ok(item)
+  }
+
   pub async fn start_cluster(
     &self,
     c_name: &str,
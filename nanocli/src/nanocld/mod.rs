@@ -10,5 +10,7 @@ pub mod container_image;
 
 pub mod nginx_log;
 pub mod nginx_template;
+pub mod certificate;
 
 pub mod container;
+pub mod system;
@@ -17,6 +17,143 @@ pub struct NamespacePartial {
   pub name: String,
 }
 
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct NamespaceQuotaPartial {
+  /// Maximum number of cargoes
+  #[clap(long)]
+  pub(crate) max_cargoes: Option<i64>,
+  /// Maximum number of clusters
+  #[clap(long)]
+  pub(crate) max_clusters: Option<i64>,
+  /// Maximum number of cluster networks
+  #[clap(long)]
+  pub(crate) max_networks: Option<i64>,
+  /// Maximum number of cargoes joined to clusters
+  #[clap(long)]
+  pub(crate) max_replicas: Option<i64>,
+  /// Maximum summed memory limit of the replicas in bytes
+  #[clap(long)]
+  pub(crate) max_memory: Option<i64>,
+  /// Maximum summed cpu limit of the replicas in units of 10^-9 cpus
+  #[clap(long)]
+  pub(crate) max_cpu: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamespaceQuotaItem {
+  pub(crate) namespace_name: String,
+  pub(crate) max_cargoes: Option<i64>,
+  pub(crate) max_clusters: Option<i64>,
+  pub(crate) max_networks: Option<i64>,
+  pub(crate) max_replicas: Option<i64>,
+  pub(crate) max_memory: Option<i64>,
+  pub(crate) max_cpu: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamespaceUsage {
+  pub(crate) cargoes: i64,
+  pub(crate) clusters: i64,
+  pub(crate) networks: i64,
+  pub(crate) replicas: i64,
+  pub(crate) memory: i64,
+  pub(crate) cpu: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamespaceInspect {
+  pub(crate) name: String,
+  pub(crate) quota: Option<NamespaceQuotaItem>,
+  pub(crate) usage: NamespaceUsage,
+}
+
+#[derive(Tabled)]
+pub struct NamespaceResource {
+  pub(crate) resource: String,
+  pub(crate) used: i64,
+  pub(crate) limit: String,
+}
+
+impl NamespaceInspect {
+  /// Resources usage compared to the quota of the namespace
+  pub fn resources(&self) -> Vec<NamespaceResource> {
+    let limit = |max: fn(&NamespaceQuotaItem) -> Option<i64>| {
+      self.quota.as_ref().and_then(max)
+    };
+    let resources = [
+      ("cargoes", self.usage.cargoes, limit(|q| q.max_cargoes)),
+      ("clusters", self.usage.clusters, limit(|q| q.max_clusters)),
+      ("networks", self.usage.networks, limit(|q| q.max_networks)),
+      ("replicas", self.usage.replicas, limit(|q| q.max_replicas)),
+      ("memory", self.usage.memory, limit(|q| q.max_memory)),
+      ("cpu", self.usage.cpu, limit(|q| q.max_cpu)),
+    ];
+    resources
+      .into_iter()
+      .map(|(resource, used, limit)| NamespaceResource {
+        resource: resource.to_owned(),
+        used,
+        limit: limit
+          .map(|limit| limit.to_string())
+          .unwrap_or_else(|| String::from("unlimited")),
+      })
+      .collect()
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamespaceDeleteQuery {
+  pub(crate) dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamespaceDeleteReport {
+  pub(crate) dry_run: bool,
+  pub(crate) containers: Vec<String>,
+  pub(crate) joins: Vec<String>,
+  pub(crate) cargo_envs: Vec<String>,
+  pub(crate) cargoes: Vec<String>,
+  pub(crate) networks: Vec<String>,
+  pub(crate) cluster_variables: Vec<String>,
+  pub(crate) cluster_routes: Vec<String>,
+  pub(crate) cluster_access_policies: Vec<String>,
+  pub(crate) cluster_auth_policies: Vec<String>,
+  pub(crate) cluster_maintenances: Vec<String>,
+  pub(crate) clusters: Vec<String>,
+  pub(crate) nginx_files: Vec<String>,
+  pub(crate) dns_entries: Vec<String>,
+}
+
+impl NamespaceDeleteReport {
+  /// Print each resource removed with the namespace in deletion order
+  pub fn print(&self) {
+    let sections = [
+      ("CONTAINERS", &self.containers),
+      ("JOINS", &self.joins),
+      ("CARGO ENVS", &self.cargo_envs),
+      ("CARGOES", &self.cargoes),
+      ("NETWORKS", &self.networks),
+      ("CLUSTER VARIABLES", &self.cluster_variables),
+      ("CLUSTER ROUTES", &self.cluster_routes),
+      ("CLUSTER ACCESS POLICIES", &self.cluster_access_policies),
+      ("CLUSTER AUTH POLICIES", &self.cluster_auth_policies),
+      ("CLUSTER MAINTENANCES", &self.cluster_maintenances),
+      ("CLUSTERS", &self.clusters),
+      ("NGINX FILES", &self.nginx_files),
+      ("DNS ENTRIES", &self.dns_entries),
+    ];
+    for (title, items) in sections {
+      if items.is_empty() {
+        continue;
+      }
+      println!("=== {} ===", title);
+      for item in items {
+        println!("{}", item);
+      }
+    }
+  }
+}
+
 impl Nanocld {
   pub async fn list_namespace(
     &self,
@@ -49,7 +186,7 @@ impl Nanocld {
   pub async fn inspect_namespace(
     &self,
     name: &str,
-  ) -> Result<NamespaceItem, NanocldError> {
+  ) -> Result<NamespaceInspect, NanocldError> {
     let mut res = self
       .get(format!("/namespaces/{name}/inspect", name = name))
       .send()
@@ -57,7 +194,58 @@ impl Nanocld {
 
     let status = res.status();
     is_api_error(&mut res, &status).await?;
-    let item = res.json::<NamespaceItem>().await?;
+    let item = res.json::<NamespaceInspect>().await?;
+
+    Ok(item)
+  }
+
+  pub async fn put_namespace_quota(
+    &self,
+    name: &str,
+    item: &NamespaceQuotaPartial,
+  ) -> Result<NamespaceQuotaItem, NanocldError> {
+    let mut res = self
+      .put(format!("/namespaces/{name}/quota", name = name))
+      .send_json(item)
+      .await?;
+
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<NamespaceQuotaItem>().await?;
+
+    Ok(item)
+  }
+
+  pub async fn delete_namespace_quota(
+    &self,
+    name: &str,
+  ) -> Result<(), NanocldError> {
+    let mut res = self
+      .delete(format!("/namespaces/{name}/quota", name = name))
+      .send()
+      .await?;
+
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+
+    Ok(())
+  }
+
+  pub async fn delete_namespace(
+    &self,
+    name: &str,
+    dry_run: bool,
+  ) -> Result<NamespaceDeleteReport, NanocldError> {
+    let mut res = self
+      .delete(format!("/namespaces/{name}", name = name))
+      .query(&NamespaceDeleteQuery { dry_run })
+      .unwrap()
+      .send()
+      .await?;
+
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<NamespaceDeleteReport>().await?;
 
     Ok(item)
   }
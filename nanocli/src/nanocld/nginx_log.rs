@@ -1,3 +1,5 @@
+use clap::{ArgEnum, Parser};
+use tabled::Tabled;
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, FixedOffset};
 use futures::{TryStreamExt, StreamExt};
@@ -7,43 +9,143 @@ use super::{
   error::{NanocldError, is_api_error},
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Tabled, Serialize, Deserialize)]
 pub struct NginxLogItem {
+  #[tabled(skip)]
   pub(crate) key: String,
   pub(crate) date_gmt: DateTime<FixedOffset>,
-  pub(crate) uri: String,
+  #[tabled(rename = "method")]
+  pub(crate) request_method: String,
   pub(crate) host: String,
+  pub(crate) uri: String,
+  pub(crate) status: i32,
+  pub(crate) request_time: f64,
   pub(crate) remote_addr: String,
+  #[tabled(skip)]
   pub(crate) realip_remote_addr: String,
+  #[tabled(skip)]
   pub(crate) server_protocol: String,
-  pub(crate) request_method: String,
+  #[tabled(skip)]
   pub(crate) content_length: i64,
-  pub(crate) status: i32,
-  pub(crate) request_time: f64,
+  #[tabled(skip)]
   pub(crate) body_bytes_sent: i64,
+  #[tabled(skip)]
   pub(crate) proxy_host: Option<String>,
+  #[tabled(skip)]
   pub(crate) upstream_addr: Option<String>,
+  #[tabled(skip)]
   pub(crate) query_string: Option<String>,
+  #[tabled(skip)]
   pub(crate) request_body: Option<String>,
+  #[tabled(skip)]
   pub(crate) content_type: Option<String>,
+  #[tabled(skip)]
   pub(crate) http_user_agent: Option<String>,
+  #[tabled(skip)]
   pub(crate) http_referrer: Option<String>,
+  #[tabled(skip)]
   pub(crate) http_accept_language: Option<String>,
 }
 
-impl Nanocld {
-  pub async fn watch_nginx_logs(&self) -> Result<(), NanocldError> {
-    let mut res = self.get(String::from("/nginx/logs")).send().await?;
-    let status = res.status();
+/// Order of stored logs
+#[derive(Debug, Clone, Copy, ArgEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NginxLogSort {
+  DateDesc,
+  DateAsc,
+  RequestTimeDesc,
+  RequestTimeAsc,
+}
 
-    println!("{:#?}", res);
-    println!("{:#?}", status);
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct NginxLogSearchQuery {
+  /// Logs at or after this rfc3339 date
+  #[clap(long)]
+  pub(crate) since: Option<String>,
+  /// Logs before this rfc3339 date
+  #[clap(long)]
+  pub(crate) until: Option<String>,
+  /// Host of the request
+  #[clap(long)]
+  pub(crate) host: Option<String>,
+  /// Start of the uri of the request
+  #[clap(long)]
+  pub(crate) uri_prefix: Option<String>,
+  /// Minimum status of the response
+  #[clap(long)]
+  pub(crate) status_min: Option<i32>,
+  /// Maximum status of the response
+  #[clap(long)]
+  pub(crate) status_max: Option<i32>,
+  /// Method of the request
+  #[clap(long)]
+  pub(crate) method: Option<String>,
+  /// Address of the client
+  #[clap(long)]
+  pub(crate) remote_addr: Option<String>,
+  /// Minimum request time in seconds
+  #[clap(long)]
+  pub(crate) min_request_time: Option<f64>,
+  /// Order of logs default to date-desc
+  #[clap(long, arg_enum)]
+  pub(crate) sort: Option<NginxLogSort>,
+  /// Cursor of the next page given by a previous query
+  #[clap(long)]
+  pub(crate) cursor: Option<String>,
+  /// Maximum number of logs to return
+  #[clap(long)]
+  pub(crate) limit: Option<i64>,
+}
 
+/// Filters of the logs streamed
+#[derive(Debug, Default, Parser, Serialize, Deserialize)]
+pub struct NginxLogWatchQuery {
+  /// Host of the requests
+  #[clap(long)]
+  pub(crate) host: Option<String>,
+  /// Cluster whose domains and route domains are used as hosts
+  #[clap(long)]
+  pub(crate) cluster: Option<String>,
+  /// Namespace of the cluster
+  #[clap(long)]
+  pub(crate) namespace: Option<String>,
+  /// Minimum status of the responses
+  #[clap(long)]
+  pub(crate) status_min: Option<i32>,
+  /// Maximum status of the responses
+  #[clap(long)]
+  pub(crate) status_max: Option<i32>,
+  /// Regex matched against the uri of the requests
+  #[clap(long)]
+  pub(crate) path: Option<String>,
+  /// Part of the matching logs to stream like 0.1 for one log out of ten
+  #[clap(long)]
+  pub(crate) sample: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NginxLogSearchResult {
+  pub(crate) items: Vec<NginxLogItem>,
+  pub(crate) next_cursor: Option<String>,
+}
+
+impl Nanocld {
+  pub async fn watch_nginx_logs(
+    &self,
+    query: &NginxLogWatchQuery,
+  ) -> Result<(), NanocldError> {
+    let mut res = self
+      .get(String::from("/nginx/logs"))
+      .query(query)
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
     is_api_error(&mut res, &status).await?;
 
-    println!("into stream");
     let mut stream = res.into_stream();
-
+    // Logs are separated by new lines and can be split between chunks
+    let mut buffer = Vec::new();
     while let Some(res) = stream.next().await {
       match res {
         Err(err) => {
@@ -51,15 +153,38 @@ impl Nanocld {
           break;
         }
         Ok(data) => {
-          let result = &String::from_utf8(data.to_vec()).unwrap();
-          let json: NginxLogItem = serde_json::from_str(result).unwrap();
-          println!("==== [LOG] ====");
-          println!("{}", &serde_json::to_string_pretty(&json).unwrap());
-          println!("====       ====");
+          buffer.extend_from_slice(&data);
+          while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+            let line = buffer.drain(..=end).collect::<Vec<_>>();
+            match serde_json::from_slice::<NginxLogItem>(&line) {
+              Err(err) => eprintln!("invalid nginx log {}", err),
+              Ok(item) => {
+                println!("==== [LOG] ====");
+                println!("{}", &serde_json::to_string_pretty(&item).unwrap());
+                println!("====       ====");
+              }
+            }
+          }
         }
       }
     }
 
     Ok(())
   }
+
+  pub async fn search_nginx_logs(
+    &self,
+    query: &NginxLogSearchQuery,
+  ) -> Result<NginxLogSearchResult, NanocldError> {
+    let mut res = self
+      .get(String::from("/nginx/logs/search"))
+      .query(query)
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let result = res.json::<NginxLogSearchResult>().await?;
+    Ok(result)
+  }
 }
@@ -32,6 +32,69 @@ pub struct NginxTemplatePartial {
   pub(crate) content: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NginxTemplateUpdateBody {
+  pub(crate) mode: Option<NginxTemplateModes>,
+  pub(crate) content: String,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct NginxTemplateRevisionItem {
+  pub(crate) revision: i32,
+  pub(crate) mode: NginxTemplateModes,
+  pub(crate) created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NginxTemplateDiff {
+  pub(crate) from: i32,
+  pub(crate) to: i32,
+  pub(crate) lines: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NginxTemplateDiffQuery {
+  from: i32,
+  to: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NginxTemplateLintBody {
+  pub(crate) name: String,
+  pub(crate) content: String,
+}
+
+#[derive(Debug, Tabled, Serialize, Deserialize)]
+pub struct NginxTemplateDiagnostic {
+  pub(crate) level: String,
+  pub(crate) line: usize,
+  pub(crate) column: usize,
+  pub(crate) message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NginxTemplateUpdateReport {
+  pub(crate) revision: NginxTemplateRevisionItem,
+  pub(crate) rendered: Vec<String>,
+  pub(crate) errors: Vec<String>,
+  pub(crate) warnings: Vec<NginxTemplateDiagnostic>,
+}
+
+impl NginxTemplateUpdateReport {
+  pub fn print(&self) {
+    for warning in &self.warnings {
+      eprintln!("{}:{} {}", warning.line, warning.column, warning.message);
+    }
+    println!("revision {}", self.revision.revision);
+    for cluster in &self.rendered {
+      println!("rendered {}", cluster);
+    }
+    for error in &self.errors {
+      eprintln!("{}", error);
+    }
+  }
+}
+
 impl Nanocld {
   pub async fn create_nginx_template(
     &self,
@@ -69,4 +132,84 @@ impl Nanocld {
     let items = res.json::<Vec<NginxTemplatePartial>>().await?;
     Ok(items)
   }
+
+  pub async fn lint_nginx_template(
+    &self,
+    item: &NginxTemplateLintBody,
+  ) -> Result<Vec<NginxTemplateDiagnostic>, NanocldError> {
+    let mut res = self
+      .post(String::from("/nginx_templates/lint"))
+      .send_json(item)
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let items = res.json::<Vec<NginxTemplateDiagnostic>>().await?;
+    Ok(items)
+  }
+
+  pub async fn update_nginx_template(
+    &self,
+    name: &str,
+    item: &NginxTemplateUpdateBody,
+  ) -> Result<NginxTemplateUpdateReport, NanocldError> {
+    let mut res = self
+      .put(format!("/nginx_templates/{name}", name = name))
+      .send_json(item)
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let report = res.json::<NginxTemplateUpdateReport>().await?;
+    Ok(report)
+  }
+
+  pub async fn list_nginx_template_revision(
+    &self,
+    name: &str,
+  ) -> Result<Vec<NginxTemplateRevisionItem>, NanocldError> {
+    let mut res = self
+      .get(format!("/nginx_templates/{name}/revisions", name = name))
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let items = res.json::<Vec<NginxTemplateRevisionItem>>().await?;
+    Ok(items)
+  }
+
+  pub async fn diff_nginx_template(
+    &self,
+    name: &str,
+    from: i32,
+    to: i32,
+  ) -> Result<NginxTemplateDiff, NanocldError> {
+    let mut res = self
+      .get(format!("/nginx_templates/{name}/diff", name = name))
+      .query(&NginxTemplateDiffQuery { from, to })
+      .unwrap()
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let diff = res.json::<NginxTemplateDiff>().await?;
+    Ok(diff)
+  }
+
+  pub async fn rollback_nginx_template(
+    &self,
+    name: &str,
+    revision: i32,
+  ) -> Result<NginxTemplateUpdateReport, NanocldError> {
+    let mut res = self
+      .post(format!(
+        "/nginx_templates/{name}/revisions/{revision}/rollback",
+        name = name,
+        revision = revision,
+      ))
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let report = res.json::<NginxTemplateUpdateReport>().await?;
+    Ok(report)
+  }
 }
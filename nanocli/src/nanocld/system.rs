@@ -0,0 +1,106 @@
+use serde::{Serialize, Deserialize};
+
+use super::client::Nanocld;
+use super::error::{NanocldError, is_api_error};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReconcileReport {
+  pub(crate) created: Vec<String>,
+  pub(crate) removed: Vec<String>,
+  pub(crate) started: Vec<String>,
+  pub(crate) reconnected: Vec<String>,
+  pub(crate) rendered: Vec<String>,
+  pub(crate) errors: Vec<String>,
+  pub(crate) finished_at: Option<String>,
+}
+
+impl ReconcileReport {
+  /// Print each change made by the reconciliation
+  pub fn print(&self) {
+    let sections = [
+      ("CREATED", &self.created),
+      ("REMOVED", &self.removed),
+      ("STARTED", &self.started),
+      ("RECONNECTED", &self.reconnected),
+      ("RENDERED", &self.rendered),
+      ("ERRORS", &self.errors),
+    ];
+    for (title, items) in sections {
+      if items.is_empty() {
+        continue;
+      }
+      println!("=== {} ===", title);
+      for item in items {
+        println!("{}", item);
+      }
+    }
+    if let Some(finished_at) = &self.finished_at {
+      println!("finished at {}", finished_at);
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CertificateRenewReport {
+  pub(crate) issued: Vec<String>,
+  pub(crate) rendered: Vec<String>,
+  pub(crate) errors: Vec<String>,
+  pub(crate) finished_at: Option<String>,
+}
+
+impl CertificateRenewReport {
+  /// Print certificates issued by the renewal
+  pub fn print(&self) {
+    let sections = [
+      ("ISSUED", &self.issued),
+      ("RENDERED", &self.rendered),
+      ("ERRORS", &self.errors),
+    ];
+    for (title, items) in sections {
+      if items.is_empty() {
+        continue;
+      }
+      println!("=== {} ===", title);
+      for item in items {
+        println!("{}", item);
+      }
+    }
+    if let Some(finished_at) = &self.finished_at {
+      println!("finished at {}", finished_at);
+    }
+  }
+}
+
+impl Nanocld {
+  pub async fn reconcile(&self) -> Result<ReconcileReport, NanocldError> {
+    let mut res = self.post(String::from("/system/reconcile")).send().await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<ReconcileReport>().await?;
+
+    Ok(item)
+  }
+
+  pub async fn last_reconcile(&self) -> Result<ReconcileReport, NanocldError> {
+    let mut res = self.get(String::from("/system/reconcile")).send().await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<ReconcileReport>().await?;
+
+    Ok(item)
+  }
+
+  pub async fn renew_certificates(
+    &self,
+  ) -> Result<CertificateRenewReport, NanocldError> {
+    let mut res = self
+      .post(String::from("/system/certificates/renew"))
+      .send()
+      .await?;
+    let status = res.status();
+    is_api_error(&mut res, &status).await?;
+    let item = res.json::<CertificateRenewReport>().await?;
+
+    Ok(item)
+  }
+}
@@ -8,6 +8,7 @@ use crate::nanocld::cargo::CargoPartial;
 use crate::nanocld::client::Nanocld;
 use crate::nanocld::cluster::{
   ClusterNetworkPartial, ClusterPartial, ClusterVarPartial,
+  ClusterNetworkPolicyPartial,
 };
 
 use crate::errors::CliError;
@@ -20,6 +21,48 @@ async fn revert_namespace(
   namespace: &NamespaceConfig,
   client: &Nanocld,
 ) -> Result<(), CliError> {
+  // Remove cargoes from clusters
+  namespace
+    .clusters
+    .iter()
+    .map(|cluster| async {
+      let joins = match &cluster.joins {
+        None => return Ok::<(), CliError>(()),
+        Some(joins) => joins,
+      };
+      joins
+        .iter()
+        .map(|join| async {
+          let result = client
+            .unjoin_cluster_cargo(
+              &cluster.name,
+              &join.cargo,
+              Some(namespace.name.to_owned()),
+            )
+            .await;
+          if let Err(err) = result {
+            if let NanocldError::Api(ref api_err) = err {
+              if api_err.status == StatusCode::NOT_FOUND {
+                return Ok::<(), CliError>(());
+              }
+            }
+            return Err::<(), CliError>(CliError::Client(err));
+          }
+          Ok::<(), CliError>(())
+        })
+        .collect::<FuturesUnordered<_>>()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<()>, CliError>>()?;
+      Ok::<(), CliError>(())
+    })
+    .collect::<FuturesUnordered<_>>()
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .collect::<Result<Vec<()>, CliError>>()?;
+
   // Delete cargoes
   namespace
     .cargoes
@@ -100,6 +143,7 @@ async fn apply_namespace(
       let item = ClusterPartial {
         name: cluster.name.to_owned(),
         proxy_templates: cluster.proxy_templates.to_owned(),
+        domains: cluster.domains.to_owned(),
       };
       if cluster_exists.is_err() {
         client
@@ -156,6 +200,12 @@ async fn apply_namespace(
             .await;
           let item = ClusterNetworkPartial {
             name: network.name.to_owned(),
+            subnet: network.subnet.to_owned(),
+            ip_range: network.ip_range.to_owned(),
+            gateway: network.gateway.to_owned(),
+            ipv6_subnet: network.ipv6_subnet.to_owned(),
+            internal: network.internal.unwrap_or(false),
+            driver_options: network.driver_options.to_owned(),
           };
           if result.is_err() {
             client
@@ -174,6 +224,24 @@ async fn apply_namespace(
         .await
         .into_iter()
         .collect::<Result<Vec<()>, CliError>>()?;
+      // Apply cluster network policies
+      for policy in cluster.network_policies.iter().flatten() {
+        let item = ClusterNetworkPolicyPartial {
+          allowed_networks: policy
+            .allowed_networks
+            .to_owned()
+            .unwrap_or_default(),
+          allow_egress: policy.allow_egress.unwrap_or(true),
+        };
+        client
+          .put_cluster_network_policy(
+            &cluster.name,
+            &policy.network,
+            &item,
+            Some(namespace.name.to_owned()),
+          )
+          .await?;
+      }
       Ok::<_, CliError>(())
     })
     .collect::<FuturesUnordered<_>>()
@@ -198,6 +266,8 @@ async fn apply_namespace(
         environnements: cargo.environnements.to_owned(),
         domainname: cargo.domainname.to_owned(),
         hostname: cargo.hostname.to_owned(),
+        memory_limit: cargo.memory_limit,
+        cpu_limit: cargo.cpu_limit,
       };
       if result.is_err() {
         client
@@ -245,6 +315,44 @@ async fn apply_namespace(
           .collect::<Result<Vec<()>, CliError>>()?;
       }
 
+      // Create cluster routes, existing ones are kept
+      for route in cluster.routes.iter().flatten() {
+        if let Err(err) = client
+          .create_cluster_route(
+            &cluster.name,
+            route,
+            Some(namespace.name.to_owned()),
+          )
+          .await
+        {
+          if let NanocldError::Api(ref err) = err {
+            if err.status == StatusCode::CONFLICT {
+              continue;
+            }
+          }
+          return Err(CliError::Client(err));
+        }
+      }
+
+      // Create cluster auth policies, existing ones are kept
+      for policy in cluster.auth_policies.iter().flatten() {
+        if let Err(err) = client
+          .create_cluster_auth_policy(
+            &cluster.name,
+            policy,
+            Some(namespace.name.to_owned()),
+          )
+          .await
+        {
+          if let NanocldError::Api(ref err) = err {
+            if err.status == StatusCode::CONFLICT {
+              continue;
+            }
+          }
+          return Err(CliError::Client(err));
+        }
+      }
+
       if let Some(auto_start) = cluster.auto_start {
         if !auto_start {
           return Ok::<_, CliError>(());
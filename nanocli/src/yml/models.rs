@@ -1,7 +1,9 @@
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
-use crate::nanocld::cluster::ClusterJoinPartial;
+use crate::nanocld::cluster::{
+  ClusterJoinPartial, ClusterRoutePartial, ClusterAuthPolicyPartial,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct Cargo {
@@ -14,11 +16,26 @@ pub(crate) struct Cargo {
   pub(crate) binds: Option<Vec<String>>,
   #[serde(rename(deserialize = "envs"))]
   pub(crate) environnements: Option<Vec<String>>,
+  pub(crate) memory_limit: Option<i64>,
+  pub(crate) cpu_limit: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct Network {
   pub(crate) name: String,
+  pub(crate) subnet: Option<String>,
+  pub(crate) ip_range: Option<String>,
+  pub(crate) gateway: Option<String>,
+  pub(crate) ipv6_subnet: Option<String>,
+  pub(crate) internal: Option<bool>,
+  pub(crate) driver_options: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct NetworkPolicy {
+  pub(crate) network: String,
+  pub(crate) allowed_networks: Option<Vec<String>>,
+  pub(crate) allow_egress: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -32,9 +49,15 @@ pub(crate) struct Cluster {
   pub(crate) name: String,
   pub(crate) auto_start: Option<bool>,
   pub(crate) proxy_templates: Option<Vec<String>>,
+  pub(crate) domains: Option<Vec<String>>,
   #[serde(rename(deserialize = "vars"))]
   pub(crate) variables: Option<HashMap<String, String>>,
   pub(crate) joins: Option<Vec<ClusterJoinPartial>>,
+  pub(crate) network_policies: Option<Vec<NetworkPolicy>>,
+  /// Routes created once the cargoes are joined
+  pub(crate) routes: Option<Vec<ClusterRoutePartial>>,
+  /// Auth policies created once the routes they protect exist
+  pub(crate) auth_policies: Option<Vec<ClusterAuthPolicyPartial>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]